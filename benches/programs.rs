@@ -0,0 +1,80 @@
+//! # Decoder and encoder benchmarks on CQG's templates
+//!
+//! The benchmarks use only the public API, so the results can be compared between revisions
+//! with criterion baselines, e.g. template programs against the instruction walker they replaced:
+//!
+//! ```sh
+//! git checkout <revision> && cargo bench --bench programs -- --save-baseline before
+//! git checkout - && cargo bench --bench programs -- --baseline before
+//! ```
+//!
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use fastlib::{Decoder, Encoder, MessageFactory, TextMessageVisitor, Value};
+
+const DEFINITION: &str = include_str!("../tests/templates.xml");
+
+const MESSAGES: [&str; 3] = [
+    "MDHeartbeat=<MessageType=0|ApplVerID=8|SenderCompID=CQG|MsgSeqNum=1|SendingTime=20240606000000000>",
+    "MDLogon=<MessageType=A|ApplVerID=8|SenderCompID=CQG|MsgSeqNum=1|SendingTime=20240606212352157|EncryptMethod=0|HeartbeatInt=10>",
+    "MDSecurityDefinition=<MessageType=d|ApplVerID=8|SenderCompID=CQG|MsgSeqNum=964|SendingTime=20240606212353155|TotNumReports=966|Events=<EventType=7|EventDate=20241129|EventTime=220000000>|SecurityGroup=MBTS13|Symbol=MBTS13C100|SecurityName=Micro Bitcoin Reverse Cal Spread|SecurityDesc=MBTS13X24|SecurityID=60714110|SecurityIDSource=100|CFICode=FXXXXX|SecurityExchange=GLBX|CQGSecurityName=F.US.MBTW13X24|StrikePrice=0|Currency=USD|MDFeedTypes=<MDFeedType=CQGC|MarketDepth=0><MDFeedType=CQGI|MarketDepth=1>|InstrAttrib=<InstrAttribType=1003|InstrAttribValue=100>|MaturityMonthYear=202411|MinPriceIncrement=1|MinPriceIncrementAmount=0.1|DisplayFactor=1|ApplID=4|Connections=<ConnectionType=1|ConnectionIPAddress=239.246.5.4|ConnectionPortNumber=11004><ConnectionType=2|ConnectionIPAddress=239.246.6.4|ConnectionPortNumber=12004><ConnectionType=3|ConnectionIPAddress=10.1.0.120|ConnectionPortNumber=10000><ConnectionType=3|ConnectionIPAddress=10.1.0.120|ConnectionPortNumber=10001>|TradingSessions=<TradeDate=20240531|TradSesStartTime=20240530220000000|TradSesOpenTime=20240530211500000|TradSesCloseTime=20240531210000000|TradSesEndTime=20240531210000000><TradeDate=20240603|TradSesStartTime=20240602220000000|TradSesOpenTime=20240602211500000|TradSesCloseTime=20240603210000000|TradSesEndTime=20240603210000000><TradeDate=20240604|TradSesStartTime=20240603220000000|TradSesOpenTime=20240603211500000|TradSesCloseTime=20240604210000000|TradSesEndTime=20240604210000000><TradeDate=20240605|TradSesStartTime=20240604220000000|TradSesOpenTime=20240604211500000|TradSesCloseTime=20240605210000000|TradSesEndTime=20240605210000000><TradeDate=20240606|TradSesStartTime=20240605220000000|TradSesOpenTime=20240605211500000|TradSesCloseTime=20240606210000000|TradSesEndTime=20240606210000000><TradeDate=20240607|TradSesStartTime=20240606220000000|TradSesOpenTime=20240606211500000|TradSesCloseTime=20240607210000000|TradSesEndTime=20240607210000000>>",
+];
+
+// Counts decoded values, so the benchmark measures the decoder rather than building messages.
+#[derive(Default)]
+struct Counter(usize);
+
+impl MessageFactory for Counter {
+    fn start_template(&mut self, _id: u32, _name: &str) {}
+    fn stop_template(&mut self) {}
+    fn set_value(&mut self, _id: u32, _name: &str, value: Option<Value>) {
+        self.0 += usize::from(value.is_some());
+    }
+    fn start_sequence(&mut self, _id: u32, _name: &str, _length: u32) {}
+    fn start_sequence_item(&mut self, _index: u32) {}
+    fn stop_sequence_item(&mut self) {}
+    fn stop_sequence(&mut self) {}
+    fn start_group(&mut self, _name: &str) {}
+    fn stop_group(&mut self) {}
+    fn start_template_ref(&mut self, _name: &str, _dynamic: bool) {}
+    fn stop_template_ref(&mut self) {}
+}
+
+fn visitors() -> Vec<TextMessageVisitor> {
+    MESSAGES
+        .iter()
+        .map(|m| TextMessageVisitor::from_text(m).unwrap())
+        .collect()
+}
+
+fn encode(encoder: &mut Encoder, visitors: Vec<TextMessageVisitor>) -> Vec<Vec<u8>> {
+    encoder.reset();
+    visitors
+        .into_iter()
+        .map(|mut v| encoder.encode_vec(&mut v).unwrap())
+        .collect()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let raw = encode(&mut Encoder::new_from_xml(DEFINITION).unwrap(), visitors());
+    let mut decoder = Decoder::new_from_xml(DEFINITION).unwrap();
+    c.bench_function("decode cqg", |b| {
+        b.iter(|| {
+            decoder.reset();
+            let mut counter = Counter::default();
+            for msg in &raw {
+                decoder.decode_slice(msg, &mut counter).unwrap();
+            }
+            counter.0
+        });
+    });
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut encoder = Encoder::new_from_xml(DEFINITION).unwrap();
+    c.bench_function("encode cqg", |b| {
+        b.iter_batched(visitors, |v| encode(&mut encoder, v), BatchSize::SmallInput);
+    });
+}
+
+criterion_group!(benches, bench_decode, bench_encode);
+criterion_main!(benches);
@@ -0,0 +1,578 @@
+//! # Derive macro for typed FAST messages
+//!
+//! `#[derive(FastMessage)]` reads the XML templates at compile time and generates the decoder and the encoder
+//! of the struct specialized for its template. The generated code addresses the instructions by their positions
+//! and doesn't go through the serde data model, see [`fastlib::FastMessage`].
+//!
+//! ```rust,ignore
+//! use fastlib::{Decimal, Decoder, Encoder};
+//! use fastlib_derive::FastMessage;
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "templates.xml", template = "MDIncRefresh")]
+//! struct IncRefresh {
+//!     msg_seq_num: u32,
+//!     sending_time: u64,
+//!     md_entries: Vec<MDEntry>,
+//! }
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "templates.xml", template = "MDIncRefresh", group = "MDEntries")]
+//! struct MDEntry {
+//!     #[fast(rename = "MDUpdateAction")]
+//!     action: u32,
+//!     md_entry_px: Option<Decimal>,
+//! }
+//!
+//! let mut decoder = Decoder::new_from_xml(include_str!("templates.xml"))?;
+//! let msg: IncRefresh = decoder.decode_typed(&mut rdr)?;
+//!
+//! let mut encoder = Encoder::new_from_xml(include_str!("templates.xml"))?;
+//! encoder.encode_typed(&mut wrt, &msg)?;
+//! ```
+//!
+//! The struct attribute `#[fast(...)]` accepts:
+//! * `xml` - path to the XML templates relative to the directory of the package manifest;
+//! * `template` - the name of the message template;
+//! * `group` - the path to a group or a sequence within the template, separated with `/`.
+//!   The struct is a group or a sequence item and doesn't implement [`fastlib::FastMessage`].
+//!
+//! Fields are matched to instructions by names ignoring case and underscores,
+//! or by the exact instruction name given with `#[fast(rename = "...")]`.
+//! Fields of statically referenced templates are inlined into the struct.
+//!
+//! Each instruction must have exactly one field and each field must have an instruction. Field types must match
+//! instruction types:
+//! * `Option<T>` for optional instructions;
+//! * `bool`, `u8`, `i8`, `u16`, `i16`, `u32`, `i32`, `u64`, `i64`, `Decimal`, `String` or `Vec<u8>` for fields;
+//! * a struct deriving `FastMessage` with `group` for groups and `Vec` of such structs for sequences.
+//!
+//! Enum, set and time fields and dynamic template references are not supported. Use serde for such templates.
+//!
+//! The struct is checked against the template, e.g. a missing field is a compile error:
+//! ```rust,compile_fail
+//! use fastlib_derive::FastMessage;
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "tests/templates.xml", template = "MDHeartbeat")]
+//! struct Heartbeat {
+//!     msg_seq_num: u32,
+//! }
+//! ```
+//!
+//! A field with a type different from the instruction type is a compile error:
+//! ```rust,compile_fail
+//! use fastlib_derive::FastMessage;
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "tests/templates.xml", template = "MDHeartbeat")]
+//! struct Heartbeat {
+//!     msg_seq_num: u64,
+//!     sending_time: u64,
+//! }
+//! ```
+//!
+//! A mandatory field of an optional instruction is a compile error:
+//! ```rust,compile_fail
+//! use fastlib_derive::FastMessage;
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "tests/templates.xml", template = "MDLogout")]
+//! struct Logout {
+//!     msg_seq_num: u32,
+//!     sending_time: u64,
+//!     text: String,
+//! }
+//! ```
+//!
+//! A field without an instruction is a compile error:
+//! ```rust,compile_fail
+//! use fastlib_derive::FastMessage;
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "tests/templates.xml", template = "MDLogout")]
+//! struct Logout {
+//!     msg_seq_num: u32,
+//!     sending_time: u64,
+//!     text: Option<String>,
+//!     reason: Option<String>,
+//! }
+//! ```
+use std::path::PathBuf;
+
+use fastlib::{Definitions, Instruction, Template, ValueType};
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments, Type,
+    parse_macro_input,
+};
+
+#[proc_macro_derive(FastMessage, attributes(fast))]
+pub fn derive_fast_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// Options of the struct attribute.
+struct Options {
+    xml: LitStr,
+    template: LitStr,
+    group: Option<LitStr>,
+}
+
+impl Options {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut xml = None;
+        let mut template = None;
+        let mut group = None;
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("fast")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("xml") {
+                    xml = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("template") {
+                    template = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("group") {
+                    group = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("expected `xml`, `template` or `group`"));
+                }
+                Ok(())
+            })?;
+        }
+        let missing = |name| {
+            syn::Error::new(
+                input.ident.span(),
+                format!("missing `#[fast({name} = \"...\")]` attribute"),
+            )
+        };
+        Ok(Self {
+            xml: xml.ok_or_else(|| missing("xml"))?,
+            template: template.ok_or_else(|| missing("template"))?,
+            group,
+        })
+    }
+}
+
+// A field of the struct and the name of the instruction it is matched to.
+struct Field {
+    ident: Ident,
+    ty: Type,
+    rename: Option<String>,
+    matched: bool,
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut rename = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("fast")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename`"))
+                }
+            })?;
+        }
+        Ok(Self {
+            ident: field.ident.clone().unwrap(),
+            ty: field.ty.clone(),
+            rename,
+            matched: false,
+        })
+    }
+
+    fn matches(&self, instruction: &Instruction) -> bool {
+        if let Some(name) = &self.rename {
+            name == instruction.name()
+        } else {
+            let ident = self.ident.to_string();
+            let ident = ident.trim_start_matches("r#").replace('_', "");
+            ident.eq_ignore_ascii_case(&normalize(instruction.name()))
+        }
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars().filter(char::is_ascii_alphanumeric).collect()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let options = Options::parse(input)?;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "FastMessage can only be derived for structs",
+        ));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "FastMessage can only be derived for structs with named fields",
+        ));
+    };
+
+    let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default())
+        .join(options.xml.value());
+    let text = std::fs::read_to_string(&path).map_err(|e| {
+        syn::Error::new(
+            options.xml.span(),
+            format!("failed to read {}: {e}", path.display()),
+        )
+    })?;
+    let definitions = Definitions::new_from_xml(&text).map_err(|e| {
+        syn::Error::new(
+            options.xml.span(),
+            format!("failed to parse {}: {e}", path.display()),
+        )
+    })?;
+    let template = definitions
+        .template_by_name(&options.template.value())
+        .ok_or_else(|| {
+            syn::Error::new(
+                options.template.span(),
+                format!("unknown template: {}", options.template.value()),
+            )
+        })?;
+    let instructions = match &options.group {
+        Some(group) => resolve_group(&definitions, template.instructions(), group)?,
+        None => template.instructions(),
+    };
+
+    let mut generator = Generator {
+        definitions: &definitions,
+        fields: named
+            .named
+            .iter()
+            .map(Field::parse)
+            .collect::<syn::Result<_>>()?,
+        span: input.ident.span(),
+    };
+    let (decode, encode, idents) = generator.instructions(instructions)?;
+    if let Some(field) = generator.fields.iter().find(|f| !f.matched) {
+        return Err(syn::Error::new(
+            field.ident.span(),
+            format!(
+                "field `{}` doesn't match any instruction of {}",
+                field.ident,
+                options.group.as_ref().unwrap_or(&options.template).value()
+            ),
+        ));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let path = path.to_string_lossy();
+    let message = if options.group.is_none() {
+        let name = &options.template;
+        quote! {
+            impl #impl_generics ::fastlib::FastMessage for #ident #ty_generics #where_clause {
+                const TEMPLATE_NAME: &'static str = #name;
+            }
+        }
+    } else {
+        quote! {}
+    };
+    Ok(quote! {
+        // Rebuild when the templates change.
+        const _: &str = include_str!(#path);
+
+        impl #impl_generics ::fastlib::FastFields for #ident #ty_generics #where_clause {
+            fn decode_fields<D: ::fastlib::FieldDecoder>(__decoder: &mut D) -> ::fastlib::Result<Self> {
+                #decode
+                Ok(Self { #(#idents),* })
+            }
+
+            fn encode_fields<E: ::fastlib::FieldEncoder>(&self, __encoder: &mut E) -> ::fastlib::Result<()> {
+                #encode
+                Ok(())
+            }
+        }
+
+        #message
+    })
+}
+
+// Finds instructions of the group or the sequence item at the path within the template.
+fn resolve_group<'a>(
+    definitions: &'a Definitions,
+    mut instructions: &'a [Instruction],
+    group: &LitStr,
+) -> syn::Result<&'a [Instruction]> {
+    for name in group.value().split('/') {
+        let instruction = find_instruction(definitions, instructions, name)
+            .ok_or_else(|| syn::Error::new(group.span(), format!("unknown group: {name}")))?;
+        instructions = match instruction.value_type() {
+            ValueType::Group => instruction.instructions(),
+            ValueType::Sequence => &instruction.instructions()[1..],
+            _ => {
+                return Err(syn::Error::new(
+                    group.span(),
+                    format!("{name} is neither a group nor a sequence"),
+                ));
+            }
+        };
+    }
+    Ok(instructions)
+}
+
+// Finds the instruction by name, including instructions of statically referenced templates.
+fn find_instruction<'a>(
+    definitions: &'a Definitions,
+    instructions: &'a [Instruction],
+    name: &str,
+) -> Option<&'a Instruction> {
+    instructions.iter().find_map(|i| match i.value_type() {
+        ValueType::TemplateReference if !i.name().is_empty() => referenced_template(definitions, i)
+            .and_then(|t| find_instruction(definitions, t.instructions(), name)),
+        _ if i.name() == name => Some(i),
+        _ => None,
+    })
+}
+
+// Finds the template of a static template reference by the name qualified with the template namespace
+// or, if not found, by the bare name.
+fn referenced_template<'a>(
+    definitions: &'a Definitions,
+    instruction: &Instruction,
+) -> Option<&'a Template> {
+    instruction
+        .template_ns()
+        .filter(|ns| !ns.is_empty())
+        .and_then(|ns| definitions.template_by_name(&format!("{{{ns}}}{}", instruction.name())))
+        .or_else(|| definitions.template_by_name(instruction.name()))
+}
+
+struct Generator<'a> {
+    definitions: &'a Definitions,
+    fields: Vec<Field>,
+    span: Span,
+}
+
+impl Generator<'_> {
+    // Generates decoding and encoding statements of the instructions.
+    // Returns the statements and the idents of decoded fields.
+    fn instructions(
+        &mut self,
+        instructions: &[Instruction],
+    ) -> syn::Result<(TokenStream2, TokenStream2, Vec<Ident>)> {
+        let mut decode = TokenStream2::new();
+        let mut encode = TokenStream2::new();
+        let mut idents = Vec::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            if *instruction.value_type() == ValueType::TemplateReference {
+                let (d, e, i) = self.template_ref(instruction)?;
+                decode.extend(quote! {
+                    let (#(#i,)*) = __decoder.template_ref(#index, |__decoder| {
+                        #d
+                        Ok((#(#i,)*))
+                    })?;
+                });
+                encode.extend(quote! {
+                    __encoder.template_ref(#index, |__encoder| {
+                        #e
+                        Ok(())
+                    })?;
+                });
+                idents.extend(i);
+                continue;
+            }
+            let field = self.field(instruction)?;
+            let ident = &field.ident;
+            let method = match instruction.value_type() {
+                ValueType::Group => {
+                    check_group(field, instruction)?;
+                    quote!(group)
+                }
+                ValueType::Sequence => {
+                    check_sequence(field, instruction)?;
+                    quote!(sequence)
+                }
+                _ => {
+                    check_field(field, instruction)?;
+                    quote!(field)
+                }
+            };
+            decode.extend(quote! {
+                let #ident = __decoder.#method(#index)?;
+            });
+            encode.extend(quote! {
+                __encoder.#method(#index, &self.#ident)?;
+            });
+            idents.push(ident.clone());
+        }
+        Ok((decode, encode, idents))
+    }
+
+    fn template_ref(
+        &mut self,
+        instruction: &Instruction,
+    ) -> syn::Result<(TokenStream2, TokenStream2, Vec<Ident>)> {
+        if instruction.name().is_empty() {
+            return Err(syn::Error::new(
+                self.span,
+                "dynamic template references are not supported, use serde",
+            ));
+        }
+        let definitions = self.definitions;
+        let template = referenced_template(definitions, instruction).ok_or_else(|| {
+            syn::Error::new(
+                self.span,
+                format!("unknown template: {}", instruction.name()),
+            )
+        })?;
+        self.instructions(template.instructions())
+    }
+
+    // Finds the field of the instruction.
+    fn field(&mut self, instruction: &Instruction) -> syn::Result<&Field> {
+        let mut candidates = self
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.matches(instruction))
+            .map(|(i, _)| i);
+        let Some(index) = candidates.next() else {
+            return Err(syn::Error::new(
+                self.span,
+                format!("missing field for instruction {}", instruction.name()),
+            ));
+        };
+        if let Some(other) = candidates.next() {
+            return Err(syn::Error::new(
+                self.fields[other].ident.span(),
+                format!(
+                    "fields `{}` and `{}` match the same instruction {}",
+                    self.fields[index].ident,
+                    self.fields[other].ident,
+                    instruction.name()
+                ),
+            ));
+        }
+        let field = &mut self.fields[index];
+        if field.matched {
+            return Err(syn::Error::new(
+                field.ident.span(),
+                format!(
+                    "field `{}` matches more than one instruction {}",
+                    field.ident,
+                    instruction.name()
+                ),
+            ));
+        }
+        field.matched = true;
+        Ok(field)
+    }
+}
+
+// Returns the type of the value of the field, i.e. `T` of `Option<T>` for optional instructions.
+fn value_type<'a>(field: &'a Field, instruction: &Instruction) -> syn::Result<&'a Type> {
+    match (instruction.is_optional(), generic_arg(&field.ty, "Option")) {
+        (true, Some(ty)) => Ok(ty),
+        (false, None) => Ok(&field.ty),
+        (true, None) => Err(mismatch(field, instruction, "must be `Option<_>`")),
+        (false, Some(_)) => Err(mismatch(field, instruction, "must not be `Option<_>`")),
+    }
+}
+
+fn check_field(field: &Field, instruction: &Instruction) -> syn::Result<()> {
+    let ty = value_type(field, instruction)?;
+    let expected = match instruction.value_type() {
+        ValueType::Boolean => "bool",
+        ValueType::UInt8 => "u8",
+        ValueType::Int8 => "i8",
+        ValueType::UInt16 => "u16",
+        ValueType::Int16 => "i16",
+        ValueType::UInt32 | ValueType::Length => "u32",
+        ValueType::Int32 | ValueType::Exponent => "i32",
+        ValueType::UInt64 => "u64",
+        ValueType::Int64 | ValueType::Mantissa => "i64",
+        ValueType::Decimal => "Decimal",
+        ValueType::ASCIIString | ValueType::UnicodeString => "String",
+        ValueType::Bytes => {
+            return match generic_arg(ty, "Vec") {
+                Some(item) if type_name(item).as_deref() == Some("u8") => Ok(()),
+                _ => Err(mismatch(field, instruction, "must be `Vec<u8>`")),
+            };
+        }
+        t => {
+            return Err(mismatch(
+                field,
+                instruction,
+                &format!(
+                    "has type {} which is not supported, use serde",
+                    t.type_str()
+                ),
+            ));
+        }
+    };
+    if type_name(ty).as_deref() == Some(expected) {
+        Ok(())
+    } else {
+        Err(mismatch(
+            field,
+            instruction,
+            &format!("must be `{expected}`"),
+        ))
+    }
+}
+
+fn check_group(field: &Field, instruction: &Instruction) -> syn::Result<()> {
+    let ty = value_type(field, instruction)?;
+    if type_name(ty).is_some_and(|n| n != "Vec" && n != "Option") {
+        Ok(())
+    } else {
+        Err(mismatch(field, instruction, "must be a struct"))
+    }
+}
+
+fn check_sequence(field: &Field, instruction: &Instruction) -> syn::Result<()> {
+    let ty = value_type(field, instruction)?;
+    match generic_arg(ty, "Vec").and_then(type_name) {
+        Some(n) if n != "u8" => Ok(()),
+        _ => Err(mismatch(field, instruction, "must be `Vec<_>` of structs")),
+    }
+}
+
+fn mismatch(field: &Field, instruction: &Instruction, message: &str) -> syn::Error {
+    syn::Error::new(
+        field.ty.span(),
+        format!(
+            "field `{}` of {} instruction {} {message}",
+            field.ident,
+            instruction.value_type().type_str(),
+            instruction.name()
+        ),
+    )
+}
+
+// Returns the last segment of the type path.
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(p) if p.qself.is_none() => p.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+// Returns `T` if the type is `name<T>`.
+fn generic_arg<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let segment = p.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(GenericArgument::Type(ty)) if args.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
@@ -0,0 +1,233 @@
+//! # Tests of typed messages
+//!
+//! Messages are encoded with the generated code and compared with the messages encoded with serde.
+//!
+use bytes::{Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+
+use fastlib::{Decimal, Decoder, Encoder, Error};
+use fastlib_derive::FastMessage;
+
+const TEMPLATES: &str = include_str!("templates.xml");
+
+#[derive(Debug, PartialEq, FastMessage, Serialize, Deserialize)]
+#[fast(xml = "tests/templates.xml", template = "MDIncRefresh")]
+#[serde(rename_all = "PascalCase")]
+struct IncRefresh {
+    message_type: String,
+    msg_seq_num: u32,
+    sending_time: u64,
+    last_fragment: Option<bool>,
+    #[serde(rename = "MDEntries")]
+    md_entries: Vec<MDEntry>,
+    #[fast(rename = "Statistics")]
+    #[serde(rename = "Statistics")]
+    stats: Stats,
+}
+
+#[derive(Debug, PartialEq, FastMessage, Serialize, Deserialize)]
+#[fast(
+    xml = "tests/templates.xml",
+    template = "MDIncRefresh",
+    group = "MDEntries"
+)]
+#[serde(rename_all = "PascalCase")]
+struct MDEntry {
+    #[fast(rename = "MDUpdateAction")]
+    #[serde(rename = "MDUpdateAction")]
+    action: u32,
+    #[serde(rename = "MDEntryType")]
+    md_entry_type: String,
+    #[serde(rename = "SecurityID")]
+    security_id: u32,
+    rpt_seq: u32,
+    #[serde(rename = "MDEntryPx")]
+    md_entry_px: Option<Decimal>,
+    #[serde(rename = "MDEntrySize")]
+    md_entry_size: Option<i32>,
+    trade: Option<Trade>,
+}
+
+#[derive(Debug, PartialEq, FastMessage, Serialize, Deserialize)]
+#[fast(
+    xml = "tests/templates.xml",
+    template = "MDIncRefresh",
+    group = "MDEntries/Trade"
+)]
+#[serde(rename_all = "PascalCase")]
+struct Trade {
+    #[serde(rename = "TradeID")]
+    trade_id: String,
+    #[serde(with = "serde_bytes")]
+    trade_condition: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, FastMessage, Serialize, Deserialize)]
+#[fast(
+    xml = "tests/templates.xml",
+    template = "MDIncRefresh",
+    group = "Statistics"
+)]
+#[serde(rename_all = "PascalCase")]
+struct Stats {
+    trade_volume: i64,
+    num_trades: u16,
+}
+
+#[derive(Debug, PartialEq, FastMessage, Serialize, Deserialize)]
+#[fast(xml = "tests/templates.xml", template = "MDHeartbeat")]
+#[serde(rename_all = "PascalCase")]
+struct Heartbeat {
+    msg_seq_num: u32,
+    sending_time: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Message {
+    MDIncRefresh(IncRefresh),
+    MDHeartbeat(Heartbeat),
+}
+
+fn inc_refresh(seq_num: u32) -> IncRefresh {
+    IncRefresh {
+        message_type: "X".to_string(),
+        msg_seq_num: seq_num,
+        sending_time: 20_240_101_120_000_000 + u64::from(seq_num),
+        last_fragment: Some(true),
+        md_entries: vec![
+            MDEntry {
+                action: 0,
+                md_entry_type: "2".to_string(),
+                security_id: 1001,
+                rpt_seq: 17,
+                md_entry_px: Some(Decimal::new(-2, 12_345)),
+                md_entry_size: Some(5),
+                trade: Some(Trade {
+                    trade_id: "Trade №1".to_string(),
+                    trade_condition: Some(vec![0x01, 0xff]),
+                }),
+            },
+            MDEntry {
+                action: 1,
+                md_entry_type: "0".to_string(),
+                security_id: 1001,
+                rpt_seq: 18,
+                md_entry_px: None,
+                md_entry_size: None,
+                trade: None,
+            },
+        ],
+        stats: Stats {
+            trade_volume: 1_000_000,
+            num_trades: 42,
+        },
+    }
+}
+
+#[test]
+fn encode_typed() {
+    let mut encoder = Encoder::new_from_xml(TEMPLATES).unwrap();
+    let mut typed = BytesMut::new();
+    encoder.encode_typed(&mut typed, &inc_refresh(1)).unwrap();
+    encoder.encode_typed(&mut typed, &inc_refresh(2)).unwrap();
+
+    let mut encoder = Encoder::new_from_xml(TEMPLATES).unwrap();
+    let mut expected =
+        fastlib::to_vec(&mut encoder, &Message::MDIncRefresh(inc_refresh(1))).unwrap();
+    expected.extend(fastlib::to_vec(&mut encoder, &Message::MDIncRefresh(inc_refresh(2))).unwrap());
+    assert_eq!(typed.as_ref(), expected.as_slice());
+}
+
+#[test]
+fn decode_typed() {
+    let mut encoder = Encoder::new_from_xml(TEMPLATES).unwrap();
+    let mut data = fastlib::to_vec(&mut encoder, &Message::MDIncRefresh(inc_refresh(1))).unwrap();
+    data.extend(fastlib::to_vec(&mut encoder, &Message::MDIncRefresh(inc_refresh(2))).unwrap());
+    let mut rdr = Bytes::from(data);
+
+    let mut decoder = Decoder::new_from_xml(TEMPLATES).unwrap();
+    let msg: IncRefresh = decoder.decode_typed(&mut rdr).unwrap();
+    assert_eq!(msg, inc_refresh(1));
+    let msg: IncRefresh = decoder.decode_typed(&mut rdr).unwrap();
+    assert_eq!(msg, inc_refresh(2));
+    assert!(rdr.is_empty());
+}
+
+#[test]
+fn decode_typed_other_template() {
+    let mut encoder = Encoder::new_from_xml(TEMPLATES).unwrap();
+    let heartbeat = Heartbeat {
+        msg_seq_num: 1,
+        sending_time: 20_240_101_120_000_000,
+    };
+    let mut data = BytesMut::new();
+    encoder.encode_typed(&mut data, &heartbeat).unwrap();
+    encoder.encode_typed(&mut data, &inc_refresh(2)).unwrap();
+    let mut rdr = data.freeze();
+
+    // The message of another template is skipped.
+    let mut decoder = Decoder::new_from_xml(TEMPLATES).unwrap();
+    let err = decoder.decode_typed::<IncRefresh>(&mut rdr).unwrap_err();
+    assert!(matches!(err.kind(), Error::Runtime(_)), "{err:?}");
+    let msg: IncRefresh = decoder.decode_typed(&mut rdr).unwrap();
+    assert_eq!(msg, inc_refresh(2));
+}
+
+#[test]
+fn encode_typed_error() {
+    let mut msg = inc_refresh(1);
+    msg.message_type = "Y".to_string();
+    let mut encoder = Encoder::new_from_xml(TEMPLATES).unwrap();
+    let err = encoder
+        .encode_typed(&mut BytesMut::new(), &msg)
+        .unwrap_err();
+    assert_eq!(err.context().unwrap().path, "MDIncRefresh/MessageType");
+}
+
+// `Header` is defined in two template namespaces, the reference resolves to the one of its own namespace.
+#[derive(Debug, PartialEq, FastMessage, Serialize, Deserialize)]
+#[fast(xml = "tests/namespaces.xml", template = "News")]
+#[serde(rename_all = "PascalCase")]
+struct News {
+    msg_seq_num: u32,
+    #[serde(rename = "SenderCompID")]
+    sender_comp_id: String,
+    routing: Option<Routing>,
+    headline: String,
+}
+
+#[derive(Debug, PartialEq, FastMessage, Serialize, Deserialize)]
+#[fast(xml = "tests/namespaces.xml", template = "News", group = "Routing")]
+struct Routing {
+    #[serde(rename = "DeliverToCompID")]
+    deliver_to_comp_id: String,
+}
+
+#[test]
+fn template_namespaces() {
+    #[derive(Serialize)]
+    enum Message<'a> {
+        News(&'a News),
+    }
+
+    const NAMESPACES: &str = include_str!("namespaces.xml");
+    let news = News {
+        msg_seq_num: 7,
+        sender_comp_id: "CQG".to_string(),
+        routing: Some(Routing {
+            deliver_to_comp_id: "ABC".to_string(),
+        }),
+        headline: "Hello".to_string(),
+    };
+    let mut encoder = Encoder::new_from_xml(NAMESPACES).unwrap();
+    let mut typed = BytesMut::new();
+    encoder.encode_typed(&mut typed, &news).unwrap();
+
+    let mut encoder = Encoder::new_from_xml(NAMESPACES).unwrap();
+    let expected = fastlib::to_vec(&mut encoder, &Message::News(&news)).unwrap();
+    assert_eq!(typed.as_ref(), expected.as_slice());
+
+    let mut decoder = Decoder::new_from_xml(NAMESPACES).unwrap();
+    let msg: News = decoder.decode_typed(&mut typed.freeze()).unwrap();
+    assert_eq!(msg, news);
+}
@@ -104,6 +104,19 @@ impl Decimal {
             }
         }
     }
+
+    // Formats the decimal without loss of precision, the way `from_string()` parses it.
+    pub(crate) fn to_exact_string(&self) -> String {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = self.mantissa.unsigned_abs().to_string();
+        if self.exponent >= 0 {
+            return format!("{sign}{digits}{}", "0".repeat(self.exponent as usize));
+        }
+        let scale = self.exponent.unsigned_abs() as usize;
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        format!("{sign}{int}.{frac}")
+    }
 }
 
 /// Format the decimal as number with specific number of digits after the decimal point.
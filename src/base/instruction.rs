@@ -1,15 +1,22 @@
-use std::cell::Cell;
-use std::ops::RangeInclusive;
-use std::rc::Rc;
+use std::borrow::Cow;
+use std::sync::Arc;
 
 use roxmltree::Node;
 
-use crate::base::types::{Dictionary, Operator, Presence, TypeRef};
-use crate::base::value::{Value, ValueType};
+use crate::base::message::MessageFactoryRef;
+use crate::base::time::{Date, TimeOfDay, TimeUnit, Timestamp, parse_epoch};
+use crate::base::types::{
+    Dictionary, Element, Operator, Presence, TypeDefinitions, TypeRef, inherited_attribute,
+    qualified_name, split_qualified_name,
+};
+use crate::base::value::{Value, ValueRef, ValueType};
+use crate::common::context::{ContextSlot, FieldSlots};
 use crate::decoder::decoder::DecoderContext;
+use crate::decoder::reader::{Raw, ReadRaw};
 use crate::encoder::encoder::EncoderContext;
 use crate::encoder::writer::Writer;
-use crate::{Decimal, Error, MessageFactory, MessageVisitor, Reader, Result};
+use crate::utils::xml::XmlElement;
+use crate::{Decimal, Error, ErrorCode, Result};
 
 const MAX_EXPONENT: i32 = 63;
 const MIN_EXPONENT: i32 = -63;
@@ -22,8 +29,8 @@ const MIN_EXPONENT: i32 = -63;
 ///
 /// A primitive field, i.e. a field that is not a group or sequence, can have a field operator. The operator specifies an optimization operation for the field.
 ///
-#[derive(Debug)]
-pub(crate) struct Instruction {
+#[derive(Debug, Clone)]
+pub struct Instruction {
     pub(crate) id: u32,
 
     // The name identifies the corresponding field in the current application type.
@@ -44,8 +51,21 @@ pub(crate) struct Instruction {
     pub(crate) initial_value: Option<Value>,
 
     // Group, Sequence and Decimal have a list of child instructions.
+    // Byte vector and unicode string may have a length instruction that names their length.
     pub(crate) instructions: Vec<Instruction>,
 
+    // Enum and Set have a list of named elements.
+    pub(crate) elements: Vec<Element>,
+
+    // Timestamp and TimeOfDay values are counted in units.
+    pub(crate) unit: TimeUnit,
+
+    // Timestamp and Date values are counted from the epoch (days since 1970-01-01).
+    pub(crate) epoch: i32,
+
+    // Static template reference is resolved in the namespace specified by `templateNs` attribute.
+    pub(crate) template_ns: Option<Arc<str>>,
+
     // The dictionary to use for previous values.
     pub(crate) dictionary: Dictionary,
 
@@ -53,15 +73,21 @@ pub(crate) struct Instruction {
     pub(crate) type_ref: TypeRef,
 
     // Internal key name for lookup in storage
-    pub(crate) key: Rc<str>,
+    pub(crate) key: Arc<str>,
 
     // For ::Sequence it shows if the instruction needs a pmap.
     // For ::Decimal it shows if any of its subcomponent needs a pmap.
-    pub(crate) has_pmap: Cell<bool>,
+    pub(crate) has_pmap: bool,
+
+    // Shows if the field occupies a bit in the presence map of its group.
+    pub(crate) pmap_bit: bool,
+
+    // Location of the previous value in the context storage.
+    pub(crate) slot: ContextSlot,
 }
 
 impl Instruction {
-    fn new(id: u32, name: &str, type_: ValueType) -> Self {
+    pub(crate) fn new(id: u32, name: &str, type_: ValueType) -> Self {
         let nm: String;
         let ky: String;
         match type_ {
@@ -82,25 +108,21 @@ impl Instruction {
             operator: Operator::None,
             initial_value: None,
             instructions: Vec::new(),
+            elements: Vec::new(),
+            unit: TimeUnit::default(),
+            epoch: 0,
+            template_ns: None,
             dictionary: Dictionary::Inherit,
             type_ref: TypeRef::Any,
-            key: Rc::from(ky),
-            has_pmap: Cell::new(false),
+            key: Arc::from(ky),
+            has_pmap: false,
+            pmap_bit: false,
+            slot: ContextSlot::None,
         }
     }
 
-    #[allow(clippy::too_many_lines)]
-    pub fn from_node(node: Node) -> Result<Self> {
-        let id = node.attribute("id").unwrap_or("0").parse::<u32>()?;
-        let name = node.attribute("name").unwrap_or("");
-        let unicode = match node.attribute("charset") {
-            Some("unicode") => true,
-            Some(charset) => {
-                return Err(Error::Static(format!("unknown charset: {charset}")));
-            }
-            _ => false,
-        };
-        let type_ = ValueType::new_from_tag(node.tag_name().name(), unicode)?;
+    // Creates an instruction checking that the field has the attributes its type requires.
+    pub(crate) fn new_checked(id: u32, name: &str, type_: ValueType) -> Result<Self> {
         match type_ {
             ValueType::Mantissa
             | ValueType::Exponent
@@ -128,34 +150,61 @@ impl Instruction {
                 }
             }
         }
+        Ok(Self::new(id, name, type_))
+    }
+
+    pub(crate) fn from_node(node: Node, defs: &TypeDefinitions) -> Result<Self> {
+        // FAST 1.2 field: the field attributes apply to the nested or referenced type element.
+        if node.tag_name().name() == "field" {
+            return Self::from_type_node(node, defs.resolve(node)?, defs);
+        }
+        Self::from_type_node(node, node, defs)
+    }
 
-        let mut instruction = Instruction::new(id, name, type_);
-        if let Some(p) = node.attribute("presence") {
+    // Field attributes (name, id, presence, etc.) are taken from `field` node, falling back to `node` which defines the type.
+    #[allow(clippy::too_many_lines)]
+    fn from_type_node(field: Node, node: Node, defs: &TypeDefinitions) -> Result<Self> {
+        let attribute = |name| field.attribute(name).or_else(|| node.attribute(name));
+        let id = attribute("id").unwrap_or("0").parse::<u32>()?;
+        let name = attribute("name").unwrap_or("");
+        let unicode = match node.attribute("charset") {
+            Some("unicode") => true,
+            Some(charset) => {
+                return Err(Error::Static(format!("unknown charset: {charset}")));
+            }
+            _ => false,
+        };
+        let type_ = ValueType::new_from_tag(node.tag_name().name(), unicode)?;
+        let mut instruction = Instruction::new_checked(id, name, type_)?;
+        if let Some(p) = attribute("presence") {
             instruction.presence = Presence::from_str(p)?;
         }
-        if let Some(d) = node.attribute("dictionary") {
+        if let Some(d) = attribute("dictionary") {
             instruction.dictionary = Dictionary::from_str(d);
         }
-        if let Some(k) = node.attribute("key") {
-            instruction.key = Rc::from(k);
+        if let Some(k) = attribute("key") {
+            instruction.key = Arc::from(k);
         }
-        if let Some(k) = node.attribute("typeRef") {
-            instruction.type_ref = TypeRef::from_str(k);
+        if let Some(k) = attribute("typeRef") {
+            let ns = inherited_attribute(field, "ns");
+            instruction.type_ref = TypeRef::from_str(&qualified_name(ns, k));
         }
 
         match instruction.value_type {
-            ValueType::TemplateReference => {}
+            ValueType::TemplateReference => {
+                instruction.template_ns = inherited_attribute(field, "templateNs").map(Arc::from);
+            }
 
             ValueType::Group => {
                 for n in node.children().filter(Node::is_element) {
-                    let i = Instruction::from_node(n)?;
+                    let i = Instruction::from_node(n, defs)?;
                     instruction.add_instruction(i);
                 }
             }
 
             ValueType::Sequence => {
                 for (i, c) in node.children().filter(Node::is_element).enumerate() {
-                    let mut instr = Instruction::from_node(c)?;
+                    let mut instr = Instruction::from_node(c, defs)?;
                     if i == 0 {
                         if let ValueType::Length = instr.value_type {
                             if instr.name.is_empty() {
@@ -194,10 +243,10 @@ impl Instruction {
                     let op_name = op_node.tag_name().name();
                     match op_name {
                         "exponent" => {
-                            exponent = Some(Instruction::from_node(op_node)?);
+                            exponent = Some(Instruction::from_node(op_node, defs)?);
                         }
                         "mantissa" => {
-                            mantissa = Some(Instruction::from_node(op_node)?);
+                            mantissa = Some(Instruction::from_node(op_node, defs)?);
                         }
                         _ => {
                             operator = Some(Operator::new_from_tag(op_name)?);
@@ -208,66 +257,75 @@ impl Instruction {
                     }
                 }
 
-                let mut op: Operator;
-                let mut ex: Instruction;
-                let mut mn: Instruction;
-                match (operator, exponent, mantissa) {
-                    // No elements.
-                    (None, None, None) => {
-                        op = Operator::None;
-                        ex = Instruction::new(0, "exponent", ValueType::Exponent);
-                        mn = Instruction::new(0, "mantissa", ValueType::Mantissa);
-                    }
-                    // Only one element and it is an operation.
-                    (Some(o), None, None) => {
-                        op = o;
-                        ex = Instruction::new(0, "exponent", ValueType::Exponent);
-                        mn = Instruction::new(0, "mantissa", ValueType::Mantissa);
-                        match o {
-                            Operator::Delta | Operator::Increment => {
-                                op = Operator::None;
-                                ex.operator = o;
-                                mn.operator = o;
-                            }
-                            _ => {}
-                        }
-                        if let Some(v) = initial_value {
-                            let d = Decimal::from_string(&v)?; // [ERR S3]
-                            ex.initial_value = Some(Value::Int32(d.exponent));
-                            mn.initial_value = Some(Value::Int64(d.mantissa));
-                        }
-                    }
-                    // Elements are decimal subcomponents.
-                    (None, Some(e), Some(m)) => {
-                        op = Operator::None;
-                        ex = e;
-                        mn = m;
-                    }
-                    _ => {
-                        return Err(Error::Static("invalid decimal elements".to_string()));
+                instruction.set_decimal_components(
+                    operator,
+                    initial_value.as_deref(),
+                    exponent,
+                    mantissa,
+                )?;
+            }
+
+            ValueType::Enum | ValueType::Set => {
+                let mut initial_value = None;
+                for n in node.children().filter(Node::is_element) {
+                    if n.tag_name().name() == "element" {
+                        let name = n.attribute("name").ok_or_else(|| {
+                            Error::Static(format!("{} element has no name", instruction.name))
+                        })?;
+                        let value = n.attribute("value").map(str::parse::<u32>).transpose()?;
+                        instruction.add_element(name, value)?;
+                    } else {
+                        instruction.operator = Operator::new_from_tag(n.tag_name().name())?;
+                        initial_value = n.attribute("value");
                     }
                 }
-                // Set proper presence flag.
-                ex.presence = instruction.presence;
-                mn.presence = Presence::Mandatory;
-                // Set proper storage keys if it is not set explicitly with 'key' attribute.
-                if ex.key.is_empty() {
-                    ex.key = Rc::from(format!("{}:exponent", &instruction.key));
+                if instruction.elements.is_empty() {
+                    return Err(Error::Static(format!(
+                        "{} {} has no elements",
+                        instruction.value_type.type_str(),
+                        instruction.name
+                    )));
                 }
-                if mn.key.is_empty() {
-                    mn.key = Rc::from(format!("{}:mantissa", &instruction.key));
+                // Elements are needed to convert the initial value.
+                if let Some(s) = initial_value {
+                    instruction.set_initial_value(s)?;
                 }
-                instruction.operator = op;
-                // Put subcomponents into instruction.
-                instruction.add_instruction(ex);
-                instruction.add_instruction(mn);
             }
 
-            _ => {
+            ValueType::Timestamp | ValueType::Date | ValueType::TimeOfDay => {
+                if let Some(unit) = node.attribute("unit") {
+                    instruction.set_unit(TimeUnit::from_str(unit)?)?;
+                }
+                if let Some(epoch) = node.attribute("epoch") {
+                    instruction.set_epoch(parse_epoch(epoch)?)?;
+                }
+                // Unit and epoch are needed to convert the initial value.
                 if let Some(operator) = node.children().find(Node::is_element) {
                     instruction.operator = Operator::new_from_tag(operator.tag_name().name())?;
                     if let Some(s) = operator.attribute("value") {
-                        instruction.set_initial_value(s)?; // [ERR S3]
+                        instruction.set_initial_value(s)?;
+                    }
+                }
+            }
+
+            _ => {
+                let mut operator = None;
+                for n in node.children().filter(Node::is_element) {
+                    if n.tag_name().name() == "length" {
+                        let id = n.attribute("id").unwrap_or("0").parse::<u32>()?;
+                        instruction.add_length(id, n.attribute("name"))?;
+                    } else if operator.replace(n).is_some() {
+                        return Err(Error::Static(format!(
+                            "{} {} has more than one operator",
+                            instruction.value_type.type_str(),
+                            instruction.name
+                        )));
+                    }
+                }
+                if let Some(operator) = operator {
+                    instruction.operator = Operator::new_from_tag(operator.tag_name().name())?;
+                    if let Some(s) = operator.attribute("value") {
+                        instruction.set_initial_value(s)?;
                     }
                 }
             }
@@ -287,35 +345,47 @@ impl Instruction {
                 // The constant operator is applicable to all field types.
                 // It is a static error [ERR S4] if the instruction context has no initial value.
                 if self.initial_value.is_none() {
-                    return Err(Error::Static(
-                        "constant operator has no initial value".to_string(),
-                    )); // [ERR S4]
+                    return Err(Error::Spec(
+                        ErrorCode::S4,
+                        format!("constant operator of {} has no initial value", self.name),
+                    ));
                 }
             }
             Operator::Default => {
                 // The default operator is applicable to all field types.
                 // Unless the field has optional presence, it is a static error [ERR S5] if the instruction context has no initial value.
                 if !self.is_optional() && self.initial_value.is_none() {
-                    return Err(Error::Static(
-                        "default operator has no initial value".to_string(),
-                    )); // [ERR S5]
+                    return Err(Error::Spec(
+                        ErrorCode::S5,
+                        format!("default operator of {} has no initial value", self.name),
+                    ));
                 }
             }
             Operator::Increment => {
                 // The increment operator is applicable to integer field types.
                 match self.value_type {
-                    ValueType::UInt32
+                    ValueType::UInt8
+                    | ValueType::Int8
+                    | ValueType::UInt16
+                    | ValueType::Int16
+                    | ValueType::UInt32
                     | ValueType::Int32
                     | ValueType::UInt64
                     | ValueType::Int64
                     | ValueType::Length
                     | ValueType::Exponent
-                    | ValueType::Mantissa => {}
+                    | ValueType::Mantissa
+                    | ValueType::Timestamp
+                    | ValueType::Date
+                    | ValueType::TimeOfDay => {}
                     _ => {
-                        return Err(Error::Static(format!(
-                            "increment operator is not applicable to {} field type",
-                            self.value_type.type_str()
-                        ))); // [ERR S2]
+                        return Err(Error::Spec(
+                            ErrorCode::S2,
+                            format!(
+                                "increment operator is not applicable to {} field type",
+                                self.value_type.type_str()
+                            ),
+                        ));
                     }
                 }
             }
@@ -324,10 +394,13 @@ impl Instruction {
                 match self.value_type {
                     ValueType::ASCIIString | ValueType::UnicodeString | ValueType::Bytes => {}
                     _ => {
-                        return Err(Error::Static(format!(
-                            "tail operator is not applicable to {} field type",
-                            self.value_type.type_str()
-                        ))); // [ERR S2]
+                        return Err(Error::Spec(
+                            ErrorCode::S2,
+                            format!(
+                                "tail operator is not applicable to {} field type",
+                                self.value_type.type_str()
+                            ),
+                        ));
                     }
                 }
             }
@@ -339,6 +412,420 @@ impl Instruction {
         self.instructions.push(instruction);
     }
 
+    // A decimal field has either one operator for the whole field or individual exponent and mantissa instructions.
+    // Delta and increment operators of the whole field apply to both subcomponents.
+    pub(crate) fn set_decimal_components(
+        &mut self,
+        operator: Option<Operator>,
+        initial_value: Option<&str>,
+        exponent: Option<Instruction>,
+        mantissa: Option<Instruction>,
+    ) -> Result<()> {
+        let mut op: Operator;
+        let mut ex: Instruction;
+        let mut mn: Instruction;
+        match (operator, exponent, mantissa) {
+            // No elements.
+            (None, None, None) => {
+                op = Operator::None;
+                ex = Instruction::new(0, "exponent", ValueType::Exponent);
+                mn = Instruction::new(0, "mantissa", ValueType::Mantissa);
+            }
+            // Only one element and it is an operation.
+            (Some(o), None, None) => {
+                op = o;
+                ex = Instruction::new(0, "exponent", ValueType::Exponent);
+                mn = Instruction::new(0, "mantissa", ValueType::Mantissa);
+                match o {
+                    Operator::Delta | Operator::Increment => {
+                        op = Operator::None;
+                        ex.operator = o;
+                        mn.operator = o;
+                    }
+                    _ => {}
+                }
+                if let Some(v) = initial_value {
+                    let d = Decimal::from_string(v).map_err(|e| self.conversion_error(v, &e))?;
+                    ex.initial_value = Some(Value::Int32(d.exponent));
+                    mn.initial_value = Some(Value::Int64(d.mantissa));
+                }
+            }
+            // Elements are decimal subcomponents.
+            (None, Some(e), Some(m)) => {
+                op = Operator::None;
+                ex = e;
+                mn = m;
+            }
+            _ => {
+                return Err(Error::Static("invalid decimal elements".to_string()));
+            }
+        }
+        // Set proper presence flag.
+        ex.presence = self.presence;
+        mn.presence = Presence::Mandatory;
+        // Set proper storage keys if it is not set explicitly with 'key' attribute.
+        if ex.key.is_empty() {
+            ex.key = Arc::from(format!("{}:exponent", &self.key));
+        }
+        if mn.key.is_empty() {
+            mn.key = Arc::from(format!("{}:mantissa", &self.key));
+        }
+        self.operator = op;
+        // Put subcomponents into instruction.
+        self.add_instruction(ex);
+        self.add_instruction(mn);
+        Ok(())
+    }
+
+    // The length element of a byte vector or a unicode string names the length preamble of the value.
+    // It has no operator and doesn't change the encoding of the field.
+    pub(crate) fn add_length(&mut self, id: u32, name: Option<&str>) -> Result<()> {
+        match self.value_type {
+            ValueType::Bytes | ValueType::UnicodeString => {}
+            _ => {
+                return Err(Error::Static(format!(
+                    "{} {} can't have length element",
+                    self.value_type.type_str(),
+                    self.name
+                )));
+            }
+        }
+        if !self.instructions.is_empty() {
+            return Err(Error::Static(format!(
+                "{} {} has more than one length element",
+                self.value_type.type_str(),
+                self.name
+            )));
+        }
+        let mut length = match name {
+            Some(name) => Instruction::new(id, name, ValueType::Length),
+            None => Instruction::new(id, &format!("{}:length", self.name), ValueType::Length),
+        };
+        length.presence = self.presence;
+        self.add_instruction(length);
+        Ok(())
+    }
+
+    // Timestamp and timeOfDay values are counted in units.
+    pub(crate) fn set_unit(&mut self, unit: TimeUnit) -> Result<()> {
+        match self.value_type {
+            ValueType::Timestamp | ValueType::TimeOfDay => {
+                self.unit = unit;
+                Ok(())
+            }
+            _ => Err(Error::Static(format!(
+                "{} {} can't have unit",
+                self.value_type.type_str(),
+                self.name
+            ))),
+        }
+    }
+
+    // Timestamp and date values are counted from the epoch.
+    pub(crate) fn set_epoch(&mut self, epoch: i32) -> Result<()> {
+        match self.value_type {
+            ValueType::Timestamp | ValueType::Date => {
+                self.epoch = epoch;
+                Ok(())
+            }
+            _ => Err(Error::Static(format!(
+                "{} {} can't have epoch",
+                self.value_type.type_str(),
+                self.name
+            ))),
+        }
+    }
+
+    // Enum elements have integer values, the value of an element is one greater than the previous one if not specified.
+    // Set elements are bits of the set value in the order of definition.
+    pub(crate) fn add_element(&mut self, name: &str, value: Option<u32>) -> Result<()> {
+        let value = if self.value_type == ValueType::Set {
+            if self.elements.len() == 64 {
+                return Err(Error::Static(format!(
+                    "set {} has more than 64 elements",
+                    self.name
+                )));
+            }
+            1 << self.elements.len()
+        } else {
+            let value = match value {
+                Some(v) => u64::from(v),
+                None => self.elements.last().map_or(0, |e| e.value + 1),
+            };
+            if value > u64::from(u32::MAX) {
+                return Err(Error::Static(format!(
+                    "enum {} element {name} is out of range",
+                    self.name
+                )));
+            }
+            value
+        };
+        if self
+            .elements
+            .iter()
+            .any(|e| *e.name == *name || e.value == value)
+        {
+            return Err(Error::Static(format!(
+                "{} {} has duplicate element {name}",
+                self.value_type.type_str(),
+                self.name
+            )));
+        }
+        self.elements.push(Element {
+            name: Arc::from(name),
+            value,
+        });
+        Ok(())
+    }
+
+    // Converts the instruction to XML element the instruction can be parsed from.
+    // `ns` and `template_ns` are the namespaces inherited from the parent elements.
+    #[allow(clippy::too_many_lines)]
+    pub(crate) fn to_xml(&self, ns: Option<&str>, template_ns: Option<&str>) -> XmlElement {
+        let mut el = XmlElement::new(self.value_type.type_str());
+        match self.value_type {
+            ValueType::Exponent | ValueType::Mantissa => {}
+            ValueType::TemplateReference => {
+                if !self.name.is_empty() {
+                    el.attribute("name", &self.name);
+                    if self.template_ns.as_deref() != template_ns {
+                        el.attribute("templateNs", self.template_ns.as_deref().unwrap_or(""));
+                    }
+                }
+                return el;
+            }
+            _ => el.attribute("name", &self.name),
+        }
+        if self.id != 0 {
+            el.attribute("id", self.id);
+        }
+        if self.is_optional() && !matches!(self.value_type, ValueType::Exponent) {
+            el.attribute("presence", "optional");
+        }
+        if self.value_type == ValueType::UnicodeString {
+            el.attribute("charset", "unicode");
+        }
+        if self.unit != TimeUnit::default() {
+            el.attribute("unit", self.unit.as_str());
+        }
+        if self.epoch != 0 {
+            el.attribute("epoch", Date::new(self.epoch, 0));
+        }
+        if let Some(d) = self.dictionary.name() {
+            el.attribute("dictionary", d);
+        }
+        if *self.key != *self.name {
+            el.attribute("key", &self.key);
+        }
+        let mut ns = ns;
+        if let TypeRef::ApplicationType(name) = &self.type_ref {
+            let (type_ns, name) = split_qualified_name(name);
+            if type_ns != ns {
+                el.attribute("ns", type_ns.unwrap_or(""));
+                ns = type_ns;
+            }
+            el.attribute("typeRef", name);
+        }
+
+        match self.value_type {
+            ValueType::Group => {
+                for i in &self.instructions {
+                    el.child(i.to_xml(ns, template_ns));
+                }
+            }
+            ValueType::Sequence => {
+                let length = &self.instructions[0];
+                if length.id != 0
+                    || length.operator != Operator::None
+                    || *length.key != *length.name
+                {
+                    let mut l = length.to_xml(ns, template_ns);
+                    l.remove_attribute("presence");
+                    if length.name == format!("{}:length", self.name) {
+                        l.remove_attribute("name");
+                    }
+                    el.child(l);
+                }
+                for i in &self.instructions[1..] {
+                    el.child(i.to_xml(ns, template_ns));
+                }
+            }
+            ValueType::Decimal => {
+                let (ex, mn) = (&self.instructions[0], &self.instructions[1]);
+                if self.operator != Operator::None {
+                    // Single field operator of the whole decimal.
+                    let mut op = XmlElement::new(self.operator.tag());
+                    if let (Some(Value::Int32(e)), Some(Value::Int64(m))) =
+                        (&ex.initial_value, &mn.initial_value)
+                    {
+                        op.attribute("value", Decimal::new(*e, *m).to_exact_string());
+                    }
+                    el.child(op);
+                } else if ex.operator != Operator::None
+                    || mn.operator != Operator::None
+                    || ex.initial_value.is_some()
+                    || mn.initial_value.is_some()
+                    || *ex.key != *format!("{}:exponent", self.key)
+                    || *mn.key != *format!("{}:mantissa", self.key)
+                    || ex.dictionary != Dictionary::Inherit
+                    || mn.dictionary != Dictionary::Inherit
+                {
+                    // Individual operators of subcomponents.
+                    for (i, suffix) in [(ex, "exponent"), (mn, "mantissa")] {
+                        let mut c = i.to_xml(ns, template_ns);
+                        if *i.key == *format!("{}:{suffix}", self.key) {
+                            c.remove_attribute("key");
+                        }
+                        el.child(c);
+                    }
+                }
+            }
+            _ => {
+                for e in &self.elements {
+                    let mut element = XmlElement::new("element");
+                    element.attribute("name", &e.name);
+                    if self.value_type == ValueType::Enum {
+                        element.attribute("value", e.value);
+                    }
+                    el.child(element);
+                }
+                if let Some(length) = self.instructions.first() {
+                    let mut l = XmlElement::new("length");
+                    if length.name != format!("{}:length", self.name) {
+                        l.attribute("name", &length.name);
+                    }
+                    if length.id != 0 {
+                        l.attribute("id", length.id);
+                    }
+                    el.child(l);
+                }
+                if self.operator != Operator::None {
+                    let mut op = XmlElement::new(self.operator.tag());
+                    if let Some(v) = &self.initial_value {
+                        op.attribute("value", self.initial_value_to_string(v));
+                    }
+                    el.child(op);
+                }
+            }
+        }
+        el
+    }
+
+    // Formats the initial value the way `set_initial_value()` parses it.
+    fn initial_value_to_string(&self, value: &Value) -> String {
+        match self.typed_value(Some(ValueRef::from(value.clone()))) {
+            Ok(Some(v)) => v.into_owned().to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// Returns the field id, zero if not specified.
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the field name. The name is empty for decimal subcomponents and dynamic template references.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the field type.
+    #[must_use]
+    pub fn value_type(&self) -> &ValueType {
+        &self.value_type
+    }
+
+    /// Returns the field presence.
+    #[must_use]
+    pub fn presence(&self) -> Presence {
+        self.presence
+    }
+
+    /// Returns the field operator. Sequences have the operator on their length field,
+    /// decimals with individual operators have them on their subcomponents.
+    #[must_use]
+    pub fn operator(&self) -> Operator {
+        self.operator
+    }
+
+    /// Returns the initial value of the operator. Enum, set and time values are returned as [`Value::Enum`],
+    /// [`Value::Set`], [`Value::Timestamp`], etc. Initial values of decimals are set on their subcomponents.
+    #[must_use]
+    pub fn initial_value(&self) -> Option<Value> {
+        let value = self.initial_value.clone()?;
+        match self.typed_value(Some(ValueRef::from(value.clone()))) {
+            Ok(Some(v)) => Some(v.into_owned()),
+            _ => Some(value),
+        }
+    }
+
+    /// Returns the dictionary of the operator.
+    /// [`Dictionary::Inherit`] means the dictionary of the enclosing group or template.
+    #[must_use]
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dictionary
+    }
+
+    /// Returns the dictionary key of the operator.
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the application type of the field.
+    #[must_use]
+    pub fn type_ref(&self) -> &TypeRef {
+        &self.type_ref
+    }
+
+    /// Returns the child instructions: fields of a group, the length and the fields of a sequence,
+    /// exponent and mantissa of a decimal, and the length of a byte vector or unicode string if it is named.
+    #[must_use]
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns the elements of an enum or set field.
+    #[must_use]
+    pub fn elements(&self) -> &[Element] {
+        &self.elements
+    }
+
+    /// Returns the unit of a timestamp or timeOfDay field.
+    #[must_use]
+    pub fn unit(&self) -> TimeUnit {
+        self.unit
+    }
+
+    /// Returns the epoch of a timestamp or date field as the number of days since 1970-01-01.
+    #[must_use]
+    pub fn epoch(&self) -> i32 {
+        self.epoch
+    }
+
+    /// Returns the namespace of a static template reference.
+    #[must_use]
+    pub fn template_ns(&self) -> Option<&str> {
+        self.template_ns.as_deref()
+    }
+
+    /// Returns `true` if the field occupies a bit in the presence map of the enclosing group or template.
+    #[must_use]
+    pub fn has_presence_map_bit(&self) -> bool {
+        self.pmap_bit
+    }
+
+    /// Returns `true` if the elements of a sequence have a presence map
+    /// or the subcomponents of a decimal occupy bits in the presence map.
+    #[must_use]
+    pub fn has_presence_map(&self) -> bool {
+        self.has_pmap
+    }
+
+    /// Returns `true` if the field is optional.
+    #[must_use]
     pub fn is_optional(&self) -> bool {
         match self.presence {
             Presence::Mandatory => false,
@@ -359,9 +846,14 @@ impl Instruction {
     // The value is a string of Unicode characters. This value is converted to the type of the field as defined
     // in the Converting from String section. The possible dynamic and reportable errors that may occur during
     // conversion are treated as static errors [ERR S3] when interpreting the initial value.
-    fn set_initial_value(&mut self, value: &str) -> Result<()> {
-        match self.value_type {
-            ValueType::UInt32
+    pub(crate) fn set_initial_value(&mut self, value: &str) -> Result<()> {
+        let converted = match self.value_type {
+            ValueType::Boolean
+            | ValueType::UInt8
+            | ValueType::Int8
+            | ValueType::UInt16
+            | ValueType::Int16
+            | ValueType::UInt32
             | ValueType::Int32
             | ValueType::UInt64
             | ValueType::Int64
@@ -370,27 +862,260 @@ impl Instruction {
             | ValueType::Mantissa
             | ValueType::ASCIIString
             | ValueType::UnicodeString
-            | ValueType::Bytes => {
-                self.initial_value = Some(self.value_type.str_to_value(value)?);
-                Ok(())
-            }
+            | ValueType::Bytes => self.value_type.str_to_value(value).map(Some),
+            // The value is an element name, a time in ISO 8601 format or an integer.
+            ValueType::Enum
+            | ValueType::Set
+            | ValueType::Timestamp
+            | ValueType::Date
+            | ValueType::TimeOfDay => self
+                .value_type
+                .str_to_value(value)
+                .and_then(|v| self.wire_value(Some(v))),
             // If the field is of type decimal, the value resulting from the conversion is normalized.
             ValueType::Decimal => unreachable!(),
-            _ => Err(Error::Static(format!(
-                "cannot set initial value to {}",
-                self.value_type.type_str()
+            _ => {
+                return Err(Error::Static(format!(
+                    "cannot set initial value to {}",
+                    self.value_type.type_str()
+                )));
+            }
+        };
+        self.initial_value = converted.map_err(|e| self.conversion_error(value, &e))?;
+        Ok(())
+    }
+
+    fn conversion_error(&self, value: &str, err: &Error) -> Error {
+        Error::Spec(
+            ErrorCode::S3,
+            format!(
+                "initial value {value:?} of {} {} can't be converted: {err}",
+                self.value_type.type_str(),
+                self.name
+            ),
+        )
+    }
+
+    // Enum, set and time values are integers in the stream and in dictionaries.
+    // Converts the integer value to the value of the field type, i.e. attaches element names
+    // to enum and set values and the unit and the epoch to time values.
+    // It is a dynamic error [ERR D2] if the value is not one of the elements or not a valid time of day.
+    pub(crate) fn typed_value<'b>(
+        &self,
+        value: Option<ValueRef<'b>>,
+    ) -> Result<Option<ValueRef<'b>>> {
+        match (&self.value_type, value) {
+            (ValueType::Enum, Some(ValueRef::UInt32(v))) => {
+                match self.elements.iter().find(|e| e.value == u64::from(v)) {
+                    Some(e) => Ok(Some(ValueRef::Enum(v, e.name.clone()))),
+                    None => Err(Error::Spec(
+                        ErrorCode::D2,
+                        format!("enum value is out of range: {v}"),
+                    )),
+                }
+            }
+            (ValueType::Set, Some(ValueRef::UInt64(v))) => {
+                let names = self
+                    .elements
+                    .iter()
+                    .filter(|e| v & e.value != 0)
+                    .map(|e| e.name.clone())
+                    .collect();
+                if v & !self.set_mask() != 0 {
+                    return Err(Error::Spec(
+                        ErrorCode::D2,
+                        format!("set value is out of range: {v:#x}"),
+                    ));
+                }
+                Ok(Some(ValueRef::Set(v, names)))
+            }
+            (ValueType::Timestamp, Some(ValueRef::Int64(v))) => Ok(Some(ValueRef::Timestamp(
+                Timestamp::new(v, self.unit, self.epoch),
             ))),
+            (ValueType::Date, Some(ValueRef::Int32(v))) => {
+                Ok(Some(ValueRef::Date(Date::new(v, self.epoch))))
+            }
+            (ValueType::TimeOfDay, Some(ValueRef::UInt64(v))) => {
+                let t = TimeOfDay::new(v, self.unit);
+                if !t.is_valid() {
+                    return Err(Error::Spec(
+                        ErrorCode::D2,
+                        format!("time of day is out of range: {v}"),
+                    ));
+                }
+                Ok(Some(ValueRef::TimeOfDay(t)))
+            }
+            (_, value) => Ok(value),
         }
     }
 
-    #[allow(clippy::too_many_lines)]
-    pub(crate) fn extract<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<Value>>
+    // Converts the value of a field to the integer value used in the stream.
+    // Enum and set values can be given by names or integers.
+    // Time values can be given by times in any unit and epoch, by ISO 8601 strings or by integers in units of the field.
+    pub(crate) fn wire_value(&self, value: Option<Value>) -> Result<Option<Value>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        match self.value_type {
+            ValueType::Enum => {
+                let value = match value {
+                    Value::Enum(v, name) if name.is_empty() => u64::from(v),
+                    Value::Enum(_, name) => self.element_value(&name)?,
+                    Value::ASCIIString(name) | Value::UnicodeString(name) => {
+                        self.element_value(&name)?
+                    }
+                    v => match v.convert_to(&ValueType::UInt32)? {
+                        Value::UInt32(v) => u64::from(v),
+                        v => {
+                            return Err(self.type_mismatch("Enum", &v));
+                        }
+                    },
+                };
+                if !self.elements.iter().any(|e| e.value == value) {
+                    return Err(Error::Runtime(format!(
+                        "enum {} has no element with value {value}",
+                        self.name
+                    )));
+                }
+                Ok(Some(Value::UInt32(value as u32)))
+            }
+            ValueType::Set => {
+                let mut value = match value {
+                    Value::Set(v, names) => {
+                        let mut v = v;
+                        for name in &names {
+                            v |= self.element_value(name)?;
+                        }
+                        v
+                    }
+                    Value::ASCIIString(name) | Value::UnicodeString(name) => {
+                        self.element_value(&name)?
+                    }
+                    Value::Enum(_, name) => self.element_value(&name)?,
+                    v => match v.convert_to(&ValueType::UInt64)? {
+                        Value::UInt64(v) => v,
+                        v => {
+                            return Err(self.type_mismatch("Set", &v));
+                        }
+                    },
+                };
+                if value & !self.set_mask() != 0 {
+                    value &= !self.set_mask();
+                    return Err(Error::Runtime(format!(
+                        "set {} has no elements for bits {value:#x}",
+                        self.name
+                    )));
+                }
+                Ok(Some(Value::UInt64(value)))
+            }
+            ValueType::Timestamp => {
+                let t = match value {
+                    Value::Timestamp(t) => t,
+                    Value::ASCIIString(s) | Value::UnicodeString(s) => s.parse()?,
+                    v => return self.time_integer(v, &ValueType::Int64),
+                };
+                Ok(Some(Value::Int64(t.convert(self.unit, self.epoch)?.value)))
+            }
+            ValueType::Date => {
+                let d = match value {
+                    Value::Date(d) => d,
+                    Value::ASCIIString(s) | Value::UnicodeString(s) => s.parse()?,
+                    v => return self.time_integer(v, &ValueType::Int32),
+                };
+                Ok(Some(Value::Int32(d.convert(self.epoch)?.days)))
+            }
+            ValueType::TimeOfDay => {
+                let t = match value {
+                    Value::TimeOfDay(t) => t,
+                    Value::ASCIIString(s) | Value::UnicodeString(s) => s.parse()?,
+                    v => {
+                        let v = self.time_integer(v, &ValueType::UInt64)?;
+                        if let Some(Value::UInt64(n)) = v
+                            && !TimeOfDay::new(n, self.unit).is_valid()
+                        {
+                            return Err(Error::Runtime(format!(
+                                "timeOfDay {} is out of range: {n}",
+                                self.name
+                            )));
+                        }
+                        return Ok(v);
+                    }
+                };
+                Ok(Some(Value::UInt64(t.convert(self.unit)?.value)))
+            }
+            _ => Ok(Some(value)),
+        }
+    }
+
+    // It is a dynamic error [ERR D1] if the value can't be converted to the type of the field.
+    fn type_mismatch(&self, type_: &str, value: &dyn std::fmt::Debug) -> Error {
+        Error::Spec(
+            ErrorCode::D1,
+            format!(
+                "Field {} must have {type_} value, got: {value:?} instead",
+                self.name
+            ),
+        )
+    }
+
+    // Integer value of a time field is a number of units of the field.
+    fn time_integer(&self, value: Value, type_: &ValueType) -> Result<Option<Value>> {
+        match value.convert_to(type_)? {
+            v @ (Value::Int64(_) | Value::Int32(_) | Value::UInt64(_)) => Ok(Some(v)),
+            v => Err(self.type_mismatch(self.value_type.type_str(), &v)),
+        }
+    }
+
+    // Checks if the dictionary value has the type of the field.
+    // Enum, set and time fields have integer values in dictionaries.
+    pub(crate) fn matches_dictionary_value(&self, v: &Value) -> bool {
+        match (&self.value_type, v) {
+            (ValueType::Enum, Value::UInt32(_))
+            | (ValueType::Set | ValueType::TimeOfDay, Value::UInt64(_))
+            | (ValueType::Timestamp, Value::Int64(_))
+            | (ValueType::Date, Value::Int32(_)) => true,
+            (t, v) => t.matches_type(v),
+        }
+    }
+
+    // Type dependant default base value for delta and tail operators.
+    // Enum, set and time fields have integer values in dictionaries.
+    pub(crate) fn default_base_value(&self) -> Result<Value> {
+        match self.value_type {
+            ValueType::Enum => Ok(Value::UInt32(0)),
+            ValueType::Set | ValueType::TimeOfDay => Ok(Value::UInt64(0)),
+            ValueType::Timestamp => Ok(Value::Int64(0)),
+            ValueType::Date => Ok(Value::Int32(0)),
+            _ => self.value_type.to_default_value(),
+        }
+    }
+
+    fn element_value(&self, name: &str) -> Result<u64> {
+        match self.elements.iter().find(|e| *e.name == *name) {
+            Some(e) => Ok(e.value),
+            None => Err(Error::Runtime(format!(
+                "{} {} has no element {name}",
+                self.value_type.type_str(),
+                self.name
+            ))),
+        }
+    }
+
+    fn set_mask(&self) -> u64 {
+        self.elements.iter().fold(0, |m, e| m | e.value)
+    }
+
+    pub(crate) fn extract<'b, R, M>(
+        &self,
+        s: &mut DecoderContext<R, M>,
+        slots: FieldSlots,
+    ) -> Result<Option<ValueRef<'b>>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
         match self.operator {
-            Operator::None => Ok(self.read(s)?),
+            Operator::None => Ok(self.read(s, slots)?),
 
             // The constant operator specifies that the value of a field will always be the same.
             // The value of the field is the initial value. It is a static error [ERR S4] if the instruction context
@@ -398,7 +1123,7 @@ impl Instruction {
             Operator::Constant => {
                 let v = if !self.is_optional() || s.pmap_next_bit_set() {
                     match &self.initial_value {
-                        Some(v) => Some(v.clone()),
+                        Some(v) => Some(v.clone().into()),
                         None => unreachable!(),
                     }
                 } else {
@@ -413,75 +1138,96 @@ impl Instruction {
             // the field is considered absent when there is no value in the stream.
             Operator::Default => {
                 if s.pmap_next_bit_set() {
-                    Ok(self.read(s)?)
+                    Ok(self.read(s, slots)?)
                 } else {
                     if self.is_nullable() && !self.is_optional() {
-                        return Err(Error::Runtime(
+                        return Err(Error::Spec(
+                            ErrorCode::D6,
                             "default operator has no default value".to_string(),
-                        )); // [ERR D6])
+                        ));
                     }
-                    Ok(self.initial_value.clone())
+                    Ok(self.initial_value.clone().map(ValueRef::from))
                 }
             }
 
+            // Operators that use the previous value always produce owned values.
+            _ => Ok(self.extract_prev(s, slots)?.map(ValueRef::from)),
+        }
+    }
+
+    // Extracts the value of a field with an operator that accesses the previous value in the dictionary.
+    #[allow(clippy::too_many_lines)]
+    fn extract_prev<'b, R, M>(
+        &self,
+        s: &mut DecoderContext<R, M>,
+        slots: FieldSlots,
+    ) -> Result<Option<Value>>
+    where
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
+    {
+        match self.operator {
             // The copy operator specifies that the value of a field is optionally present in the stream.
             Operator::Copy => {
                 if s.pmap_next_bit_set() {
                     // If the value is present in the stream it becomes the new previous value.
-                    let v = self.read(s)?;
-                    s.ctx_set(self, v.clone());
+                    let v = self.read(s, slots)?.map(ValueRef::into_owned);
+                    s.ctx_set(self, slots.field, v.clone())?;
                     return Ok(v);
                 }
 
                 // When the value is not present in the stream there are three cases depending
                 // on the state of the previous value:
-                let Some(v) = s.ctx_get(self)? else {
+                let Some(v) = s.ctx_get(self, slots.field)? else {
                     // Undefined: The value of the field is the initial value that also becomes the new previous value.
                     // Unless the field has optional presence, it is a dynamic error [ERR D5] if the instruction context has no initial value.
                     // If the field has optional presence and no initial value, the field is considered absent and the state of the previous
                     // value is changed to empty.
                     if self.initial_value.is_none() && !self.is_optional() {
-                        return Err(Error::Runtime(
+                        return Err(Error::Spec(
+                            ErrorCode::D5,
                             "copy operator has no initial value".to_string(),
-                        )); // [ERR D5]
+                        ));
                     }
 
-                    s.ctx_set(self, self.initial_value.clone());
+                    s.ctx_set(self, slots.field, self.initial_value.clone())?;
                     return Ok(self.initial_value.clone());
                 };
 
                 // Empty: If the field is optional the value is considered absent.
                 // It is a dynamic error [ERR D6] if the field is mandatory.
                 if v.is_none() && !self.is_optional() {
-                    return Err(Error::Runtime(
+                    return Err(Error::Spec(
+                        ErrorCode::D6,
                         "copy operator has no previous value".to_string(),
-                    )); // [ERR D6]
+                    ));
                 }
 
                 // Assigned: The value of the field is the previous value.
-                Ok(v)
+                Ok(v.cloned())
             }
 
             // The increment operator specifies that the value of a field is optionally present in the stream.
             Operator::Increment => {
                 if s.pmap_next_bit_set() {
                     //If the value is present in the stream it becomes the new previous value.
-                    let v = self.read(s)?;
-                    s.ctx_set(self, v.clone());
+                    let v = self.read(s, slots)?.map(ValueRef::into_owned);
+                    s.ctx_set(self, slots.field, v.clone())?;
                     return Ok(v);
                 }
                 // When the value is not present in the stream there are three cases depending on the state of the previous value:
-                let Some(v) = s.ctx_get(self)? else {
+                let Some(v) = s.ctx_get(self, slots.field)? else {
                     // Undefined: the value of the field is the initial value that also becomes the new previous value.
                     // Unless the field has optional presence, it is a dynamic error [ERR D5] if the instruction context
                     // has no initial value. If the field has optional presence and no initial value, the field is considered
                     // absent and the state of the previous value is changed to empty.
                     if !self.is_optional() && self.initial_value.is_none() {
-                        return Err(Error::Runtime(
+                        return Err(Error::Spec(
+                            ErrorCode::D5,
                             "increment operator has no initial value".to_string(),
-                        )); // [ERR D5]
+                        ));
                     }
-                    s.ctx_set(self, self.initial_value.clone());
+                    s.ctx_set(self, slots.field, self.initial_value.clone())?;
                     return Ok(self.initial_value.clone());
                 };
 
@@ -492,7 +1238,8 @@ impl Instruction {
                     // If the field is optional, the value is considered absent.
                     // It is a dynamic error [ERR D6] if the field is mandatory.
                     if !self.is_optional() {
-                        return Err(Error::Runtime(
+                        return Err(Error::Spec(
+                            ErrorCode::D6,
                             "increment operator has no previous value".to_string(),
                         ));
                     }
@@ -500,7 +1247,7 @@ impl Instruction {
                 };
 
                 let v = Some(prev.apply_increment()?);
-                s.ctx_set(self, v.clone());
+                s.ctx_set(self, slots.field, v.clone())?;
                 Ok(v)
             }
 
@@ -513,26 +1260,27 @@ impl Instruction {
                 };
                 // Otherwise, the field is obtained by combining the delta value with a base value.
                 // The base value depends on the state of the previous value in the following way:
-                let base = match s.ctx_get(self)? {
+                let value = match s.ctx_get(self, slots.field)? {
                     Some(v) => match v {
                         // Assigned: the base value is the previous value.
-                        Some(prev) => prev.clone(),
+                        Some(prev) => prev.apply_delta(&delta, aux)?,
                         // Empty: It is a dynamic error [ERR D6] if the previous value is empty.
                         None => {
-                            return Err(Error::Runtime(
+                            return Err(Error::Spec(
+                                ErrorCode::D6,
                                 "delta operator has no previous value".to_string(),
-                            )); // [ERR D6]
+                            ));
                         }
                     },
                     // Undefined: The base value is the initial value if present in the instruction context.
                     // Otherwise, a type dependant default base value is used.
                     None => match &self.initial_value {
-                        Some(v) => v.clone(),
-                        None => self.value_type.to_default_value()?,
+                        Some(v) => v.apply_delta(&delta, aux)?,
+                        None => self.default_base_value()?.apply_delta(&delta, aux)?,
                     },
                 };
-                let value = Some(base.apply_delta(&delta, aux)?);
-                s.ctx_set(self, value.clone());
+                let value = Some(value);
+                s.ctx_set(self, slots.field, value.clone())?;
                 Ok(value)
             }
 
@@ -545,143 +1293,194 @@ impl Instruction {
                         return if self.is_optional() {
                             Ok(None)
                         } else {
-                            Err(Error::Runtime(
+                            Err(Error::Spec(
+                                ErrorCode::D6,
                                 "tail operator has no previous value".to_string(),
-                            )) // [ERR D7]
+                            ))
                         };
                     };
                     // Otherwise, if the tail value is present, the value of the field is obtained by combining
                     // the tail value with a base value. The base value depends on the state of the previous value:
                     #[allow(clippy::collapsible_match)]
-                    let base = match s.ctx_get(self)? {
+                    let base = match s.ctx_get(self, slots.field)? {
                         Some(v) => match v {
                             // Assigned: the base value is the previous value.
-                            Some(prev) => prev.clone(),
+                            Some(prev) => prev,
                             // Empty: the base value is the initial value if present in the instruction context.
                             // Otherwise, a type dependant default base value is used.
                             None => match &self.initial_value {
-                                Some(v) => v.clone(),
-                                None => self.value_type.to_default_value()?,
+                                Some(v) => v,
+                                None => &self.default_base_value()?,
                             },
                         },
                         // Undefined: the base value is the initial value if present in the instruction context.
                         // Otherwise, a type dependant default base value is used.
                         None => match &self.initial_value {
-                            Some(v) => v.clone(),
-                            None => self.value_type.to_default_value()?,
+                            Some(v) => v,
+                            None => &self.default_base_value()?,
                         },
                     };
                     let value = Some(base.apply_tail(&tail)?);
                     // The combined value becomes the new previous value.
-                    s.ctx_set(self, value.clone());
+                    s.ctx_set(self, slots.field, value.clone())?;
                     return Ok(value);
                 }
 
                 // If the tail value is not present in the stream, the value of the field depends
                 // on the state of the previous value.
-                let Some(v) = s.ctx_get(self)? else {
+                let Some(v) = s.ctx_get(self, slots.field)? else {
                     // Undefined: the value of the field is the initial value that also becomes the new previous value.
-                    // Unless the field has optional presence, it is a dynamic error [ERR D6] if the instruction context
+                    // Unless the field has optional presence, it is a dynamic error [ERR D5] if the instruction context
                     // has no initial value. If the field has optional presence and no initial value, the field is considered
                     // absent and the state of the previous value is changed to empty.
                     if self.initial_value.is_none() && !self.is_optional() {
-                        return Err(Error::Runtime(
+                        return Err(Error::Spec(
+                            ErrorCode::D5,
                             "tail operator has no initial value".to_string(),
-                        )); // [ERR D6]
+                        ));
                     }
-                    s.ctx_set(self, self.initial_value.clone());
+                    s.ctx_set(self, slots.field, self.initial_value.clone())?;
                     return Ok(self.initial_value.clone());
                 };
 
                 // Empty: the value of the field is empty. If the field is optional the value is considered absent.
-                // It is a dynamic error [ERR D7] if the field is mandatory.
+                // It is a dynamic error [ERR D6] if the field is mandatory.
                 if v.is_none() && !self.is_optional() {
-                    return Err(Error::Runtime(
+                    return Err(Error::Spec(
+                        ErrorCode::D6,
                         "tail operator has no previous value".to_string(),
-                    )); // [ERR D7]
+                    ));
                 }
-                Ok(v)
+                Ok(v.cloned())
             }
+            Operator::None | Operator::Constant | Operator::Default => unreachable!(),
         }
     }
 
-    fn read<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<Value>>
+    fn read<'b, R, M>(
+        &self,
+        s: &mut DecoderContext<R, M>,
+        slots: FieldSlots,
+    ) -> Result<Option<ValueRef<'b>>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
         match self.value_type {
-            ValueType::UInt32 | ValueType::Length => match self.read_uint32(s)? {
+            // A boolean is represented as an unsigned integer that is either 0 (false) or 1 (true).
+            ValueType::Boolean => match self.read_uint_in::<u8, _, _>(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::UInt32(v))),
+                Some(0) => Ok(Some(ValueRef::Bool(false))),
+                Some(1) => Ok(Some(ValueRef::Bool(true))),
+                Some(v) => Err(Error::Spec(
+                    ErrorCode::D2,
+                    format!("boolean value is out of range: {v}"),
+                )),
             },
-            ValueType::UInt64 => match self.read_uint64(s)? {
+            ValueType::UInt8 => match self.read_uint_in(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::UInt64(v))),
+                Some(v) => Ok(Some(ValueRef::UInt8(v))),
             },
-            ValueType::Int32 => match self.read_int32(s)? {
+            ValueType::Int8 => match self.read_int_in(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::Int32(v))),
+                Some(v) => Ok(Some(ValueRef::Int8(v))),
             },
-            ValueType::Int64 | ValueType::Mantissa => match self.read_int64(s)? {
+            ValueType::UInt16 => match self.read_uint_in(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::Int64(v))),
+                Some(v) => Ok(Some(ValueRef::UInt16(v))),
             },
+            ValueType::Int16 => match self.read_int_in(s)? {
+                None => Ok(None),
+                Some(v) => Ok(Some(ValueRef::Int16(v))),
+            },
+            ValueType::UInt32 | ValueType::Length | ValueType::Enum => {
+                match self.read_uint_in(s)? {
+                    None => Ok(None),
+                    Some(v) => Ok(Some(ValueRef::UInt32(v))),
+                }
+            }
+            ValueType::UInt64 | ValueType::Set | ValueType::TimeOfDay => {
+                match self.read_uint64(s)? {
+                    None => Ok(None),
+                    Some(v) => Ok(Some(ValueRef::UInt64(v))),
+                }
+            }
+            ValueType::Int32 | ValueType::Date => match self.read_int_in(s)? {
+                None => Ok(None),
+                Some(v) => Ok(Some(ValueRef::Int32(v))),
+            },
+            ValueType::Int64 | ValueType::Mantissa | ValueType::Timestamp => {
+                match self.read_int64(s)? {
+                    None => Ok(None),
+                    Some(v) => Ok(Some(ValueRef::Int64(v))),
+                }
+            }
             ValueType::ASCIIString => match self.read_ascii_string(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::ASCIIString(v))),
+                Some(v) => Ok(Some(ValueRef::ASCIIString(Cow::Owned(v)))),
             },
-            ValueType::UnicodeString => match self.read_unicode_string(s)? {
+            ValueType::UnicodeString => match self.read_raw_bytes(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::UnicodeString(v))),
+                Some(Raw::Borrowed(v)) => match std::str::from_utf8(v) {
+                    Ok(v) => Ok(Some(ValueRef::UnicodeString(Cow::Borrowed(v)))),
+                    // report the same error as for owned bytes
+                    Err(_) => Err(String::from_utf8(v.to_vec()).unwrap_err().into()),
+                },
+                Some(Raw::Owned(v)) => Ok(Some(ValueRef::UnicodeString(Cow::Owned(
+                    String::from_utf8(v)?,
+                )))),
+                #[cfg(feature = "shared-bytes")]
+                Some(Raw::Shared(v)) => match std::str::from_utf8(&v) {
+                    Ok(_) => Ok(Some(ValueRef::SharedUnicodeString(v))),
+                    Err(_) => Err(String::from_utf8(v.into()).unwrap_err().into()),
+                },
             },
-            ValueType::Bytes => match self.read_bytes(s)? {
+            ValueType::Bytes => match self.read_raw_bytes(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::Bytes(v))),
+                Some(Raw::Borrowed(v)) => Ok(Some(ValueRef::Bytes(Cow::Borrowed(v)))),
+                Some(Raw::Owned(v)) => Ok(Some(ValueRef::Bytes(Cow::Owned(v)))),
+                #[cfg(feature = "shared-bytes")]
+                Some(Raw::Shared(v)) => Ok(Some(ValueRef::SharedBytes(v))),
             },
             // A scaled number is represented as a Signed Integer exponent followed by a Signed Integer mantissa.
             ValueType::Decimal => {
-                let Some((exponent, mantissa)) = self.read_decimal_components(s)? else {
+                let Some((exponent, mantissa)) = self.read_decimal_components(s, slots)? else {
                     return Ok(None);
                 };
-                Ok(Some(Value::Decimal(Decimal::new(exponent, mantissa))))
+                Ok(Some(ValueRef::Decimal(Decimal::new(exponent, mantissa))))
             }
             ValueType::Exponent => match self.read_exponent(s)? {
                 None => Ok(None),
-                Some(v) => Ok(Some(Value::Int32(v))),
+                Some(v) => Ok(Some(ValueRef::Int32(v))),
             },
             _ => unreachable!(),
         }
     }
 
-    fn read_uint32<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<u32>>
+    // Reads an unsigned integer that must fit into the field type `T`.
+    // It is a dynamic error [ERR D2] if the value does not fall within the bounds of the field type.
+    fn read_uint_in<'b, T, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<T>>
     where
-        R: Reader,
-        M: MessageFactory,
+        T: TryFrom<u64>,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
-        if self.is_nullable() {
-            match s.rdr.read_uint_nullable()? {
-                None => Ok(None),
-                Some(v) => {
-                    if v > u64::from(u32::MAX) {
-                        return Err(Error::Runtime(format!("uInt32 value is out of range: {v}")));
-                    }
-                    Ok(Some(v as u32))
-                }
-            }
-        } else {
-            let v = s.rdr.read_uint()?;
-            if v > u64::from(u32::MAX) {
-                return Err(Error::Runtime(format!("uInt32 value is out of range: {v}")));
-            }
-            Ok(Some(v as u32))
+        let Some(v) = self.read_uint64(s)? else {
+            return Ok(None);
+        };
+        match T::try_from(v) {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(Error::Spec(
+                ErrorCode::D2,
+                format!("{} value is out of range: {v}", self.value_type.type_str()),
+            )),
         }
     }
 
-    fn read_uint64<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<u64>>
+    fn read_uint64<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<u64>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
         if self.is_nullable() {
             Ok(s.rdr.read_uint_nullable()?)
@@ -690,35 +1489,30 @@ impl Instruction {
         }
     }
 
-    fn read_int32<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<i32>>
+    // Reads a signed integer that must fit into the field type `T`.
+    // It is a dynamic error [ERR D2] if the value does not fall within the bounds of the field type.
+    fn read_int_in<'b, T, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<T>>
     where
-        R: Reader,
-        M: MessageFactory,
+        T: TryFrom<i64>,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
-        const INT32_RANGE: RangeInclusive<i64> = (i32::MIN as i64)..=(i32::MAX as i64);
-        if self.is_nullable() {
-            match s.rdr.read_int_nullable()? {
-                None => Ok(None),
-                Some(v) => {
-                    if !INT32_RANGE.contains(&v) {
-                        return Err(Error::Runtime(format!("Int32 value is out of range: {v}"))); // [ERR D2]
-                    }
-                    Ok(Some(v as i32))
-                }
-            }
-        } else {
-            let v = s.rdr.read_int()?;
-            if !INT32_RANGE.contains(&v) {
-                return Err(Error::Runtime(format!("Int32 value is out of range: {v}"))); // [ERR D2]
-            }
-            Ok(Some(v as i32))
+        let Some(v) = self.read_int64(s)? else {
+            return Ok(None);
+        };
+        match T::try_from(v) {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(Error::Spec(
+                ErrorCode::D2,
+                format!("{} value is out of range: {v}", self.value_type.type_str()),
+            )),
         }
     }
 
-    fn read_int64<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<i64>>
+    fn read_int64<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<i64>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
         if self.is_nullable() {
             Ok(s.rdr.read_int_nullable()?)
@@ -727,85 +1521,100 @@ impl Instruction {
         }
     }
 
-    fn read_ascii_string<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<String>>
+    fn read_ascii_string<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<String>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
+        // The reader enforces `max_string_length` while reading the characters.
         if self.is_nullable() {
-            Ok(s.rdr.read_ascii_string_nullable()?)
+            s.rdr.read_ascii_string_nullable()
         } else {
             Ok(Some(s.rdr.read_ascii_string()?))
         }
     }
 
-    fn read_unicode_string<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<String>>
+    fn read_raw_bytes<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<Raw<'b>>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
-        if self.is_nullable() {
-            Ok(s.rdr.read_unicode_string_nullable()?)
+        let length = if self.is_nullable() {
+            match s.rdr.read_uint_nullable()? {
+                None => return Ok(None),
+                Some(length) => length,
+            }
         } else {
-            Ok(Some(s.rdr.read_unicode_string()?))
-        }
+            s.rdr.read_uint()?
+        };
+        s.limits.check_length(&self.value_type, length)?;
+        Ok(Some(s.rdr.read_raw(length as usize)?))
     }
 
-    fn read_bytes<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<Vec<u8>>>
+    fn read_bytes<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<Vec<u8>>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
-        if self.is_nullable() {
-            Ok(s.rdr.read_bytes_nullable()?)
-        } else {
-            Ok(Some(s.rdr.read_bytes()?))
-        }
+        Ok(self.read_raw_bytes(s)?.map(Raw::into_vec))
     }
 
     // The delta operator specifies that a delta value is present in the stream.
     // If the field has optional presence, the delta value can be NULL. In that case the value of the field
     // is considered absent. Otherwise, the field is obtained by combining the delta value with a base value.
-    fn read_delta<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<(Value, i32)>>
+    fn read_delta<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<(Value, i32)>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
         match self.value_type {
-            ValueType::UInt32
+            ValueType::Boolean
+            | ValueType::UInt8
+            | ValueType::Int8
+            | ValueType::UInt16
+            | ValueType::Int16
+            | ValueType::UInt32
             | ValueType::Int32
             | ValueType::UInt64
             | ValueType::Int64
             | ValueType::Length
             | ValueType::Exponent
-            | ValueType::Mantissa => match self.read_int64(s)? {
+            | ValueType::Mantissa
+            | ValueType::Enum
+            | ValueType::Set
+            | ValueType::Timestamp
+            | ValueType::Date
+            | ValueType::TimeOfDay => match self.read_int64(s)? {
                 None => Ok(None),
                 Some(v) => Ok(Some((Value::Int64(v), 0))),
             },
             ValueType::ASCIIString | ValueType::UnicodeString | ValueType::Bytes => {
-                match self.read_int32(s)? {
-                    None => Ok(None),
-                    Some(sub) => match self.value_type {
-                        ValueType::ASCIIString => {
-                            let diff = self.read_ascii_string(s)?.unwrap();
-                            Ok(Some((Value::ASCIIString(diff), sub)))
-                        }
-                        ValueType::UnicodeString | ValueType::Bytes => {
-                            let diff = self.read_bytes(s)?.unwrap();
-                            Ok(Some((Value::Bytes(diff), sub)))
-                        }
-                        _ => unreachable!(),
-                    },
+                // It is a dynamic error [ERR D7] if the subtraction length does not fall in the value range of an int32.
+                let Some(sub) = self.read_int64(s)? else {
+                    return Ok(None);
+                };
+                let sub = i32::try_from(sub).map_err(|_| {
+                    Error::Spec(
+                        ErrorCode::D7,
+                        format!("subtraction length is out of range: {sub}"),
+                    )
+                })?;
+                if self.value_type == ValueType::ASCIIString {
+                    let diff = self.read_ascii_string(s)?.unwrap();
+                    Ok(Some((Value::ASCIIString(diff), sub)))
+                } else {
+                    let diff = self.read_bytes(s)?.unwrap();
+                    Ok(Some((Value::Bytes(diff), sub)))
                 }
             }
             _ => unreachable!(),
         }
     }
 
-    fn read_tail<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<Value>>
+    fn read_tail<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<Value>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
         match self.value_type {
             ValueType::ASCIIString => Ok(self.read_ascii_string(s)?.map(Value::ASCIIString)),
@@ -816,19 +1625,21 @@ impl Instruction {
         }
     }
 
-    fn read_decimal_components<R, M>(
+    fn read_decimal_components<'b, R, M>(
         &self,
         s: &mut DecoderContext<R, M>,
+        slots: FieldSlots,
     ) -> Result<Option<(i32, i64)>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
         let exponent = self
             .instructions
             .first()
             .ok_or_else(|| Error::Runtime("exponent field not found".to_string()))?
-            .extract(s)?;
+            .extract(s, slots.component(0))
+            .map_err(|e| s.located(e, "exponent"))?;
         if exponent.is_none() {
             return Ok(None);
         }
@@ -836,41 +1647,42 @@ impl Instruction {
             .instructions
             .get(1)
             .ok_or_else(|| Error::Runtime("mantissa field not found".to_string()))?
-            .extract(s)?;
+            .extract(s, slots.component(1))
+            .map_err(|e| s.located(e, "mantissa"))?;
 
-        if let (Some(Value::Int32(e)), Some(Value::Int64(m))) = (exponent, mantissa) {
+        if let (Some(ValueRef::Int32(e)), Some(ValueRef::Int64(m))) = (exponent, mantissa) {
             Ok(Some((e, m)))
         } else {
             Err(Error::Runtime("exponent or mantissa not found".to_string()))
         }
     }
 
-    fn read_exponent<R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<i32>>
+    fn read_exponent<'b, R, M>(&self, s: &mut DecoderContext<R, M>) -> Result<Option<i32>>
     where
-        R: Reader,
-        M: MessageFactory,
+        R: ReadRaw<'b>,
+        M: MessageFactoryRef<'b>,
     {
-        let Some(e) = self.read_int32(s)? else {
+        let Some(e) = self.read_int_in::<i32, _, _>(s)? else {
             return Ok(None);
         };
         if !(MIN_EXPONENT..=MAX_EXPONENT).contains(&e) {
-            return Err(Error::Dynamic(format!(
-                "exponent value is out of range: {e}"
-            ))); // [ERR R1]
+            s.rdr.report(Error::Spec(
+                ErrorCode::R1,
+                format!("exponent value is out of range: {e}"),
+            ))?;
         }
         Ok(Some(e))
     }
 
-    #[allow(clippy::too_many_lines)]
     pub(crate) fn inject<W, M>(
         &self,
         s: &mut EncoderContext<W, M>,
         buf: &mut impl Writer,
-        value: Option<Value>,
+        value: Option<ValueRef<'_>>,
+        slots: FieldSlots,
     ) -> Result<()>
     where
         W: Writer,
-        M: MessageVisitor,
     {
         if value.is_none() && !self.is_optional() {
             return Err(Error::Runtime(format!(
@@ -878,8 +1690,26 @@ impl Instruction {
                 self.name
             )));
         }
+        if self.operator == Operator::None {
+            return self.write(buf, s, value, slots);
+        }
+        // The value is compared with the initial or the previous value and may become the previous value.
+        self.inject_operator(s, buf, value.map(ValueRef::into_owned), slots)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn inject_operator<W, M>(
+        &self,
+        s: &mut EncoderContext<W, M>,
+        buf: &mut impl Writer,
+        value: Option<Value>,
+        slots: FieldSlots,
+    ) -> Result<()>
+    where
+        W: Writer,
+    {
         match self.operator {
-            Operator::None => self.write(buf, s, value),
+            Operator::None => self.write(buf, s, value.map(ValueRef::from), slots),
             Operator::Constant => {
                 if value.is_some() && self.initial_value != value {
                     return Err(Error::Runtime(format!(
@@ -898,40 +1728,40 @@ impl Instruction {
                     Ok(())
                 } else {
                     s.pmap_set_next_bit(true);
-                    self.write(buf, s, value)
+                    self.write(buf, s, value.map(ValueRef::from), slots)
                 }
             }
             Operator::Copy => {
-                let prev_value = if let Some(v) = s.ctx_get(self)? {
-                    v
+                let unchanged = if let Some(v) = s.ctx_get(self, slots.field)? {
+                    v == value.as_ref()
                 } else {
-                    s.ctx_set(self, self.initial_value.clone());
-                    self.initial_value.clone()
+                    s.ctx_set(self, slots.field, self.initial_value.clone())?;
+                    self.initial_value == value
                 };
-                if prev_value == value {
+                if unchanged {
                     s.pmap_set_next_bit(false);
                     Ok(())
                 } else {
                     s.pmap_set_next_bit(true);
-                    s.ctx_set(self, value.clone());
-                    self.write(buf, s, value)
+                    s.ctx_set(self, slots.field, value.clone())?;
+                    self.write(buf, s, value.map(ValueRef::from), slots)
                 }
             }
             Operator::Increment => {
                 let prev_value = s
-                    .ctx_get(self)?
-                    .unwrap_or_else(|| self.initial_value.clone());
+                    .ctx_get(self, slots.field)?
+                    .unwrap_or(self.initial_value.as_ref());
                 let next_value = match prev_value {
                     None => None,
                     Some(v) => Some(v.apply_increment()?),
                 };
-                s.ctx_set(self, value.clone());
+                s.ctx_set(self, slots.field, value.clone())?;
                 if next_value == value {
                     s.pmap_set_next_bit(false);
                     Ok(())
                 } else {
                     s.pmap_set_next_bit(true);
-                    self.write(buf, s, value)
+                    self.write(buf, s, value.map(ValueRef::from), slots)
                 }
             }
             Operator::Delta => {
@@ -939,45 +1769,45 @@ impl Instruction {
                     return self.write_delta(buf, None);
                 };
 
-                let base = match s.ctx_get(self)? {
+                let delta = match s.ctx_get(self, slots.field)? {
                     Some(v) => match v {
-                        Some(v) => v,
+                        Some(v) => value.find_delta(v),
                         None => {
-                            return Err(Error::Runtime(
-                                "delta operator has empty previous value".to_string(),
-                            )); // [ERR D6]
+                            // It is a dynamic error [ERR D6] if the previous value is empty.
+                            return Err(Error::Spec(
+                                ErrorCode::D6,
+                                format!("field {} has empty previous value", self.name),
+                            ));
                         }
                     },
                     None => match &self.initial_value {
-                        Some(v) => v.clone(),
-                        None => self.value_type.to_default_value()?,
+                        Some(v) => value.find_delta(v),
+                        None => value.find_delta(&self.default_base_value()?),
                     },
                 };
 
-                let delta = value.find_delta(&base);
-                s.ctx_set(self, Some(value));
+                s.ctx_set(self, slots.field, Some(value))?;
                 self.write_delta(buf, Some(delta))
             }
             Operator::Tail => {
                 let prev_value = s
-                    .ctx_get(self)?
-                    .unwrap_or_else(|| self.initial_value.clone());
-                if prev_value == value {
+                    .ctx_get(self, slots.field)?
+                    .unwrap_or(self.initial_value.as_ref());
+                if prev_value == value.as_ref() {
                     s.pmap_set_next_bit(false);
-                    s.ctx_set(self, value);
+                    s.ctx_set(self, slots.field, value)?;
                     Ok(())
                 } else {
                     let tail = match &value {
                         None => None,
-                        Some(v) => {
-                            s.ctx_set(self, value.clone());
-                            let prev = match prev_value {
-                                Some(p) => p,
-                                None => self.value_type.to_default_value()?,
-                            };
-                            Some(v.find_tail(&prev)?)
-                        }
+                        Some(v) => Some(match prev_value {
+                            Some(p) => v.find_tail(p)?,
+                            None => v.find_tail(&self.default_base_value()?)?,
+                        }),
                     };
+                    if value.is_some() {
+                        s.ctx_set(self, slots.field, value)?;
+                    }
                     s.pmap_set_next_bit(true);
                     self.write_tail(buf, tail)
                 }
@@ -985,100 +1815,104 @@ impl Instruction {
         }
     }
 
+    #[allow(clippy::too_many_lines)]
     fn write<W, M>(
         &self,
         buf: &mut impl Writer,
         s: &mut EncoderContext<W, M>,
-        value: Option<Value>,
+        value: Option<ValueRef<'_>>,
+        slots: FieldSlots,
     ) -> Result<()>
     where
         W: Writer,
-        M: MessageVisitor,
     {
         match self.value_type {
-            ValueType::UInt32 | ValueType::Length => match value {
+            ValueType::Boolean => match value {
+                None => self.write_uint::<u8>(buf, None),
+                Some(ValueRef::Bool(v)) => self.write_uint(buf, Some(u8::from(v))),
+                _ => Err(self.type_mismatch("Bool", &value)),
+            },
+            ValueType::UInt8 => match value {
+                None => self.write_uint::<u8>(buf, None),
+                Some(ValueRef::UInt8(v)) => self.write_uint(buf, Some(v)),
+                _ => Err(self.type_mismatch("UInt8", &value)),
+            },
+            ValueType::Int8 => match value {
+                None => self.write_int::<i8>(buf, None),
+                Some(ValueRef::Int8(v)) => self.write_int(buf, Some(v)),
+                _ => Err(self.type_mismatch("Int8", &value)),
+            },
+            ValueType::UInt16 => match value {
+                None => self.write_uint::<u16>(buf, None),
+                Some(ValueRef::UInt16(v)) => self.write_uint(buf, Some(v)),
+                _ => Err(self.type_mismatch("UInt16", &value)),
+            },
+            ValueType::Int16 => match value {
+                None => self.write_int::<i16>(buf, None),
+                Some(ValueRef::Int16(v)) => self.write_int(buf, Some(v)),
+                _ => Err(self.type_mismatch("Int16", &value)),
+            },
+            ValueType::UInt32 | ValueType::Length | ValueType::Enum => match value {
                 None => self.write_uint::<u32>(buf, None),
-                Some(Value::UInt32(v)) => self.write_uint(buf, Some(v)),
-                _ => Err(Error::Runtime(format!(
-                    "Field {} must have UInt32 value, got: {:?} instead",
-                    self.name, value
-                ))),
+                Some(ValueRef::UInt32(v)) => self.write_uint(buf, Some(v)),
+                _ => Err(self.type_mismatch("UInt32", &value)),
             },
-            ValueType::Int32 => match value {
+            ValueType::Int32 | ValueType::Date => match value {
                 None => self.write_int::<i32>(buf, None),
-                Some(Value::Int32(v)) => self.write_int(buf, Some(v)),
-                _ => Err(Error::Runtime(format!(
-                    "Field {} must have Int32 value, got: {:?} instead",
-                    self.name, value
-                ))),
+                Some(ValueRef::Int32(v)) => self.write_int(buf, Some(v)),
+                _ => Err(self.type_mismatch("Int32", &value)),
             },
-            ValueType::UInt64 => match value {
+            ValueType::UInt64 | ValueType::Set | ValueType::TimeOfDay => match value {
                 None => self.write_uint::<u64>(buf, None),
-                Some(Value::UInt64(v)) => self.write_uint(buf, Some(v)),
-                _ => Err(Error::Runtime(format!(
-                    "Field {} must have UInt64 value, got: {:?} instead",
-                    self.name, value
-                ))),
+                Some(ValueRef::UInt64(v)) => self.write_uint(buf, Some(v)),
+                _ => Err(self.type_mismatch("UInt64", &value)),
             },
-            ValueType::Int64 | ValueType::Mantissa => match value {
+            ValueType::Int64 | ValueType::Mantissa | ValueType::Timestamp => match value {
                 None => self.write_int::<i64>(buf, None),
-                Some(Value::Int64(v)) => self.write_int(buf, Some(v)),
-                _ => Err(Error::Runtime(format!(
-                    "Field {}:mantissa must have Int64 value, got: {:?} instead",
-                    self.name, value
-                ))),
+                Some(ValueRef::Int64(v)) => self.write_int(buf, Some(v)),
+                _ => Err(self.type_mismatch("Int64", &value)),
             },
             ValueType::Exponent => match value {
                 None => self.write_exponent(buf, None),
-                Some(Value::Int32(v)) => self.write_exponent(buf, Some(v)),
-                _ => Err(Error::Runtime(format!(
-                    "Field {}:exponent must have Int32 value, got: {:?} instead",
-                    self.name, value
-                ))),
+                Some(ValueRef::Int32(v)) => self.write_exponent(buf, Some(v)),
+                _ => Err(self.type_mismatch("Int32", &value)),
             },
             ValueType::Decimal => match value {
-                None => self.write_decimal(buf, s, None),
-                Some(Value::Decimal(d)) => self.write_decimal(buf, s, Some(d)),
-                _ => Err(Error::Runtime(format!(
-                    "Field {} must have Decimal value, got: {:?} instead",
-                    self.name, value
-                ))),
+                None => self.write_decimal(buf, s, slots, None),
+                Some(ValueRef::Decimal(d)) => self.write_decimal(buf, s, slots, Some(d)),
+                _ => Err(self.type_mismatch("Decimal", &value)),
             },
             ValueType::ASCIIString => match value {
                 None => self.write_ascii_string(buf, None),
-                Some(Value::ASCIIString(v)) => self.write_ascii_string(buf, Some(&v)),
-                Some(Value::UnicodeString(v)) => {
+                Some(ValueRef::ASCIIString(v)) => self.write_ascii_string(buf, Some(&v)),
+                Some(ValueRef::UnicodeString(v)) => {
                     if v.is_ascii() {
                         self.write_ascii_string(buf, Some(&v))
                     } else {
-                        Err(Error::Runtime(format!(
-                            "Field {} must be valid ASCII string",
-                            self.name
-                        )))
+                        // It is a reportable error [ERR R3] if a unicode string contains non-ASCII characters.
+                        Err(Error::Spec(
+                            ErrorCode::R3,
+                            format!("Field {} must be valid ASCII string", self.name),
+                        ))
                     }
                 }
-                _ => Err(Error::Runtime(format!(
-                    "Field {} must have ASCIIString value, got: {:?} instead",
-                    self.name, value
-                ))),
+                _ => Err(self.type_mismatch("ASCIIString", &value)),
             },
             ValueType::UnicodeString => match value {
                 None => self.write_unicode_string(buf, None),
-                Some(Value::UnicodeString(v) | Value::ASCIIString(v)) => {
+                Some(ValueRef::UnicodeString(v) | ValueRef::ASCIIString(v)) => {
                     self.write_unicode_string(buf, Some(&v))
                 }
-                _ => Err(Error::Runtime(format!(
-                    "Field {} must have UnicodeString value, got: {:?} instead",
-                    self.name, value
-                ))),
+                Some(ValueRef::SharedUnicodeString(v)) => {
+                    self.write_unicode_string(buf, Some(&String::from_utf8_lossy(&v)))
+                }
+                _ => Err(self.type_mismatch("UnicodeString", &value)),
             },
             ValueType::Bytes => match value {
                 None => self.write_bytes(buf, None),
-                Some(Value::Bytes(v)) => self.write_bytes(buf, Some(&v)),
-                _ => Err(Error::Runtime(format!(
-                    "Field {} must have Bytes value, got: {:?} instead",
-                    self.name, value
-                ))),
+                Some(ValueRef::Bytes(v)) => self.write_bytes(buf, Some(&v)),
+                Some(ValueRef::SharedBytes(v)) => self.write_bytes(buf, Some(&v)),
+                _ => Err(self.type_mismatch("Bytes", &value)),
             },
             _ => unreachable!(),
         }
@@ -1144,13 +1978,23 @@ impl Instruction {
 
     fn write_delta(&self, buf: &mut impl Writer, value: Option<(Value, i32)>) -> Result<()> {
         match self.value_type {
-            ValueType::UInt32
+            ValueType::Boolean
+            | ValueType::UInt8
+            | ValueType::Int8
+            | ValueType::UInt16
+            | ValueType::Int16
+            | ValueType::UInt32
             | ValueType::Int32
             | ValueType::UInt64
             | ValueType::Int64
             | ValueType::Length
             | ValueType::Exponent
-            | ValueType::Mantissa => match value {
+            | ValueType::Mantissa
+            | ValueType::Enum
+            | ValueType::Set
+            | ValueType::Timestamp
+            | ValueType::Date
+            | ValueType::TimeOfDay => match value {
                 None => self.write_int::<i64>(buf, None),
                 Some((Value::Int64(v), _)) => self.write_int(buf, Some(v)),
                 Some((v, _)) => Err(Error::Runtime(format!(
@@ -1203,15 +2047,18 @@ impl Instruction {
         &self,
         buf: &mut impl Writer,
         s: &mut EncoderContext<W, M>,
+        slots: FieldSlots,
         value: Option<Decimal>,
     ) -> Result<()>
     where
         W: Writer,
-        M: MessageVisitor,
     {
         let (e, m) = match value {
-            None => (None, Value::Int64(0)),
-            Some(d) => (Some(Value::Int32(d.exponent)), Value::Int64(d.mantissa)),
+            None => (None, ValueRef::Int64(0)),
+            Some(d) => (
+                Some(ValueRef::Int32(d.exponent)),
+                ValueRef::Int64(d.mantissa),
+            ),
         };
 
         let without_exponent = e.is_none();
@@ -1219,7 +2066,8 @@ impl Instruction {
         self.instructions
             .first()
             .ok_or_else(|| Error::Runtime("exponent field not found".to_string()))?
-            .inject(s, buf, e)?;
+            .inject(s, buf, e, slots.component(0))
+            .map_err(|err| s.located(err, "exponent"))?;
 
         if without_exponent {
             return Ok(());
@@ -1228,16 +2076,19 @@ impl Instruction {
         self.instructions
             .get(1)
             .ok_or_else(|| Error::Runtime("mantissa field not found".to_string()))?
-            .inject(s, buf, Some(m))
+            .inject(s, buf, Some(m), slots.component(1))
+            .map_err(|e| s.located(e, "mantissa"))
     }
 
     fn write_exponent(&self, buf: &mut impl Writer, value: Option<i32>) -> Result<()> {
         if let Some(e) = value
             && !(MIN_EXPONENT..=MAX_EXPONENT).contains(&e)
         {
-            return Err(Error::Dynamic(format!(
-                "exponent value is out of range: {e}"
-            ))); // [ERR R1]
+            // It is a reportable error [ERR R1] if the exponent does not fall in the [-63, 63] range.
+            return Err(Error::Spec(
+                ErrorCode::R1,
+                format!("exponent value is out of range: {e}"),
+            ));
         }
         self.write_int(buf, value)
     }
@@ -1,4 +1,5 @@
 use crate::Value;
+use crate::base::value::ValueRef;
 use crate::{Result, ValueType};
 
 /// Defines the interface for message factories.
@@ -52,6 +53,106 @@ pub trait MessageFactory {
     fn stop_template_ref(&mut self);
 }
 
+/// Defines the interface for message factories that accept values borrowed from the decoded buffer.
+///
+/// It is the same as [`MessageFactory`] except [`MessageFactoryRef::set_value`] receives [`ValueRef`] that can
+/// borrow strings and byte vectors from the input buffer of lifetime `'a`.
+/// Used with [`Decoder::decode_buffer_ref`][crate::Decoder::decode_buffer_ref].
+/// Every [`MessageFactory`] is also a `MessageFactoryRef` that receives owned copies of the values.
+///
+pub trait MessageFactoryRef<'a> {
+    /// Called when a \<template> processing is started.
+    /// * `id` is the template id;
+    /// * `name` is the template name.
+    fn start_template(&mut self, id: u32, name: &str);
+
+    /// Called when a \<template> processing is finished.
+    fn stop_template(&mut self);
+
+    /// Called when a field element is processed.
+    /// * `id` is the field instruction id;
+    /// * `name` is the field name;
+    /// * `value` is the field value which is optional.
+    fn set_value(&mut self, id: u32, name: &str, value: Option<ValueRef<'a>>);
+
+    /// Called when a \<sequence> element processing is started.
+    /// * `id` is the sequence instruction id; can be `0` if id is not specified;
+    /// * `name` is the sequence name;
+    /// * `length` is the sequence length.
+    fn start_sequence(&mut self, id: u32, name: &str, length: u32);
+
+    /// Called when a sequence item processing is started.
+    /// * `index` is the sequence item index.
+    fn start_sequence_item(&mut self, index: u32);
+
+    /// Called when a sequence item processing is finished.
+    fn stop_sequence_item(&mut self);
+
+    /// Called when a \<sequence> processing is finished.
+    fn stop_sequence(&mut self);
+
+    /// Called when a \<group> element processing is started.
+    /// * `name` is the group name.
+    fn start_group(&mut self, name: &str);
+
+    /// Called when a \<group> element processing is finished.
+    fn stop_group(&mut self);
+
+    /// Called when a template reference (\<templateRef>) processing is started.
+    /// * `name` is the template name;
+    /// * `dynamic` is `true` if the template reference is dynamic.
+    fn start_template_ref(&mut self, name: &str, dynamic: bool);
+
+    /// Called when a template reference (\<templateRef>) processing is finished.
+    fn stop_template_ref(&mut self);
+}
+
+impl<M: MessageFactory> MessageFactoryRef<'_> for M {
+    fn start_template(&mut self, id: u32, name: &str) {
+        MessageFactory::start_template(self, id, name);
+    }
+
+    fn stop_template(&mut self) {
+        MessageFactory::stop_template(self);
+    }
+
+    fn set_value(&mut self, id: u32, name: &str, value: Option<ValueRef<'_>>) {
+        MessageFactory::set_value(self, id, name, value.map(ValueRef::into_owned));
+    }
+
+    fn start_sequence(&mut self, id: u32, name: &str, length: u32) {
+        MessageFactory::start_sequence(self, id, name, length);
+    }
+
+    fn start_sequence_item(&mut self, index: u32) {
+        MessageFactory::start_sequence_item(self, index);
+    }
+
+    fn stop_sequence_item(&mut self) {
+        MessageFactory::stop_sequence_item(self);
+    }
+
+    fn stop_sequence(&mut self) {
+        MessageFactory::stop_sequence(self);
+    }
+
+    fn start_group(&mut self, name: &str) {
+        MessageFactory::start_group(self, name);
+    }
+
+    fn stop_group(&mut self) {
+        MessageFactory::stop_group(self);
+    }
+
+    fn start_template_ref(&mut self, name: &str, dynamic: bool) {
+        MessageFactory::start_template_ref(self, name, dynamic);
+    }
+
+    fn stop_template_ref(&mut self) {
+        MessageFactory::stop_template_ref(self);
+    }
+}
+
 /// Defines the interface for message visitors.
 ///
 /// The callback functions are called when the specific information required during message processing.
@@ -2,5 +2,6 @@ pub(crate) mod decimal;
 pub(crate) mod instruction;
 pub(crate) mod message;
 pub(crate) mod pmap;
+pub(crate) mod time;
 pub(crate) mod types;
 pub(crate) mod value;
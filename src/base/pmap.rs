@@ -1,8 +1,22 @@
+use crate::decoder::reader::Reader;
+use crate::encoder::writer::Writer;
+use crate::{Error, ErrorCode, Result};
+
+// Maximum number of bits in one chunk of the bitmap, i.e. 9 bytes of the presence map.
+const CHUNK_BITS: u8 = 63;
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct PresenceMap {
     pub(crate) bitmap: u64,
     pub(crate) mask: u64,
     pub(crate) size: u8,
+    // Index of the first bit of `bitmap` in the presence map.
+    base: usize,
+    // Presence maps longer than 63 bits are split into chunks, `bitmap` is the current one.
+    // When decoding, these are the chunks that follow the current one, in reverse order.
+    // When encoding, these are the completed chunks that precede the current one.
+    // Short presence maps don't use it, so it never allocates in the common case.
+    chunks: Vec<(u64, u8)>,
 }
 
 /// Represents the presence map field.
@@ -12,6 +26,8 @@ impl PresenceMap {
             bitmap: 0,
             mask: 0x40, // 0100 0000
             size: 7,
+            base: 0,
+            chunks: Vec::new(),
         }
     }
 
@@ -20,20 +36,134 @@ impl PresenceMap {
             bitmap,
             mask: 1u64 << (size - 1),
             size,
+            base: 0,
+            chunks: Vec::new(),
         }
     }
 
+    /// Reads the presence map of any length.
+    /// # Errors
+    /// Returns [`Error::Eof`] if the end of the stream is reached at the first byte of the presence map.
+    pub(crate) fn read<R: Reader + ?Sized>(rdr: &mut R) -> Result<Self> {
+        let mut bitmap: u64 = 0;
+        let mut size: u8 = 0;
+        let mut chunks = Vec::new();
+        let mut byte = match rdr.read_u8() {
+            Ok(b) => b,
+            Err(Error::UnexpectedEof) => return Err(Error::Eof),
+            Err(e) => return Err(e),
+        };
+        loop {
+            if size == CHUNK_BITS {
+                chunks.push((bitmap, size));
+                bitmap = 0;
+                size = 0;
+            }
+            bitmap <<= 7;
+            bitmap |= u64::from(byte & 0x7f);
+            size += 7;
+
+            if byte & 0x80 == 0x80 {
+                if byte == 0x80 && (size > 7 || !chunks.is_empty()) {
+                    rdr.report(Error::Spec(
+                        ErrorCode::R7,
+                        "presence map is overlong".to_string(),
+                    ))?;
+                }
+                break;
+            }
+            byte = rdr.read_u8()?;
+        }
+        if chunks.is_empty() {
+            return Ok(Self::new(bitmap, size));
+        }
+        chunks.push((bitmap, size));
+        chunks.reverse();
+        let (bitmap, size) = chunks.pop().unwrap();
+        Ok(Self {
+            chunks,
+            ..Self::new(bitmap, size)
+        })
+    }
+
+    /// Writes the presence map dropping trailing bytes with no bits set.
+    /// # Errors
+    /// Returns error if writer can't handle more bytes.
+    pub(crate) fn write<W: Writer + ?Sized>(&self, wrt: &mut W) -> Result<()> {
+        if self.chunks.is_empty() {
+            return wrt.write_presence_map(self.bitmap, self.size);
+        }
+        let mut bytes: Vec<u8> = self
+            .chunks
+            .iter()
+            .chain(std::iter::once(&(self.bitmap, self.size)))
+            .flat_map(|&(bitmap, size)| {
+                (0..size / 7)
+                    .rev()
+                    .map(move |i| ((bitmap >> (i * 7)) & 0x7f) as u8)
+            })
+            .collect();
+        while bytes.len() > 1 && bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        if let Some(last) = bytes.last_mut() {
+            *last |= 0x80;
+        }
+        wrt.write_buf(&bytes)
+    }
+
+    /// Returns the bitmap and its size if the presence map is not longer than 63 bits.
+    pub(crate) fn to_short(&self) -> Option<(u64, u8)> {
+        self.chunks.is_empty().then_some((self.bitmap, self.size))
+    }
+
     pub(crate) fn next_bit_set(&mut self) -> bool {
+        if self.mask == 0 && !self.chunks.is_empty() {
+            self.next_chunk();
+        }
         let res = self.bitmap & self.mask != 0;
         self.mask >>= 1;
         res
     }
 
+    #[cold]
+    fn next_chunk(&mut self) {
+        if let Some((bitmap, size)) = self.chunks.pop() {
+            self.base += self.size as usize;
+            self.bitmap = bitmap;
+            self.mask = 1u64 << (size - 1);
+            self.size = size;
+        }
+    }
+
+    // Returns `true` if any bit that was not consumed by `next_bit_set()` is set.
+    pub(crate) fn has_unused_bits_set(&self) -> bool {
+        self.bitmap & (self.mask | self.mask.saturating_sub(1)) != 0
+            || self.chunks.iter().any(|(bitmap, _)| *bitmap != 0)
+    }
+
+    // Returns the index of the next bit to be consumed or set.
+    pub(crate) fn bit_index(&self) -> usize {
+        let index = if self.mask == 0 {
+            self.size as usize
+        } else {
+            (self.size as usize) - 1 - self.mask.trailing_zeros() as usize
+        };
+        self.base + index
+    }
+
     pub(crate) fn set_next_bit(&mut self, value: bool) {
         if self.mask == 0 {
-            self.bitmap <<= 7;
+            if self.size == CHUNK_BITS {
+                self.chunks.push((self.bitmap, self.size));
+                self.base += self.size as usize;
+                self.bitmap = 0;
+                self.size = 7;
+            } else {
+                self.bitmap <<= 7;
+                self.size += 7;
+            }
             self.mask = 0x40;
-            self.size += 7;
         }
         if value {
             self.bitmap |= self.mask;
@@ -49,18 +179,19 @@ mod tests {
     #[test]
     fn presence_map_next_bit_set() {
         let mut pmap = PresenceMap::new(0b1010110, 7);
-        assert_eq!(pmap.next_bit_set(), true);
-        assert_eq!(pmap.next_bit_set(), false);
-        assert_eq!(pmap.next_bit_set(), true);
-        assert_eq!(pmap.next_bit_set(), false);
-        assert_eq!(pmap.next_bit_set(), true);
-        assert_eq!(pmap.next_bit_set(), true);
-        assert_eq!(pmap.next_bit_set(), false);
+        assert!(pmap.next_bit_set());
+        assert!(!pmap.next_bit_set());
+        assert!(pmap.next_bit_set());
+        assert!(!pmap.next_bit_set());
+        assert!(pmap.next_bit_set());
+        assert!(pmap.next_bit_set());
+        assert!(!pmap.next_bit_set());
+        assert_eq!(pmap.bit_index(), 7);
         // all other bits are false
-        assert_eq!(pmap.next_bit_set(), false);
-        assert_eq!(pmap.next_bit_set(), false);
-        assert_eq!(pmap.next_bit_set(), false);
-        assert_eq!(pmap.next_bit_set(), false);
+        assert!(!pmap.next_bit_set());
+        assert!(!pmap.next_bit_set());
+        assert!(!pmap.next_bit_set());
+        assert!(!pmap.next_bit_set());
     }
 
     #[test]
@@ -79,7 +210,46 @@ mod tests {
         pmap.set_next_bit(true);
         pmap.set_next_bit(false);
         pmap.set_next_bit(true);
+        assert_eq!(pmap.bit_index(), 10);
         assert_eq!(pmap.bitmap, 0b10101101010000);
         assert_eq!(pmap.size, 14);
     }
+
+    #[test]
+    fn long_presence_map() {
+        // 10 bytes with bits 0, 62, 63 and 69 set
+        let set = [0, 62, 63, 69];
+        let mut raw = vec![0u8; 10];
+        for bit in set {
+            raw[bit / 7] |= 0x40 >> (bit % 7);
+        }
+        raw[9] |= 0x80;
+
+        let mut rdr = bytes::Bytes::from(raw.clone());
+        let mut pmap = PresenceMap::read(&mut rdr).unwrap();
+        assert!(rdr.is_empty());
+        assert!(pmap.has_unused_bits_set());
+        for bit in 0..70 {
+            assert_eq!(pmap.bit_index(), bit);
+            assert_eq!(pmap.next_bit_set(), set.contains(&bit), "bit {bit}");
+            assert_eq!(pmap.has_unused_bits_set(), bit < 69, "bit {bit}");
+        }
+        // all other bits are false
+        assert!(!pmap.next_bit_set());
+        assert!(!pmap.next_bit_set());
+
+        let mut pmap = PresenceMap::new_empty();
+        for bit in 0..77 {
+            pmap.set_next_bit(set.contains(&bit));
+        }
+        assert_eq!(pmap.bit_index(), 77);
+        let mut buf = bytes::BytesMut::new();
+        pmap.write(&mut buf).unwrap();
+        // trailing byte with no bits set is dropped
+        assert_eq!(&buf[..], &raw[..]);
+
+        // the short form is not available for long presence maps
+        let mut rdr = bytes::Bytes::from(raw);
+        assert!(rdr.read_presence_map().is_err());
+    }
 }
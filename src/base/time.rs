@@ -0,0 +1,697 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::{Error, ErrorCode, Result};
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+const SECONDS_PER_DAY: i128 = 86_400;
+const NANOS_PER_DAY: i128 = NANOS_PER_SECOND * SECONDS_PER_DAY;
+
+/// Unit of `timestamp` and `timeOfDay` field values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TimeUnit {
+    Second,
+    #[default]
+    Millisecond,
+    Microsecond,
+    Nanosecond,
+}
+
+impl TimeUnit {
+    pub(crate) fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "second" => Ok(Self::Second),
+            "millisecond" => Ok(Self::Millisecond),
+            "microsecond" => Ok(Self::Microsecond),
+            "nanosecond" => Ok(Self::Nanosecond),
+            _ => Err(Error::Static(format!("unknown time unit: {s}"))),
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeUnit::Second => "second",
+            TimeUnit::Millisecond => "millisecond",
+            TimeUnit::Microsecond => "microsecond",
+            TimeUnit::Nanosecond => "nanosecond",
+        }
+    }
+
+    /// Returns the number of nanoseconds in one unit.
+    #[must_use]
+    pub fn nanos(&self) -> i64 {
+        match self {
+            TimeUnit::Second => 1_000_000_000,
+            TimeUnit::Millisecond => 1_000_000,
+            TimeUnit::Microsecond => 1_000,
+            TimeUnit::Nanosecond => 1,
+        }
+    }
+
+    // Number of fraction digits to format values of the unit.
+    fn digits(self) -> usize {
+        match self {
+            TimeUnit::Second => 0,
+            TimeUnit::Millisecond => 3,
+            TimeUnit::Microsecond => 6,
+            TimeUnit::Nanosecond => 9,
+        }
+    }
+}
+
+/// Value of a `timestamp` field: the number of `unit`s since midnight UTC of the `epoch` date.
+/// The `epoch` is the number of days since 1970-01-01.
+///
+/// Formats and parses as RFC 3339 date and time in UTC, e.g. `2024-06-06T12:30:00.000Z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timestamp {
+    pub value: i64,
+    pub unit: TimeUnit,
+    pub epoch: i32,
+}
+
+impl Timestamp {
+    #[must_use]
+    pub fn new(value: i64, unit: TimeUnit, epoch: i32) -> Self {
+        Self { value, unit, epoch }
+    }
+
+    /// Returns the number of nanoseconds since 1970-01-01T00:00:00Z.
+    #[must_use]
+    pub fn unix_nanos(&self) -> i128 {
+        i128::from(self.epoch) * NANOS_PER_DAY
+            + i128::from(self.value) * i128::from(self.unit.nanos())
+    }
+
+    /// Creates a timestamp in given `unit` and `epoch` from the number of nanoseconds since 1970-01-01T00:00:00Z.
+    /// # Errors
+    /// Returns error if the time can't be represented exactly in given unit or is out of range.
+    pub fn from_unix_nanos(nanos: i128, unit: TimeUnit, epoch: i32) -> Result<Self> {
+        let nanos = nanos - i128::from(epoch) * NANOS_PER_DAY;
+        let unit_nanos = i128::from(unit.nanos());
+        if nanos % unit_nanos != 0 {
+            return Err(Error::Runtime(format!(
+                "time can't be represented in {}s",
+                unit.as_str()
+            )));
+        }
+        let value = i64::try_from(nanos / unit_nanos)
+            .map_err(|_| Error::Runtime("timestamp is out of range".to_string()))?;
+        Ok(Self { value, unit, epoch })
+    }
+
+    /// Converts the timestamp to given `unit` and `epoch`.
+    /// # Errors
+    /// Returns error if the time can't be represented exactly in given unit or is out of range.
+    pub fn convert(&self, unit: TimeUnit, epoch: i32) -> Result<Self> {
+        Self::from_unix_nanos(self.unix_nanos(), unit, epoch)
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let nanos = self.unix_nanos();
+        let days = nanos.div_euclid(NANOS_PER_DAY);
+        let (y, m, d) = civil_from_days(days as i64);
+        write!(f, "{y:04}-{m:02}-{d:02}T")?;
+        write_time(f, nanos.rem_euclid(NANOS_PER_DAY), self.unit)?;
+        f.write_str("Z")
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    /// Parses RFC 3339 date and time, e.g. `2024-06-06T12:30:00.123Z`. The time and the offset are optional,
+    /// the time is in UTC if the offset is omitted. The result is in nanoseconds since 1970-01-01.
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || Error::Spec(ErrorCode::D11, format!("invalid timestamp: {s}"));
+        let mut p = Parser::new(s);
+        let days = p.date().ok_or_else(err)?;
+        let mut nanos = i128::from(days) * NANOS_PER_DAY;
+        if p.eat(b'T') || p.eat(b't') || p.eat(b' ') {
+            nanos += i128::from(p.time().ok_or_else(err)?);
+            if !(p.eat(b'Z') || p.eat(b'z') || p.is_empty()) {
+                let sign = if p.eat(b'+') {
+                    1
+                } else if p.eat(b'-') {
+                    -1
+                } else {
+                    return Err(err());
+                };
+                let h = p.number(2).ok_or_else(err)?;
+                if !p.eat(b':') {
+                    return Err(err());
+                }
+                let m = p.number(2).ok_or_else(err)?;
+                nanos -= sign * i128::from(h * 3600 + m * 60) * NANOS_PER_SECOND;
+            }
+        }
+        if !p.is_empty() {
+            return Err(err());
+        }
+        Self::from_unix_nanos(nanos, TimeUnit::Nanosecond, 0)
+    }
+}
+
+/// Value of a `date` field: the number of days since the `epoch` date.
+/// The `epoch` is the number of days since 1970-01-01.
+///
+/// Formats and parses as `YYYY-MM-DD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Date {
+    pub days: i32,
+    pub epoch: i32,
+}
+
+impl Date {
+    #[must_use]
+    pub fn new(days: i32, epoch: i32) -> Self {
+        Self { days, epoch }
+    }
+
+    /// Creates a date from year, month (1-12) and day (1-31) relative to 1970-01-01.
+    /// # Errors
+    /// Returns error if the date is invalid.
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Result<Self> {
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return Err(Error::Runtime(format!(
+                "invalid date: {year:04}-{month:02}-{day:02}"
+            )));
+        }
+        let days = i32::try_from(days_from_civil(year, month, day))
+            .map_err(|_| Error::Runtime("date is out of range".to_string()))?;
+        Ok(Self { days, epoch: 0 })
+    }
+
+    /// Returns year, month (1-12) and day (1-31).
+    #[must_use]
+    pub fn to_ymd(&self) -> (i32, u32, u32) {
+        civil_from_days(self.unix_days())
+    }
+
+    /// Returns the number of days since 1970-01-01.
+    #[must_use]
+    pub fn unix_days(&self) -> i64 {
+        i64::from(self.epoch) + i64::from(self.days)
+    }
+
+    /// Converts the date to given `epoch`.
+    /// # Errors
+    /// Returns error if the date is out of range.
+    pub fn convert(&self, epoch: i32) -> Result<Self> {
+        let days = i32::try_from(self.unix_days() - i64::from(epoch))
+            .map_err(|_| Error::Runtime("date is out of range".to_string()))?;
+        Ok(Self { days, epoch })
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (y, m, d) = self.to_ymd();
+        write!(f, "{y:04}-{m:02}-{d:02}")
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut p = Parser::new(s);
+        match p.date() {
+            Some(days) if p.is_empty() => Ok(Self { days, epoch: 0 }),
+            _ => Err(Error::Spec(ErrorCode::D11, format!("invalid date: {s}"))),
+        }
+    }
+}
+
+/// Value of a `timeOfDay` field: the number of `unit`s since midnight.
+///
+/// Formats and parses as `hh:mm:ss` with fraction of a second, e.g. `12:30:00.000`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeOfDay {
+    pub value: u64,
+    pub unit: TimeUnit,
+}
+
+impl TimeOfDay {
+    #[must_use]
+    pub fn new(value: u64, unit: TimeUnit) -> Self {
+        Self { value, unit }
+    }
+
+    /// Returns the number of nanoseconds since midnight.
+    #[must_use]
+    pub fn nanos(&self) -> u128 {
+        u128::from(self.value) * u128::from(self.unit.nanos().unsigned_abs())
+    }
+
+    /// Returns `true` if the time is less than 24 hours.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.nanos() < NANOS_PER_DAY.unsigned_abs()
+    }
+
+    /// Converts the time to given `unit`.
+    /// # Errors
+    /// Returns error if the time can't be represented exactly in given unit.
+    pub fn convert(&self, unit: TimeUnit) -> Result<Self> {
+        let nanos = self.nanos();
+        let unit_nanos = u128::from(unit.nanos().unsigned_abs());
+        if !nanos.is_multiple_of(unit_nanos) {
+            return Err(Error::Runtime(format!(
+                "time can't be represented in {}s",
+                unit.as_str()
+            )));
+        }
+        let value = u64::try_from(nanos / unit_nanos)
+            .map_err(|_| Error::Runtime("time of day is out of range".to_string()))?;
+        Ok(Self { value, unit })
+    }
+}
+
+impl Display for TimeOfDay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_time(f, self.nanos().cast_signed(), self.unit)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut p = Parser::new(s);
+        match p.time() {
+            Some(nanos) if p.is_empty() => Ok(Self {
+                value: nanos,
+                unit: TimeUnit::Nanosecond,
+            }),
+            _ => Err(Error::Spec(
+                ErrorCode::D11,
+                format!("invalid time of day: {s}"),
+            )),
+        }
+    }
+}
+
+/// Parses `epoch` attribute of time fields: `YYYY-MM-DD` date. Returns the number of days since 1970-01-01.
+pub(crate) fn parse_epoch(s: &str) -> Result<i32> {
+    Date::from_str(s)
+        .map(|d| d.days)
+        .map_err(|_| Error::Static(format!("invalid epoch: {s}")))
+}
+
+// Writes `hh:mm:ss` and the fraction of a second with as many digits as the unit needs.
+fn write_time(f: &mut Formatter<'_>, nanos: i128, unit: TimeUnit) -> std::fmt::Result {
+    let secs = nanos / NANOS_PER_SECOND;
+    write!(
+        f,
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )?;
+    let digits = unit.digits();
+    if digits > 0 {
+        let frac = nanos % NANOS_PER_SECOND / 10i128.pow(9 - digits as u32);
+        write!(f, ".{frac:0digits$}")?;
+    }
+    Ok(())
+}
+
+fn is_leap_year(y: i32) -> bool {
+    y % 4 == 0 && (y % 100 != 0 || y % 400 == 0)
+}
+
+fn days_in_month(y: i32, m: u32) -> u32 {
+    match m {
+        2 if is_leap_year(y) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Returns the number of days since 1970-01-01 for the proleptic Gregorian calendar date.
+// See: http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(y: i32, m: u32, d: u32) -> i64 {
+    let y = i64::from(y) - i64::from(m <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let m = i64::from(m);
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + i64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Returns the proleptic Gregorian calendar date for the number of days since 1970-01-01.
+fn civil_from_days(z: i64) -> (i32, u32, u32) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y as i32, m, d)
+}
+
+// A tiny parser of ISO 8601 dates and times.
+struct Parser<'a> {
+    s: &'a [u8],
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { s: s.as_bytes() }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.s.is_empty()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.s.first() == Some(&c) {
+            self.s = &self.s[1..];
+            true
+        } else {
+            false
+        }
+    }
+
+    // Parses exactly `n` digits.
+    fn number(&mut self, n: usize) -> Option<i64> {
+        if self.s.len() < n || !self.s[..n].iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let v = self.s[..n]
+            .iter()
+            .fold(0, |v, c| v * 10 + i64::from(c - b'0'));
+        self.s = &self.s[n..];
+        Some(v)
+    }
+
+    // Parses `YYYY-MM-DD` and returns the number of days since 1970-01-01.
+    fn date(&mut self) -> Option<i32> {
+        let y = self.number(4)?;
+        self.eat(b'-').then_some(())?;
+        let m = self.number(2)?;
+        self.eat(b'-').then_some(())?;
+        let d = self.number(2)?;
+        Date::from_ymd(y as i32, m as u32, d as u32)
+            .ok()
+            .map(|d| d.days)
+    }
+
+    // Parses `hh:mm[:ss[.fraction]]` and returns the number of nanoseconds since midnight.
+    fn time(&mut self) -> Option<u64> {
+        let h = self.number(2)?;
+        self.eat(b':').then_some(())?;
+        let m = self.number(2)?;
+        let mut s = 0;
+        let mut nanos = 0;
+        if self.eat(b':') {
+            s = self.number(2)?;
+            if self.eat(b'.') || self.eat(b',') {
+                let digits = self.s.iter().take_while(|c| c.is_ascii_digit()).count();
+                if digits == 0 || digits > 9 {
+                    return None;
+                }
+                nanos = self.number(digits)? * 10i64.pow(9 - digits as u32);
+            }
+        }
+        if h > 23 || m > 59 || s > 59 {
+            return None;
+        }
+        Some(((h * 3600 + m * 60 + s) * 1_000_000_000 + nanos) as u64)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Timestamp> for chrono::DateTime<chrono::Utc> {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self> {
+        let nanos = value.unix_nanos();
+        i64::try_from(nanos.div_euclid(NANOS_PER_SECOND))
+            .ok()
+            .and_then(|secs| {
+                chrono::DateTime::from_timestamp(secs, nanos.rem_euclid(NANOS_PER_SECOND) as u32)
+            })
+            .ok_or_else(|| Error::Runtime(format!("timestamp is out of range: {value}")))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<chrono::DateTime<chrono::Utc>> for Timestamp {
+    type Error = Error;
+
+    fn try_from(value: chrono::DateTime<chrono::Utc>) -> Result<Self> {
+        let nanos = i128::from(value.timestamp()) * NANOS_PER_SECOND
+            + i128::from(value.timestamp_subsec_nanos());
+        Timestamp::from_unix_nanos(nanos, TimeUnit::Nanosecond, 0)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Date> for chrono::NaiveDate {
+    type Error = Error;
+
+    fn try_from(value: Date) -> Result<Self> {
+        let (y, m, d) = value.to_ymd();
+        chrono::NaiveDate::from_ymd_opt(y, m, d)
+            .ok_or_else(|| Error::Runtime(format!("date is out of range: {value}")))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDate> for Date {
+    fn from(value: chrono::NaiveDate) -> Self {
+        use chrono::Datelike;
+        let days = days_from_civil(value.year(), value.month(), value.day());
+        // chrono dates are within ±262143 years which always fits into i32 days
+        Date::new(days as i32, 0)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<TimeOfDay> for chrono::NaiveTime {
+    type Error = Error;
+
+    fn try_from(value: TimeOfDay) -> Result<Self> {
+        let nanos = value.nanos();
+        u32::try_from(nanos / NANOS_PER_SECOND.unsigned_abs())
+            .ok()
+            .and_then(|secs| {
+                chrono::NaiveTime::from_num_seconds_from_midnight_opt(
+                    secs,
+                    (nanos % NANOS_PER_SECOND.unsigned_abs()) as u32,
+                )
+            })
+            .ok_or_else(|| Error::Runtime(format!("time of day is out of range: {value}")))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveTime> for TimeOfDay {
+    fn from(value: chrono::NaiveTime) -> Self {
+        use chrono::Timelike;
+        TimeOfDay::new(
+            u64::from(value.num_seconds_from_midnight()) * 1_000_000_000
+                + u64::from(value.nanosecond()),
+            TimeUnit::Nanosecond,
+        )
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Timestamp> for time::OffsetDateTime {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self> {
+        time::OffsetDateTime::from_unix_timestamp_nanos(value.unix_nanos())
+            .map_err(|_| Error::Runtime(format!("timestamp is out of range: {value}")))
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<time::OffsetDateTime> for Timestamp {
+    type Error = Error;
+
+    fn try_from(value: time::OffsetDateTime) -> Result<Self> {
+        Timestamp::from_unix_nanos(value.unix_timestamp_nanos(), TimeUnit::Nanosecond, 0)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Date> for time::Date {
+    type Error = Error;
+
+    fn try_from(value: Date) -> Result<Self> {
+        let (y, m, d) = value.to_ymd();
+        time::Month::try_from(m as u8)
+            .and_then(|m| time::Date::from_calendar_date(y, m, d as u8))
+            .map_err(|_| Error::Runtime(format!("date is out of range: {value}")))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::Date> for Date {
+    fn from(value: time::Date) -> Self {
+        let days = days_from_civil(
+            value.year(),
+            u32::from(u8::from(value.month())),
+            u32::from(value.day()),
+        );
+        // time dates are within ±999999 years which always fits into i32 days
+        Date::new(days as i32, 0)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<TimeOfDay> for time::Time {
+    type Error = Error;
+
+    fn try_from(value: TimeOfDay) -> Result<Self> {
+        let nanos = value.nanos();
+        let secs = nanos / NANOS_PER_SECOND.unsigned_abs();
+        u8::try_from(secs / 3600)
+            .ok()
+            .and_then(|h| {
+                time::Time::from_hms_nano(
+                    h,
+                    (secs / 60 % 60) as u8,
+                    (secs % 60) as u8,
+                    (nanos % NANOS_PER_SECOND.unsigned_abs()) as u32,
+                )
+                .ok()
+            })
+            .ok_or_else(|| Error::Runtime(format!("time of day is out of range: {value}")))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::Time> for TimeOfDay {
+    fn from(value: time::Time) -> Self {
+        let (h, m, s, ns) = value.as_hms_nano();
+        TimeOfDay::new(
+            (u64::from(h) * 3600 + u64::from(m) * 60 + u64::from(s)) * 1_000_000_000
+                + u64::from(ns),
+            TimeUnit::Nanosecond,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_days() {
+        for (y, m, d, days) in [
+            (1970, 1, 1, 0),
+            (2000, 3, 1, 11_017),
+            (1969, 12, 31, -1),
+            (2024, 2, 29, 19_782),
+            (1600, 1, 1, -135_140),
+        ] {
+            assert_eq!(days_from_civil(y, m, d), days);
+            assert_eq!(civil_from_days(days), (y, m, d));
+        }
+    }
+
+    #[test]
+    fn timestamp() {
+        let ts = Timestamp::new(1_717_677_000_123, TimeUnit::Millisecond, 0);
+        assert_eq!(ts.to_string(), "2024-06-06T12:30:00.123Z");
+        let parsed: Timestamp = "2024-06-06T12:30:00.123Z".parse().unwrap();
+        assert_eq!(parsed.convert(TimeUnit::Millisecond, 0).unwrap(), ts);
+        let parsed: Timestamp = "2024-06-06T14:30:00.123+02:00".parse().unwrap();
+        assert_eq!(parsed.convert(TimeUnit::Millisecond, 0).unwrap(), ts);
+        let parsed: Timestamp = "2024-06-06".parse().unwrap();
+        assert_eq!(parsed.unix_nanos(), 19_880 * NANOS_PER_DAY);
+        assert!(ts.convert(TimeUnit::Second, 0).is_err());
+
+        let epoch = parse_epoch("2000-01-01").unwrap();
+        let ts = ts.convert(TimeUnit::Microsecond, epoch).unwrap();
+        assert_eq!(ts.value, 770_992_200_123_000);
+        assert_eq!(ts.to_string(), "2024-06-06T12:30:00.123000Z");
+
+        let ts = Timestamp::new(-1, TimeUnit::Second, 0);
+        assert_eq!(ts.to_string(), "1969-12-31T23:59:59Z");
+
+        for s in [
+            "2024-06-06T25:00:00Z",
+            "2024-02-30",
+            "2024-06-06T12:30:00X",
+            "20240606",
+        ] {
+            assert!(s.parse::<Timestamp>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn date_and_time_of_day() {
+        let date = Date::from_ymd(2024, 6, 6).unwrap();
+        assert_eq!(date.days, 19_880);
+        assert_eq!(date.to_string(), "2024-06-06");
+        assert_eq!("2024-06-06".parse::<Date>().unwrap(), date);
+        assert_eq!(date.convert(10_957).unwrap().days, 8_923);
+
+        let time = TimeOfDay::new(45_000_123, TimeUnit::Millisecond);
+        assert_eq!(time.to_string(), "12:30:00.123");
+        let parsed: TimeOfDay = "12:30:00.123".parse().unwrap();
+        assert_eq!(parsed.convert(TimeUnit::Millisecond).unwrap(), time);
+        assert!(!TimeOfDay::new(86_400, TimeUnit::Second).is_valid());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_conversions() {
+        let ts = Timestamp::new(1_717_677_000_123, TimeUnit::Millisecond, 0);
+        let dt = chrono::DateTime::<chrono::Utc>::try_from(ts).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2024-06-06T12:30:00.123+00:00");
+        assert_eq!(
+            Timestamp::try_from(dt).unwrap().unix_nanos(),
+            ts.unix_nanos()
+        );
+
+        let date = Date::from_ymd(2024, 6, 6).unwrap();
+        let nd = chrono::NaiveDate::try_from(date).unwrap();
+        assert_eq!(nd, chrono::NaiveDate::from_ymd_opt(2024, 6, 6).unwrap());
+        assert_eq!(Date::from(nd), date);
+
+        let time = TimeOfDay::new(45_000_123, TimeUnit::Millisecond);
+        let nt = chrono::NaiveTime::try_from(time).unwrap();
+        assert_eq!(nt.to_string(), "12:30:00.123");
+        assert_eq!(TimeOfDay::from(nt).nanos(), time.nanos());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_conversions() {
+        let ts = Timestamp::new(1_717_677_000_123, TimeUnit::Millisecond, 0);
+        let dt = time::OffsetDateTime::try_from(ts).unwrap();
+        assert_eq!(dt.unix_timestamp(), 1_717_677_000);
+        assert_eq!(
+            Timestamp::try_from(dt).unwrap().unix_nanos(),
+            ts.unix_nanos()
+        );
+
+        let date = Date::from_ymd(2024, 6, 6).unwrap();
+        let td = time::Date::try_from(date).unwrap();
+        assert_eq!(
+            td,
+            time::Date::from_calendar_date(2024, time::Month::June, 6).unwrap()
+        );
+        assert_eq!(Date::from(td), date);
+
+        let time = TimeOfDay::new(45_000_123, TimeUnit::Millisecond);
+        let tt = time::Time::try_from(time).unwrap();
+        assert_eq!(tt.as_hms_milli(), (12, 30, 0, 123));
+        assert_eq!(TimeOfDay::from(tt).nanos(), time.nanos());
+    }
+}
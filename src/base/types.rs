@@ -1,27 +1,30 @@
-use std::cell::Cell;
-use std::rc::Rc;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use roxmltree::Node;
 
 use crate::base::instruction::Instruction;
+use crate::utils::xml::XmlElement;
 use crate::{Error, Result};
 
 /// A template contains a sequence of instructions. The order of the instructions is significant and corresponds
 /// to the order of the data in the stream.
-pub(crate) struct Template {
+pub struct Template {
     pub(crate) id: u32,
     pub(crate) name: String,
+    // The namespace of the template name, specified by `templateNs` attribute of the template or its parent.
+    pub(crate) namespace: Option<Arc<str>>,
     pub(crate) type_ref: TypeRef,
     pub(crate) dictionary: Dictionary,
     pub(crate) instructions: Vec<Instruction>,
 
     // This flag indicates if the template requires a presence map in case of statically referenced
     // from another template. If the flag is None, the presence map is not calculated yet.
-    pub(crate) require_pmap: Cell<Option<bool>>,
+    pub(crate) require_pmap: Option<bool>,
 }
 
 impl Template {
-    pub(crate) fn from_node(node: Node) -> Result<Self> {
+    pub(crate) fn from_node(node: Node, defs: &TypeDefinitions) -> Result<Self> {
         if node.tag_name().name() != "template" {
             return Err(Error::Static(format!(
                 "expected <template/> node, got <{}/>",
@@ -33,32 +36,226 @@ impl Template {
             .attribute("name")
             .ok_or_else(|| Error::Static("template name not found".to_string()))?
             .to_string();
-        let type_ref = node
-            .attribute("typeRef")
-            .map_or(TypeRef::Any, TypeRef::from_str);
+        let type_ref = node.attribute("typeRef").map_or(TypeRef::Any, |t| {
+            TypeRef::from_str(&qualified_name(inherited_attribute(node, "ns"), t))
+        });
         let dictionary = node
             .attribute("dictionary")
             .map_or(Dictionary::Global, Dictionary::from_str);
         let mut instructions = Vec::new();
         for child in node.children() {
-            if child.is_element() {
-                instructions.push(Instruction::from_node(child)?);
+            if child.is_element() && child.tag_name().name() != "define" {
+                instructions.push(Instruction::from_node(child, defs)?);
             }
         }
         Ok(Self {
             id,
             name,
+            namespace: inherited_attribute(node, "templateNs").map(Arc::from),
             type_ref,
             dictionary,
             instructions,
-            require_pmap: Cell::new(None),
+            require_pmap: None,
         })
     }
+
+    /// Returns the template id, zero if the template can only be referenced by name.
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the template name.
+    /// Names that are ambiguous between namespaces are qualified with the namespace as `{namespace}name`.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the namespace of the template name.
+    #[must_use]
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Returns the application type of the template.
+    #[must_use]
+    pub fn type_ref(&self) -> &TypeRef {
+        &self.type_ref
+    }
+
+    /// Returns the dictionary of the template fields.
+    #[must_use]
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dictionary
+    }
+
+    /// Returns the template fields.
+    #[must_use]
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns `true` if any field of the template occupies a bit in the presence map.
+    /// A statically referenced template that doesn't require a presence map doesn't add one to the enclosing template.
+    #[must_use]
+    pub fn requires_presence_map(&self) -> bool {
+        self.require_pmap.unwrap_or(false)
+    }
+
+    // Converts the template to XML element the template can be parsed from.
+    pub(crate) fn to_xml(&self) -> XmlElement {
+        let mut el = XmlElement::new("template");
+        let template_ns = self.namespace.as_deref();
+        // Ambiguous names are qualified with the namespace when definitions are created.
+        let name = template_ns
+            .and_then(|ns| self.name.strip_prefix(&format!("{{{ns}}}")))
+            .unwrap_or(&self.name);
+        el.attribute("name", name);
+        if self.id != 0 {
+            el.attribute("id", self.id);
+        }
+        if let Some(ns) = template_ns {
+            el.attribute("templateNs", ns);
+        }
+        if self.dictionary != Dictionary::Global
+            && let Some(d) = self.dictionary.name()
+        {
+            el.attribute("dictionary", d);
+        }
+        let mut ns = None;
+        if let TypeRef::ApplicationType(name) = &self.type_ref {
+            let (type_ns, name) = split_qualified_name(name);
+            if let Some(type_ns) = type_ns {
+                el.attribute("ns", type_ns);
+            }
+            el.attribute("typeRef", name);
+            ns = type_ns;
+        }
+        for i in &self.instructions {
+            el.child(i.to_xml(ns, template_ns));
+        }
+        el
+    }
+}
+
+/// Returns the value of the attribute of the node or of the closest ancestor that has it.
+/// Namespace attributes (`ns` and `templateNs`) are inherited from enclosing elements.
+pub(crate) fn inherited_attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.ancestors().find_map(|n| n.attribute(name))
+}
+
+/// Returns the name qualified with the namespace as `{namespace}name`, or the name itself if there is no namespace.
+pub(crate) fn qualified_name(ns: Option<&str>, name: &str) -> String {
+    match ns {
+        Some(ns) if !ns.is_empty() => format!("{{{ns}}}{name}"),
+        _ => name.to_string(),
+    }
+}
+
+/// Splits `{namespace}name` into the namespace and the name.
+pub(crate) fn split_qualified_name(name: &str) -> (Option<&str>, &str) {
+    name.strip_prefix('{')
+        .and_then(|s| s.split_once('}'))
+        .map_or((None, name), |(ns, name)| (Some(ns), name))
+}
+
+/// FAST 1.2 named type definitions. A `<define>` element names a field type, e.g. a decimal with operators,
+/// and `<field>` elements refer to it with a `<type>` element:
+/// ```xml
+/// <define name="Price"><decimal><delta/></decimal></define>
+/// <template name="Quote" id="1">
+///     <field name="Bid" id="132"><type name="Price"/></field>
+/// </template>
+/// ```
+/// Definitions can appear anywhere in the document and may refer to other definitions.
+pub(crate) struct TypeDefinitions<'a, 'input> {
+    defines: HashMap<&'a str, Node<'a, 'input>>,
+}
+
+impl<'a, 'input> TypeDefinitions<'a, 'input> {
+    pub(crate) fn from_node(root: Node<'a, 'input>) -> Result<Self> {
+        let (defs, mut errors) = Self::from_node_with_errors(root);
+        match errors.drain(..).next() {
+            Some((_, err)) => Err(err),
+            None => Ok(defs),
+        }
+    }
+
+    /// Collects all valid type definitions and returns the definitions that are ill-formed with their errors.
+    /// The first of duplicate definitions is used.
+    pub(crate) fn from_node_with_errors(
+        root: Node<'a, 'input>,
+    ) -> (Self, Vec<(Node<'a, 'input>, Error)>) {
+        let mut defines = HashMap::new();
+        let mut errors = Vec::new();
+        for node in root
+            .descendants()
+            .filter(|n| n.is_element() && n.tag_name().name() == "define")
+        {
+            let Some(name) = node.attribute("name") else {
+                errors.push((
+                    node,
+                    Error::Static("type definition has no name".to_string()),
+                ));
+                continue;
+            };
+            if defines.contains_key(name) {
+                errors.push((
+                    node,
+                    Error::Static(format!("duplicate type definition: {name}")),
+                ));
+                continue;
+            }
+            defines.insert(name, node);
+        }
+        (Self { defines }, errors)
+    }
+
+    /// Returns the type element of the `<field>` following `<type>` references.
+    /// It is a static error if a referenced type is not defined or the references form a cycle.
+    pub(crate) fn resolve(&self, field: Node<'a, 'input>) -> Result<Node<'a, 'input>> {
+        let mut node = type_element(field)?;
+        let mut path: Vec<&str> = Vec::new();
+        while node.tag_name().name() == "type" {
+            let name = node
+                .attribute("name")
+                .ok_or_else(|| Error::Static("type reference has no name".to_string()))?;
+            if path.contains(&name) {
+                path.push(name);
+                return Err(Error::Static(format!(
+                    "cyclic type definition: {}",
+                    path.join(" -> ")
+                )));
+            }
+            path.push(name);
+            let define = self
+                .defines
+                .get(name)
+                .ok_or_else(|| Error::Static(format!("unknown type: {name}")))?;
+            node = type_element(*define)?;
+        }
+        Ok(node)
+    }
+}
+
+// The only child element of `<field>` and `<define>` is the type of the field.
+fn type_element<'a, 'input>(node: Node<'a, 'input>) -> Result<Node<'a, 'input>> {
+    let mut children = node.children().filter(Node::is_element);
+    match (children.next(), children.next()) {
+        (Some(n), None) => Ok(n),
+        _ => Err(Error::Static(format!(
+            "<{}/> {} must have exactly one type element",
+            node.tag_name().name(),
+            node.attribute("name").unwrap_or_default()
+        ))),
+    }
 }
 
 /// Field operators specify ways to optimize the encoding of a field.
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub(crate) enum Operator {
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operator {
+    /// The value is always present in the stream.
     None,
     Constant,
     Default,
@@ -80,11 +277,23 @@ impl Operator {
             _ => Err(Error::Static(format!("Unknown operator: {t}"))),
         }
     }
+
+    pub(crate) fn tag(self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Constant => "constant",
+            Self::Default => "default",
+            Self::Copy => "copy",
+            Self::Increment => "increment",
+            Self::Delta => "delta",
+            Self::Tail => "tail",
+        }
+    }
 }
 
 /// The optional presence attribute indicates whether the field is mandatory or optional.
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub(crate) enum Presence {
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Presence {
     Mandatory,
     Optional,
 }
@@ -102,13 +311,13 @@ impl Presence {
 /// The dictionary name is specified by the dictionary attribute on the field operator element.
 /// There are three predefined dictionaries: "global", "template" and "type".
 /// "inherit" means that the dictionary name is inherited from the parent element.
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) enum Dictionary {
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Dictionary {
     Inherit,
     Global,
     Template,
     Type,
-    UserDefined(Rc<str>),
+    UserDefined(Arc<str>),
 }
 
 impl Dictionary {
@@ -117,21 +326,56 @@ impl Dictionary {
             "global" => Self::Global,
             "template" => Self::Template,
             "type" => Self::Type,
-            _ => Self::UserDefined(Rc::from(name)),
+            _ => Self::UserDefined(Arc::from(name)),
+        }
+    }
+
+    /// Returns the dictionary name or `None` if the dictionary is inherited.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Inherit => None,
+            Self::Global => Some("global"),
+            Self::Template => Some("template"),
+            Self::Type => Some("type"),
+            Self::UserDefined(name) => Some(name),
         }
     }
 }
 
 /// The current application type initially the special type any.
 /// The current application type changes when the processor encounters an element containing a "typeRef" element.
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) enum TypeRef {
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum TypeRef {
     Any,
-    ApplicationType(Rc<str>),
+    /// Application type name qualified with its namespace as `{namespace}name`.
+    ApplicationType(Arc<str>),
 }
 
 impl TypeRef {
     pub(crate) fn from_str(name: &str) -> Self {
-        Self::ApplicationType(Rc::from(name))
+        Self::ApplicationType(Arc::from(name))
+    }
+}
+
+/// Named element of `enum` and `set` field types.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Element {
+    pub(crate) name: Arc<str>,
+    // The integer value of an enum element or the bit mask of a set element.
+    pub(crate) value: u64,
+}
+
+impl Element {
+    /// Returns the element name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the integer value of an enum element or the bit mask of a set element.
+    #[must_use]
+    pub fn value(&self) -> u64 {
+        self.value
     }
 }
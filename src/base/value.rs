@@ -1,15 +1,23 @@
+use std::borrow::Cow;
 use std::cmp::min;
 use std::fmt::{Display, Formatter, Write};
+use std::sync::Arc;
 
 use crate::base::decimal::Decimal;
+use crate::base::time::{Date, TimeOfDay, TimeUnit, Timestamp};
 use crate::utils::bytes::{bytes_delta, bytes_tail, string_delta, string_tail, string_to_bytes};
-use crate::{Error, Result};
+use crate::{Error, ErrorCode, Result};
 
 /// Represents type of field instruction.
 ///
 /// It can be field instruction (including sequence and group) or template reference instruction.
 #[derive(Debug, PartialEq, Clone)]
 pub enum ValueType {
+    Boolean,
+    UInt8,
+    Int8,
+    UInt16,
+    Int16,
     UInt32,
     Int32,
     UInt64,
@@ -21,6 +29,11 @@ pub enum ValueType {
     ASCIIString,
     UnicodeString,
     Bytes,
+    Enum,
+    Set,
+    Timestamp,
+    Date,
+    TimeOfDay,
     Sequence,
     Group,
     TemplateReference,
@@ -32,6 +45,11 @@ impl ValueType {
     /// Returns error if tag is unknown.
     pub fn new_from_tag(tag: &str, unicode: bool) -> Result<Self> {
         match tag {
+            "boolean" => Ok(Self::Boolean),
+            "uInt8" => Ok(Self::UInt8),
+            "int8" => Ok(Self::Int8),
+            "uInt16" => Ok(Self::UInt16),
+            "int16" => Ok(Self::Int16),
             "uInt32" => Ok(Self::UInt32),
             "int32" => Ok(Self::Int32),
             "uInt64" => Ok(Self::UInt64),
@@ -48,6 +66,11 @@ impl ValueType {
                 }
             }
             "byteVector" => Ok(Self::Bytes),
+            "enum" => Ok(Self::Enum),
+            "set" => Ok(Self::Set),
+            "timestamp" => Ok(Self::Timestamp),
+            "date" => Ok(Self::Date),
+            "timeOfDay" => Ok(Self::TimeOfDay),
             "sequence" => Ok(Self::Sequence),
             "group" => Ok(Self::Group),
             "templateRef" => Ok(Self::TemplateReference),
@@ -58,6 +81,11 @@ impl ValueType {
     #[must_use]
     pub fn type_str(&self) -> &'static str {
         match self {
+            ValueType::Boolean => "boolean",
+            ValueType::UInt8 => "uInt8",
+            ValueType::Int8 => "int8",
+            ValueType::UInt16 => "uInt16",
+            ValueType::Int16 => "int16",
             ValueType::UInt32 => "uInt32",
             ValueType::Int32 => "int32",
             ValueType::UInt64 => "uInt64",
@@ -68,6 +96,11 @@ impl ValueType {
             ValueType::Decimal => "decimal",
             ValueType::ASCIIString | ValueType::UnicodeString => "string",
             ValueType::Bytes => "byteVector",
+            ValueType::Enum => "enum",
+            ValueType::Set => "set",
+            ValueType::Timestamp => "timestamp",
+            ValueType::Date => "date",
+            ValueType::TimeOfDay => "timeOfDay",
             ValueType::Sequence => "sequence",
             ValueType::Group => "group",
             ValueType::TemplateReference => "templateRef",
@@ -79,6 +112,11 @@ impl ValueType {
     /// Returns error if type does not have default type.
     pub fn to_default_value(&self) -> Result<Value> {
         match self {
+            ValueType::Boolean => Ok(Value::Bool(false)),
+            ValueType::UInt8 => Ok(Value::UInt8(0)),
+            ValueType::Int8 => Ok(Value::Int8(0)),
+            ValueType::UInt16 => Ok(Value::UInt16(0)),
+            ValueType::Int16 => Ok(Value::Int16(0)),
             ValueType::Int32 | ValueType::Exponent => Ok(Value::Int32(0)),
             ValueType::Int64 | ValueType::Mantissa => Ok(Value::Int64(0)),
             ValueType::UInt32 | ValueType::Length => Ok(Value::UInt32(0)),
@@ -87,6 +125,11 @@ impl ValueType {
             ValueType::ASCIIString => Ok(Value::ASCIIString(String::new())),
             ValueType::UnicodeString => Ok(Value::UnicodeString(String::new())),
             ValueType::Bytes => Ok(Value::Bytes(Vec::new())),
+            ValueType::Enum => Ok(Value::Enum(0, Arc::from(""))),
+            ValueType::Set => Ok(Value::Set(0, Vec::new())),
+            ValueType::Timestamp => Ok(Value::Timestamp(Timestamp::new(0, TimeUnit::default(), 0))),
+            ValueType::Date => Ok(Value::Date(Date::new(0, 0))),
+            ValueType::TimeOfDay => Ok(Value::TimeOfDay(TimeOfDay::new(0, TimeUnit::default()))),
             _ => Err(Error::Runtime(format!(
                 "{} cannot be converted to value",
                 self.type_str()
@@ -99,6 +142,11 @@ impl ValueType {
     /// Returns error if type is not convertible from string.
     pub fn str_to_value(&self, s: &str) -> Result<Value> {
         let mut value = match self {
+            ValueType::Boolean => Value::Bool(false),
+            ValueType::UInt8 => Value::UInt8(0),
+            ValueType::Int8 => Value::Int8(0),
+            ValueType::UInt16 => Value::UInt16(0),
+            ValueType::Int16 => Value::Int16(0),
             ValueType::Int32 | ValueType::Exponent => Value::Int32(0),
             ValueType::Int64 | ValueType::Mantissa => Value::Int64(0),
             ValueType::UInt32 | ValueType::Length => Value::UInt32(0),
@@ -107,6 +155,11 @@ impl ValueType {
             ValueType::ASCIIString => Value::ASCIIString(String::new()),
             ValueType::UnicodeString => Value::UnicodeString(String::new()),
             ValueType::Bytes => Value::Bytes(Vec::new()),
+            ValueType::Enum => Value::Enum(0, Arc::from("")),
+            ValueType::Set => Value::Set(0, Vec::new()),
+            ValueType::Timestamp => Value::Timestamp(Timestamp::new(0, TimeUnit::default(), 0)),
+            ValueType::Date => Value::Date(Date::new(0, 0)),
+            ValueType::TimeOfDay => Value::TimeOfDay(TimeOfDay::new(0, TimeUnit::default())),
             _ => {
                 return Err(Error::Runtime(format!(
                     "{} cannot be converted to value",
@@ -122,6 +175,11 @@ impl ValueType {
     #[must_use]
     pub fn matches_type(&self, v: &Value) -> bool {
         match (self, v) {
+            (ValueType::Boolean, Value::Bool(_)) => true,
+            (ValueType::UInt8, Value::UInt8(_)) => true,
+            (ValueType::Int8, Value::Int8(_)) => true,
+            (ValueType::UInt16, Value::UInt16(_)) => true,
+            (ValueType::Int16, Value::Int16(_)) => true,
             (ValueType::UInt32, Value::UInt32(_)) => true,
             (ValueType::Int32, Value::Int32(_)) => true,
             (ValueType::UInt64, Value::UInt64(_)) => true,
@@ -133,14 +191,32 @@ impl ValueType {
             (ValueType::ASCIIString, Value::ASCIIString(_)) => true,
             (ValueType::UnicodeString, Value::UnicodeString(_)) => true,
             (ValueType::Bytes, Value::Bytes(_)) => true,
+            (ValueType::Enum, Value::Enum(..)) => true,
+            (ValueType::Set, Value::Set(..)) => true,
+            (ValueType::Timestamp, Value::Timestamp(_)) => true,
+            (ValueType::Date, Value::Date(_)) => true,
+            (ValueType::TimeOfDay, Value::TimeOfDay(_)) => true,
             _ => false,
         }
     }
 }
 
 /// Represents current value of a field.
+///
+/// Values of `enum` and `set` fields carry both the integer and the symbolic names:
+/// * `Enum(value, name)` is the enum element; when encoding, the element is looked up by `name` unless it is empty;
+/// * `Set(mask, names)` is the bit mask of the set and the names of the flags it contains; when encoding,
+///   the flags from `names` are added to `mask`.
+///
+/// Values of `timestamp`, `date` and `timeOfDay` fields carry the unit and the epoch of the field.
+/// When encoding, they are converted to the unit and the epoch of the field.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
+    Bool(bool),
+    UInt8(u8),
+    Int8(i8),
+    UInt16(u16),
+    Int16(i16),
     UInt32(u32),
     Int32(i32),
     UInt64(u64),
@@ -149,6 +225,11 @@ pub enum Value {
     ASCIIString(String),
     UnicodeString(String),
     Bytes(Vec<u8>),
+    Enum(u32, Arc<str>),
+    Set(u64, Vec<Arc<str>>),
+    Timestamp(Timestamp),
+    Date(Date),
+    TimeOfDay(TimeOfDay),
 }
 
 impl Value {
@@ -157,6 +238,30 @@ impl Value {
     /// Returns error if string parse fails.
     pub fn set_from_string(&mut self, s: &str) -> Result<()> {
         match self {
+            Value::Bool(_) => {
+                *self = Value::Bool(match s.trim() {
+                    "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => {
+                        return Err(Error::Spec(
+                            ErrorCode::D11,
+                            format!("invalid boolean value: {s}"),
+                        ));
+                    }
+                });
+            }
+            Value::UInt8(_) => {
+                *self = Value::UInt8(s.parse()?);
+            }
+            Value::Int8(_) => {
+                *self = Value::Int8(s.parse()?);
+            }
+            Value::UInt16(_) => {
+                *self = Value::UInt16(s.parse()?);
+            }
+            Value::Int16(_) => {
+                *self = Value::Int16(s.parse()?);
+            }
             Value::UInt32(_) => {
                 *self = Value::UInt32(s.parse()?);
             }
@@ -184,6 +289,50 @@ impl Value {
             Value::Bytes(_) => {
                 *self = Value::Bytes(string_to_bytes(s)?);
             }
+            // Enum element is either an integer value or a name.
+            Value::Enum(..) => {
+                let s = s.trim();
+                *self = match s.parse() {
+                    Ok(v) => Value::Enum(v, Arc::from("")),
+                    Err(_) => Value::Enum(0, Arc::from(s)),
+                };
+            }
+            // Set is either an integer bit mask or a list of names separated by commas or spaces.
+            Value::Set(..) => {
+                let s = s.trim();
+                *self = match s.parse() {
+                    Ok(v) => Value::Set(v, Vec::new()),
+                    Err(_) => Value::Set(
+                        0,
+                        s.split([',', ' '])
+                            .filter(|n| !n.is_empty())
+                            .map(Arc::from)
+                            .collect(),
+                    ),
+                };
+            }
+            // Time is either an integer number of units of the field or formatted as ISO 8601.
+            Value::Timestamp(_) => {
+                let s = s.trim();
+                *self = match s.parse() {
+                    Ok(v) => Value::Int64(v),
+                    Err(_) => Value::Timestamp(s.parse()?),
+                };
+            }
+            Value::Date(_) => {
+                let s = s.trim();
+                *self = match s.parse() {
+                    Ok(v) => Value::Int32(v),
+                    Err(_) => Value::Date(s.parse()?),
+                };
+            }
+            Value::TimeOfDay(_) => {
+                let s = s.trim();
+                *self = match s.parse() {
+                    Ok(v) => Value::UInt64(v),
+                    Err(_) => Value::TimeOfDay(s.parse()?),
+                };
+            }
         }
         Ok(())
     }
@@ -191,6 +340,7 @@ impl Value {
     /// Applies delta to the current value and returns new value.
     /// # Errors
     /// Returns error if types are not bytes/string or previous and current types mismatch.
+    #[allow(clippy::too_many_lines)]
     pub fn apply_delta(&self, delta: &Value, sub: i32) -> Result<Value> {
         fn sub2index(sub: i32, len: usize) -> Result<(bool, usize)> {
             // A negative subtraction length is used to remove values from the front of the string.
@@ -205,9 +355,10 @@ impl Value {
                 i = sub as usize;
             }
             if i > len {
-                return Err(Error::Dynamic(format!(
-                    "subtraction length ({i}) is larger than string length ('{len}')"
-                ))); // [ERR D7]
+                return Err(Error::Spec(
+                    ErrorCode::D7,
+                    format!("subtraction length ({i}) is larger than string length ('{len}')"),
+                ));
             }
             if !front {
                 i = len - i;
@@ -228,23 +379,56 @@ impl Value {
             Ok(b)
         }
 
+        // It is a dynamic error [ERR D2] if the result does not fall within the bounds of the field type.
+        let out_of_range = || {
+            Error::Spec(
+                ErrorCode::D2,
+                format!("delta {delta:?} applied to {self:?} is out of range"),
+            )
+        };
         match (self, &delta) {
-            (Value::UInt32(v), Value::Int64(d)) => {
-                if *d < 0 {
-                    Ok(Value::UInt32(*v - (-*d) as u32))
-                } else {
-                    Ok(Value::UInt32(*v + *d as u32))
-                }
-            }
-            (Value::Int32(v), Value::Int64(d)) => Ok(Value::Int32(*v + *d as i32)),
-            (Value::UInt64(v), Value::Int64(d)) => {
-                if *d < 0 {
-                    Ok(Value::UInt64(*v - (-*d) as u64))
-                } else {
-                    Ok(Value::UInt64(*v + *d as u64))
-                }
+            (Value::Bool(v), Value::Int64(d)) => match i64::from(*v).checked_add(*d) {
+                Some(0) => Ok(Value::Bool(false)),
+                Some(1) => Ok(Value::Bool(true)),
+                _ => Err(out_of_range()),
+            },
+            (Value::UInt8(v), Value::Int64(d)) => i64::from(*v)
+                .checked_add(*d)
+                .and_then(|v| u8::try_from(v).ok())
+                .map(Value::UInt8)
+                .ok_or_else(out_of_range),
+            (Value::Int8(v), Value::Int64(d)) => i64::from(*v)
+                .checked_add(*d)
+                .and_then(|v| i8::try_from(v).ok())
+                .map(Value::Int8)
+                .ok_or_else(out_of_range),
+            (Value::UInt16(v), Value::Int64(d)) => i64::from(*v)
+                .checked_add(*d)
+                .and_then(|v| u16::try_from(v).ok())
+                .map(Value::UInt16)
+                .ok_or_else(out_of_range),
+            (Value::Int16(v), Value::Int64(d)) => i64::from(*v)
+                .checked_add(*d)
+                .and_then(|v| i16::try_from(v).ok())
+                .map(Value::Int16)
+                .ok_or_else(out_of_range),
+            (Value::UInt32(v), Value::Int64(d)) => i64::from(*v)
+                .checked_add(*d)
+                .and_then(|v| u32::try_from(v).ok())
+                .map(Value::UInt32)
+                .ok_or_else(out_of_range),
+            (Value::Int32(v), Value::Int64(d)) => i64::from(*v)
+                .checked_add(*d)
+                .and_then(|v| i32::try_from(v).ok())
+                .map(Value::Int32)
+                .ok_or_else(out_of_range),
+            (Value::UInt64(v), Value::Int64(d)) => v
+                .checked_add_signed(*d)
+                .map(Value::UInt64)
+                .ok_or_else(out_of_range),
+            (Value::Int64(v), Value::Int64(d)) => {
+                v.checked_add(*d).map(Value::Int64).ok_or_else(out_of_range)
             }
-            (Value::Int64(v), Value::Int64(d)) => Ok(Value::Int64(*v + *d)),
             (Value::ASCIIString(v), Value::ASCIIString(d)) => {
                 let (front, i) = sub2index(sub, v.len())?;
                 let s = if front {
@@ -257,7 +441,8 @@ impl Value {
             (Value::Bytes(v), Value::Bytes(d)) => Ok(Value::Bytes(bytes_delta(v, d, sub)?)),
             (Value::UnicodeString(v), Value::Bytes(d)) => {
                 let b = bytes_delta(v.as_bytes(), d, sub)?;
-                let s = String::from_utf8(b)?; // [ERR R2]
+                let s =
+                    String::from_utf8(b).map_err(|e| Error::Spec(ErrorCode::R2, e.to_string()))?;
                 Ok(Value::UnicodeString(s))
             }
             _ => Err(Error::Runtime(format!(
@@ -287,7 +472,18 @@ impl Value {
     /// # Errors
     /// Returns error if self type is not an integer.
     pub fn apply_increment(&self) -> Result<Value> {
+        // It is a dynamic error [ERR D2] if the result does not fall within the bounds of the field type.
+        let out_of_range = || {
+            Error::Spec(
+                ErrorCode::D2,
+                format!("increment of {self:?} is out of range"),
+            )
+        };
         match self {
+            Value::UInt8(v) => v.checked_add(1).map(Value::UInt8).ok_or_else(out_of_range),
+            Value::Int8(v) => v.checked_add(1).map(Value::Int8).ok_or_else(out_of_range),
+            Value::UInt16(v) => v.checked_add(1).map(Value::UInt16).ok_or_else(out_of_range),
+            Value::Int16(v) => v.checked_add(1).map(Value::Int16).ok_or_else(out_of_range),
             Value::UInt32(v) => Ok(Value::UInt32(v + 1)),
             Value::Int32(v) => Ok(Value::Int32(v + 1)),
             Value::UInt64(v) => Ok(Value::UInt64(v + 1)),
@@ -302,6 +498,13 @@ impl Value {
     #[must_use]
     pub fn find_delta(&self, prev: &Value) -> (Value, i32) {
         match (self, prev) {
+            (Value::Bool(v), Value::Bool(p)) => (Value::Int64(i64::from(*v) - i64::from(*p)), 0),
+            (Value::UInt8(v), Value::UInt8(p)) => (Value::Int64(i64::from(*v) - i64::from(*p)), 0),
+            (Value::Int8(v), Value::Int8(p)) => (Value::Int64(i64::from(*v) - i64::from(*p)), 0),
+            (Value::UInt16(v), Value::UInt16(p)) => {
+                (Value::Int64(i64::from(*v) - i64::from(*p)), 0)
+            }
+            (Value::Int16(v), Value::Int16(p)) => (Value::Int64(i64::from(*v) - i64::from(*p)), 0),
             (Value::Int32(v), Value::Int32(p)) => (Value::Int64(i64::from(v - p)), 0),
             (Value::Int64(v), Value::Int64(p)) => (Value::Int64(v - p), 0),
             (Value::UInt32(v), Value::UInt32(p)) => {
@@ -350,11 +553,147 @@ impl Value {
             _ => unimplemented!(),
         }
     }
+
+    // Converts an integer value to the integer type of a field, e.g. `Value::UInt8` to `ValueType::UInt32`.
+    // Other values are returned as is.
+    pub(crate) fn convert_to(self, type_: &ValueType) -> Result<Value> {
+        if type_.matches_type(&self) {
+            return Ok(self);
+        }
+        let v = match self {
+            Value::UInt8(v) => i128::from(v),
+            Value::Int8(v) => i128::from(v),
+            Value::UInt16(v) => i128::from(v),
+            Value::Int16(v) => i128::from(v),
+            Value::UInt32(v) => i128::from(v),
+            Value::Int32(v) => i128::from(v),
+            Value::UInt64(v) => i128::from(v),
+            Value::Int64(v) => i128::from(v),
+            _ => return Ok(self),
+        };
+        let converted = match type_ {
+            ValueType::UInt8 => u8::try_from(v).ok().map(Value::UInt8),
+            ValueType::Int8 => i8::try_from(v).ok().map(Value::Int8),
+            ValueType::UInt16 => u16::try_from(v).ok().map(Value::UInt16),
+            ValueType::Int16 => i16::try_from(v).ok().map(Value::Int16),
+            ValueType::UInt32 | ValueType::Length => u32::try_from(v).ok().map(Value::UInt32),
+            ValueType::Int32 | ValueType::Exponent => i32::try_from(v).ok().map(Value::Int32),
+            ValueType::UInt64 => u64::try_from(v).ok().map(Value::UInt64),
+            ValueType::Int64 | ValueType::Mantissa => i64::try_from(v).ok().map(Value::Int64),
+            _ => return Ok(self),
+        };
+        // It is a reportable error [ERR R4] if the integer does not fit into the field type.
+        converted.ok_or_else(|| {
+            Error::Spec(
+                ErrorCode::R4,
+                format!(
+                    "{self:?} is out of range of {} field type",
+                    type_.type_str()
+                ),
+            )
+        })
+    }
+}
+
+/// Represents a field value that may borrow strings and byte vectors from the decoded buffer.
+///
+/// Produced by [`Decoder::decode_buffer_ref`][crate::Decoder::decode_buffer_ref]. Unicode strings and byte vectors
+/// are borrowed from the input unless the value is taken from the dictionary (copy, increment, delta and tail operators)
+/// or from the instruction's initial value. ASCII strings are always owned, because the last character in the stream
+/// carries the stop bit.
+///
+/// When decoding from [`bytes::Bytes`] with `Decoder::decode_bytes_ref` (`shared-bytes` feature), unicode strings and
+/// byte vectors are reference-counted sub-slices of the buffer instead.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ValueRef<'a> {
+    Bool(bool),
+    UInt8(u8),
+    Int8(i8),
+    UInt16(u16),
+    Int16(i16),
+    UInt32(u32),
+    Int32(i32),
+    UInt64(u64),
+    Int64(i64),
+    Decimal(Decimal),
+    ASCIIString(Cow<'a, str>),
+    UnicodeString(Cow<'a, str>),
+    Bytes(Cow<'a, [u8]>),
+    /// Unicode string shared with the decoded [`bytes::Bytes`] buffer. The bytes are valid UTF-8.
+    SharedUnicodeString(bytes::Bytes),
+    /// Byte vector shared with the decoded [`bytes::Bytes`] buffer.
+    SharedBytes(bytes::Bytes),
+    Enum(u32, Arc<str>),
+    Set(u64, Vec<Arc<str>>),
+    Timestamp(Timestamp),
+    Date(Date),
+    TimeOfDay(TimeOfDay),
+}
+
+impl ValueRef<'_> {
+    /// Converts into owned value. Borrowed strings and byte vectors are copied.
+    #[must_use]
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Bool(v) => Value::Bool(v),
+            ValueRef::UInt8(v) => Value::UInt8(v),
+            ValueRef::Int8(v) => Value::Int8(v),
+            ValueRef::UInt16(v) => Value::UInt16(v),
+            ValueRef::Int16(v) => Value::Int16(v),
+            ValueRef::UInt32(v) => Value::UInt32(v),
+            ValueRef::Int32(v) => Value::Int32(v),
+            ValueRef::UInt64(v) => Value::UInt64(v),
+            ValueRef::Int64(v) => Value::Int64(v),
+            ValueRef::Decimal(v) => Value::Decimal(v),
+            ValueRef::ASCIIString(s) => Value::ASCIIString(s.into_owned()),
+            ValueRef::UnicodeString(s) => Value::UnicodeString(s.into_owned()),
+            ValueRef::Bytes(b) => Value::Bytes(b.into_owned()),
+            ValueRef::SharedUnicodeString(s) => {
+                Value::UnicodeString(String::from_utf8_lossy(&s).into_owned())
+            }
+            ValueRef::SharedBytes(b) => Value::Bytes(b.into()),
+            ValueRef::Enum(v, name) => Value::Enum(v, name),
+            ValueRef::Set(v, names) => Value::Set(v, names),
+            ValueRef::Timestamp(v) => Value::Timestamp(v),
+            ValueRef::Date(v) => Value::Date(v),
+            ValueRef::TimeOfDay(v) => Value::TimeOfDay(v),
+        }
+    }
+}
+
+impl From<Value> for ValueRef<'_> {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Bool(v) => ValueRef::Bool(v),
+            Value::UInt8(v) => ValueRef::UInt8(v),
+            Value::Int8(v) => ValueRef::Int8(v),
+            Value::UInt16(v) => ValueRef::UInt16(v),
+            Value::Int16(v) => ValueRef::Int16(v),
+            Value::UInt32(v) => ValueRef::UInt32(v),
+            Value::Int32(v) => ValueRef::Int32(v),
+            Value::UInt64(v) => ValueRef::UInt64(v),
+            Value::Int64(v) => ValueRef::Int64(v),
+            Value::Decimal(v) => ValueRef::Decimal(v),
+            Value::ASCIIString(s) => ValueRef::ASCIIString(Cow::Owned(s)),
+            Value::UnicodeString(s) => ValueRef::UnicodeString(Cow::Owned(s)),
+            Value::Bytes(b) => ValueRef::Bytes(Cow::Owned(b)),
+            Value::Enum(v, name) => ValueRef::Enum(v, name),
+            Value::Set(v, names) => ValueRef::Set(v, names),
+            Value::Timestamp(v) => ValueRef::Timestamp(v),
+            Value::Date(v) => ValueRef::Date(v),
+            Value::TimeOfDay(v) => ValueRef::TimeOfDay(v),
+        }
+    }
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            Value::Bool(v) => f.write_fmt(format_args!("{v}")),
+            Value::UInt8(v) => f.write_fmt(format_args!("{v}")),
+            Value::Int8(v) => f.write_fmt(format_args!("{v}")),
+            Value::UInt16(v) => f.write_fmt(format_args!("{v}")),
+            Value::Int16(v) => f.write_fmt(format_args!("{v}")),
             Value::UInt32(v) => f.write_fmt(format_args!("{v}")),
             Value::Int32(v) => f.write_fmt(format_args!("{v}")),
             Value::UInt64(v) => f.write_fmt(format_args!("{v}")),
@@ -369,6 +708,23 @@ impl Display for Value {
                 }
                 f.write_fmt(format_args!("{s}"))
             }
+            Value::Enum(v, name) => {
+                if name.is_empty() {
+                    f.write_fmt(format_args!("{v}"))
+                } else {
+                    f.write_str(name)
+                }
+            }
+            Value::Set(v, names) => {
+                if names.is_empty() && *v != 0 {
+                    f.write_fmt(format_args!("{v}"))
+                } else {
+                    f.write_str(&names.join(","))
+                }
+            }
+            Value::Timestamp(v) => v.fmt(f),
+            Value::Date(v) => v.fmt(f),
+            Value::TimeOfDay(v) => v.fmt(f),
         }
     }
 }
@@ -0,0 +1,574 @@
+//! # Code generator
+//!
+//! Generates Rust types that (de)serialize messages of template definitions with serde,
+//! following the data model of `ModelFactory` and `ModelVisitor`:
+//! templates are variants of the message enum, groups and sequence items are structs,
+//! static template references are inlined into the referencing struct and dynamic template references
+//! are boxed messages named `templateRef:N`.
+//!
+use std::fmt::Write;
+
+use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
+
+use crate::base::instruction::Instruction;
+use crate::base::types::Element;
+use crate::base::value::ValueType;
+use crate::common::definitions::{Definitions, find_template};
+
+/// Rust type of `decimal` fields in generated code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalType {
+    /// [`Decimal`][crate::Decimal] that preserves the scale of the value.
+    #[default]
+    Decimal,
+    /// `f64`.
+    Float,
+}
+
+/// Rust types of `timestamp`, `date` and `timeOfDay` fields in generated code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimeType {
+    /// Integer number of units of the field: `i64`, `i32` and `u64`.
+    #[default]
+    Integer,
+    /// `chrono::DateTime<chrono::Utc>`, `chrono::NaiveDate` and `chrono::NaiveTime`, requires `chrono` feature.
+    Chrono,
+    /// `time::OffsetDateTime`, `time::Date` and `time::Time`, requires `time` feature.
+    Time,
+}
+
+/// Generates Rust types for serde (de)serialization of messages from template definitions.
+///
+/// Usually called from a build script:
+/// ```rust,no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use fastlib::{CodeGenerator, Definitions};
+///
+/// let definitions = Definitions::new_from_xml(&std::fs::read_to_string("templates.xml")?)?;
+/// let code = CodeGenerator::new(&definitions).message_name("Message").generate();
+/// std::fs::write(std::path::Path::new(&std::env::var("OUT_DIR")?).join("messages.rs"), code)?;
+/// # Ok(())
+/// # }
+/// ```
+/// and included into the application with `include!(concat!(env!("OUT_DIR"), "/messages.rs"));`.
+///
+/// The generated code depends on `serde` with `derive` feature and on `serde_bytes` if templates have `byteVector` fields.
+pub struct CodeGenerator<'a> {
+    definitions: &'a Definitions,
+    message_name: String,
+    decimal_type: DecimalType,
+    time_type: TimeType,
+    derives: Vec<String>,
+}
+
+impl<'a> CodeGenerator<'a> {
+    /// Creates a code generator for given definitions.
+    #[must_use]
+    pub fn new(definitions: &'a Definitions) -> Self {
+        Self {
+            definitions,
+            message_name: "Message".to_string(),
+            decimal_type: DecimalType::default(),
+            time_type: TimeType::default(),
+            derives: Vec::new(),
+        }
+    }
+
+    /// Sets the name of the message enum, `Message` by default.
+    #[must_use]
+    pub fn message_name(mut self, name: &str) -> Self {
+        self.message_name = name.to_string();
+        self
+    }
+
+    /// Sets the Rust type of `decimal` fields, [`DecimalType::Decimal`] by default.
+    #[must_use]
+    pub fn decimal_type(mut self, decimal_type: DecimalType) -> Self {
+        self.decimal_type = decimal_type;
+        self
+    }
+
+    /// Sets the Rust types of time fields, [`TimeType::Integer`] by default.
+    #[must_use]
+    pub fn time_type(mut self, time_type: TimeType) -> Self {
+        self.time_type = time_type;
+        self
+    }
+
+    /// Adds a derive macro to the message enum and the structs,
+    /// which derive `Debug`, `Clone`, `PartialEq`, `Serialize` and `Deserialize` by default.
+    #[must_use]
+    pub fn derive(mut self, path: &str) -> Self {
+        self.derives.push(path.to_string());
+        self
+    }
+
+    /// Generates the code.
+    #[must_use]
+    pub fn generate(&self) -> String {
+        Generator::new(self).run()
+    }
+}
+
+// Names of types used by the generated code that must not be shadowed.
+const RESERVED_TYPES: [&str; 4] = ["Box", "Option", "String", "Vec"];
+
+const KEYWORDS: [&str; 51] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while",
+];
+
+struct Field {
+    ident: String,
+    type_: String,
+    attributes: Vec<String>,
+}
+
+// Fields of one struct. Names that appear more than once (e.g. from inlined template references)
+// map onto the same value of the data model, so only the first field is kept.
+#[derive(Default)]
+struct Fields {
+    list: Vec<Field>,
+    names: HashSet<String>,
+    idents: HashSet<String>,
+}
+
+impl Fields {
+    fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+
+    fn push(&mut self, name: String, type_: String, mut attributes: Vec<String>) {
+        let ident = unique_ident(field_ident(&name), &self.idents, "_");
+        if pascal_case(&ident) != name {
+            attributes.insert(0, format!("rename = {name:?}"));
+        }
+        self.names.insert(name);
+        self.idents.insert(ident.clone());
+        self.list.push(Field {
+            ident,
+            type_,
+            attributes,
+        });
+    }
+}
+
+struct Generator<'a> {
+    config: &'a CodeGenerator<'a>,
+    derives: String,
+    // Generated items, in order of appearance. Nested types follow the types that contain them.
+    items: Vec<String>,
+    // Bodies of generated types by name, to reuse identical groups, sequence items and enums.
+    // Types without body are template structs and reserved names.
+    types: HashMap<String, Option<String>>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(config: &'a CodeGenerator<'a>) -> Self {
+        let mut derives = vec![
+            "Debug".to_string(),
+            "Clone".to_string(),
+            "PartialEq".to_string(),
+            "serde::Serialize".to_string(),
+            "serde::Deserialize".to_string(),
+        ];
+        derives.extend(config.derives.iter().cloned());
+        let mut types = HashMap::default();
+        for name in RESERVED_TYPES {
+            types.insert(name.to_string(), None);
+        }
+        types.insert(config.message_name.clone(), None);
+        Self {
+            config,
+            derives: derives.join(", "),
+            items: Vec::new(),
+            types,
+        }
+    }
+
+    fn run(mut self) -> String {
+        // Only templates with identifiers can be encoded as messages or dynamic template references.
+        let templates: Vec<_> = self
+            .config
+            .definitions
+            .templates()
+            .filter(|t| t.id != 0)
+            .collect();
+        let idents: Vec<_> = templates
+            .iter()
+            .map(|t| {
+                let ident = type_ident(&t.name);
+                let ident = self.unique_type(&[ident.clone(), format!("{ident}Msg")]);
+                self.types.insert(ident.clone(), None);
+                ident
+            })
+            .collect();
+
+        let mut message = String::new();
+        _ = writeln!(
+            message,
+            "#[allow(clippy::large_enum_variant, clippy::enum_variant_names)]"
+        );
+        _ = writeln!(message, "#[derive({})]", self.derives);
+        _ = writeln!(message, "pub enum {} {{", self.config.message_name);
+        let mut variants = HashSet::default();
+        for (t, ident) in templates.iter().zip(&idents) {
+            let variant = unique_ident(type_ident(&t.name), &variants, "");
+            if variant != t.name {
+                _ = writeln!(message, "    #[serde(rename = {:?})]", t.name);
+            }
+            _ = writeln!(message, "    {variant}({ident}),");
+            variants.insert(variant);
+        }
+        message.push('}');
+        self.items.push(message);
+
+        for (t, ident) in templates.iter().zip(&idents) {
+            let slot = self.reserve_item();
+            let fields = self.fields(ident, &t.instructions);
+            self.items[slot] = self.struct_item(ident, &fields);
+        }
+
+        let mut code = String::from(
+            "// Generated by fastlib::CodeGenerator from template definitions. Do not edit.\n",
+        );
+        for item in self.items.iter().filter(|i| !i.is_empty()) {
+            code.push('\n');
+            code.push_str(item);
+            code.push('\n');
+        }
+        code
+    }
+
+    fn fields(&mut self, parent: &str, instructions: &[Instruction]) -> Fields {
+        let mut fields = Fields::default();
+        self.collect_fields(parent, instructions, &mut fields, &mut 0);
+        fields
+    }
+
+    // Dynamic template references are numbered within the group, the sequence item
+    // or the inlined static template reference, the same way as in the data model.
+    fn collect_fields(
+        &mut self,
+        parent: &str,
+        instructions: &[Instruction],
+        fields: &mut Fields,
+        ref_num: &mut u32,
+    ) {
+        for instruction in instructions {
+            let name = instruction.name.as_str();
+            let (type_, attributes) = match instruction.value_type {
+                ValueType::TemplateReference if name.is_empty() => {
+                    let name = format!("templateRef:{ref_num}");
+                    *ref_num += 1;
+                    if !fields.contains(&name) {
+                        let type_ = format!("Box<{}>", self.config.message_name);
+                        fields.push(name, type_, Vec::new());
+                    }
+                    continue;
+                }
+                ValueType::TemplateReference => {
+                    if let Some(t) =
+                        find_template(&self.config.definitions.templates_by_name, instruction)
+                    {
+                        self.collect_fields(parent, &t.instructions, fields, &mut 0);
+                    }
+                    continue;
+                }
+                _ if fields.contains(name) => continue,
+                ValueType::Group => {
+                    let ident = type_ident(name);
+                    let candidates = [ident.clone(), format!("{parent}{ident}")];
+                    (
+                        self.struct_type(&candidates, &instruction.instructions),
+                        vec![],
+                    )
+                }
+                ValueType::Sequence => {
+                    let ident = format!("{}Item", type_ident(name));
+                    let candidates = [ident.clone(), format!("{parent}{ident}")];
+                    let item = self.struct_type(&candidates, &instruction.instructions[1..]);
+                    (format!("Vec<{item}>"), vec![])
+                }
+                ValueType::Enum | ValueType::Set => {
+                    let ident = type_ident(name);
+                    let candidates = [ident.clone(), format!("{parent}{ident}")];
+                    let ident = self.enum_type(&candidates, &instruction.elements);
+                    if instruction.value_type == ValueType::Set {
+                        (format!("std::collections::BTreeSet<{ident}>"), vec![])
+                    } else {
+                        (ident, vec![])
+                    }
+                }
+                ValueType::Bytes => ("Vec<u8>".to_string(), vec!["with = \"serde_bytes\""]),
+                ValueType::Timestamp if self.config.time_type == TimeType::Time => {
+                    let with = if instruction.is_optional() {
+                        "with = \"time::serde::rfc3339::option\""
+                    } else {
+                        "with = \"time::serde::rfc3339\""
+                    };
+                    ("time::OffsetDateTime".to_string(), vec![with])
+                }
+                ref value_type => match self.scalar_type(value_type) {
+                    Some(type_) => (type_.to_string(), vec![]),
+                    None => continue,
+                },
+            };
+            let mut attributes: Vec<_> = attributes.into_iter().map(str::to_string).collect();
+            let type_ = if instruction.is_optional() {
+                // Fields deserialized `with` a module are not optional by default.
+                if !attributes.is_empty() {
+                    attributes.insert(0, "default".to_string());
+                }
+                format!("Option<{type_}>")
+            } else {
+                type_
+            };
+            fields.push(name.to_string(), type_, attributes);
+        }
+    }
+
+    fn scalar_type(&self, value_type: &ValueType) -> Option<&'static str> {
+        Some(match value_type {
+            ValueType::Boolean => "bool",
+            ValueType::UInt8 => "u8",
+            ValueType::Int8 => "i8",
+            ValueType::UInt16 => "u16",
+            ValueType::Int16 => "i16",
+            ValueType::UInt32 => "u32",
+            ValueType::Int32 => "i32",
+            ValueType::UInt64 => "u64",
+            ValueType::Int64 => "i64",
+            ValueType::Decimal => match self.config.decimal_type {
+                DecimalType::Decimal => "fastlib::Decimal",
+                DecimalType::Float => "f64",
+            },
+            ValueType::ASCIIString | ValueType::UnicodeString => "String",
+            ValueType::Timestamp => match self.config.time_type {
+                TimeType::Integer => "i64",
+                TimeType::Chrono => "chrono::DateTime<chrono::Utc>",
+                TimeType::Time => "time::OffsetDateTime",
+            },
+            ValueType::Date => match self.config.time_type {
+                TimeType::Integer => "i32",
+                TimeType::Chrono => "chrono::NaiveDate",
+                TimeType::Time => "time::Date",
+            },
+            ValueType::TimeOfDay => match self.config.time_type {
+                TimeType::Integer => "u64",
+                TimeType::Chrono => "chrono::NaiveTime",
+                TimeType::Time => "time::Time",
+            },
+            _ => return None,
+        })
+    }
+
+    // Generates a struct of a group or a sequence item, returns its name.
+    fn struct_type(&mut self, candidates: &[String], instructions: &[Instruction]) -> String {
+        let slot = self.reserve_item();
+        let fields = self.fields(&candidates[0], instructions);
+        let (ident, new) = self.typed_item(candidates, &self.struct_item("", &fields));
+        if new {
+            self.items[slot] = self.struct_item(&ident, &fields);
+        }
+        ident
+    }
+
+    fn enum_type(&mut self, candidates: &[String], elements: &[Element]) -> String {
+        let mut body = String::new();
+        let mut variants = HashSet::default();
+        for element in elements {
+            let variant = unique_ident(type_ident(&element.name), &variants, "");
+            if variant != *element.name {
+                _ = writeln!(body, "    #[serde(rename = {:?})]", &*element.name);
+            }
+            _ = writeln!(body, "    {variant},");
+            variants.insert(variant);
+        }
+        let (ident, new) = self.typed_item(candidates, &body);
+        if new {
+            self.items.push(format!(
+                "#[allow(clippy::enum_variant_names)]\n\
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]\n\
+                pub enum {ident} {{\n{body}}}"
+            ));
+        }
+        ident
+    }
+
+    // Returns the name of the type with given body and whether the type is new:
+    // an existing type with the same body or the first free candidate name.
+    fn typed_item(&mut self, candidates: &[String], body: &str) -> (String, bool) {
+        for c in candidates {
+            if matches!(self.types.get(c), Some(Some(b)) if b == body) {
+                return (c.clone(), false);
+            }
+        }
+        let ident = self.unique_type(candidates);
+        self.types.insert(ident.clone(), Some(body.to_string()));
+        (ident, true)
+    }
+
+    fn unique_type(&self, candidates: &[String]) -> String {
+        if let Some(c) = candidates.iter().find(|c| !self.types.contains_key(*c)) {
+            return c.clone();
+        }
+        let base = candidates.last().unwrap();
+        let mut n = 2;
+        loop {
+            let c = format!("{base}{n}");
+            if !self.types.contains_key(&c) {
+                return c;
+            }
+            n += 1;
+        }
+    }
+
+    // Reserves a place for an item, so it precedes nested items generated while building it.
+    fn reserve_item(&mut self) -> usize {
+        self.items.push(String::new());
+        self.items.len() - 1
+    }
+
+    fn struct_item(&self, ident: &str, fields: &Fields) -> String {
+        let mut s = String::new();
+        if !ident.is_empty() {
+            _ = writeln!(s, "#[derive({})]", self.derives);
+            _ = writeln!(s, "#[serde(rename_all = \"PascalCase\")]");
+            _ = writeln!(s, "pub struct {ident} {{");
+        }
+        for f in &fields.list {
+            if !f.attributes.is_empty() {
+                _ = writeln!(s, "    #[serde({})]", f.attributes.join(", "));
+            }
+            _ = writeln!(s, "    pub {}: {},", f.ident, f.type_);
+        }
+        if !ident.is_empty() {
+            s.push('}');
+        }
+        s
+    }
+}
+
+// Splits a name into words at non-alphanumeric characters and case changes, e.g. `MDEntryPx` into `MD`, `Entry`, `Px`.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut word = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1);
+            let boundary = c.is_ascii_uppercase()
+                && prev.is_some_and(|p| {
+                    p.is_ascii_lowercase()
+                        || p.is_ascii_digit()
+                        || (p.is_ascii_uppercase() && next.is_some_and(char::is_ascii_lowercase))
+                });
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.push(c);
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn field_ident(name: &str) -> String {
+    let ident = words(name)
+        .iter()
+        .map(|w| w.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    if ident.is_empty() {
+        "field".to_string()
+    } else if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{ident}")
+    } else if matches!(ident.as_str(), "crate" | "self" | "super") {
+        format!("{ident}_")
+    } else if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{ident}")
+    } else {
+        ident
+    }
+}
+
+fn type_ident(name: &str) -> String {
+    let mut ident = String::new();
+    for w in words(name) {
+        let mut chars = w.chars();
+        if let Some(c) = chars.next() {
+            ident.push(c.to_ascii_uppercase());
+            ident.push_str(&chars.as_str().to_ascii_lowercase());
+        }
+    }
+    if ident.is_empty() {
+        "Value".to_string()
+    } else if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{ident}")
+    } else if ident == "Self" {
+        "SelfType".to_string()
+    } else {
+        ident
+    }
+}
+
+// Converts a field identifier the same way as `#[serde(rename_all = "PascalCase")]` does.
+fn pascal_case(ident: &str) -> String {
+    let mut pascal = String::with_capacity(ident.len());
+    let mut capitalize = true;
+    for c in ident.trim_start_matches("r#").chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if capitalize {
+            pascal.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            pascal.push(c);
+        }
+    }
+    pascal
+}
+
+// Appends a number to the identifier if it is taken.
+fn unique_ident(ident: String, taken: &HashSet<String>, separator: &str) -> String {
+    if !taken.contains(&ident) {
+        return ident;
+    }
+    let mut n = 2;
+    loop {
+        let i = format!("{ident}{separator}{n}");
+        if !taken.contains(&i) {
+            return i;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(field_ident("MDEntryPx"), "md_entry_px");
+        assert_eq!(field_ident("SecurityIDSource"), "security_id_source");
+        assert_eq!(field_ident("templateRef:1"), "template_ref_1");
+        assert_eq!(field_ident("type"), "r#type");
+        assert_eq!(field_ident("self"), "self_");
+        assert_eq!(field_ident("1st"), "_1st");
+        assert_eq!(field_ident("::"), "field");
+        assert_eq!(type_ident("MDIncRefresh"), "MdIncRefresh");
+        assert_eq!(type_ident("{urn:a}Header"), "UrnAHeader");
+        assert_eq!(type_ident("Self"), "SelfType");
+        assert_eq!(type_ident("2"), "_2");
+        assert_eq!(pascal_case("appl_ver_id"), "ApplVerId");
+        assert_eq!(pascal_case("r#type"), "Type");
+    }
+}
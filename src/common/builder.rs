@@ -0,0 +1,510 @@
+//! # Template builder
+//!
+//! Templates are usually loaded from XML, but tests and applications that synthesize templates at runtime
+//! can build them in code. The builder creates the same instructions as the XML parser and runs the same checks,
+//! so a built template behaves exactly like its XML counterpart.
+//!
+use std::sync::Arc;
+
+use crate::base::instruction::Instruction;
+use crate::base::time::TimeUnit;
+use crate::base::types::{Dictionary, Operator, Presence, Template, TypeRef, split_qualified_name};
+use crate::base::value::ValueType;
+use crate::{Error, Result};
+
+/// Builds a template from code.
+///
+/// Fields are added with [`field`](Self::field), [`sequence`](Self::sequence), [`group`](Self::group)
+/// and template references. Other methods modify the last added field, e.g. the template
+/// ```xml
+/// <template name="MDIncRefresh" id="1">
+///     <uInt32 name="MsgSeqNum" id="34"><increment/></uInt32>
+///     <sequence name="MDEntries">
+///         <length name="NoMDEntries" id="268"/>
+///         <uInt32 name="MDUpdateAction" id="279"><copy value="0"/></uInt32>
+///         <decimal name="MDEntryPx" id="270" presence="optional">
+///             <exponent><copy/></exponent>
+///             <mantissa><delta/></mantissa>
+///         </decimal>
+///     </sequence>
+/// </template>
+/// ```
+/// is built as:
+/// ```rust
+/// use fastlib::{TemplateBuilder, ValueType};
+///
+/// let template = TemplateBuilder::new(1, "MDIncRefresh")
+///     .field(ValueType::UInt32, 34, "MsgSeqNum").increment()
+///     .sequence("MDEntries", |s| {
+///         s.field(ValueType::UInt32, 279, "MDUpdateAction").copy().value("0")
+///             .field(ValueType::Decimal, 270, "MDEntryPx").optional()
+///             .exponent().copy()
+///             .mantissa().delta()
+///     })
+///     .length(268, "NoMDEntries");
+/// ```
+/// Errors are collected while building and returned when the definitions are created,
+/// see [`Definitions::new_from_builders`][crate::Definitions::new_from_builders].
+pub struct TemplateBuilder {
+    template: Template,
+    field: Option<FieldBuilder>,
+    error: Option<Error>,
+}
+
+impl TemplateBuilder {
+    /// Creates a builder of the template with given id and name.
+    /// A template with zero id can only be referenced by name from other templates.
+    #[must_use]
+    pub fn new(id: u32, name: &str) -> Self {
+        Self {
+            template: Template {
+                id,
+                name: name.to_string(),
+                namespace: None,
+                type_ref: TypeRef::Any,
+                dictionary: Dictionary::Global,
+                instructions: Vec::new(),
+                require_pmap: None,
+            },
+            field: None,
+            error: None,
+        }
+    }
+
+    /// Sets the namespace of the template name (the `templateNs` attribute).
+    #[must_use]
+    pub fn template_ns(mut self, ns: &str) -> Self {
+        self.template.namespace = Some(Arc::from(ns));
+        self
+    }
+
+    /// Sets the dictionary of the template fields.
+    #[must_use]
+    pub fn template_dictionary(mut self, name: &str) -> Self {
+        self.template.dictionary = Dictionary::from_str(name);
+        self
+    }
+
+    /// Sets the application type of the template. The name can be qualified with a namespace as `{namespace}name`.
+    #[must_use]
+    pub fn template_type_ref(mut self, name: &str) -> Self {
+        self.template.type_ref = TypeRef::from_str(name);
+        self
+    }
+
+    /// Adds a field of primitive, decimal, enum, set or time type.
+    /// Use [`sequence`](Self::sequence), [`group`](Self::group), [`template_ref`](Self::template_ref)
+    /// and [`dynamic_template_ref`](Self::dynamic_template_ref) for other kinds of fields.
+    #[must_use]
+    pub fn field(self, value_type: ValueType, id: u32, name: &str) -> Self {
+        match value_type {
+            ValueType::Length
+            | ValueType::Exponent
+            | ValueType::Mantissa
+            | ValueType::Sequence
+            | ValueType::Group
+            | ValueType::TemplateReference => {
+                let err = Error::Static(format!(
+                    "{} {name} can't be added as a field",
+                    value_type.type_str()
+                ));
+                self.push(Err(err))
+            }
+            _ => {
+                let instruction = Instruction::new_checked(id, name, value_type);
+                self.push(instruction)
+            }
+        }
+    }
+
+    /// Adds a sequence. The closure gets an empty builder and adds the fields of the sequence element to it.
+    /// The sequence has an implicit length field, use [`length`](Self::length) to name it.
+    /// Operators of the sequence apply to its length field.
+    #[must_use]
+    pub fn sequence(self, name: &str, f: impl FnOnce(TemplateBuilder) -> TemplateBuilder) -> Self {
+        let instruction =
+            Instruction::new_checked(0, name, ValueType::Sequence).and_then(|mut s| {
+                let length = Instruction::new(0, &format!("{name}:length"), ValueType::Length);
+                s.add_instruction(length);
+                for i in f(TemplateBuilder::new(0, name)).into_instructions()? {
+                    s.add_instruction(i);
+                }
+                Ok(s)
+            });
+        self.push(instruction)
+    }
+
+    /// Adds a group. The closure gets an empty builder and adds the fields of the group to it.
+    #[must_use]
+    pub fn group(self, name: &str, f: impl FnOnce(TemplateBuilder) -> TemplateBuilder) -> Self {
+        let instruction = Instruction::new_checked(0, name, ValueType::Group).and_then(|mut g| {
+            for i in f(TemplateBuilder::new(0, name)).into_instructions()? {
+                g.add_instruction(i);
+            }
+            Ok(g)
+        });
+        self.push(instruction)
+    }
+
+    /// Adds a static reference to the template with given name.
+    /// The name can be qualified with the template namespace as `{namespace}name`.
+    #[must_use]
+    pub fn template_ref(self, name: &str) -> Self {
+        let (ns, name) = split_qualified_name(name);
+        let instruction =
+            Instruction::new_checked(0, name, ValueType::TemplateReference).map(|mut i| {
+                i.template_ns = ns.map(Arc::from);
+                i
+            });
+        self.push(instruction)
+    }
+
+    /// Adds a dynamic template reference.
+    #[must_use]
+    pub fn dynamic_template_ref(self) -> Self {
+        self.push(Instruction::new_checked(
+            0,
+            "",
+            ValueType::TemplateReference,
+        ))
+    }
+
+    /// Makes the last field optional.
+    #[must_use]
+    pub fn optional(self) -> Self {
+        self.modify(|f| {
+            f.instruction.presence = Presence::Optional;
+            Ok(())
+        })
+    }
+
+    /// Sets the constant operator. The value must be set with [`value`](Self::value).
+    #[must_use]
+    pub fn constant(self) -> Self {
+        self.operator(Operator::Constant)
+    }
+
+    /// Sets the default operator.
+    #[must_use]
+    pub fn default(self) -> Self {
+        self.operator(Operator::Default)
+    }
+
+    /// Sets the copy operator.
+    #[must_use]
+    pub fn copy(self) -> Self {
+        self.operator(Operator::Copy)
+    }
+
+    /// Sets the increment operator.
+    #[must_use]
+    pub fn increment(self) -> Self {
+        self.operator(Operator::Increment)
+    }
+
+    /// Sets the delta operator.
+    #[must_use]
+    pub fn delta(self) -> Self {
+        self.operator(Operator::Delta)
+    }
+
+    /// Sets the tail operator.
+    #[must_use]
+    pub fn tail(self) -> Self {
+        self.operator(Operator::Tail)
+    }
+
+    /// Sets the initial value of the operator. The value is converted from string the same way as the `value`
+    /// attribute of the operator element in XML: enum and set values are element names, times are in ISO 8601 format.
+    #[must_use]
+    pub fn value(self, value: &str) -> Self {
+        self.modify(|f| {
+            if let Some(c) = f.component() {
+                return c.set_initial_value(value);
+            }
+            f.initial_value = Some(value.to_string());
+            Ok(())
+        })
+    }
+
+    /// Sets the dictionary of the operator.
+    #[must_use]
+    pub fn dictionary(self, name: &str) -> Self {
+        self.modify(|f| {
+            f.component_or_field().dictionary = Dictionary::from_str(name);
+            Ok(())
+        })
+    }
+
+    /// Sets the dictionary key of the operator.
+    #[must_use]
+    pub fn key(self, key: &str) -> Self {
+        self.modify(|f| {
+            f.component_or_field().key = Arc::from(key);
+            Ok(())
+        })
+    }
+
+    /// Sets the application type of the last field. The name can be qualified with a namespace as `{namespace}name`.
+    #[must_use]
+    pub fn type_ref(self, name: &str) -> Self {
+        self.modify(|f| {
+            f.instruction.type_ref = TypeRef::from_str(name);
+            Ok(())
+        })
+    }
+
+    /// Names the length field of the last sequence, byte vector or unicode string.
+    #[must_use]
+    pub fn length(self, id: u32, name: &str) -> Self {
+        self.modify(|f| {
+            let i = &mut f.instruction;
+            if i.value_type == ValueType::Sequence {
+                i.instructions[0] = Instruction::new(id, name, ValueType::Length);
+                return Ok(());
+            }
+            i.add_length(id, Some(name))
+        })
+    }
+
+    /// Following operator methods apply to the exponent of the last decimal field.
+    #[must_use]
+    pub fn exponent(self) -> Self {
+        self.modify(|f| f.select(Component::Exponent))
+    }
+
+    /// Following operator methods apply to the mantissa of the last decimal field.
+    #[must_use]
+    pub fn mantissa(self) -> Self {
+        self.modify(|f| f.select(Component::Mantissa))
+    }
+
+    /// Adds an element to the last enum or set field.
+    /// The value of an enum element is one greater than the value of the previous element.
+    #[must_use]
+    pub fn element(self, name: &str) -> Self {
+        self.modify(|f| f.instruction.add_element(name, None))
+    }
+
+    /// Adds an element with given value to the last enum field.
+    #[must_use]
+    pub fn element_with_value(self, name: &str, value: u32) -> Self {
+        self.modify(|f| f.instruction.add_element(name, Some(value)))
+    }
+
+    /// Sets the unit of the last timestamp or timeOfDay field.
+    #[must_use]
+    pub fn unit(self, unit: TimeUnit) -> Self {
+        self.modify(|f| f.instruction.set_unit(unit))
+    }
+
+    /// Sets the epoch of the last timestamp or date field as the number of days since 1970-01-01.
+    #[must_use]
+    pub fn epoch(self, epoch: i32) -> Self {
+        self.modify(|f| f.instruction.set_epoch(epoch))
+    }
+
+    pub(crate) fn build(self) -> Result<Template> {
+        let mut template = self.template;
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if let Some(f) = self.field {
+            template.instructions.push(f.build()?);
+        }
+        Ok(template)
+    }
+
+    fn into_instructions(self) -> Result<Vec<Instruction>> {
+        Ok(self.build()?.instructions)
+    }
+
+    fn operator(self, operator: Operator) -> Self {
+        self.modify(|f| {
+            match f.component() {
+                Some(c) => c.operator = operator,
+                None => f.operator = Some(operator),
+            }
+            Ok(())
+        })
+    }
+
+    // Finishes the last field and starts a new one. Only the first error is kept.
+    fn push(mut self, instruction: Result<Instruction>) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        if let Some(f) = self.field.take() {
+            match f.build() {
+                Ok(i) => self.template.instructions.push(i),
+                Err(e) => {
+                    self.error = Some(e);
+                    return self;
+                }
+            }
+        }
+        match instruction {
+            Ok(instruction) => self.field = Some(FieldBuilder::new(instruction)),
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    fn modify(mut self, f: impl FnOnce(&mut FieldBuilder) -> Result<()>) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        let res = match &mut self.field {
+            Some(field) => f(field),
+            None => Err(Error::Static(format!(
+                "template {} has no field to modify",
+                self.template.name
+            ))),
+        };
+        if let Err(e) = res {
+            self.error = Some(e);
+        }
+        self
+    }
+}
+
+// Subcomponent of a decimal field the operator methods apply to.
+#[derive(Clone, Copy)]
+enum Component {
+    Field,
+    Exponent,
+    Mantissa,
+}
+
+// The last added field. The operator and initial value of the field are applied when the field is finished,
+// because they depend on enum elements, time units and the presence of the field.
+struct FieldBuilder {
+    instruction: Instruction,
+    operator: Option<Operator>,
+    initial_value: Option<String>,
+    exponent: Option<Instruction>,
+    mantissa: Option<Instruction>,
+    selected: Component,
+}
+
+impl FieldBuilder {
+    fn new(instruction: Instruction) -> Self {
+        Self {
+            instruction,
+            operator: None,
+            initial_value: None,
+            exponent: None,
+            mantissa: None,
+            selected: Component::Field,
+        }
+    }
+
+    fn select(&mut self, component: Component) -> Result<()> {
+        if self.instruction.value_type != ValueType::Decimal {
+            return Err(Error::Static(format!(
+                "{} {} has no subcomponents",
+                self.instruction.value_type.type_str(),
+                self.instruction.name
+            )));
+        }
+        self.selected = component;
+        let (c, value_type) = match component {
+            Component::Field => return Ok(()),
+            Component::Exponent => (&mut self.exponent, ValueType::Exponent),
+            Component::Mantissa => (&mut self.mantissa, ValueType::Mantissa),
+        };
+        c.get_or_insert_with(|| Instruction::new(0, "", value_type));
+        Ok(())
+    }
+
+    // Returns the selected decimal subcomponent.
+    fn component(&mut self) -> Option<&mut Instruction> {
+        match self.selected {
+            Component::Field => None,
+            Component::Exponent => self.exponent.as_mut(),
+            Component::Mantissa => self.mantissa.as_mut(),
+        }
+    }
+
+    fn component_or_field(&mut self) -> &mut Instruction {
+        match self.selected {
+            Component::Field => &mut self.instruction,
+            Component::Exponent => self.exponent.as_mut().unwrap_or(&mut self.instruction),
+            Component::Mantissa => self.mantissa.as_mut().unwrap_or(&mut self.instruction),
+        }
+    }
+
+    fn build(self) -> Result<Instruction> {
+        let mut instruction = self.instruction;
+        match instruction.value_type {
+            ValueType::Decimal => {
+                let components = if self.exponent.is_some() || self.mantissa.is_some() {
+                    let ex = self
+                        .exponent
+                        .unwrap_or_else(|| Instruction::new(0, "", ValueType::Exponent));
+                    let mn = self
+                        .mantissa
+                        .unwrap_or_else(|| Instruction::new(0, "", ValueType::Mantissa));
+                    ex.check_is_valid()?;
+                    mn.check_is_valid()?;
+                    (Some(ex), Some(mn))
+                } else {
+                    (None, None)
+                };
+                instruction.set_decimal_components(
+                    self.operator,
+                    self.initial_value.as_deref(),
+                    components.0,
+                    components.1,
+                )?;
+            }
+            ValueType::Group | ValueType::TemplateReference => {
+                if self.operator.is_some() || self.initial_value.is_some() {
+                    return Err(Error::Static(format!(
+                        "{} {} can't have operator",
+                        instruction.value_type.type_str(),
+                        instruction.name
+                    )));
+                }
+            }
+            _ => {
+                // An optional sequence, byte vector or unicode string means that the length field is optional.
+                let presence = instruction.presence;
+                if let Some(length) = instruction.instructions.first_mut() {
+                    length.presence = presence;
+                }
+                // Operator of a sequence applies to its length field.
+                let target = if instruction.value_type == ValueType::Sequence {
+                    &mut instruction.instructions[0]
+                } else {
+                    &mut instruction
+                };
+                if let Some(operator) = self.operator {
+                    target.operator = operator;
+                }
+                if let Some(value) = &self.initial_value {
+                    if self.operator.is_none() {
+                        return Err(Error::Static(format!(
+                            "{} {} has initial value but no operator",
+                            target.value_type.type_str(),
+                            target.name
+                        )));
+                    }
+                    target.set_initial_value(value)?;
+                }
+                target.check_is_valid()?;
+            }
+        }
+        if matches!(instruction.value_type, ValueType::Enum | ValueType::Set)
+            && instruction.elements.is_empty()
+        {
+            return Err(Error::Static(format!(
+                "{} {} has no elements",
+                instruction.value_type.type_str(),
+                instruction.name
+            )));
+        }
+        instruction.check_is_valid()?;
+        Ok(instruction)
+    }
+}
@@ -1,14 +1,61 @@
-use rustc_hash::FxHashMap as HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::Value;
+use crate::base::instruction::Instruction;
 
+/// Dictionary instance the previous value belongs to.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DictionaryType {
     Global,
     Template(u32),
-    Type(Rc<str>),
-    UserDefined(Rc<str>),
+    Type(Arc<str>),
+    /// Type dictionary of elements without application type.
+    AnyType,
+    UserDefined(Arc<str>),
+}
+
+/// Location of the previous value of an instruction in the [`Context`].
+/// Resolved for every instruction when definitions are created.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) enum ContextSlot {
+    /// The instruction doesn't access dictionaries.
+    #[default]
+    None,
+    /// The instruction always uses the same dictionary entry.
+    Fixed(usize),
+    /// The dictionary entry depends on the processing context the instruction is used in,
+    /// e.g. a statically referenced template with "template" dictionary.
+    Scoped(Box<[(DictionaryType, usize)]>),
+}
+
+/// Slots of a field resolved for the processing context the field is used in:
+/// the slot of the field and the slots of the exponent and the mantissa of a decimal field.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct FieldSlots {
+    pub(crate) field: Option<usize>,
+    pub(crate) components: [Option<usize>; 2],
+}
+
+impl FieldSlots {
+    /// Slots of an instruction that always uses the same dictionary entry, e.g. the template identifier.
+    pub(crate) fn fixed(instruction: &Instruction) -> Self {
+        let field = match instruction.slot {
+            ContextSlot::Fixed(slot) => Some(slot),
+            _ => None,
+        };
+        Self {
+            field,
+            ..Self::default()
+        }
+    }
+
+    /// Slots of the exponent (0) or the mantissa (1) of a decimal field.
+    pub(crate) fn component(&self, idx: usize) -> Self {
+        Self {
+            field: self.components[idx],
+            ..Self::default()
+        }
+    }
 }
 
 /// Decoder state that stores global state during all messages decoding.
@@ -17,227 +64,131 @@ pub enum DictionaryType {
 /// Can be reset during messages decoding.
 #[derive(Debug, PartialEq, Default)]
 pub(crate) struct Context {
-    values: HashMap<(DictionaryType, ValueKey), Option<Value>>,
-}
+    // Previous values indexed by slot. The outer `None` means the value is undefined.
+    values: Vec<Option<Option<Value>>>,
 
-type ValueKey = Rc<str>;
+    // Undo journal. When enabled, the previous value of every updated slot is recorded,
+    // so all changes made since `begin()` can be rolled back.
+    journaling: bool,
+    journal: Vec<(usize, Option<Option<Value>>)>,
+}
 
 impl Context {
-    pub(crate) fn new() -> Self {
-        Self::default()
+    pub(crate) fn new(slots: usize) -> Self {
+        Self {
+            values: vec![None; slots],
+            ..Self::default()
+        }
     }
 
     pub(crate) fn reset(&mut self) {
-        self.values.clear();
+        self.values.fill(None);
+        self.journaling = false;
+        self.journal.clear();
     }
 
-    pub(crate) fn set(&mut self, dict: DictionaryType, key: ValueKey, val: Option<Value>) {
-        self.values.insert((dict, key), val);
+    #[inline]
+    pub(crate) fn set(&mut self, slot: usize, val: Option<Value>) {
+        let old = self.values[slot].replace(val);
+        if self.journaling {
+            self.journal.push((slot, old));
+        }
     }
 
-    pub(crate) fn get(&self, dict: DictionaryType, key: &ValueKey) -> Option<Option<Value>> {
-        self.values.get(&(dict, key.clone())).cloned()
+    #[inline]
+    pub(crate) fn get(&self, slot: usize) -> Option<&Option<Value>> {
+        self.values[slot].as_ref()
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::rc::Rc;
-
-    use crate::{
-        Value,
-        common::context::{Context, DictionaryType},
-    };
-
-    #[test]
-    fn global_set_get_some_value() {
-        let mut context = Context::new();
-        let value = Some(Value::Int32(1));
-
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::Global;
-
-        let before_set = context.get(dict.clone(), &key);
-        assert!(
-            before_set.is_none(),
-            "Value is set before insertion: {before_set:#?}"
-        );
-
-        context.set(dict.clone(), key.clone(), value.clone());
 
-        let after_set = context.get(dict, &key);
-        assert_eq!(
-            after_set,
-            Some(value.clone()),
-            "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
-        );
+    /// Starts recording changes, so they can be rolled back.
+    pub(crate) fn begin(&mut self) {
+        self.journaling = true;
+        self.journal.clear();
     }
 
-    #[test]
-    fn global_set_get_none() {
-        let mut context = Context::new();
-        let value = None;
-
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::Global;
-
-        let before_set = context.get(dict.clone(), &key);
-        assert!(
-            before_set.is_none(),
-            "Value is set before insertion: {before_set:#?}"
-        );
-
-        context.set(dict.clone(), key.clone(), value.clone());
-
-        let after_set = context.get(dict, &key);
-        assert_eq!(
-            after_set,
-            Some(value.clone()),
-            "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
-        );
+    /// Accepts all changes made since `begin()`.
+    pub(crate) fn commit(&mut self) {
+        self.journaling = false;
+        self.journal.clear();
     }
 
-    #[test]
-    fn template_set_get_some_value() {
-        let mut context = Context::new();
-        let value = Some(Value::Int32(1));
-
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::Template(2);
-
-        let before_set = context.get(dict.clone(), &key);
-        assert!(
-            before_set.is_none(),
-            "Value is set before insertion: {before_set:#?}"
-        );
-
-        context.set(dict.clone(), key.clone(), value.clone());
-
-        let after_set = context.get(dict, &key);
-        assert_eq!(
-            after_set,
-            Some(value.clone()),
-            "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
-        );
+    /// Discards all changes made since `begin()`.
+    pub(crate) fn rollback(&mut self) {
+        self.journaling = false;
+        while let Some((slot, old)) = self.journal.pop() {
+            self.values[slot] = old;
+        }
     }
+}
 
-    #[test]
-    fn template_set_get_none() {
-        let mut context = Context::new();
-        let value = None;
-
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::Template(2);
-
-        let before_set = context.get(dict.clone(), &key);
-        assert!(
-            before_set.is_none(),
-            "Value is set before insertion: {before_set:#?}"
-        );
-
-        context.set(dict.clone(), key.clone(), value.clone());
-
-        let after_set = context.get(dict, &key);
-        assert_eq!(
-            after_set,
-            Some(value.clone()),
-            "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
-        );
-    }
+#[cfg(test)]
+mod tests {
+    use crate::{Value, common::context::Context};
 
     #[test]
-    fn type_set_get_some_value() {
-        let mut context = Context::new();
+    fn set_get_some_value() {
+        let mut context = Context::new(2);
         let value = Some(Value::Int32(1));
 
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::Type(Rc::from("type"));
-
-        let before_set = context.get(dict.clone(), &key);
+        let before_set = context.get(1);
         assert!(
             before_set.is_none(),
             "Value is set before insertion: {before_set:#?}"
         );
 
-        context.set(dict.clone(), key.clone(), value.clone());
+        context.set(1, value.clone());
 
-        let after_set = context.get(dict, &key);
+        let after_set = context.get(1);
         assert_eq!(
             after_set,
-            Some(value.clone()),
+            Some(&value),
             "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
         );
+        assert!(context.get(0).is_none());
     }
 
     #[test]
-    fn type_set_get_none() {
-        let mut context = Context::new();
-        let value = None;
+    fn set_get_none() {
+        let mut context = Context::new(2);
 
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::Type(Rc::from("type"));
+        context.set(0, None);
 
-        let before_set = context.get(dict.clone(), &key);
-        assert!(
-            before_set.is_none(),
-            "Value is set before insertion: {before_set:#?}"
-        );
-
-        context.set(dict.clone(), key.clone(), value.clone());
-
-        let after_set = context.get(dict, &key);
+        let after_set = context.get(0);
         assert_eq!(
             after_set,
-            Some(value.clone()),
-            "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
+            Some(&None),
+            "Values does not match after set: lhs ({after_set:?}) != rhs (None)"
         );
     }
 
     #[test]
-    fn user_set_get_some_value() {
-        let mut context = Context::new();
-        let value = Some(Value::Int32(1));
-
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::UserDefined(Rc::from("type"));
-
-        let before_set = context.get(dict.clone(), &key);
-        assert!(
-            before_set.is_none(),
-            "Value is set before insertion: {before_set:#?}"
-        );
-
-        context.set(dict.clone(), key.clone(), value.clone());
-
-        let after_set = context.get(dict, &key);
-        assert_eq!(
-            after_set,
-            Some(value.clone()),
-            "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
-        );
+    fn reset() {
+        let mut context = Context::new(2);
+        context.set(0, Some(Value::Int32(1)));
+        context.set(1, None);
+        context.reset();
+        assert_eq!(context, Context::new(2));
     }
 
     #[test]
-    fn user_set_get_none() {
-        let mut context = Context::new();
-        let value = None;
+    fn rollback() {
+        let mut context = Context::new(2);
+        context.set(0, Some(Value::Int32(1)));
 
-        let key = Rc::from("aboba");
-        let dict = DictionaryType::UserDefined(Rc::from("type"));
+        context.begin();
+        context.set(0, Some(Value::Int32(2)));
+        context.set(0, None);
+        context.set(1, Some(Value::Int32(3)));
+        context.rollback();
 
-        let before_set = context.get(dict.clone(), &key);
-        assert!(
-            before_set.is_none(),
-            "Value is set before insertion: {before_set:#?}"
-        );
+        assert_eq!(context.get(0), Some(&Some(Value::Int32(1))));
+        assert_eq!(context.get(1), None);
 
-        context.set(dict.clone(), key.clone(), value.clone());
+        context.begin();
+        context.set(0, Some(Value::Int32(2)));
+        context.commit();
+        context.rollback();
 
-        let after_set = context.get(dict, &key);
-        assert_eq!(
-            after_set,
-            Some(value.clone()),
-            "Values does not match after set: lhs ({after_set:?}) != rhs ({value:?})"
-        );
+        assert_eq!(context.get(0), Some(&Some(Value::Int32(2))));
     }
 }
@@ -1,37 +1,40 @@
-use std::cell::Cell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::base::instruction::Instruction;
-use crate::base::types::{Dictionary, Operator, Presence, Template, TypeRef};
+use crate::base::time::TimeUnit;
+use crate::base::types::{
+    Dictionary, Operator, Presence, Template, TypeDefinitions, TypeRef, qualified_name,
+};
 use crate::base::value::ValueType;
+use crate::common::builder::TemplateBuilder;
+use crate::common::context::ContextSlot;
+use crate::common::program::{Programs, compile_programs};
+use crate::common::slots::resolve_slots;
+use crate::common::validator::{Diagnostic, Severity, validate_xml};
+use crate::utils::xml::XmlElement;
 use crate::{Error, Result};
 
-/// Stores template definitions and global processing context.
+/// Stores template definitions.
+/// Definitions are immutable once created, so they can be shared between threads as `Arc<Definitions>`.
 pub struct Definitions {
-    pub(crate) templates: Vec<Rc<Template>>,
-    pub(crate) templates_by_id: HashMap<u32, Rc<Template>>,
-    pub(crate) templates_by_name: HashMap<String, Rc<Template>>,
-    pub(crate) template_id_instruction: Rc<Instruction>,
+    pub(crate) templates: Vec<Arc<Template>>,
+    pub(crate) templates_by_id: HashMap<u32, Arc<Template>>,
+    // Templates by namespace qualified name (`{namespace}name`) and by bare name if it is unique.
+    pub(crate) templates_by_name: HashMap<String, Arc<Template>>,
+    pub(crate) template_id_instruction: Arc<Instruction>,
+    // Number of dictionary entries (slots) in the processing context.
+    pub(crate) slots: usize,
+    // Compiled templates; they refer to the instructions of `templates`.
+    pub(crate) programs: Programs,
 }
 
 impl Definitions {
-    pub(crate) fn new_from_templates(ts: Vec<Template>) -> Result<Self> {
-        let mut templates = Vec::with_capacity(ts.len());
-        let mut templates_by_id = HashMap::with_capacity(ts.len());
-        let mut templates_by_name = HashMap::with_capacity(ts.len());
-        for t in ts {
-            let t = Rc::new(t);
-            if t.id != 0 {
-                templates_by_id.insert(t.id, t.clone());
-            }
-            if !t.name.is_empty() {
-                templates_by_name.insert(t.name.clone(), t.clone());
-            }
-            templates.push(t);
-        }
+    pub(crate) fn new_from_templates(mut ts: Vec<Template>) -> Result<Self> {
+        let names = template_names(&mut ts)?;
+        finalize(&mut ts, &names)?;
 
-        let template_id_instruction = Rc::new(Instruction {
+        let mut template_id_instruction = Instruction {
             id: 0,
             name: "__template_id__".to_string(),
             value_type: ValueType::UInt32,
@@ -39,23 +42,71 @@ impl Definitions {
             operator: Operator::Copy,
             initial_value: None,
             instructions: Vec::new(),
+            elements: Vec::new(),
+            unit: TimeUnit::default(),
+            epoch: 0,
+            template_ns: None,
             dictionary: Dictionary::Global,
-            key: Rc::from("__template_id__"),
+            key: Arc::from("__template_id__"),
             type_ref: TypeRef::Any,
-            has_pmap: Cell::new(false),
-        });
+            has_pmap: false,
+            pmap_bit: true,
+            slot: ContextSlot::None,
+        };
+        let slots = resolve_slots(&mut ts, &names, &mut template_id_instruction);
 
-        let definitions = Self {
+        let templates: Vec<_> = ts.into_iter().map(Arc::new).collect();
+        let programs = compile_programs(&templates, &names);
+        let templates_by_id = templates
+            .iter()
+            .filter(|t| t.id != 0)
+            .map(|t| (t.id, t.clone()))
+            .collect();
+        let templates_by_name = names
+            .into_iter()
+            .map(|(name, idx)| (name, templates[idx].clone()))
+            .collect();
+
+        Ok(Self {
             templates,
             templates_by_id,
             templates_by_name,
-            template_id_instruction,
-        };
-        definitions.finalize()?;
-        Ok(definitions)
+            template_id_instruction: Arc::new(template_id_instruction),
+            slots,
+            programs,
+        })
     }
 
+    /// Creates definitions from given XML definition.
+    /// The definitions can be shared between many decoders and encoders, see [`Decoder::with_definitions`][crate::Decoder::with_definitions]
+    /// and [`Encoder::with_definitions`][crate::Encoder::with_definitions].
+    /// # Errors
+    /// Returns error if definition is ill-formed.
     pub fn new_from_xml(text: &str) -> Result<Self> {
+        Self::load_xml(text).map_err(without_code)
+    }
+
+    /// Checks template definitions given as XML and returns all problems found with their locations,
+    /// see [`Diagnostic`]. Errors are the problems [`new_from_xml`](Self::new_from_xml) fails on,
+    /// warnings are the problems that make decoding or encoding fail at runtime.
+    #[must_use]
+    pub fn validate_xml(text: &str) -> Vec<Diagnostic> {
+        validate_xml(text)
+    }
+
+    /// Creates definitions from given XML definition after validating it.
+    /// Returns the definitions and the warnings found.
+    /// # Errors
+    /// Returns [`Error::Validation`] with all diagnostics if the definition has errors.
+    pub fn new_from_xml_validated(text: &str) -> Result<(Self, Vec<Diagnostic>)> {
+        let diagnostics = validate_xml(text);
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            return Err(Error::Validation(diagnostics));
+        }
+        Ok((Self::new_from_xml(text)?, diagnostics))
+    }
+
+    pub(crate) fn load_xml(text: &str) -> Result<Self> {
         let doc = roxmltree::Document::parse(text)?;
         let root = doc
             .root()
@@ -64,104 +115,223 @@ impl Definitions {
         if root.tag_name().name() != "templates" {
             return Err(Error::Static("<templates/> node not found".to_string()));
         }
+        let defs = TypeDefinitions::from_node(root)?;
         let mut templates = Vec::new();
         for child in root.children() {
-            if child.is_element() {
-                templates.push(Template::from_node(child)?);
+            if child.is_element() && child.tag_name().name() != "define" {
+                templates.push(Template::from_node(child, &defs)?);
             }
         }
         Self::new_from_templates(templates)
     }
 
-    // After generating the templates we have to go through all the instructions and set flags
-    // for structures that must have a presence map. That can only be done when whole
-    // templates structure is generated.
-    fn finalize(&self) -> Result<()> {
-        for tpl in &self.templates {
-            let need_pmap = self.require_presence_map_bit(&tpl.instructions)?;
-            tpl.require_pmap.set(Some(need_pmap));
+    /// Creates definitions from templates built in code, see [`TemplateBuilder`].
+    /// # Errors
+    /// Returns error if any template is ill-formed.
+    pub fn new_from_builders(builders: impl IntoIterator<Item = TemplateBuilder>) -> Result<Self> {
+        let templates = builders
+            .into_iter()
+            .map(TemplateBuilder::build)
+            .collect::<Result<Vec<_>>>()
+            .map_err(without_code)?;
+        Self::new_from_templates(templates).map_err(without_code)
+    }
+
+    /// Returns the templates in the order of definition.
+    pub fn templates(&self) -> impl Iterator<Item = &Template> {
+        self.templates.iter().map(AsRef::as_ref)
+    }
+
+    /// Returns the template with given id.
+    #[must_use]
+    pub fn template_by_id(&self, id: u32) -> Option<&Template> {
+        self.templates_by_id.get(&id).map(AsRef::as_ref)
+    }
+
+    /// Returns the template with given name. The name can be qualified with the template namespace as `{namespace}name`.
+    #[must_use]
+    pub fn template_by_name(&self, name: &str) -> Option<&Template> {
+        self.templates_by_name.get(name).map(AsRef::as_ref)
+    }
+
+    /// Writes the definitions as a template XML document.
+    /// The document is canonical: type definitions are inlined, operators and initial values are written
+    /// in one form, attributes with default values are omitted. Parsing the document gives the same definitions.
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut root = XmlElement::new("templates");
+        root.attribute("xmlns", "http://www.fixprotocol.org/ns/fast/td/1.1");
+        for t in &self.templates {
+            root.child(t.to_xml());
         }
-        Ok(())
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        root.write(&mut out, 0);
+        out
     }
+}
 
-    // Go through sequence of instructions and check if any of them require presence map bit.
-    // No early exit! Must iterate over all items because has_presence_map_bit() also initializes has_pmap bit.
-    fn require_presence_map_bit(&self, instructions: &[Instruction]) -> Result<bool> {
-        let mut has_pmap_bit = false;
-        for i in instructions {
-            if self.has_presence_map_bit(i)? {
-                has_pmap_bit = true;
-            }
+// Static errors are reported as `Error::Static` when definitions are created,
+// the validator reports their error codes, see `validate_xml()`.
+fn without_code(err: Error) -> Error {
+    match err {
+        Error::Spec(code, msg) if code.is_static() => Error::Static(msg),
+        err => err,
+    }
+}
+
+// Indexes templates by namespace qualified names and by bare names that are unique.
+// Templates with the same bare name in different namespaces are renamed to their qualified names,
+// so they can be told apart by message factories and visitors.
+// It is a static error if two templates have the same qualified name or the same identifier.
+fn template_names(templates: &mut [Template]) -> Result<HashMap<String, usize>> {
+    let mut names = HashMap::with_capacity(templates.len());
+    let mut ids = HashMap::with_capacity(templates.len());
+    let mut bare_names: HashMap<&str, usize> = HashMap::with_capacity(templates.len());
+    for (idx, t) in templates.iter().enumerate() {
+        let name = qualified_name(t.namespace.as_deref(), &t.name);
+        if names.insert(name.clone(), idx).is_some() {
+            return Err(Error::Static(format!("duplicate template name: {name}")));
+        }
+        if t.id != 0 && ids.insert(t.id, idx).is_some() {
+            return Err(Error::Static(format!("duplicate template id: {}", t.id)));
         }
-        Ok(has_pmap_bit)
+        *bare_names.entry(t.name.as_str()).or_default() += 1;
     }
+    let ambiguous: Vec<bool> = templates
+        .iter()
+        .map(|t| bare_names[t.name.as_str()] > 1)
+        .collect();
+    for (idx, t) in templates.iter_mut().enumerate() {
+        if !ambiguous[idx] {
+            names.insert(t.name.clone(), idx);
+        } else if t.namespace.is_some() {
+            t.name = qualified_name(t.namespace.as_deref(), &t.name);
+        }
+    }
+    Ok(names)
+}
 
-    fn set_has_pmap(&self, instr: &Instruction) -> Result<()> {
-        let instructions: &[Instruction] = match instr.value_type {
-            ValueType::Group | ValueType::TemplateReference | ValueType::Decimal => {
-                &instr.instructions
-            }
-            ValueType::Sequence => &instr.instructions[1..],
-            _ => {
-                return Ok(());
-            }
-        };
-        let need_pmap = self.require_presence_map_bit(instructions)?;
-        instr.has_pmap.set(need_pmap);
-        Ok(())
+/// Looks up the template of a static template reference by the name qualified with the template namespace
+/// or, if not found, by the bare name.
+pub(crate) fn find_template<'a, T>(
+    by_name: &'a HashMap<String, T>,
+    instruction: &Instruction,
+) -> Option<&'a T> {
+    by_name
+        .get(&qualified_name(
+            instruction.template_ns.as_deref(),
+            &instruction.name,
+        ))
+        .or_else(|| by_name.get(&instruction.name))
+}
+
+// Presence map requirements of templates. None if not calculated yet.
+struct PmapRequirements<'a> {
+    names: &'a HashMap<String, usize>,
+    require_pmap: Vec<Option<bool>>,
+}
+
+// After generating the templates we have to go through all the instructions and set flags
+// for structures that must have a presence map. That can only be done when whole
+// templates structure is generated.
+fn finalize(templates: &mut [Template], names: &HashMap<String, usize>) -> Result<()> {
+    let mut require_pmap = PmapRequirements {
+        names,
+        require_pmap: vec![None; templates.len()],
+    };
+    for (idx, tpl) in templates.iter_mut().enumerate() {
+        let need_pmap = require_presence_map_bit(&require_pmap, &mut tpl.instructions)?;
+        tpl.require_pmap = Some(need_pmap);
+        require_pmap.require_pmap[idx] = Some(need_pmap);
     }
+    Ok(())
+}
 
-    fn has_presence_map_bit(&self, instr: &Instruction) -> Result<bool> {
-        // first, initialize internals of the instruction
-        self.set_has_pmap(instr)?;
+// Go through sequence of instructions and check if any of them require presence map bit.
+// No early exit! Must iterate over all items because has_presence_map_bit() also initializes has_pmap bit.
+fn require_presence_map_bit(
+    require_pmap: &PmapRequirements,
+    instructions: &mut [Instruction],
+) -> Result<bool> {
+    let mut has_pmap_bit = false;
+    for i in instructions {
+        if has_presence_map_bit(require_pmap, i)? {
+            has_pmap_bit = true;
+        }
+    }
+    Ok(has_pmap_bit)
+}
 
-        // then, check if it has a presence map bit
-        match instr.value_type {
-            ValueType::Group => {
-                // If a ::Group field is optional, it will occupy a single bit in the presence map.
-                return Ok(instr.is_optional());
-            }
-            ValueType::Sequence => {
-                // For ::Sequence its length field show if the sequence has a bit in the presence map.
-                return self.has_presence_map_bit(instr.instructions.first().ok_or_else(|| {
-                    Error::Static(format!("sequence '{}' has no length field", instr.name))
-                })?);
-            }
-            ValueType::TemplateReference => {
-                if instr.name.is_empty() {
-                    // Dynamic template ref doesn't need a presence map bit.
-                    return Ok(false);
-                }
-                // Static template ref checks corresponding template it is needs any presence bit.
-                let template = self
-                    .templates_by_name
-                    .get(&instr.name)
-                    .ok_or_else(|| Error::Static(format!("template '{}' not found", instr.name)))?;
-                return template.require_pmap.get().ok_or_else(|| {
-                    Error::Static(format!(
-                        "template '{}' not initialized yet; consider reordering templates",
-                        instr.name
-                    ))
-                });
-            }
-            ValueType::Decimal => {
-                if instr.has_pmap.get() {
-                    // We already know that this field require a presence bit due to its subcomponents.
-                    return Ok(true);
-                }
-                // Otherwise, fall-though and check the field's operator (like for normal field).
+fn set_has_pmap(require_pmap: &PmapRequirements, instr: &mut Instruction) -> Result<()> {
+    let instructions: &mut [Instruction] = match instr.value_type {
+        ValueType::Group | ValueType::TemplateReference | ValueType::Decimal => {
+            &mut instr.instructions
+        }
+        ValueType::Sequence => &mut instr.instructions[1..],
+        _ => {
+            return Ok(());
+        }
+    };
+    instr.has_pmap = require_presence_map_bit(require_pmap, instructions)?;
+    Ok(())
+}
+
+fn has_presence_map_bit(require_pmap: &PmapRequirements, instr: &mut Instruction) -> Result<bool> {
+    let pmap_bit = presence_map_bit(require_pmap, instr)?;
+    instr.pmap_bit = pmap_bit;
+    Ok(pmap_bit)
+}
+
+fn presence_map_bit(require_pmap: &PmapRequirements, instr: &mut Instruction) -> Result<bool> {
+    // first, initialize internals of the instruction
+    set_has_pmap(require_pmap, instr)?;
+
+    // then, check if it has a presence map bit
+    #[allow(clippy::collapsible_match)]
+    match instr.value_type {
+        ValueType::Group => {
+            // If a ::Group field is optional, it will occupy a single bit in the presence map.
+            return Ok(instr.is_optional());
+        }
+        ValueType::Sequence => {
+            // For ::Sequence its length field show if the sequence has a bit in the presence map.
+            let length = instr.instructions.first_mut().ok_or_else(|| {
+                Error::Static(format!("sequence '{}' has no length field", instr.name))
+            })?;
+            return has_presence_map_bit(require_pmap, length);
+        }
+        ValueType::TemplateReference => {
+            if instr.name.is_empty() {
+                // Dynamic template ref doesn't need a presence map bit.
+                return Ok(false);
             }
-            _ => {}
+            // Static template ref checks corresponding template it is needs any presence bit.
+            let idx = find_template(require_pmap.names, instr)
+                .ok_or_else(|| Error::Static(format!("template '{}' not found", instr.name)))?;
+            return require_pmap.require_pmap[*idx].ok_or_else(|| {
+                Error::Static(format!(
+                    "template '{}' not initialized yet; consider reordering templates",
+                    instr.name
+                ))
+            });
         }
-        match instr.operator {
-            // If a field (is mandatory and) has no field operator, it will not occupy any bit in the presence map
-            // and its value must always appear in the stream.
-            // Delta is always present in the stream, so doesn't need a presence bit.
-            Operator::None | Operator::Delta => Ok(false),
-            // Always require a presence bit.
-            Operator::Default | Operator::Copy | Operator::Increment | Operator::Tail => Ok(true),
-            // An optional field with the constant operator will occupy a single bit.
-            Operator::Constant => Ok(instr.is_optional()),
+        ValueType::Decimal => {
+            if instr.has_pmap {
+                // We already know that this field require a presence bit due to its subcomponents.
+                return Ok(true);
+            }
+            // Otherwise, fall-though and check the field's operator (like for normal field).
         }
+        _ => {}
+    }
+    match instr.operator {
+        // If a field (is mandatory and) has no field operator, it will not occupy any bit in the presence map
+        // and its value must always appear in the stream.
+        // Delta is always present in the stream, so doesn't need a presence bit.
+        Operator::None | Operator::Delta => Ok(false),
+        // Always require a presence bit.
+        Operator::Default | Operator::Copy | Operator::Increment | Operator::Tail => Ok(true),
+        // An optional field with the constant operator will occupy a single bit.
+        Operator::Constant => Ok(instr.is_optional()),
     }
 }
@@ -0,0 +1,272 @@
+//! # Transport framing
+//!
+//! FAST messages are usually wrapped into some kind of framing by the transport layer, e.g.:
+//! * the block size preamble described in FAST 1.1 specification,
+//! * the packet sequence number header used by exchanges for UDP multicast feeds,
+//! * the length prefix used for TCP records.
+//!
+//! A [`Framing`] reads and writes the frame header, the payload is one FAST message.
+//!
+use crate::decoder::reader::Reader;
+use crate::encoder::writer::Writer;
+use crate::{Error, ErrorCode, Result};
+
+/// A trait that describes the frame header that precedes each FAST message.
+pub trait Framing {
+    /// The frame header fields exposed along with the decoded message.
+    type Header;
+
+    /// Reads a frame header.
+    /// Returns the header and the length of the frame payload if the framing defines it.
+    /// If the payload length is `None` the payload is exactly one message that follows the header.
+    /// # Errors
+    /// Returns [`Error::Eof`][crate::Error::Eof] if the end of the stream is reached at the first byte of the header.
+    /// Otherwise, returns any other error, e.g.: [`Error::UnexpectedEof`][crate::Error::UnexpectedEof].
+    fn read_header(&mut self, rdr: &mut impl Reader) -> Result<(Self::Header, Option<u64>)>;
+
+    /// Writes a frame header for a payload of `len` bytes.
+    /// # Errors
+    /// Returns error if the header can't be encoded or writer can't handle more bytes.
+    fn write_header(
+        &mut self,
+        wrt: &mut impl Writer,
+        header: &Self::Header,
+        len: u64,
+    ) -> Result<()>;
+}
+
+/// The block size preamble from FAST 1.1 specification.
+/// The size of the message in bytes is encoded as stop bit encoded unsigned integer that precedes the message.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockSizeFraming;
+
+impl Framing for BlockSizeFraming {
+    type Header = ();
+
+    fn read_header(&mut self, rdr: &mut impl Reader) -> Result<(Self::Header, Option<u64>)> {
+        let mut value = u64::from(read_first_u8(rdr)?);
+        let mut byte = value as u8;
+        value &= 0x7f;
+        while byte & 0x80 == 0 {
+            if value >> 57 != 0 {
+                return Err(Error::IntegerOverflow);
+            }
+            byte = rdr.read_u8()?;
+            value <<= 7;
+            value |= u64::from(byte & 0x7f);
+        }
+        // It is a dynamic error [ERR D12] if a block size preamble is zero.
+        if value == 0 {
+            return Err(Error::Spec(
+                ErrorCode::D12,
+                "block size preamble is zero".to_string(),
+            ));
+        }
+        Ok(((), Some(value)))
+    }
+
+    fn write_header(
+        &mut self,
+        wrt: &mut impl Writer,
+        _header: &Self::Header,
+        len: u64,
+    ) -> Result<()> {
+        wrt.write_uint(len)
+    }
+}
+
+/// The 4 bytes little-endian packet sequence number that precedes the message.
+/// The header is the sequence number.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SequenceNumberFraming;
+
+impl Framing for SequenceNumberFraming {
+    type Header = u32;
+
+    fn read_header(&mut self, rdr: &mut impl Reader) -> Result<(Self::Header, Option<u64>)> {
+        let mut buf = [read_first_u8(rdr)?, 0, 0, 0];
+        for b in &mut buf[1..] {
+            *b = rdr.read_u8()?;
+        }
+        Ok((u32::from_le_bytes(buf), None))
+    }
+
+    fn write_header(
+        &mut self,
+        wrt: &mut impl Writer,
+        header: &Self::Header,
+        _len: u64,
+    ) -> Result<()> {
+        wrt.write_buf(&header.to_le_bytes())
+    }
+}
+
+/// Fixed size length of the message that precedes the message.
+#[derive(Debug, Clone, Copy)]
+pub enum LengthPrefixFraming {
+    U16BigEndian,
+    U16LittleEndian,
+    U32BigEndian,
+    U32LittleEndian,
+}
+
+impl LengthPrefixFraming {
+    fn size(self) -> usize {
+        match self {
+            LengthPrefixFraming::U16BigEndian | LengthPrefixFraming::U16LittleEndian => 2,
+            LengthPrefixFraming::U32BigEndian | LengthPrefixFraming::U32LittleEndian => 4,
+        }
+    }
+
+    fn is_big_endian(self) -> bool {
+        matches!(
+            self,
+            LengthPrefixFraming::U16BigEndian | LengthPrefixFraming::U32BigEndian
+        )
+    }
+}
+
+impl Framing for LengthPrefixFraming {
+    type Header = ();
+
+    fn read_header(&mut self, rdr: &mut impl Reader) -> Result<(Self::Header, Option<u64>)> {
+        let mut buf = [0u8; 4];
+        let size = self.size();
+        buf[0] = read_first_u8(rdr)?;
+        for b in &mut buf[1..size] {
+            *b = rdr.read_u8()?;
+        }
+        let buf = &mut buf[..size];
+        if !self.is_big_endian() {
+            buf.reverse();
+        }
+        let len = buf.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        Ok(((), Some(len)))
+    }
+
+    fn write_header(
+        &mut self,
+        wrt: &mut impl Writer,
+        _header: &Self::Header,
+        len: u64,
+    ) -> Result<()> {
+        let size = self.size();
+        if len >> (size * 8) != 0 {
+            return Err(Error::Runtime(format!(
+                "Message length {len} doesn't fit into {size} bytes prefix"
+            )));
+        }
+        let be = len.to_be_bytes();
+        let mut buf = [0u8; 4];
+        buf[..size].copy_from_slice(&be[8 - size..]);
+        let buf = &mut buf[..size];
+        if !self.is_big_endian() {
+            buf.reverse();
+        }
+        wrt.write_buf(buf)
+    }
+}
+
+// Reads the first byte of a frame header.
+// Returns `Error::Eof` if the end of the stream is reached.
+fn read_first_u8(rdr: &mut impl Reader) -> Result<u8> {
+    match rdr.read_u8() {
+        Ok(b) => Ok(b),
+        Err(Error::UnexpectedEof) => Err(Error::Eof),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn do_test<F: Framing>(mut framing: F, header: &F::Header, len: u64, raw: &[u8]) -> F::Header {
+        let mut buf = bytes::BytesMut::new();
+        framing.write_header(&mut buf, header, len).unwrap();
+        assert_eq!(&buf[..], raw, "write mismatch");
+
+        let mut rdr = bytes::Bytes::copy_from_slice(raw);
+        let (h, l) = framing.read_header(&mut rdr).unwrap();
+        assert!(rdr.is_empty(), "header not consumed");
+        if let Some(l) = l {
+            assert_eq!(l, len, "length mismatch");
+        }
+        h
+    }
+
+    #[test]
+    fn block_size() {
+        do_test(BlockSizeFraming, &(), 5, &[0x85]);
+        do_test(BlockSizeFraming, &(), 300, &[0x02, 0xac]);
+    }
+
+    #[test]
+    fn sequence_number() {
+        let h = do_test(
+            SequenceNumberFraming,
+            &0x0102_0304,
+            5,
+            &[0x04, 0x03, 0x02, 0x01],
+        );
+        assert_eq!(h, 0x0102_0304);
+    }
+
+    #[test]
+    fn length_prefix() {
+        do_test(
+            LengthPrefixFraming::U16BigEndian,
+            &(),
+            0x0102,
+            &[0x01, 0x02],
+        );
+        do_test(
+            LengthPrefixFraming::U16LittleEndian,
+            &(),
+            0x0102,
+            &[0x02, 0x01],
+        );
+        do_test(
+            LengthPrefixFraming::U32BigEndian,
+            &(),
+            0x0102_0304,
+            &[0x01, 0x02, 0x03, 0x04],
+        );
+        do_test(
+            LengthPrefixFraming::U32LittleEndian,
+            &(),
+            0x0102_0304,
+            &[0x04, 0x03, 0x02, 0x01],
+        );
+        let mut buf = bytes::BytesMut::new();
+        assert!(
+            LengthPrefixFraming::U16BigEndian
+                .write_header(&mut buf, &(), 0x1_0000)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn eof() {
+        let mut rdr = bytes::Bytes::new();
+        assert!(matches!(
+            BlockSizeFraming.read_header(&mut rdr),
+            Err(Error::Eof)
+        ));
+        let mut rdr = bytes::Bytes::from_static(&[0x01, 0x02]);
+        assert!(matches!(
+            SequenceNumberFraming.read_header(&mut rdr),
+            Err(Error::UnexpectedEof)
+        ));
+        let mut rdr = bytes::Bytes::from_static(&[0x80]);
+        assert_eq!(
+            BlockSizeFraming.read_header(&mut rdr).unwrap_err().code(),
+            Some(ErrorCode::D12)
+        );
+        let mut rdr = bytes::Bytes::from_static(&[0x7f; 11]);
+        assert!(matches!(
+            BlockSizeFraming.read_header(&mut rdr),
+            Err(Error::IntegerOverflow)
+        ));
+    }
+}
@@ -1,2 +1,7 @@
+pub(crate) mod builder;
 pub(crate) mod context;
 pub(crate) mod definitions;
+pub(crate) mod framing;
+pub(crate) mod program;
+pub(crate) mod slots;
+pub(crate) mod validator;
@@ -0,0 +1,420 @@
+//! # Template programs
+//!
+//! Each template with identifier is compiled into a flat list of operations when definitions are created.
+//! Static template references are inlined, dictionary entries of fields are resolved for the processing context,
+//! and groups, sequences and template references are delimited by start and end operations.
+//! The decoder and the encoder run a message in a single loop over the operations, without switching dictionaries,
+//! application types and templates for every element.
+//!
+//! A dynamic template reference can refer to any template with identifier. Dictionary entries of the referenced
+//! template depend on the dictionary and the application type of the enclosing element, so templates are compiled
+//! for each such scope, and the reference runs the program of the template for its scope.
+//!
+//! Operations refer to the instructions of the templates by their paths instead of copying them.
+//!
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::Error;
+use crate::base::instruction::Instruction;
+use crate::base::types::{Dictionary, Template, TypeRef};
+use crate::base::value::ValueType;
+use crate::common::context::{ContextSlot, DictionaryType, FieldSlots};
+use crate::common::definitions::find_template;
+use crate::common::slots::Scope;
+
+pub(crate) type Program = Box<[Op]>;
+
+/// The scope of messages: the global dictionary and no application type.
+pub(crate) const MESSAGE_SCOPE: usize = 0;
+
+/// Programs of all templates with identifiers for every scope they can be processed in.
+#[derive(Debug, Default)]
+pub(crate) struct Programs {
+    // Programs by template identifier, indexed by scope. The first scope is `MESSAGE_SCOPE`,
+    // the others are the scopes of dynamic template references.
+    scopes: Vec<HashMap<u32, Program>>,
+}
+
+impl Programs {
+    /// Returns the program of the template for the scope.
+    pub(crate) fn get(&self, scope: usize, template_id: u32) -> Option<&[Op]> {
+        self.scopes
+            .get(scope)
+            .and_then(|programs| programs.get(&template_id))
+            .map(AsRef::as_ref)
+    }
+}
+
+/// Template the program is compiled from.
+pub(crate) struct TemplateRef(Arc<Template>);
+
+impl Deref for TemplateRef {
+    type Target = Template;
+
+    fn deref(&self) -> &Template {
+        &self.0
+    }
+}
+
+impl Debug for TemplateRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TemplateRef").field(&self.0.name).finish()
+    }
+}
+
+/// Instruction of a template the program is compiled from, located by the indexes of the instruction
+/// and its enclosing groups and sequences in the template.
+pub(crate) struct InstructionRef {
+    template: TemplateRef,
+    path: Box<[usize]>,
+}
+
+impl Deref for InstructionRef {
+    type Target = Instruction;
+
+    #[inline]
+    fn deref(&self) -> &Instruction {
+        let (last, parents) = self.path.split_last().expect("empty instruction path");
+        let instructions = parents
+            .iter()
+            .fold(&self.template.instructions, |instructions, idx| {
+                &instructions[*idx].instructions
+            });
+        &instructions[*last]
+    }
+}
+
+impl Debug for InstructionRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InstructionRef")
+            .field("template", &self.template)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Field instruction with its dictionary slots resolved for the scope of the program.
+#[derive(Debug)]
+pub(crate) struct Field {
+    pub(crate) instruction: InstructionRef,
+    pub(crate) slots: FieldSlots,
+}
+
+/// Operation of a template program.
+#[derive(Debug)]
+pub(crate) enum Op {
+    /// Field, including a decimal field with its exponent and mantissa.
+    Field(Field),
+    /// Start of a group; `end` is the index of the matching [`Op::GroupEnd`].
+    Group { group: InstructionRef, end: usize },
+    /// End of the group.
+    GroupEnd { has_pmap: bool },
+    /// Start of a sequence and its item; `end` is the index of the matching [`Op::SequenceEnd`].
+    Sequence {
+        sequence: InstructionRef,
+        length: Field,
+        end: usize,
+    },
+    /// End of the sequence item; the next item starts after `start`.
+    SequenceEnd { start: usize, has_pmap: bool },
+    /// Start of the instructions of a statically referenced template; `end` is the index of the matching
+    /// [`Op::TemplateRefEnd`].
+    TemplateRef {
+        instruction: InstructionRef,
+        template: TemplateRef,
+        end: usize,
+    },
+    /// End of the instructions of a statically referenced template.
+    TemplateRefEnd,
+    /// Dynamic template reference. The referenced template is processed by its program for `scope`.
+    DynamicTemplateRef { scope: usize },
+}
+
+/// Compiles programs of all templates with identifiers.
+/// Must be called when presence map bits and dictionary slots of the instructions are resolved.
+/// A template whose dictionary entries can't be resolved for a scope has no program for it.
+pub(crate) fn compile_programs(
+    templates: &[Arc<Template>],
+    by_name: &HashMap<String, usize>,
+) -> Programs {
+    let mut scopes = vec![(Dictionary::Global, TypeRef::Any)];
+    let mut programs = Programs::default();
+    // Compiling a scope may find new scopes of dynamic template references.
+    while let Some((dictionary, type_ref)) = scopes.get(programs.scopes.len()).cloned() {
+        let mut scope_programs = HashMap::new();
+        for template in templates.iter().filter(|t| t.id != 0) {
+            let scope = Scope {
+                dictionary: dictionary.clone(),
+                template_id: template.id,
+                type_ref: type_ref.clone(),
+            }
+            .switch(&template.dictionary, &template.type_ref);
+            let mut compiler = Compiler {
+                templates,
+                by_name,
+                scopes: &mut scopes,
+                ops: Vec::new(),
+                path: Vec::new(),
+                resolved: true,
+            };
+            compiler.instructions(template, &template.instructions, 0, &scope);
+            if compiler.resolved {
+                scope_programs.insert(template.id, compiler.ops.into_boxed_slice());
+            }
+        }
+        programs.scopes.push(scope_programs);
+    }
+    programs
+}
+
+struct Compiler<'a> {
+    templates: &'a [Arc<Template>],
+    by_name: &'a HashMap<String, usize>,
+    // The dictionaries and the application types of the scopes found so far.
+    scopes: &'a mut Vec<(Dictionary, TypeRef)>,
+    ops: Vec<Op>,
+    // The path of the current instruction in its template.
+    path: Vec<usize>,
+    // False if a dictionary entry of a field or a referenced template can't be resolved.
+    resolved: bool,
+}
+
+impl Compiler<'_> {
+    // Compiles the instructions of the template starting from `first`.
+    fn instructions(
+        &mut self,
+        template: &Arc<Template>,
+        instructions: &[Instruction],
+        first: usize,
+        scope: &Scope,
+    ) {
+        for (idx, instruction) in instructions.iter().enumerate().skip(first) {
+            self.path.push(idx);
+            self.instruction(template, instruction, scope);
+            self.path.pop();
+        }
+    }
+
+    fn instruction(&mut self, template: &Arc<Template>, instruction: &Instruction, scope: &Scope) {
+        match instruction.value_type {
+            ValueType::Group => {
+                let start = self.ops.len();
+                self.ops.push(Op::Group {
+                    group: self.instruction_ref(template),
+                    end: 0,
+                });
+                let scope = scope.switch(&instruction.dictionary, &instruction.type_ref);
+                self.instructions(template, &instruction.instructions, 0, &scope);
+                let end = self.ops.len();
+                self.ops.push(Op::GroupEnd {
+                    has_pmap: instruction.has_pmap,
+                });
+                if let Op::Group { end: e, .. } = &mut self.ops[start] {
+                    *e = end;
+                }
+            }
+            ValueType::Sequence => {
+                let scope = scope.switch(&instruction.dictionary, &instruction.type_ref);
+                self.path.push(0);
+                let length = self.field(template, &instruction.instructions[0], &scope);
+                self.path.pop();
+                let start = self.ops.len();
+                self.ops.push(Op::Sequence {
+                    sequence: self.instruction_ref(template),
+                    length,
+                    end: 0,
+                });
+                self.instructions(template, &instruction.instructions, 1, &scope);
+                let end = self.ops.len();
+                self.ops.push(Op::SequenceEnd {
+                    start,
+                    has_pmap: instruction.has_pmap,
+                });
+                if let Op::Sequence { end: e, .. } = &mut self.ops[start] {
+                    *e = end;
+                }
+            }
+            ValueType::TemplateReference if instruction.name.is_empty() => {
+                let key = (scope.dictionary.clone(), scope.type_ref.clone());
+                let scope = if let Some(idx) = self.scopes.iter().position(|s| *s == key) {
+                    idx
+                } else {
+                    self.scopes.push(key);
+                    self.scopes.len() - 1
+                };
+                self.ops.push(Op::DynamicTemplateRef { scope });
+            }
+            ValueType::TemplateReference => {
+                let Some(idx) = find_template(self.by_name, instruction) else {
+                    self.resolved = false;
+                    return;
+                };
+                let referenced = &self.templates[*idx];
+                let start = self.ops.len();
+                self.ops.push(Op::TemplateRef {
+                    instruction: self.instruction_ref(template),
+                    template: TemplateRef(referenced.clone()),
+                    end: 0,
+                });
+                // Static reference keeps the processing context.
+                let scope = scope.switch(&referenced.dictionary, &referenced.type_ref);
+                // The instructions of the referenced template are located in that template.
+                let path = std::mem::take(&mut self.path);
+                self.instructions(referenced, &referenced.instructions, 0, &scope);
+                self.path = path;
+                let end = self.ops.len();
+                self.ops.push(Op::TemplateRefEnd);
+                if let Op::TemplateRef { end: e, .. } = &mut self.ops[start] {
+                    *e = end;
+                }
+            }
+            _ => {
+                let field = self.field(template, instruction, scope);
+                self.ops.push(Op::Field(field));
+            }
+        }
+    }
+
+    // Refers to the current instruction of the template.
+    fn instruction_ref(&self, template: &Arc<Template>) -> InstructionRef {
+        InstructionRef {
+            template: TemplateRef(template.clone()),
+            path: self.path.clone().into_boxed_slice(),
+        }
+    }
+
+    // Resolves the dictionary slots of the field for the scope.
+    // Decimal subcomponents use the dictionary of the decimal field.
+    fn field(
+        &mut self,
+        template: &Arc<Template>,
+        instruction: &Instruction,
+        scope: &Scope,
+    ) -> Field {
+        let dict = scope
+            .switch(&instruction.dictionary, &TypeRef::Any)
+            .dictionary_type();
+        let mut slots = FieldSlots {
+            field: self.slot(instruction, &dict),
+            ..FieldSlots::default()
+        };
+        for (slot, sub) in slots.components.iter_mut().zip(&instruction.instructions) {
+            *slot = self.slot(sub, &dict);
+        }
+        Field {
+            instruction: self.instruction_ref(template),
+            slots,
+        }
+    }
+
+    fn slot(&mut self, instruction: &Instruction, dict: &DictionaryType) -> Option<usize> {
+        match &instruction.slot {
+            ContextSlot::None => None,
+            ContextSlot::Fixed(slot) => Some(*slot),
+            ContextSlot::Scoped(slots) => {
+                let slot = slots.iter().find(|(d, _)| d == dict).map(|(_, s)| *s);
+                self.resolved &= slot.is_some();
+                slot
+            }
+        }
+    }
+}
+
+/// Group or sequence item being processed by a program.
+pub(crate) enum Frame {
+    Group {
+        start: usize,
+    },
+    Sequence {
+        start: usize,
+        item: Option<usize>,
+        length: usize,
+    },
+}
+
+/// Adds the locations of the groups and sequence items being processed to the error, innermost first.
+pub(crate) fn locate_error(
+    err: Error,
+    program: &[Op],
+    frames: &[Frame],
+    mut located: impl FnMut(Error, &str) -> Error,
+) -> Error {
+    frames.iter().rev().fold(err, |err, frame| match frame {
+        Frame::Group { start } => located(err, program[*start].name()),
+        Frame::Sequence { start, item, .. } => {
+            let err = match item {
+                Some(idx) => located(err, &format!("[{idx}]")),
+                None => err,
+            };
+            located(err, program[*start].name())
+        }
+    })
+}
+
+impl Op {
+    /// The name of the instruction of the operation; empty for end operations and dynamic template references.
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Op::Field(Field { instruction, .. })
+            | Op::Group {
+                group: instruction, ..
+            }
+            | Op::Sequence {
+                sequence: instruction,
+                ..
+            }
+            | Op::TemplateRef { instruction, .. } => &instruction.name,
+            Op::GroupEnd { .. }
+            | Op::SequenceEnd { .. }
+            | Op::TemplateRefEnd
+            | Op::DynamicTemplateRef { .. } => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::definitions::Definitions;
+
+    const DEFINITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="T" id="2" dictionary="type">
+        <uInt32 name="Y" id="1"><copy/></uInt32>
+    </template>
+    <template name="A" id="1">
+        <group name="G" dictionary="type" typeRef="Quote">
+            <templateRef/>
+        </group>
+    </template>
+</templates>
+"#;
+
+    fn field_slots(program: &[Op]) -> Vec<Option<usize>> {
+        program
+            .iter()
+            .filter_map(|op| match op {
+                Op::Field(field) => Some(field.slots.field),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dynamic_reference_scope() {
+        let defs = Definitions::new_from_xml(DEFINITION).unwrap();
+        let a = defs.programs.get(MESSAGE_SCOPE, 1).unwrap();
+        let Some(Op::DynamicTemplateRef { scope }) = a.get(1) else {
+            panic!("expected dynamic template reference, got {a:?}");
+        };
+        assert_ne!(*scope, MESSAGE_SCOPE);
+        // `Y` uses the type dictionary without application type in a message and the one of `Quote` in the group.
+        let message = field_slots(defs.programs.get(MESSAGE_SCOPE, 2).unwrap());
+        let group = field_slots(defs.programs.get(*scope, 2).unwrap());
+        assert!(message[0].is_some() && group[0].is_some());
+        assert_ne!(message, group);
+    }
+}
@@ -0,0 +1,328 @@
+//! # Dictionary slots resolution
+//!
+//! Every field with copy, increment, delta or tail operator stores its previous value in a dictionary entry
+//! identified by a dictionary and a key. The dictionary depends on the `dictionary` attributes of the field and
+//! of its enclosing elements, the current template (for "template" dictionary) and the current application type
+//! (for "type" dictionary). Some instructions can be used in several processing contexts, e.g. instructions of a
+//! template that is referenced from other templates.
+//!
+//! Here we walk all templates the way the decoder does, collect every dictionary entry that can ever be accessed
+//! and assign each one an index (slot) in the flat [`Context`][crate::common::context::Context] storage.
+//!
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::base::instruction::Instruction;
+use crate::base::types::{Dictionary, Operator, Template, TypeRef};
+use crate::base::value::ValueType;
+use crate::common::context::{ContextSlot, DictionaryType};
+use crate::common::definitions::find_template;
+
+// Processing context state that affects dictionary entry resolution.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Scope {
+    pub(crate) dictionary: Dictionary,
+    pub(crate) template_id: u32,
+    pub(crate) type_ref: TypeRef,
+}
+
+impl Scope {
+    pub(crate) fn switch(&self, dictionary: &Dictionary, type_ref: &TypeRef) -> Scope {
+        Scope {
+            dictionary: if *dictionary == Dictionary::Inherit {
+                self.dictionary.clone()
+            } else {
+                dictionary.clone()
+            },
+            template_id: self.template_id,
+            type_ref: if *type_ref == TypeRef::Any {
+                self.type_ref.clone()
+            } else {
+                type_ref.clone()
+            },
+        }
+    }
+
+    pub(crate) fn dictionary_type(&self) -> DictionaryType {
+        match &self.dictionary {
+            Dictionary::Inherit => unreachable!(),
+            Dictionary::Global => DictionaryType::Global,
+            Dictionary::Template => DictionaryType::Template(self.template_id),
+            Dictionary::Type => match &self.type_ref {
+                TypeRef::Any => DictionaryType::AnyType,
+                TypeRef::ApplicationType(name) => DictionaryType::Type(name.clone()),
+            },
+            Dictionary::UserDefined(name) => DictionaryType::UserDefined(name.clone()),
+        }
+    }
+}
+
+// Instructions are identified by the index of their template and their position within the template.
+type InstructionPath = (usize, Vec<usize>);
+
+#[derive(Default)]
+struct Resolver {
+    // All dictionary entries and their slots.
+    slots: HashMap<(DictionaryType, Arc<str>), usize>,
+    // Dictionary entries used by instructions.
+    resolved: HashMap<InstructionPath, Vec<(DictionaryType, usize)>>,
+    // Templates to walk and contexts they are used in.
+    queue: Vec<(usize, Scope)>,
+    visited: HashSet<(usize, Scope)>,
+}
+
+/// Resolves dictionary slots for all instructions of given templates and the template identifier instruction.
+/// Returns the number of slots.
+pub(crate) fn resolve_slots(
+    templates: &mut [Template],
+    by_name: &HashMap<String, usize>,
+    template_id: &mut Instruction,
+) -> usize {
+    let mut resolver = Resolver::default();
+
+    // The template identifier always uses the global dictionary.
+    let slot = resolver.slot(DictionaryType::Global, &template_id.key);
+    template_id.slot = ContextSlot::Fixed(slot);
+
+    resolver.walk_templates(templates, by_name);
+    for (idx, t) in templates.iter_mut().enumerate() {
+        let mut path = (idx, Vec::new());
+        resolver.assign(&mut t.instructions, &mut path);
+    }
+    resolver.slots.len()
+}
+
+impl Resolver {
+    fn slot(&mut self, dict: DictionaryType, key: &Arc<str>) -> usize {
+        let next = self.slots.len();
+        *self.slots.entry((dict, key.clone())).or_insert(next)
+    }
+
+    fn walk_templates(&mut self, templates: &[Template], by_name: &HashMap<String, usize>) {
+        let by_id: Vec<(usize, u32)> = templates
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.id != 0)
+            .map(|(i, t)| (i, t.id))
+            .collect();
+
+        // Each template with identifier can be decoded as a message.
+        for (idx, id) in &by_id {
+            self.queue.push((
+                *idx,
+                Scope {
+                    dictionary: Dictionary::Global,
+                    template_id: *id,
+                    type_ref: TypeRef::Any,
+                },
+            ));
+        }
+
+        while let Some((idx, scope)) = self.queue.pop() {
+            if !self.visited.insert((idx, scope.clone())) {
+                continue;
+            }
+            let template = &templates[idx];
+            let scope = scope.switch(&template.dictionary, &template.type_ref);
+            let mut path = (idx, Vec::new());
+            self.walk(&template.instructions, &mut path, &scope, by_name, &by_id);
+        }
+    }
+
+    fn walk(
+        &mut self,
+        instructions: &[Instruction],
+        path: &mut InstructionPath,
+        scope: &Scope,
+        by_name: &HashMap<String, usize>,
+        by_id: &[(usize, u32)],
+    ) {
+        for (i, instruction) in instructions.iter().enumerate() {
+            path.1.push(i);
+            match instruction.value_type {
+                // The length field of a sequence is its first instruction.
+                ValueType::Group | ValueType::Sequence => {
+                    let scope = scope.switch(&instruction.dictionary, &instruction.type_ref);
+                    self.walk(&instruction.instructions, path, &scope, by_name, by_id);
+                }
+                ValueType::TemplateReference => {
+                    if instruction.name.is_empty() {
+                        // Dynamic reference changes the current template only.
+                        for (idx, id) in by_id {
+                            self.queue.push((
+                                *idx,
+                                Scope {
+                                    template_id: *id,
+                                    ..scope.clone()
+                                },
+                            ));
+                        }
+                    } else if let Some(idx) = find_template(by_name, instruction) {
+                        // Static reference keeps the processing context.
+                        self.queue.push((*idx, scope.clone()));
+                    }
+                }
+                _ => self.field(instruction, path, scope),
+            }
+            path.1.pop();
+        }
+    }
+
+    fn field(&mut self, instruction: &Instruction, path: &mut InstructionPath, scope: &Scope) {
+        let scope = scope.switch(&instruction.dictionary, &TypeRef::Any);
+        let dict = scope.dictionary_type();
+        self.use_entry(instruction, path, &dict);
+        // Decimal subcomponents use the dictionary of the decimal field.
+        for (i, sub) in instruction.instructions.iter().enumerate() {
+            path.1.push(i);
+            self.use_entry(sub, path, &dict);
+            path.1.pop();
+        }
+    }
+
+    fn use_entry(
+        &mut self,
+        instruction: &Instruction,
+        path: &InstructionPath,
+        dict: &DictionaryType,
+    ) {
+        if !matches!(
+            instruction.operator,
+            Operator::Copy | Operator::Increment | Operator::Delta | Operator::Tail
+        ) {
+            return;
+        }
+        let slot = self.slot(dict.clone(), &instruction.key);
+        let entries = self.resolved.entry(path.clone()).or_default();
+        if !entries.iter().any(|(_, s)| *s == slot) {
+            entries.push((dict.clone(), slot));
+        }
+    }
+
+    fn assign(&self, instructions: &mut [Instruction], path: &mut InstructionPath) {
+        for (i, instruction) in instructions.iter_mut().enumerate() {
+            path.1.push(i);
+            if let Some(entries) = self.resolved.get(path) {
+                instruction.slot = if let [(_, slot)] = entries.as_slice() {
+                    ContextSlot::Fixed(*slot)
+                } else {
+                    ContextSlot::Scoped(entries.clone().into_boxed_slice())
+                };
+            }
+            self.assign(&mut instruction.instructions, path);
+            path.1.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::definitions::Definitions;
+
+    fn slot(defs: &Definitions, template: &str, path: &[&str]) -> ContextSlot {
+        let mut instructions = &defs.templates_by_name[template].instructions;
+        let mut instruction = None;
+        for name in path {
+            let i = instructions.iter().find(|i| i.name == *name).unwrap();
+            instructions = &i.instructions;
+            instruction = Some(i);
+        }
+        instruction.unwrap().slot.clone()
+    }
+
+    fn fixed(defs: &Definitions, template: &str, path: &[&str]) -> usize {
+        match slot(defs, template, path) {
+            ContextSlot::Fixed(s) => s,
+            s => panic!("expected fixed slot, got {s:?}"),
+        }
+    }
+
+    const DEFINITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="A" id="1">
+        <uInt32 name="G" id="1"><copy/></uInt32>
+        <uInt32 name="T" id="2" dictionary="template"><copy/></uInt32>
+        <uInt32 name="U" id="3" dictionary="user" key="shared"><copy/></uInt32>
+        <uInt32 name="N" id="4"/>
+        <group name="Grp" dictionary="type" typeRef="Type1">
+            <uInt32 name="Y" id="5"><copy/></uInt32>
+        </group>
+    </template>
+    <template name="R" dictionary="template">
+        <uInt32 name="T" id="2"><copy/></uInt32>
+    </template>
+    <template name="B" id="2" dictionary="template">
+        <uInt32 name="G" id="1" dictionary="global"><copy/></uInt32>
+        <uInt32 name="T" id="2"><copy/></uInt32>
+        <uInt32 name="U2" id="3" dictionary="user" key="shared"><copy/></uInt32>
+        <group name="Grp" dictionary="type" typeRef="Type1">
+            <uInt32 name="Y" id="5"><copy/></uInt32>
+        </group>
+        <templateRef name="R"/>
+    </template>
+    <template name="C" id="3">
+        <templateRef name="R"/>
+    </template>
+</templates>
+"#;
+
+    #[test]
+    fn dictionary_sharing() {
+        let defs = Definitions::new_from_xml(DEFINITION).unwrap();
+        // global dictionary is shared between templates
+        assert_eq!(fixed(&defs, "A", &["G"]), fixed(&defs, "B", &["G"]));
+        // template dictionary is not shared between templates
+        assert_ne!(fixed(&defs, "A", &["T"]), fixed(&defs, "B", &["T"]));
+        // template dictionary is not shared with global
+        assert_ne!(fixed(&defs, "A", &["G"]), fixed(&defs, "A", &["T"]));
+        // user defined dictionary is shared by key
+        assert_eq!(fixed(&defs, "A", &["U"]), fixed(&defs, "B", &["U2"]));
+        // type dictionary is shared by application type
+        assert_eq!(
+            fixed(&defs, "A", &["Grp", "Y"]),
+            fixed(&defs, "B", &["Grp", "Y"])
+        );
+        // fields without dictionary operators have no slot
+        assert_eq!(slot(&defs, "A", &["N"]), ContextSlot::None);
+    }
+
+    #[test]
+    fn scoped_slots() {
+        let defs = Definitions::new_from_xml(DEFINITION).unwrap();
+        // R's template dictionary is the dictionary of the referencing template: B or C
+        let ContextSlot::Scoped(slots) = slot(&defs, "R", &["T"]) else {
+            panic!("expected scoped slot");
+        };
+        let mut dicts: Vec<_> = slots.iter().map(|(d, _)| d.clone()).collect();
+        dicts.sort_by_key(|d| format!("{d:?}"));
+        assert_eq!(
+            dicts,
+            vec![DictionaryType::Template(2), DictionaryType::Template(3)]
+        );
+        // R's field in B's template dictionary is the same entry as B's own field
+        let b = fixed(&defs, "B", &["T"]);
+        assert!(
+            slots
+                .iter()
+                .any(|(d, s)| *d == DictionaryType::Template(2) && *s == b)
+        );
+    }
+
+    #[test]
+    fn type_dictionary_without_type() {
+        let defs = Definitions::new_from_xml(
+            r#"<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="A" id="1" dictionary="type">
+        <uInt32 name="X" id="1"><copy/></uInt32>
+        <group name="Grp" typeRef="__any__">
+            <uInt32 name="X" id="1"><copy/></uInt32>
+        </group>
+    </template>
+</templates>"#,
+        )
+        .unwrap();
+        // elements without application type don't share entries with any application type
+        assert_ne!(fixed(&defs, "A", &["X"]), fixed(&defs, "A", &["Grp", "X"]));
+    }
+}
@@ -0,0 +1,423 @@
+//! # Template definitions validator
+//!
+//! Loading definitions stops at the first error. The validator checks the whole document and collects
+//! all problems with their locations in the XML source, so ill-formed vendor templates can be fixed in one go.
+//! Errors are the problems that make loading fail, warnings are the problems loading accepts, but that fail
+//! or misbehave at runtime, e.g. fields of different types sharing a dictionary entry.
+//!
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use roxmltree::{Document, Node};
+
+use crate::base::instruction::Instruction;
+use crate::base::types::{Template, TypeDefinitions, inherited_attribute, qualified_name};
+use crate::base::value::ValueType;
+use crate::common::context::ContextSlot;
+use crate::common::definitions::Definitions;
+use crate::{Error, ErrorCode};
+
+/// Severity of a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The definitions can't be loaded.
+    Error,
+    /// The definitions can be loaded, but messages may fail to decode or encode.
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Problem found in template definitions, see [`Definitions::validate_xml`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Error code defined by the FAST specification, if any.
+    pub code: Option<ErrorCode>,
+    pub message: String,
+    /// Line number in the XML source, starting from 1.
+    pub line: u32,
+    /// Column number in the XML source, starting from 1.
+    pub column: u32,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}: ", self.line, self.column, self.severity)?;
+        if let Some(code) = self.code {
+            write!(f, "[{code}] ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Validates template definitions given as XML and returns all diagnostics in the order of their location.
+pub(crate) fn validate_xml(text: &str) -> Vec<Diagnostic> {
+    let doc = match Document::parse(text) {
+        Ok(doc) => doc,
+        Err(e) => {
+            let pos = e.pos();
+            return vec![Diagnostic {
+                severity: Severity::Error,
+                code: Some(ErrorCode::S1),
+                message: e.to_string(),
+                line: pos.row,
+                column: pos.col,
+            }];
+        }
+    };
+    let mut validator = Validator {
+        doc: &doc,
+        diagnostics: Vec::new(),
+    };
+    validator.validate();
+    let mut diagnostics = validator.diagnostics;
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+struct Validator<'a, 'input> {
+    doc: &'a Document<'input>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// Template element with its qualified name and identifier.
+struct TemplateNode<'a, 'input> {
+    node: Node<'a, 'input>,
+    name: String,
+    id: u32,
+}
+
+impl<'a, 'input> Validator<'a, 'input> {
+    fn validate(&mut self) {
+        let Some(root) = self.doc.root().first_element_child() else {
+            self.report_at(
+                self.doc.root(),
+                Severity::Error,
+                None,
+                "no root element found",
+            );
+            return;
+        };
+        if root.tag_name().name() != "templates" {
+            self.report_at(
+                root,
+                Severity::Error,
+                Some(ErrorCode::S1),
+                "<templates/> node not found",
+            );
+            return;
+        }
+        let (defs, errors) = TypeDefinitions::from_node_with_errors(root);
+        for (node, err) in errors {
+            self.error(node, &err);
+        }
+        let templates = self.templates(root, &defs);
+        self.template_refs(&templates);
+        if self.has_errors() {
+            return;
+        }
+        // Loading must succeed if no errors are found, otherwise the error is reported at the root element.
+        match Definitions::load_xml(self.doc.input_text()) {
+            Ok(definitions) => {
+                let nodes: Vec<_> = templates.iter().map(|t| t.node).collect();
+                self.dictionary_entries(&definitions, &nodes, &defs);
+            }
+            Err(e) => self.error(root, &e),
+        }
+    }
+
+    // Checks each template and its fields. Returns the templates that have names.
+    fn templates(
+        &mut self,
+        root: Node<'a, 'input>,
+        defs: &TypeDefinitions<'a, 'input>,
+    ) -> Vec<TemplateNode<'a, 'input>> {
+        let mut templates: Vec<TemplateNode> = Vec::new();
+        for node in root.children().filter(Node::is_element) {
+            match node.tag_name().name() {
+                "define" => continue,
+                "template" => {}
+                tag => {
+                    self.report_at(
+                        node,
+                        Severity::Error,
+                        Some(ErrorCode::S1),
+                        &format!("expected <template/> node, got <{tag}/>"),
+                    );
+                    continue;
+                }
+            }
+            let errors = self.diagnostics.len();
+            self.fields(node, defs);
+            if self.diagnostics.len() == errors
+                && let Err(e) = Template::from_node(node, defs)
+            {
+                self.error(node, &e);
+            }
+            let Some(name) = node.attribute("name") else {
+                continue;
+            };
+            let name = qualified_name(inherited_attribute(node, "templateNs"), name);
+            let id = node
+                .attribute("id")
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(0);
+            if templates.iter().any(|t| t.name == name) {
+                let msg = format!("duplicate template name: {name}");
+                self.report_at(node, Severity::Error, None, &msg);
+            }
+            if id != 0 && templates.iter().any(|t| t.id == id) {
+                let msg = format!("duplicate template id: {id}");
+                self.report_at(node, Severity::Error, None, &msg);
+            }
+            templates.push(TemplateNode { node, name, id });
+        }
+        templates
+    }
+
+    // Checks field instructions one by one, so all broken fields are reported.
+    // Groups and sequences are checked as a whole only if their fields have no errors.
+    fn fields(&mut self, parent: Node<'a, 'input>, defs: &TypeDefinitions<'a, 'input>) {
+        for node in parent.children().filter(Node::is_element) {
+            match node.tag_name().name() {
+                "define" => continue,
+                "group" | "sequence" => {
+                    let errors = self.diagnostics.len();
+                    self.fields(node, defs);
+                    if self.diagnostics.len() != errors {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            if let Err(e) = Instruction::from_node(node, defs) {
+                self.error(node, &e);
+            }
+        }
+    }
+
+    // Checks static template references: the referenced template must exist and be defined before
+    // the referencing one, references must not form cycles. Templates without identifiers that are never
+    // referenced can't be used.
+    fn template_refs(&mut self, templates: &[TemplateNode<'a, 'input>]) {
+        let mut by_name: HashMap<&str, usize> = HashMap::new();
+        let mut bare_names: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, t) in templates.iter().enumerate() {
+            by_name.entry(t.name.as_str()).or_insert(idx);
+            let bare = t.node.attribute("name").unwrap_or_default();
+            bare_names.entry(bare).or_default().push(idx);
+        }
+        let mut refs: Vec<Vec<(Node, usize)>> = vec![Vec::new(); templates.len()];
+        let mut referenced = vec![false; templates.len()];
+        for (idx, t) in templates.iter().enumerate() {
+            for node in t.node.descendants().filter(|n| {
+                n.is_element() && n.tag_name().name() == "templateRef" && n.has_attribute("name")
+            }) {
+                let name = node.attribute("name").unwrap_or_default();
+                let qualified = qualified_name(inherited_attribute(node, "templateNs"), name);
+                let target = by_name
+                    .get(qualified.as_str())
+                    .copied()
+                    .or_else(|| match bare_names.get(name).map(Vec::as_slice) {
+                        Some([idx]) => Some(*idx),
+                        _ => None,
+                    });
+                if let Some(target) = target {
+                    referenced[target] = true;
+                    refs[idx].push((node, target));
+                } else {
+                    let msg = format!("template '{name}' not found");
+                    self.report_at(node, Severity::Error, Some(ErrorCode::D8), &msg);
+                }
+            }
+        }
+        for (idx, t) in templates.iter().enumerate() {
+            for (node, target) in &refs[idx] {
+                if let Some(path) = cycle(&refs, idx, *target) {
+                    let names: Vec<_> = path.iter().map(|i| templates[*i].name.as_str()).collect();
+                    let msg = format!("cyclic template reference: {}", names.join(" -> "));
+                    self.report_at(*node, Severity::Error, None, &msg);
+                } else if *target >= idx {
+                    let msg = format!(
+                        "template '{}' must be defined before template '{}' that references it",
+                        templates[*target].name, t.name
+                    );
+                    self.report_at(*node, Severity::Error, None, &msg);
+                }
+            }
+            if t.id == 0 && !referenced[idx] {
+                let msg = format!("template '{}' has no id and is never referenced", t.name);
+                self.report_at(t.node, Severity::Warning, None, &msg);
+            }
+        }
+    }
+
+    // Fields that share a dictionary entry must have the same type,
+    // otherwise decoding and encoding fail with [ERR D4] when the entry is accessed.
+    fn dictionary_entries(
+        &mut self,
+        definitions: &Definitions,
+        nodes: &[Node<'a, 'input>],
+        defs: &TypeDefinitions<'a, 'input>,
+    ) {
+        let mut fields = Vec::new();
+        for (t, node) in definitions.templates.iter().zip(nodes) {
+            let children: Vec<_> = field_nodes(*node).collect();
+            collect_nodes(&t.instructions, &children, defs, &mut fields);
+        }
+        let mut entries: HashMap<usize, (&Instruction, Node)> = HashMap::new();
+        for (instruction, node) in fields {
+            let slots: Vec<usize> = match &instruction.slot {
+                ContextSlot::None => continue,
+                ContextSlot::Fixed(slot) => vec![*slot],
+                ContextSlot::Scoped(slots) => slots.iter().map(|(_, s)| *s).collect(),
+            };
+            for slot in slots {
+                let (first, first_node) = *entries.entry(slot).or_insert((instruction, node));
+                let Ok(value) = first.default_base_value() else {
+                    continue;
+                };
+                if !instruction.matches_dictionary_value(&value) {
+                    let pos = self.doc.text_pos_at(first_node.range().start);
+                    let msg = format!(
+                        "{} {} shares dictionary entry '{}' with {} {} at {}:{}",
+                        instruction.value_type.type_str(),
+                        display_name(instruction, node),
+                        instruction.key,
+                        first.value_type.type_str(),
+                        display_name(first, first_node),
+                        pos.row,
+                        pos.col
+                    );
+                    self.report_at(node, Severity::Warning, Some(ErrorCode::D4), &msg);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+
+    fn error(&mut self, node: Node, err: &Error) {
+        let (code, msg) = match err.kind() {
+            Error::Spec(code, msg) => (Some(*code), msg.clone()),
+            Error::Static(msg) | Error::Runtime(msg) => (None, msg.clone()),
+            e => (None, e.to_string()),
+        };
+        self.report_at(node, Severity::Error, code, &msg);
+    }
+
+    fn report_at(&mut self, node: Node, severity: Severity, code: Option<ErrorCode>, msg: &str) {
+        let pos = self.doc.text_pos_at(node.range().start);
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            message: msg.to_string(),
+            line: pos.row,
+            column: pos.col,
+        });
+    }
+}
+
+// Returns the path of template indexes if the reference from `from` to `to` closes a cycle.
+fn cycle(refs: &[Vec<(Node, usize)>], from: usize, to: usize) -> Option<Vec<usize>> {
+    fn find(
+        refs: &[Vec<(Node, usize)>],
+        idx: usize,
+        from: usize,
+        visited: &mut [bool],
+        path: &mut Vec<usize>,
+    ) -> bool {
+        path.push(idx);
+        if idx == from {
+            return true;
+        }
+        if !visited[idx] {
+            visited[idx] = true;
+            for (_, target) in &refs[idx] {
+                if find(refs, *target, from, visited, path) {
+                    return true;
+                }
+            }
+        }
+        path.pop();
+        false
+    }
+
+    let mut path = vec![from];
+    let mut visited = vec![false; refs.len()];
+    find(refs, to, from, &mut visited, &mut path).then_some(path)
+}
+
+// Element children of a template or a group that are field instructions.
+fn field_nodes<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(|n| n.is_element() && n.tag_name().name() != "define")
+}
+
+// Pairs instructions with the XML elements they are created from.
+// Subcomponents without own elements are paired with the element of their field.
+fn collect_nodes<'i, 'a, 'input>(
+    instructions: &'i [Instruction],
+    nodes: &[Node<'a, 'input>],
+    defs: &TypeDefinitions<'a, 'input>,
+    out: &mut Vec<(&'i Instruction, Node<'a, 'input>)>,
+) {
+    for (instruction, node) in instructions.iter().zip(nodes) {
+        out.push((instruction, *node));
+        let type_node = if node.tag_name().name() == "field" {
+            defs.resolve(*node).unwrap_or(*node)
+        } else {
+            *node
+        };
+        let children: Vec<_> = field_nodes(type_node).collect();
+        match instruction.value_type {
+            ValueType::Group => collect_nodes(&instruction.instructions, &children, defs, out),
+            ValueType::Sequence => {
+                let (length, fields) = match children.first() {
+                    Some(n) if n.tag_name().name() == "length" => (*n, &children[1..]),
+                    _ => (*node, &children[..]),
+                };
+                if let Some((first, rest)) = instruction.instructions.split_first() {
+                    out.push((first, length));
+                    collect_nodes(rest, fields, defs, out);
+                }
+            }
+            _ => {
+                for sub in &instruction.instructions {
+                    let tag = match sub.value_type {
+                        ValueType::Exponent => "exponent",
+                        ValueType::Mantissa => "mantissa",
+                        _ => "length",
+                    };
+                    let sub_node = children
+                        .iter()
+                        .find(|n| n.tag_name().name() == tag)
+                        .unwrap_or(node);
+                    out.push((sub, *sub_node));
+                }
+            }
+        }
+    }
+}
+
+// Decimal subcomponents have no names, they are named after their decimal field.
+fn display_name<'n>(instruction: &'n Instruction, node: Node<'n, '_>) -> &'n str {
+    if instruction.name.is_empty() {
+        node.attribute("name").unwrap_or(&instruction.key)
+    } else {
+        &instruction.name
+    }
+}
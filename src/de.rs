@@ -1,8 +1,10 @@
 use std::io::Read;
+use std::marker::PhantomData;
 
 use serde::de::Deserialize;
 
-use crate::model::ModelFactory;
+use crate::decoder::reader::{CountingReader, SliceReader, StreamReader};
+use crate::model::wire::{deserialize_message, deserialize_message_raw};
 use crate::{Decoder, Error, Reader, Result};
 
 /// Decode single message from `Vec<u8>`.
@@ -12,12 +14,15 @@ pub fn from_vec<'de, T>(decoder: &mut Decoder, bytes: Vec<u8>) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    // Decode FAST message into internal data model
-    let mut msg = ModelFactory::new();
-    decoder.decode_vec(bytes, &mut msg)?;
-
-    // Deserialize from internal data model into user data type
-    deserialize(msg)
+    let mut raw = bytes::Bytes::from(bytes);
+    let msg = deserialize_message(decoder, &mut raw)?;
+    if !raw.is_empty() {
+        return Err(Error::Runtime(format!(
+            "Bytes left in the buffer after decoding: {}",
+            raw.len()
+        )));
+    }
+    msg
 }
 
 /// Decode single message from buffer.
@@ -28,12 +33,9 @@ pub fn from_buffer<'de, T>(decoder: &mut Decoder, buffer: &[u8]) -> Result<(T, u
 where
     T: Deserialize<'de>,
 {
-    // Decode FAST message into internal data model
-    let mut msg = ModelFactory::new();
-    let n = decoder.decode_buffer(buffer, &mut msg)?;
-
-    // Deserialize from internal data model into user data type
-    deserialize(msg).map(|r| (r, n))
+    let mut rdr = SliceReader::new(buffer);
+    let msg = deserialize_message_raw(decoder, &mut rdr)?;
+    msg.map(|r| (r, rdr.position()))
 }
 
 /// Decode single message from buffer.
@@ -44,12 +46,15 @@ pub fn from_slice<'de, T>(decoder: &mut Decoder, bytes: &[u8]) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    // Decode FAST message into internal data model
-    let mut msg = ModelFactory::new();
-    decoder.decode_slice(bytes, &mut msg)?;
-
-    // Deserialize from internal data model into user data type
-    deserialize(msg)
+    let mut rdr = SliceReader::new(bytes);
+    let msg = deserialize_message_raw(decoder, &mut rdr)?;
+    let remaining = bytes.len() as u64 - rdr.position();
+    if remaining > 0 {
+        return Err(Error::Runtime(format!(
+            "Bytes left in the buffer after decoding: {remaining}"
+        )));
+    }
+    msg
 }
 
 /// Decode single message from `bytes::Bytes`.
@@ -60,12 +65,7 @@ pub fn from_bytes<'de, T>(decoder: &mut Decoder, bytes: &mut bytes::Bytes) -> Re
 where
     T: Deserialize<'de>,
 {
-    // Decode FAST message into internal data model
-    let mut msg = ModelFactory::new();
-    decoder.decode_bytes(bytes, &mut msg)?;
-
-    // Deserialize from internal data model into user data type
-    deserialize(msg)
+    deserialize_message(decoder, bytes)?
 }
 
 /// Decode single message from object that implements `fastlib::Reader` trait.
@@ -76,12 +76,7 @@ pub fn from_reader<'de, T>(decoder: &mut Decoder, rdr: &mut impl Reader) -> Resu
 where
     T: Deserialize<'de>,
 {
-    // Decode FAST message into internal data model
-    let mut msg = ModelFactory::new();
-    decoder.decode_reader(rdr, &mut msg)?;
-
-    // Deserialize from internal data model into user data type
-    deserialize(msg)
+    deserialize_message(decoder, rdr)?
 }
 
 /// Decode single message from object that implements `std::io::Read` trait.
@@ -92,25 +87,80 @@ pub fn from_stream<'de, T>(decoder: &mut Decoder, rdr: &mut dyn Read) -> Result<
 where
     T: Deserialize<'de>,
 {
-    // Decode FAST message into internal data model
-    let mut msg = ModelFactory::new();
-    decoder.decode_stream(rdr, &mut msg)?;
+    deserialize_message(decoder, &mut StreamReader::new(rdr))?
+}
+
+/// Iterator that decodes messages from object that implements `fastlib::Reader` trait and deserializes them into `T`.
+///
+/// Yields messages until a clean end of stream. Truncated trailing data is reported as `Error::UnexpectedEof`.
+/// The iterator stops after the first decoding error, because the stream position is no longer at a message boundary.
+/// Deserialization errors are yielded and iteration can continue with the next message,
+/// unless the error occurred before any input was consumed (e.g. `T` is not an enum of templates).
+pub struct StreamDeserializer<'a, R, T> {
+    decoder: &'a mut Decoder,
+    rdr: CountingReader<R>,
+    done: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, 'de, R, T> StreamDeserializer<'a, R, T>
+where
+    R: Reader,
+    T: Deserialize<'de>,
+{
+    /// Creates stream deserializer reading messages from `rdr`.
+    pub fn new(decoder: &'a mut Decoder, rdr: R) -> Self {
+        Self {
+            decoder,
+            rdr: CountingReader::new(rdr),
+            done: false,
+            _marker: PhantomData,
+        }
+    }
 
-    // Deserialize from internal data model into user data type
-    deserialize(msg)
+    /// Returns number of bytes consumed from the reader so far.
+    /// Called before [`Iterator::next`] it is the byte offset of the next message in the stream.
+    pub fn byte_offset(&self) -> u64 {
+        self.rdr.position()
+    }
+}
+
+impl<'de, R, T> Iterator for StreamDeserializer<'_, R, T>
+where
+    R: Reader,
+    T: Deserialize<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let offset = self.rdr.position();
+        match deserialize_message(self.decoder, &mut self.rdr) {
+            Ok(Err(e)) if self.rdr.position() == offset => {
+                // Nothing is consumed, the next call would fail the same way.
+                self.done = true;
+                Some(Err(e))
+            }
+            Ok(msg) => Some(msg),
+            Err(Error::Eof) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
 }
 
-// Deserializes message to the given type.
-// # Errors
-// Returns error if deserialization failed.
-fn deserialize<'de, T>(msg: ModelFactory) -> Result<T>
+impl<'de, R, T> std::iter::FusedIterator for StreamDeserializer<'_, R, T>
 where
+    R: Reader,
     T: Deserialize<'de>,
 {
-    let data = msg
-        .data
-        .ok_or_else(|| Error::Runtime("No data in message".to_string()))?;
-    T::deserialize(data)
 }
 
 impl serde::de::Error for Error {
@@ -1,22 +1,43 @@
 use bytes::Buf;
 use std::io::Read;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::base::instruction::Instruction;
-use crate::base::message::MessageFactory;
+use crate::base::message::{MessageFactory, MessageFactoryRef};
 use crate::base::pmap::PresenceMap;
-use crate::base::types::{Dictionary, Template, TypeRef};
-use crate::base::value::{Value, ValueType};
-use crate::common::context::{Context, DictionaryType};
+use crate::base::types::Template;
+use crate::base::value::{Value, ValueRef};
+use crate::common::context::{Context, FieldSlots};
 use crate::common::definitions::Definitions;
-use crate::decoder::reader::{Reader, StreamReader};
+use crate::common::framing::Framing;
+use crate::common::program::{Field, Frame, MESSAGE_SCOPE, Op, locate_error};
+use crate::decoder::iter::MessageIter;
+use crate::decoder::limits::DecoderLimits;
+use crate::decoder::reader::{
+    CheckedReader, CopyingReader, LimitedReader, ReadRaw, Reader, SliceReader, StreamReader,
+};
+use crate::typed::{self, FastMessage};
 use crate::utils::stacked::Stacked;
-use crate::{Error, Result};
+use crate::{Error, ErrorCode, Result, TemplateBuilder};
+
+/// Defines how the decoder handles reportable errors (see [`ErrorCode::is_reportable`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPolicy {
+    /// Reportable errors are collected as warnings of the decoded message, see [`Decoder::warnings`].
+    #[default]
+    Warn,
+    /// Reportable errors fail decoding.
+    Fail,
+}
 
 /// Decoder for FAST protocol messages.
 pub struct Decoder {
-    pub(crate) definitions: Definitions,
+    pub(crate) definitions: Arc<Definitions>,
     pub(crate) context: Context,
+    pub(crate) transactional: bool,
+    pub(crate) limits: DecoderLimits,
+    pub(crate) report_policy: ReportPolicy,
+    pub(crate) warnings: Vec<Error>,
 }
 
 impl Decoder {
@@ -25,26 +46,124 @@ impl Decoder {
     /// # Errors
     /// Returns error if invalid templates given.
     pub(crate) fn new_from_templates(ts: Vec<Template>) -> Result<Self> {
-        Ok(Decoder {
-            definitions: Definitions::new_from_templates(ts)?,
-            context: Context::new(),
-        })
+        Ok(Self::with_definitions(Arc::new(
+            Definitions::new_from_templates(ts)?,
+        )))
     }
 
     /// Creates Decoder from XML definitions.
     /// # Errors
     /// Returns error if invalid definitions given.
     pub fn new_from_xml(text: &str) -> Result<Self> {
-        Ok(Decoder {
-            definitions: Definitions::new_from_xml(text)?,
-            context: Context::new(),
-        })
+        Ok(Self::with_definitions(Arc::new(Definitions::new_from_xml(
+            text,
+        )?)))
+    }
+
+    /// Creates Decoder from templates built in code, see [`TemplateBuilder`].
+    /// # Errors
+    /// Returns error if invalid templates given.
+    pub fn new_from_builders(builders: impl IntoIterator<Item = TemplateBuilder>) -> Result<Self> {
+        Ok(Self::with_definitions(Arc::new(
+            Definitions::new_from_builders(builders)?,
+        )))
+    }
+
+    /// Creates Decoder that uses shared definitions.
+    #[must_use]
+    pub fn with_definitions(definitions: Arc<Definitions>) -> Self {
+        Decoder {
+            context: Context::new(definitions.slots),
+            definitions,
+            transactional: false,
+            limits: DecoderLimits::default(),
+            report_policy: ReportPolicy::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns definitions used by the decoder.
+    #[must_use]
+    pub fn definitions(&self) -> &Arc<Definitions> {
+        &self.definitions
     }
 
     pub fn reset(&mut self) {
         self.context.reset();
     }
 
+    /// Enables or disables transactional decoding.
+    /// In transactional mode dictionary updates made while decoding a message are committed only if the message
+    /// is decoded successfully. If decoding fails, the dictionaries are restored to the state before the message,
+    /// so decoding can continue (or be retried) without [`Decoder::reset`].
+    /// Disabled by default.
+    pub fn set_transactional(&mut self, transactional: bool) {
+        self.transactional = transactional;
+    }
+
+    /// Sets safety limits checked while decoding messages.
+    /// A message that exceeds the limits fails with a dedicated error, e.g. [`Error::LengthLimitExceeded`].
+    pub fn set_limits(&mut self, limits: DecoderLimits) {
+        self.limits = limits;
+    }
+
+    /// Returns safety limits checked while decoding messages.
+    #[must_use]
+    pub fn limits(&self) -> &DecoderLimits {
+        &self.limits
+    }
+
+    /// Sets how reportable errors are handled. Reportable errors are collected as warnings by default.
+    /// Reportable errors that leave no value to continue with, e.g. [`ErrorCode::R2`], always fail decoding.
+    pub fn set_report_policy(&mut self, policy: ReportPolicy) {
+        self.report_policy = policy;
+    }
+
+    /// Returns reportable errors detected while decoding the last message.
+    #[must_use]
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
+    }
+
+    /// Decode single message from buffer that may contain an incomplete message, e.g. data of a partial TCP read.
+    /// Returns `Some` with number of bytes consumed from the buffer if a message is decoded.
+    /// Returns `None` if the buffer ends before the message does. The dictionaries are left untouched in this case,
+    /// so decoding can be retried when more bytes are received. Note that `msg` may have been partially built and
+    /// should be discarded.
+    /// # Errors
+    /// Returns error if message decode failed.
+    pub fn try_decode_buffer(
+        &mut self,
+        buffer: &[u8],
+        msg: &mut impl MessageFactory,
+    ) -> Result<Option<u64>> {
+        let mut rdr = SliceReader::new(buffer);
+        let mut checked = self.checked_reader(&mut rdr);
+        self.context.begin();
+        let res =
+            DecoderContext::new(self, &mut CopyingReader(&mut checked), msg).decode_template();
+        let position = checked.position();
+        self.warnings = checked.into_warnings();
+        match res {
+            Ok(()) => {
+                self.context.commit();
+                Ok(Some(rdr.position()))
+            }
+            Err(e) if matches!(e.kind(), Error::Eof | Error::UnexpectedEof) => {
+                self.context.rollback();
+                Ok(None)
+            }
+            Err(e) => {
+                if self.transactional {
+                    self.context.rollback();
+                } else {
+                    self.context.commit();
+                }
+                Err(e.at_offset(position))
+            }
+        }
+    }
+
     /// Decode single message from buffer.
     /// Returns number of bytes consumed from the buffer.
     /// # Errors
@@ -55,6 +174,25 @@ impl Decoder {
         Ok(cursor.position())
     }
 
+    /// Decode single message from buffer without copying strings and byte vectors.
+    /// Unicode strings and byte vectors are passed to `msg` as slices borrowed from `buffer`, unless they are
+    /// stored in a dictionary as previous values (fields with copy, increment, delta or tail operators).
+    /// Returns number of bytes consumed from the buffer.
+    ///
+    /// To keep the decoded values beyond the lifetime of the buffer borrow, decode from `bytes::Bytes`
+    /// with `Decoder::decode_bytes_ref` (`shared-bytes` feature).
+    /// # Errors
+    /// Returns error if message decode failed.
+    pub fn decode_buffer_ref<'a>(
+        &mut self,
+        buffer: &'a [u8],
+        msg: &mut impl MessageFactoryRef<'a>,
+    ) -> Result<u64> {
+        let mut rdr = SliceReader::new(buffer);
+        self.decode_raw(&mut rdr, msg)?;
+        Ok(rdr.position())
+    }
+
     /// Decode single message from slice.
     /// The `bytes` slice must be consumed completely. It is an error if any bytes left after the message is decoded.
     /// # Errors
@@ -98,6 +236,22 @@ impl Decoder {
         self.decode_reader(bytes, msg)
     }
 
+    /// Decode single message from `bytes::Bytes` without copying strings and byte vectors.
+    /// Unicode strings and byte vectors are passed to `msg` as reference-counted sub-slices of `bytes`
+    /// ([`ValueRef::SharedUnicodeString`][crate::ValueRef::SharedUnicodeString] and
+    /// [`ValueRef::SharedBytes`][crate::ValueRef::SharedBytes]), unless they are stored in a dictionary
+    /// as previous values (fields with copy, increment, delta or tail operators).
+    /// # Errors
+    /// Returns error if message decode failed.
+    #[cfg(feature = "shared-bytes")]
+    pub fn decode_bytes_ref(
+        &mut self,
+        bytes: &mut bytes::Bytes,
+        msg: &mut impl MessageFactoryRef<'static>,
+    ) -> Result<()> {
+        self.decode_raw(bytes, msg)
+    }
+
     /// Decode single message from object that implements [`std::io::Read`][std::io::Read] trait.
     /// # Errors
     /// Returns error if message decode failed.
@@ -118,54 +272,221 @@ impl Decoder {
         rdr: &mut impl Reader,
         msg: &mut impl MessageFactory,
     ) -> Result<()> {
-        DecoderContext::new(self, rdr, msg).decode_template()
+        self.decode_raw(&mut CopyingReader(rdr), msg)
+    }
+
+    /// Decode single message from object that implements [`fastlib::Reader`][crate::decoder::reader::Reader] trait
+    /// into a typed message, see [`FastMessage`].
+    /// # Errors
+    /// Returns error if message decode failed or the message has a different template.
+    pub fn decode_typed<T: FastMessage>(&mut self, rdr: &mut impl Reader) -> Result<T> {
+        typed::decode_message(self, rdr)
+    }
+
+    // The method path doesn't satisfy the higher-ranked lifetime of the context argument, so the closure is kept.
+    #[allow(clippy::redundant_closure_for_method_calls)]
+    fn decode_raw<'a, R, M>(&mut self, rdr: &mut R, msg: &mut M) -> Result<()>
+    where
+        R: ReadRaw<'a>,
+        M: MessageFactoryRef<'a>,
+    {
+        self.decode_raw_with(rdr, msg, |ctx| ctx.decode_template())
+    }
+
+    // Runs `f` in the processing context of one message read from `rdr`. Applies the limits and the report policy
+    // to the reader, and commits or rolls back dictionary updates in transactional mode.
+    pub(crate) fn decode_raw_with<'a, R, M, T, F>(
+        &mut self,
+        rdr: &mut R,
+        msg: &mut M,
+        f: F,
+    ) -> Result<T>
+    where
+        R: ReadRaw<'a>,
+        M: MessageFactoryRef<'a>,
+        F: FnOnce(&mut DecoderContext<'_, CheckedReader<'_, R>, M>) -> Result<T>,
+    {
+        let mut rdr = self.checked_reader(rdr);
+        if !self.transactional {
+            let res = f(&mut DecoderContext::new(self, &mut rdr, msg));
+            let position = rdr.position();
+            self.warnings = rdr.into_warnings();
+            return res.map_err(|e| e.at_offset(position));
+        }
+        self.context.begin();
+        let res = f(&mut DecoderContext::new(self, &mut rdr, msg))
+            .map_err(|e| e.at_offset(rdr.position()));
+        self.warnings = rdr.into_warnings();
+        if res.is_ok() {
+            self.context.commit();
+        } else {
+            self.context.rollback();
+        }
+        res
+    }
+
+    // Wraps the reader of one message to enforce the limits and the report policy.
+    fn checked_reader<'r, R: ?Sized>(&self, rdr: &'r mut R) -> CheckedReader<'r, R> {
+        CheckedReader::new(
+            rdr,
+            self.limits.max_message_bytes,
+            self.report_policy == ReportPolicy::Fail,
+        )
+        .with_max_string_length(self.limits.max_string_length)
+    }
+
+    /// Decode single framed message from buffer.
+    /// Returns the frame header and number of bytes consumed from the buffer.
+    /// # Errors
+    /// Returns error if frame header read or message decode failed.
+    pub fn decode_frame_buffer<F: Framing>(
+        &mut self,
+        framing: &mut F,
+        buffer: &[u8],
+        msg: &mut impl MessageFactory,
+    ) -> Result<(F::Header, u64)> {
+        let mut cursor = std::io::Cursor::new(buffer);
+        let header = self.decode_frame_stream(framing, &mut cursor, msg)?;
+        Ok((header, cursor.position()))
+    }
+
+    /// Decode single framed message from object that implements [`std::io::Read`][std::io::Read] trait.
+    /// Returns the frame header.
+    /// # Errors
+    /// Returns error if frame header read or message decode failed.
+    pub fn decode_frame_stream<F: Framing>(
+        &mut self,
+        framing: &mut F,
+        rdr: &mut dyn Read,
+        msg: &mut impl MessageFactory,
+    ) -> Result<F::Header> {
+        let mut rdr = StreamReader::new(rdr);
+        self.decode_frame(framing, &mut rdr, msg)
+    }
+
+    /// Decode single framed message from object that implements [`fastlib::Reader`][crate::decoder::reader::Reader] trait.
+    /// Returns the frame header.
+    ///
+    /// If the framing defines the payload length, the message must consume the payload completely.
+    /// Bytes left in the payload are skipped, so the reader stays at the frame boundary, and an error is returned.
+    /// If the message fails to decode, the rest of the payload is skipped as well.
+    /// # Errors
+    /// Returns error if frame header read or message decode failed.
+    /// Returns [`Error::Eof`] if the end of the stream is reached at the frame boundary.
+    /// Returns [`Error::MessageSizeLimitExceeded`] without reading the payload if the framing
+    /// declares more than [`DecoderLimits::max_message_bytes`][crate::DecoderLimits::max_message_bytes].
+    pub fn decode_frame<F: Framing>(
+        &mut self,
+        framing: &mut F,
+        rdr: &mut impl Reader,
+        msg: &mut impl MessageFactory,
+    ) -> Result<F::Header> {
+        let (header, len) = framing.read_header(rdr)?;
+        let res = match len {
+            None => self.decode_reader(rdr, msg),
+            Some(len) if len > self.limits.max_message_bytes => {
+                return Err(Error::MessageSizeLimitExceeded(
+                    self.limits.max_message_bytes,
+                ));
+            }
+            Some(len) => {
+                let mut rdr = LimitedReader::new(rdr, len);
+                let res = self.decode_reader(&mut rdr, msg);
+                match res {
+                    Ok(()) if rdr.remaining() > 0 => {
+                        let left = rdr.remaining();
+                        rdr.skip_remaining()?;
+                        Err(Error::Runtime(format!(
+                            "Bytes left in the frame after decoding: {left}"
+                        )))
+                    }
+                    Ok(()) => Ok(()),
+                    Err(e) => {
+                        // The decode error is more relevant than a failure to skip the payload.
+                        _ = rdr.skip_remaining();
+                        Err(e)
+                    }
+                }
+            }
+        };
+        match res {
+            Ok(()) => Ok(header),
+            // the header promised a message
+            Err(Error::Eof) => Err(Error::UnexpectedEof),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Creates an iterator that decodes messages from object that implements [`fastlib::Reader`][crate::decoder::reader::Reader] trait
+    /// until the end of stream. The `factory` is called to create a new message factory for every message.
+    ///
+    /// The iterator yields decoded messages together with their byte offsets in the stream.
+    /// A clean end of stream (no bytes left at a message boundary) ends the iteration.
+    /// Truncated trailing data is reported as [`Error::UnexpectedEof`].
+    pub fn iter_reader<R, F, M>(&mut self, rdr: R, factory: F) -> MessageIter<'_, R, F>
+    where
+        R: Reader,
+        F: FnMut() -> M,
+        M: MessageFactory,
+    {
+        MessageIter::new(self, rdr, factory)
+    }
+
+    /// Creates an iterator that decodes messages from object that implements [`std::io::Read`][std::io::Read] trait
+    /// until the end of stream. See [`Decoder::iter_reader`] for details.
+    pub fn iter_stream<'a, F, M>(
+        &'a mut self,
+        rdr: &'a mut dyn Read,
+        factory: F,
+    ) -> MessageIter<'a, StreamReader<'a>, F>
+    where
+        F: FnMut() -> M,
+        M: MessageFactory,
+    {
+        MessageIter::new(self, StreamReader::new(rdr), factory)
     }
 }
 
 /// Processing context of the decoder. It represents context state during one message decoding.
 /// Created when it starts decoding a new message and destroyed after decoding of a message.
 pub(crate) struct DecoderContext<'a, R, M> {
-    pub(crate) definitions: &'a mut Definitions,
+    pub(crate) definitions: &'a Definitions,
     pub(crate) context: &'a mut Context,
     pub(crate) rdr: &'a mut R,
     pub(crate) msg: &'a mut M,
+    pub(crate) limits: DecoderLimits,
+
+    // The current nesting depth of groups, sequences and template references.
+    depth: usize,
 
     // The current template id.
-    // It is updated when a template identifier is encountered in the stream. A static template reference can also change
-    // the current template as described in the Template Reference Instruction section.
+    // It is updated when a template identifier is encountered in the stream.
+    // Dictionaries and application types are resolved when templates are compiled into programs.
     pub(crate) template_id: Stacked<u32>,
 
-    // The dictionary set and initial value are described in the Operators section.
-    pub(crate) dictionary: Stacked<Dictionary>,
-
-    // The current application type is initially the special type `any`. The current application type changes when the processor
-    // encounters an element containing a `typeRef` element. The new type is applicable to the instructions contained within
-    // the element. The `typeRef` can appear in the <template>, <group> and <sequence> elements.
-    pub(crate) type_ref: Stacked<TypeRef>,
-
     // The presence map of the current segment.
     pub(crate) presence_map: Stacked<PresenceMap>,
 }
 
-impl<'a, R: Reader, M: MessageFactory> DecoderContext<'a, R, M> {
+impl<'a, 'b, R: ReadRaw<'b>, M: MessageFactoryRef<'b>> DecoderContext<'a, R, M> {
     pub(crate) fn new(d: &'a mut Decoder, r: &'a mut R, m: &'a mut M) -> Self {
         Self {
-            definitions: &mut d.definitions,
+            definitions: &d.definitions,
             context: &mut d.context,
             rdr: r,
             msg: m,
+            limits: d.limits,
+            depth: 0,
             template_id: Stacked::new_empty(),
-            dictionary: Stacked::new(Dictionary::Global),
-            type_ref: Stacked::new(TypeRef::Any),
             presence_map: Stacked::new_empty(),
         }
     }
 
     // Read template id from the stream.
     fn read_template_id(&mut self) -> Result<u32> {
-        let instruction = self.definitions.template_id_instruction.clone();
-        match instruction.extract(self)? {
-            Some(Value::UInt32(id)) => Ok(id),
+        let instruction = &self.definitions.template_id_instruction;
+        match instruction.extract(self, FieldSlots::fixed(instruction))? {
+            Some(ValueRef::UInt32(id)) => Ok(id),
             Some(_) => Err(Error::Runtime(
                 "Wrong template id type in context storage".to_string(),
             )),
@@ -176,255 +497,278 @@ impl<'a, R: Reader, M: MessageFactory> DecoderContext<'a, R, M> {
     }
 
     // Decode template id from the stream and change the current processing context accordingly.
-    fn decode_template_id(&mut self) -> Result<()> {
+    pub(crate) fn decode_template_id(&mut self) -> Result<()> {
         let template_id = self.read_template_id()?;
         self.template_id.push(template_id);
         Ok(())
     }
 
     // Stop processing the current template id, restore the previous value in the processing context.
-    fn drop_template_id(&mut self) {
+    pub(crate) fn drop_template_id(&mut self) {
         self.template_id.pop();
     }
 
     // Decode presence map from the stream and change the current processing context accordingly.
-    fn decode_presence_map(&mut self) -> Result<()> {
-        let (bitmap, size) = self.rdr.read_presence_map()?;
-        let presence_map = PresenceMap::new(bitmap, size);
+    pub(crate) fn decode_presence_map(&mut self) -> Result<()> {
+        let presence_map = match PresenceMap::read(self.rdr) {
+            Ok(r) => r,
+            // Only the presence map of a message may start at the end of the stream.
+            Err(Error::Eof) if self.presence_map.peek().is_some() => {
+                return Err(Error::UnexpectedEof);
+            }
+            Err(e) => return Err(e),
+        };
         self.presence_map.push(presence_map);
         Ok(())
     }
 
     // Restore the previous value for presence map in the processing context.
-    fn drop_presence_map(&mut self) {
-        _ = self.presence_map.pop();
+    // Bits left in the presence map of a finished segment must not be set.
+    pub(crate) fn drop_presence_map(&mut self) -> Result<()> {
+        if let Some(pmap) = self.presence_map.pop()
+            && pmap.has_unused_bits_set()
+        {
+            self.rdr.report(Error::Spec(
+                ErrorCode::R8,
+                "presence map contains more bits than required".to_string(),
+            ))?;
+        }
+        Ok(())
     }
 
     // Decode a template from the stream.
     pub(crate) fn decode_template(&mut self) -> Result<()> {
         self.decode_presence_map()?;
         self.decode_template_id()?;
-        let template = self
-            .definitions
+        let template = self.current_template()?;
+        self.decode_message(template)
+            .map_err(|e| e.in_template(template.id, &template.name))
+    }
+
+    // Returns the template of the current template id.
+    pub(crate) fn current_template(&self) -> Result<&'a Template> {
+        let id = *self.template_id.must_peek();
+        self.definitions
             .templates_by_id
-            .get(self.template_id.peek().unwrap())
+            .get(&id)
+            .map(AsRef::as_ref)
+            .ok_or_else(|| Error::Spec(ErrorCode::D9, format!("Unknown template id: {id}")))
+    }
+
+    // Returns the program of the template compiled for the scope.
+    pub(crate) fn program(&self, template: &Template, scope: usize) -> Result<&'a [Op]> {
+        self.definitions
+            .programs
+            .get(scope, template.id)
             .ok_or_else(|| {
-                Error::Dynamic(format!(
-                    "Unknown template id: {}",
-                    self.template_id.peek().unwrap()
+                Error::Runtime(format!(
+                    "template {} has unresolved dictionary entries",
+                    template.name
                 ))
-            })? // [ErrD09]
-            .clone(); //
-        self.msg.start_template(template.id, &template.name);
-
-        // Update some context variables
-        let has_dictionary = self.switch_dictionary(&template.dictionary);
-        let has_type_ref = self.switch_type_ref(&template.type_ref);
-
-        self.decode_instructions(&template.instructions)?;
-
-        if has_dictionary {
-            self.restore_dictionary();
-        }
-        if has_type_ref {
-            self.restore_type_ref();
-        }
+            })
+    }
 
+    // Decode instructions of the message template.
+    pub(crate) fn decode_message(&mut self, template: &Template) -> Result<()> {
+        let program = self.program(template, MESSAGE_SCOPE)?;
+        self.msg.start_template(template.id, &template.name);
+        self.decode_program(program)?;
         self.msg.stop_template();
         self.drop_template_id();
-        self.drop_presence_map();
+        self.drop_presence_map()?;
         Ok(())
     }
 
-    fn decode_instructions(&mut self, instructions: &[Instruction]) -> Result<()> {
-        for instruction in instructions {
-            match instruction.value_type {
-                ValueType::Sequence => {
-                    self.decode_sequence(instruction)?;
-                }
-                ValueType::Group => {
-                    self.decode_group(instruction)?;
+    // Decode instructions of the template compiled into the program.
+    fn decode_program(&mut self, program: &[Op]) -> Result<()> {
+        let mut frames = Vec::new();
+        self.run_program(program, &mut frames)
+            .map_err(|e| locate_error(e, program, &frames, |e, name| self.located(e, name)))
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn run_program(&mut self, program: &[Op], frames: &mut Vec<Frame>) -> Result<()> {
+        let mut pc = 0;
+        while let Some(op) = program.get(pc) {
+            pc += 1;
+            match op {
+                Op::Field(field) => {
+                    let value = self.decode_field(field)?;
+                    let instruction = &*field.instruction;
+                    self.msg.set_value(instruction.id, &instruction.name, value);
                 }
-                ValueType::TemplateReference => {
-                    self.decode_template_ref(instruction)?;
+                // A group field instruction associates a name and presence attribute with a group of instructions.
+                // If any instruction of the group needs to allocate a bit in a presence map, the group is represented
+                // as a segment in the transfer encoding.
+                Op::Group { group, end } => {
+                    frames.push(Frame::Group { start: pc - 1 });
+                    self.enter_nested()?;
+                    if group.is_optional() && !self.pmap_next_bit_set() {
+                        self.leave_nested();
+                        frames.pop();
+                        pc = end + 1;
+                        continue;
+                    }
+                    self.msg.start_group(&group.name);
+                    if group.has_pmap {
+                        self.decode_presence_map()?;
+                    }
                 }
-                _ => {
-                    self.decode_field(instruction)?;
+                Op::GroupEnd { has_pmap } => {
+                    if *has_pmap {
+                        self.drop_presence_map()?;
+                    }
+                    self.msg.stop_group();
+                    self.leave_nested();
+                    frames.pop();
                 }
-            }
-        }
-        Ok(())
-    }
-
-    fn decode_segment(&mut self, instructions: &[Instruction]) -> Result<()> {
-        self.decode_presence_map()?;
-        self.decode_instructions(instructions)?;
-        self.drop_presence_map();
-        Ok(())
-    }
-
-    fn decode_field(&mut self, instruction: &Instruction) -> Result<()> {
-        let value = self.extract_field(instruction)?;
-        self.msg.set_value(instruction.id, &instruction.name, value);
-        Ok(())
-    }
-
-    // A sequence field instruction specifies that the field in the application type is of sequence type and that
-    // the contained group of instructions should be used repeatedly to encode each element.
-    fn decode_sequence(&mut self, instruction: &Instruction) -> Result<()> {
-        let has_dictionary = self.switch_dictionary(&instruction.dictionary);
-        let has_type_ref = self.switch_type_ref(&instruction.type_ref);
-
-        // A sequence has an associated length field containing an unsigned integer indicating the number of encoded
-        // elements. When a length field is present in the stream, it must appear directly before the encoded elements.
-        // The length field has a name, is of type uInt32 and can have a field operator.
-        let length_instruction = instruction.instructions.first().unwrap();
-        match self.extract_field(length_instruction)? {
-            None => {}
-            Some(Value::UInt32(length)) => {
-                self.msg
-                    .start_sequence(instruction.id, &instruction.name, length);
-                for idx in 0..length {
-                    self.msg.start_sequence_item(idx);
+                // A sequence field instruction specifies that the field in the application type is of sequence type and that
+                // the contained group of instructions should be used repeatedly to encode each element.
+                Op::Sequence {
+                    sequence,
+                    length,
+                    end,
+                } => {
+                    let start = pc - 1;
+                    frames.push(Frame::Sequence {
+                        start,
+                        item: None,
+                        length: 0,
+                    });
+                    self.enter_nested()?;
+                    let Some(length) = self.decode_sequence_length(length)? else {
+                        self.leave_nested();
+                        frames.pop();
+                        pc = end + 1;
+                        continue;
+                    };
+                    self.msg.start_sequence(sequence.id, &sequence.name, length);
+                    if length == 0 {
+                        self.msg.stop_sequence();
+                        self.leave_nested();
+                        frames.pop();
+                        pc = end + 1;
+                        continue;
+                    }
+                    *frames.last_mut().unwrap() = Frame::Sequence {
+                        start,
+                        item: Some(0),
+                        length: length as usize,
+                    };
+                    self.msg.start_sequence_item(0);
                     // If any instruction of the sequence needs to allocate a bit in a presence map, each element is represented
                     // as a segment in the transfer encoding.
-                    if instruction.has_pmap.get() {
-                        self.decode_segment(&instruction.instructions[1..])?;
-                    } else {
-                        self.decode_instructions(&instruction.instructions[1..])?;
+                    if sequence.has_pmap {
+                        self.decode_presence_map()?;
+                    }
+                }
+                Op::SequenceEnd { start, has_pmap } => {
+                    if *has_pmap {
+                        self.drop_presence_map()?;
                     }
                     self.msg.stop_sequence_item();
+                    let Some(Frame::Sequence {
+                        item: Some(item),
+                        length,
+                        ..
+                    }) = frames.last_mut()
+                    else {
+                        unreachable!();
+                    };
+                    *item += 1;
+                    if *item < *length {
+                        self.msg.start_sequence_item(*item as u32);
+                        if *has_pmap {
+                            self.decode_presence_map()?;
+                        }
+                        pc = start + 1;
+                    } else {
+                        self.msg.stop_sequence();
+                        self.leave_nested();
+                        frames.pop();
+                    }
+                }
+                Op::TemplateRef { template, .. } => {
+                    self.enter_nested()?;
+                    self.msg.start_template_ref(&template.name, false);
+                }
+                Op::TemplateRefEnd => {
+                    self.msg.stop_template_ref();
+                    self.leave_nested();
+                }
+                Op::DynamicTemplateRef { scope } => {
+                    self.enter_nested()?;
+                    self.decode_dynamic_template_ref(*scope)?;
+                    self.leave_nested();
                 }
-                self.msg.stop_sequence();
             }
-            _ => return Err(Error::Dynamic("Length field must be UInt32".to_string())), // [ErrD10]
-        }
-
-        if has_dictionary {
-            self.restore_dictionary();
-        }
-        if has_type_ref {
-            self.restore_type_ref();
-        }
-        Ok(())
-    }
-
-    // A group field instruction associates a name and presence attribute with a group of instructions.
-    // If any instruction of the group needs to allocate a bit in a presence map, the group is represented
-    // as a segment in the transfer encoding.
-    fn decode_group(&mut self, instruction: &Instruction) -> Result<()> {
-        if instruction.is_optional() && !self.pmap_next_bit_set() {
-            return Ok(());
-        }
-
-        let has_dictionary = self.switch_dictionary(&instruction.dictionary);
-        let has_type_ref = self.switch_type_ref(&instruction.type_ref);
-
-        self.msg.start_group(&instruction.name);
-        // If any instruction of the group needs to allocate a bit in a presence map, each element is represented
-        // as a segment in the transfer encoding.
-        if instruction.has_pmap.get() {
-            self.decode_segment(&instruction.instructions)?;
-        } else {
-            self.decode_instructions(&instruction.instructions)?;
-        }
-        self.msg.stop_group();
-
-        if has_dictionary {
-            self.restore_dictionary();
-        }
-        if has_type_ref {
-            self.restore_type_ref();
         }
         Ok(())
     }
 
-    // The template reference instruction specifies that a part of the template is specified by another template.
-    // A template reference can be either static or dynamic. A reference is static when a name is specified in the
-    // instruction. Otherwise, it is dynamic.
-    fn decode_template_ref(&mut self, instruction: &Instruction) -> Result<()> {
-        let is_dynamic = instruction.name.is_empty();
-
-        let template: Rc<Template> = if is_dynamic {
-            self.decode_presence_map()?;
-            self.decode_template_id()?;
-            self.definitions
-                .templates_by_id
-                .get(self.template_id.peek().unwrap())
-                .ok_or_else(|| {
-                    Error::Dynamic(format!(
-                        "Unknown template id: {}",
-                        self.template_id.peek().unwrap()
-                    ))
-                })? // [ErrD09]
-                .clone()
-        } else {
-            self.definitions
-                .templates_by_name
-                .get(&instruction.name)
-                .ok_or_else(|| Error::Dynamic(format!("Unknown template: {}", instruction.name)))? // [ErrD09]
-                .clone()
-        };
-        self.msg.start_template_ref(&template.name, is_dynamic);
-
-        // Update some context variables
-        let has_dictionary = self.switch_dictionary(&template.dictionary);
-        let has_type_ref = self.switch_type_ref(&template.type_ref);
-
-        self.decode_instructions(&template.instructions)?;
-
-        if has_dictionary {
-            self.restore_dictionary();
-        }
-        if has_type_ref {
-            self.restore_type_ref();
-        }
-
+    // A dynamic template reference is followed by a segment with the identifier of the referenced template.
+    // Instructions of the referenced template are located within that template.
+    fn decode_dynamic_template_ref(&mut self, scope: usize) -> Result<()> {
+        self.decode_presence_map()?;
+        self.decode_template_id()?;
+        let template = self.current_template()?;
+        let program = self.program(template, scope)?;
+        self.msg.start_template_ref(&template.name, true);
+        self.decode_program(program)
+            .map_err(|e| self.located(e, &template.name))?;
         self.msg.stop_template_ref();
-        if is_dynamic {
-            self.drop_template_id();
-            self.drop_presence_map();
-        }
-        Ok(())
+        self.drop_template_id();
+        self.drop_presence_map()
     }
 
-    fn extract_field(&mut self, instruction: &Instruction) -> Result<Option<Value>> {
-        let has_dict = self.switch_dictionary(&instruction.dictionary);
-        let value = instruction.extract(self)?;
-        if has_dict {
-            self.restore_dictionary();
-        }
-        Ok(value)
+    // Decode the value of the field, located by the field name on failure.
+    pub(crate) fn decode_field(&mut self, field: &Field) -> Result<Option<ValueRef<'b>>> {
+        let instruction = &*field.instruction;
+        instruction
+            .extract(self, field.slots)
+            .and_then(|v| instruction.typed_value(v))
+            .map_err(|e| self.located(e, &instruction.name))
     }
 
-    #[inline]
-    fn switch_dictionary(&mut self, dictionary: &Dictionary) -> bool {
-        if *dictionary == Dictionary::Inherit {
-            false
-        } else {
-            self.dictionary.push(dictionary.clone());
-            true
+    // Decode the length of a sequence. `None` if an optional sequence is absent.
+    // A sequence has an associated length field containing an unsigned integer indicating the number of encoded
+    // elements. When a length field is present in the stream, it must appear directly before the encoded elements.
+    // The length field has a name, is of type uInt32 and can have a field operator.
+    pub(crate) fn decode_sequence_length(&mut self, length: &Field) -> Result<Option<u32>> {
+        let value = length
+            .instruction
+            .extract(self, length.slots)
+            .map_err(|e| self.located(e, &length.instruction.name))?;
+        match value {
+            None => Ok(None),
+            Some(ValueRef::UInt32(length)) => {
+                if length > self.limits.max_sequence_length {
+                    return Err(Error::SequenceLimitExceeded(
+                        length,
+                        self.limits.max_sequence_length,
+                    ));
+                }
+                Ok(Some(length))
+            }
+            // It is a dynamic error [ERR D10] if the length is not of the uInt32 type.
+            _ => Err(Error::Spec(
+                ErrorCode::D10,
+                "Length field must be UInt32".to_string(),
+            )),
         }
     }
 
-    #[inline]
-    fn restore_dictionary(&mut self) {
-        _ = self.dictionary.pop();
-    }
-
-    #[inline]
-    fn switch_type_ref(&mut self, type_ref: &TypeRef) -> bool {
-        if *type_ref == TypeRef::Any {
-            false
-        } else {
-            self.type_ref.push(type_ref.clone());
-            true
+    // Go one level deeper into groups, sequences or template references.
+    pub(crate) fn enter_nested(&mut self) -> Result<()> {
+        if self.depth >= self.limits.max_nesting_depth {
+            return Err(Error::NestingLimitExceeded(self.limits.max_nesting_depth));
         }
+        self.depth += 1;
+        Ok(())
     }
 
-    #[inline]
-    fn restore_type_ref(&mut self) {
-        _ = self.type_ref.pop();
+    pub(crate) fn leave_nested(&mut self) {
+        self.depth -= 1;
     }
 
     #[inline]
@@ -432,41 +776,48 @@ impl<'a, R: Reader, M: MessageFactory> DecoderContext<'a, R, M> {
         self.presence_map.must_peek_mut().next_bit_set()
     }
 
+    // Adds the element to the location of the error.
+    pub(crate) fn located(&self, err: Error, name: &str) -> Error {
+        err.within(name, self.presence_map.peek().map(PresenceMap::bit_index))
+    }
+
     #[inline]
-    pub(crate) fn ctx_set(&mut self, i: &Instruction, v: Option<Value>) {
-        self.context.set(self.make_dict_type(), i.key.clone(), v);
+    pub(crate) fn ctx_set(
+        &mut self,
+        i: &Instruction,
+        slot: Option<usize>,
+        v: Option<Value>,
+    ) -> Result<()> {
+        self.context.set(ctx_slot(i, slot)?, v);
+        Ok(())
     }
 
+    // Returns the previous value of the instruction without copying it:
+    // `None` if it is undefined, `Some(None)` if it is empty.
     #[inline]
-    pub(crate) fn ctx_get(&mut self, i: &Instruction) -> Result<Option<Option<Value>>> {
-        let v = self.context.get(self.make_dict_type(), &i.key);
-        if let Some(Some(ref v)) = v
-            && !i.value_type.matches_type(v)
+    pub(crate) fn ctx_get(
+        &self,
+        i: &Instruction,
+        slot: Option<usize>,
+    ) -> Result<Option<Option<&Value>>> {
+        let v = self.context.get(ctx_slot(i, slot)?).map(Option::as_ref);
+        if let Some(Some(v)) = v
+            && !i.matches_dictionary_value(v)
         {
             // It is a dynamic error [ERR D4] if the field of an operator accessing an entry does not have
             // the same type as the value of the entry.
-            return Err(Error::Runtime(format!(
-                "field {} has wrong value type in context",
-                i.name
-            ))); // [ERR D4]
+            return Err(Error::Spec(
+                ErrorCode::D4,
+                format!("field {} has wrong value type in context", i.name),
+            ));
         }
         Ok(v)
     }
+}
 
-    fn make_dict_type(&self) -> DictionaryType {
-        let dictionary = self.dictionary.must_peek();
-        match dictionary {
-            Dictionary::Inherit => unreachable!(),
-            Dictionary::Global => DictionaryType::Global,
-            Dictionary::Template => DictionaryType::Template(*self.template_id.must_peek()),
-            Dictionary::Type => {
-                let name = match self.type_ref.must_peek() {
-                    TypeRef::Any => Rc::from("__any__"),
-                    TypeRef::ApplicationType(name) => name.clone(),
-                };
-                DictionaryType::Type(name)
-            }
-            Dictionary::UserDefined(name) => DictionaryType::UserDefined(name.clone()),
-        }
-    }
+// Returns the slot of the instruction's previous value in the context storage,
+// resolved for the processing context when the program was compiled.
+#[inline]
+fn ctx_slot(i: &Instruction, slot: Option<usize>) -> Result<usize> {
+    slot.ok_or_else(|| Error::Runtime(format!("field {} has no dictionary entry", i.name)))
 }
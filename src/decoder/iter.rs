@@ -0,0 +1,77 @@
+use crate::base::message::MessageFactory;
+use crate::decoder::decoder::Decoder;
+use crate::decoder::reader::{CountingReader, Reader};
+use crate::{Error, Result};
+
+/// Iterator over messages decoded from a [`Reader`].
+///
+/// Yields decoded messages together with their byte offsets in the input until a clean end of stream is reached.
+/// If the stream ends in the middle of a message, [`Error::UnexpectedEof`] is yielded. After any error
+/// the iterator is exhausted, because the stream position is no longer at a message boundary.
+///
+/// Created by [`Decoder::iter_reader`] and [`Decoder::iter_stream`].
+pub struct MessageIter<'a, R, F> {
+    decoder: &'a mut Decoder,
+    rdr: CountingReader<R>,
+    factory: F,
+    done: bool,
+}
+
+impl<'a, R, F, M> MessageIter<'a, R, F>
+where
+    R: Reader,
+    F: FnMut() -> M,
+    M: MessageFactory,
+{
+    pub(crate) fn new(decoder: &'a mut Decoder, rdr: R, factory: F) -> Self {
+        Self {
+            decoder,
+            rdr: CountingReader::new(rdr),
+            factory,
+            done: false,
+        }
+    }
+
+    /// Returns number of bytes consumed from the reader so far.
+    /// After a message is yielded it is the offset of the next message.
+    pub fn offset(&self) -> u64 {
+        self.rdr.position()
+    }
+}
+
+impl<R, F, M> Iterator for MessageIter<'_, R, F>
+where
+    R: Reader,
+    F: FnMut() -> M,
+    M: MessageFactory,
+{
+    /// Decoded message and the byte offset of its first byte in the input.
+    type Item = Result<(u64, M)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let offset = self.rdr.position();
+        let mut msg = (self.factory)();
+        match self.decoder.decode_reader(&mut self.rdr, &mut msg) {
+            Ok(()) => Some(Ok((offset, msg))),
+            Err(Error::Eof) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R, F, M> std::iter::FusedIterator for MessageIter<'_, R, F>
+where
+    R: Reader,
+    F: FnMut() -> M,
+    M: MessageFactory,
+{
+}
@@ -0,0 +1,76 @@
+//! # Decoder limits
+//!
+//! The transfer encoding lets the sender declare lengths of strings, byte vectors and sequences and nest
+//! template references arbitrarily deep. Decoding corrupt or hostile input must not exhaust memory or the stack,
+//! so the decoder rejects messages that exceed the configured limits.
+//!
+use crate::base::value::ValueType;
+use crate::{Error, Result};
+
+/// Safety limits enforced by the [`Decoder`][crate::Decoder] while decoding a message.
+///
+/// Default limits are generous enough for typical market data feeds:
+///
+/// Limit                    | Default
+/// -------------------------|---
+/// `max_string_length`      | 1 MiB
+/// `max_byte_vector_length` | 1 MiB
+/// `max_sequence_length`    | 65536
+/// `max_nesting_depth`      | 64
+/// `max_message_bytes`      | 16 MiB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderLimits {
+    /// Maximum length of ASCII and unicode strings in bytes.
+    pub max_string_length: usize,
+
+    /// Maximum length of byte vectors.
+    pub max_byte_vector_length: usize,
+
+    /// Maximum number of elements in a sequence.
+    pub max_sequence_length: u32,
+
+    /// Maximum nesting depth of groups, sequences and template references.
+    pub max_nesting_depth: usize,
+
+    /// Maximum size of one encoded message in bytes.
+    pub max_message_bytes: u64,
+}
+
+impl Default for DecoderLimits {
+    fn default() -> Self {
+        Self {
+            max_string_length: 1 << 20,
+            max_byte_vector_length: 1 << 20,
+            max_sequence_length: 1 << 16,
+            max_nesting_depth: 64,
+            max_message_bytes: 1 << 24,
+        }
+    }
+}
+
+impl DecoderLimits {
+    /// Returns limits that never reject a message. Use for trusted input only.
+    #[must_use]
+    pub fn unlimited() -> Self {
+        Self {
+            max_string_length: usize::MAX,
+            max_byte_vector_length: usize::MAX,
+            max_sequence_length: u32::MAX,
+            max_nesting_depth: usize::MAX,
+            max_message_bytes: u64::MAX,
+        }
+    }
+
+    // Checks the length of a string or byte vector of given type.
+    pub(crate) fn check_length(&self, value_type: &ValueType, length: u64) -> Result<()> {
+        let limit = if *value_type == ValueType::Bytes {
+            self.max_byte_vector_length
+        } else {
+            self.max_string_length
+        };
+        if length > limit as u64 {
+            return Err(Error::LengthLimitExceeded(length, limit));
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
 #[allow(clippy::module_inception)]
 pub(crate) mod decoder;
+pub(crate) mod iter;
+pub(crate) mod limits;
 pub(crate) mod reader;
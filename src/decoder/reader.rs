@@ -8,7 +8,12 @@ use std::io::{ErrorKind, Read};
 
 use bytes::Buf;
 
-use crate::{Error, Result};
+use crate::base::pmap::PresenceMap;
+use crate::{Error, ErrorCode, Result};
+
+// Upper bound of memory preallocated for byte vectors before their bytes are actually read.
+// The declared length comes from the stream and can't be trusted.
+const MAX_PREALLOC: usize = 4096;
 
 /// A trait that provides methods for reading basic primitive types.
 pub trait Reader {
@@ -18,54 +23,56 @@ pub trait Reader {
     /// Return [`Error::UnexpectedEof`][crate::Error::UnexpectedEof] instead.
     fn read_u8(&mut self) -> Result<u8>;
 
+    /// Called when a reportable error (see [`ErrorCode::is_reportable`][crate::ErrorCode::is_reportable]) is detected
+    /// in the stream, e.g. an overlong integer encoding. The default implementation ignores the error.
+    /// # Errors
+    /// Return the error to fail decoding.
+    fn report(&mut self, err: Error) -> Result<()> {
+        _ = err;
+        Ok(())
+    }
+
+    /// Reads exactly `buf.len()` bytes. The default implementation reads them one by one.
+    /// # Errors
+    /// Returns [`Error::UnexpectedEof`][crate::Error::UnexpectedEof] if the end of the stream is reached
+    /// before `buf` is filled.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        for b in buf {
+            *b = self.read_u8()?;
+        }
+        Ok(())
+    }
+
     /// Read the presence map. Return the bitmap and the number of bits in the bitmap.
+    /// The bitmap holds up to 63 bits; the decoder itself reads presence maps of any length.
     /// # Errors
     /// In case of error, return [`Error::Eof`][crate::Error::Eof] if the end of the stream is reached at the first byte
     /// of the presence map. Otherwise, return any other error, e.g.: [`Error::UnexpectedEof`][crate::Error::UnexpectedEof].
+    /// Returns [`Error::Runtime`][crate::Error::Runtime] if the presence map is longer than 63 bits.
     fn read_presence_map(&mut self) -> Result<(u64, u8)> {
-        let mut bitmap: u64 = 0;
-        let mut size: u8 = 0;
-        let mut byte = match self.read_u8() {
-            Ok(b) => b,
-            Err(Error::UnexpectedEof) => return Err(Error::Eof),
-            Err(e) => return Err(e),
-        };
-        loop {
-            bitmap <<= 7;
-            bitmap |= u64::from(byte & 0x7f);
-            size += 7;
-
-            if byte & 0x80 == 0x80 {
-                return Ok((bitmap, size));
-            }
-            byte = self.read_u8()?;
-        }
+        PresenceMap::read(self)?.to_short().ok_or_else(|| {
+            Error::Runtime("read_presence_map: presence map is longer than 63 bits".to_string())
+        })
     }
 
     /// Returns decoded non-nullable 64 bit unsigned integer.
     /// # Errors
     /// Returns error if can not read bytes len or bytes len mismatch.
     fn read_uint(&mut self) -> Result<u64> {
-        let mut value: u64 = 0;
-        loop {
-            let byte = self.read_u8()?;
-            value <<= 7;
-            value |= u64::from(byte & 0x7f);
-            if byte & 0x80 == 0x80 {
-                return Ok(value);
-            }
-        }
+        let value = read_uint_bits(self, u64::BITS)?;
+        u64::try_from(value).map_err(|_| Error::IntegerOverflow)
     }
 
     /// Returns decoded nullable 64 bit unsigned integer.
     /// # Errors
     /// Returns error if can not read bytes len or bytes len mismatch.
     fn read_uint_nullable(&mut self) -> Result<Option<u64>> {
-        let value = self.read_uint()?;
-        if value == 0 {
-            Ok(None)
-        } else {
-            Ok(Some(value - 1))
+        // Non-null values are incremented by one, so `u64::MAX` is transferred as 2^64.
+        match read_uint_bits(self, u64::BITS + 1)? {
+            0 => Ok(None),
+            value => u64::try_from(value - 1)
+                .map(Some)
+                .map_err(|_| Error::IntegerOverflow),
         }
     }
 
@@ -73,82 +80,37 @@ pub trait Reader {
     /// # Errors
     /// Returns error if can not read bytes len or bytes len mismatch.
     fn read_int(&mut self) -> Result<i64> {
-        let mut value: i64 = 0;
-
-        let mut byte = self.read_u8()?;
-        if byte & 0x40 != 0 {
-            // Negative Integer
-            value = -1;
-        }
-        loop {
-            value <<= 7;
-            value |= i64::from(byte & 0x7f);
-
-            if byte & 0x80 == 0x80 {
-                return Ok(value);
-            }
-            byte = self.read_u8()?;
-        }
+        let value = read_int_bits(self, i64::BITS)?;
+        i64::try_from(value).map_err(|_| Error::IntegerOverflow)
     }
 
     /// Returns decoded nullable 64 bit signed integer.
     /// # Errors
     /// Returns error if can not read bytes len or bytes len mismatch.
     fn read_int_nullable(&mut self) -> Result<Option<i64>> {
-        let value = self.read_int()?;
-        match value {
-            0 => Ok(None),
-            value if value < 0 => Ok(Some(value)),
-            value => Ok(Some(value - 1)),
-        }
+        // Non-negative values are incremented by one, so `i64::MAX` is transferred as 2^63.
+        let value = match read_int_bits(self, i64::BITS + 1)? {
+            0 => return Ok(None),
+            value if value < 0 => value,
+            value => value - 1,
+        };
+        i64::try_from(value)
+            .map(Some)
+            .map_err(|_| Error::IntegerOverflow)
     }
 
     /// Returns decoded non-nullable ASCII encoded string.
     /// # Errors
     /// Returns error if can not read bytes len or bytes len mismatch.
     fn read_ascii_string(&mut self) -> Result<String> {
-        let mut byte = self.read_u8()?;
-        if byte == 0x80 {
-            return Ok(String::new());
-        }
-
-        let mut buf: Vec<u8> = Vec::new();
-        loop {
-            buf.push(byte & 0x7f);
-            if byte & 0x80 == 0x80 {
-                break;
-            }
-            byte = self.read_u8()?;
-        }
-        // SAFETY: `buf` contains ASCII 7-bit characters
-        unsafe { Ok(String::from_utf8_unchecked(buf)) }
+        read_ascii_string(self, usize::MAX)
     }
 
     /// Returns decoded nullable ASCII encoded string.
     /// # Errors
     /// Returns error if can not read bytes len or bytes len mismatch.
     fn read_ascii_string_nullable(&mut self) -> Result<Option<String>> {
-        let mut byte = self.read_u8()?;
-
-        if byte == 0x80 {
-            return Ok(None);
-        } else if byte == 0x00 {
-            byte = self.read_u8()?;
-            if byte == 0x80 {
-                return Ok(Some(String::new()));
-            }
-        }
-
-        let mut buf: Vec<u8> = Vec::new();
-        loop {
-            buf.push(byte & 0x7f);
-            if byte & 0x80 == 0x80 {
-                break;
-            }
-            byte = self.read_u8()?;
-        }
-        // SAFETY: `buf` contains ASCII 7-bit characters
-        unsafe { Ok(Some(String::from_utf8_unchecked(buf))) }
+        read_ascii_string_nullable(self, usize::MAX)
     }
 
     /// Returns decoded non-nullable unicode encoded string.
@@ -173,11 +135,7 @@ pub trait Reader {
     /// Returns error if can not read bytes len or bytes len mismatch.
     fn read_bytes(&mut self) -> Result<Vec<u8>> {
         let length = self.read_uint()?;
-        let mut buf = Vec::with_capacity(length as usize);
-        for _ in 0..length {
-            buf.push(self.read_u8()?);
-        }
-        Ok(buf)
+        read_vec(self, length as usize)
     }
 
     /// Returns decoded nullable bytes.
@@ -186,14 +144,181 @@ pub trait Reader {
     fn read_bytes_nullable(&mut self) -> Result<Option<Vec<u8>>> {
         match self.read_uint_nullable()? {
             None => Ok(None),
-            Some(length) => {
-                let mut buf = Vec::with_capacity(length as usize);
-                for _ in 0..length {
-                    buf.push(self.read_u8()?);
-                }
-                Ok(Some(buf))
-            }
+            Some(length) => Ok(Some(read_vec(self, length as usize)?)),
+        }
+    }
+}
+
+// Reads an unsigned integer of at most `bits` bits.
+fn read_uint_bits<R: Reader + ?Sized>(rdr: &mut R, bits: u32) -> Result<u128> {
+    let mut value: u128 = 0;
+    let mut byte = rdr.read_u8()?;
+    if byte == 0x00 {
+        rdr.report(Error::Spec(
+            ErrorCode::R6,
+            "unsigned integer is overlong".to_string(),
+        ))?;
+    }
+    loop {
+        if value >> (bits - 7) != 0 {
+            return Err(Error::IntegerOverflow);
+        }
+        value <<= 7;
+        value |= u128::from(byte & 0x7f);
+        if byte & 0x80 == 0x80 {
+            return Ok(value);
+        }
+        byte = rdr.read_u8()?;
+    }
+}
+
+// Reads a signed integer of at most `bits` bits, the sign bit included.
+fn read_int_bits<R: Reader + ?Sized>(rdr: &mut R, bits: u32) -> Result<i128> {
+    let mut value: i128 = 0;
+
+    let mut byte = rdr.read_u8()?;
+    if byte & 0x40 != 0 {
+        // Negative Integer
+        value = -1;
+    }
+    // The first byte is redundant if it only extends the sign bit of the next one.
+    let mut sign_extension = match byte {
+        0x00 => Some(0x00),
+        0x7f => Some(0x40),
+        _ => None,
+    };
+    let bound = 1 << (bits - 8);
+    loop {
+        if !(-bound..bound).contains(&value) {
+            return Err(Error::IntegerOverflow);
+        }
+        value <<= 7;
+        value |= i128::from(byte & 0x7f);
+
+        if byte & 0x80 == 0x80 {
+            return Ok(value);
+        }
+        byte = rdr.read_u8()?;
+        if let Some(sign) = sign_extension.take()
+            && byte & 0x40 == sign
+        {
+            rdr.report(Error::Spec(
+                ErrorCode::R6,
+                "signed integer is overlong".to_string(),
+            ))?;
+        }
+    }
+}
+
+// Reads ASCII string of at most `max_length` characters, so no more is buffered for a longer string.
+pub(crate) fn read_ascii_string<R: Reader + ?Sized>(
+    rdr: &mut R,
+    max_length: usize,
+) -> Result<String> {
+    let mut byte = rdr.read_u8()?;
+    if byte == 0x80 {
+        return Ok(String::new());
+    }
+
+    let preamble = byte == 0x00;
+    let mut buf: Vec<u8> = Vec::new();
+    loop {
+        if buf.len() == max_length {
+            return Err(Error::LengthLimitExceeded(
+                max_length as u64 + 1,
+                max_length,
+            ));
+        }
+        buf.push(byte & 0x7f);
+        if byte & 0x80 == 0x80 {
+            break;
+        }
+        byte = rdr.read_u8()?;
+        if preamble && buf.len() == 1 && byte != 0x80 {
+            rdr.report(overlong_string())?;
+        }
+    }
+    // SAFETY: `buf` contains ASCII 7-bit characters
+    unsafe { Ok(String::from_utf8_unchecked(buf)) }
+}
+
+// Reads nullable ASCII string of at most `max_length` characters.
+pub(crate) fn read_ascii_string_nullable<R: Reader + ?Sized>(
+    rdr: &mut R,
+    max_length: usize,
+) -> Result<Option<String>> {
+    let mut byte = rdr.read_u8()?;
+
+    let mut preamble = false;
+    if byte == 0x80 {
+        return Ok(None);
+    } else if byte == 0x00 {
+        byte = rdr.read_u8()?;
+        if byte == 0x80 {
+            return Ok(Some(String::new()));
+        }
+        if byte != 0x00 {
+            rdr.report(overlong_string())?;
+        }
+        preamble = byte == 0x00;
+    }
+
+    let mut buf: Vec<u8> = Vec::new();
+    loop {
+        if buf.len() == max_length {
+            return Err(Error::LengthLimitExceeded(
+                max_length as u64 + 1,
+                max_length,
+            ));
         }
+        buf.push(byte & 0x7f);
+        if byte & 0x80 == 0x80 {
+            break;
+        }
+        byte = rdr.read_u8()?;
+        if preamble && buf.len() == 1 && byte != 0x80 {
+            rdr.report(overlong_string())?;
+        }
+    }
+    // SAFETY: `buf` contains ASCII 7-bit characters
+    unsafe { Ok(Some(String::from_utf8_unchecked(buf))) }
+}
+
+// Reads `len` bytes. The buffer grows as the bytes are actually read, because the length comes from the stream.
+fn read_vec<R: Reader + ?Sized>(rdr: &mut R, len: usize) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    while buf.len() < len {
+        let start = buf.len();
+        buf.resize(start + (len - start).min(start.max(MAX_PREALLOC)), 0);
+        rdr.read_exact(&mut buf[start..])?;
+    }
+    Ok(buf)
+}
+
+// A string starting with a zero preamble is overlong unless it is an empty or "\0" string.
+fn overlong_string() -> Error {
+    Error::Spec(ErrorCode::R9, "string is overlong".to_string())
+}
+
+impl<R: Reader + ?Sized> Reader for &mut R {
+    fn read_u8(&mut self) -> Result<u8> {
+        (**self).read_u8()
+    }
+
+    fn report(&mut self, err: Error) -> Result<()> {
+        (**self).report(err)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        (**self).read_exact(buf)
+    }
+
+    fn read_ascii_string(&mut self) -> Result<String> {
+        (**self).read_ascii_string()
+    }
+
+    fn read_ascii_string_nullable(&mut self) -> Result<Option<String>> {
+        (**self).read_ascii_string_nullable()
     }
 }
 
@@ -205,10 +330,269 @@ impl Reader for bytes::Bytes {
         let b = self.get_u8();
         Ok(b)
     }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        if buf.len() > self.len() {
+            return Err(Error::UnexpectedEof);
+        }
+        self.copy_to_slice(buf);
+        Ok(())
+    }
+}
+
+/// Bytes of a string or a byte vector read by [`ReadRaw`].
+pub(crate) enum Raw<'a> {
+    /// Borrowed from the underlying buffer.
+    Borrowed(&'a [u8]),
+    /// Copied from the underlying reader.
+    Owned(Vec<u8>),
+    /// Reference-counted sub-slice of the underlying [`bytes::Bytes`] buffer.
+    #[cfg(feature = "shared-bytes")]
+    Shared(bytes::Bytes),
+}
+
+impl Raw<'_> {
+    pub(crate) fn into_vec(self) -> Vec<u8> {
+        match self {
+            Raw::Borrowed(b) => b.to_vec(),
+            Raw::Owned(b) => b,
+            #[cfg(feature = "shared-bytes")]
+            Raw::Shared(b) => b.into(),
+        }
+    }
+}
+
+impl AsRef<[u8]> for Raw<'_> {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Raw::Borrowed(b) => b,
+            Raw::Owned(b) => b,
+            #[cfg(feature = "shared-bytes")]
+            Raw::Shared(b) => b,
+        }
+    }
+}
+
+/// A reader that can return byte vectors borrowed from the underlying buffer of lifetime `'a`.
+pub(crate) trait ReadRaw<'a>: Reader {
+    /// Reads `len` bytes. Borrows them from the underlying buffer if possible.
+    fn read_raw(&mut self, len: usize) -> Result<Raw<'a>>;
+}
+
+/// Byte vectors are reference-counted sub-slices of the buffer.
+#[cfg(feature = "shared-bytes")]
+impl ReadRaw<'static> for bytes::Bytes {
+    fn read_raw(&mut self, len: usize) -> Result<Raw<'static>> {
+        if len > self.len() {
+            return Err(Error::UnexpectedEof);
+        }
+        let raw = self.slice(..len);
+        self.advance(len);
+        Ok(Raw::Shared(raw))
+    }
+}
+
+/// Wrapper around [`fastlib::Reader`][crate::decoder::reader::Reader] that returns owned copies of byte vectors.
+pub(crate) struct CopyingReader<'r, R: ?Sized>(pub &'r mut R);
+
+impl<R: Reader + ?Sized> Reader for CopyingReader<'_, R> {
+    fn read_u8(&mut self) -> Result<u8> {
+        self.0.read_u8()
+    }
+
+    fn report(&mut self, err: Error) -> Result<()> {
+        self.0.report(err)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.0.read_exact(buf)
+    }
+
+    fn read_presence_map(&mut self) -> Result<(u64, u8)> {
+        self.0.read_presence_map()
+    }
+
+    fn read_uint(&mut self) -> Result<u64> {
+        self.0.read_uint()
+    }
+
+    fn read_uint_nullable(&mut self) -> Result<Option<u64>> {
+        self.0.read_uint_nullable()
+    }
+
+    fn read_int(&mut self) -> Result<i64> {
+        self.0.read_int()
+    }
+
+    fn read_int_nullable(&mut self) -> Result<Option<i64>> {
+        self.0.read_int_nullable()
+    }
+
+    fn read_ascii_string(&mut self) -> Result<String> {
+        self.0.read_ascii_string()
+    }
+
+    fn read_ascii_string_nullable(&mut self) -> Result<Option<String>> {
+        self.0.read_ascii_string_nullable()
+    }
+
+    fn read_unicode_string(&mut self) -> Result<String> {
+        self.0.read_unicode_string()
+    }
+
+    fn read_unicode_string_nullable(&mut self) -> Result<Option<String>> {
+        self.0.read_unicode_string_nullable()
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>> {
+        self.0.read_bytes()
+    }
+
+    fn read_bytes_nullable(&mut self) -> Result<Option<Vec<u8>>> {
+        self.0.read_bytes_nullable()
+    }
+}
+
+impl<R: Reader + ?Sized> ReadRaw<'static> for CopyingReader<'_, R> {
+    fn read_raw(&mut self, len: usize) -> Result<Raw<'static>> {
+        Ok(Raw::Owned(read_vec(self.0, len)?))
+    }
+}
+
+/// Reader over a byte slice that borrows byte vectors from the slice.
+pub(crate) struct SliceReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Returns number of bytes read so far.
+    pub fn position(&self) -> u64 {
+        self.pos as u64
+    }
+}
+
+impl Reader for SliceReader<'_> {
+    fn read_u8(&mut self) -> Result<u8> {
+        let b = *self.buf.get(self.pos).ok_or(Error::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        if let Raw::Borrowed(raw) = self.read_raw(buf.len())? {
+            buf.copy_from_slice(raw);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ReadRaw<'a> for SliceReader<'a> {
+    fn read_raw(&mut self, len: usize) -> Result<Raw<'a>> {
+        if len > self.buf.len() - self.pos {
+            return Err(Error::UnexpectedEof);
+        }
+        let raw = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(Raw::Borrowed(raw))
+    }
+}
+
+/// Wrapper around [`ReadRaw`] reader used by the decoder for every message.
+/// Fails with [`Error::MessageSizeLimitExceeded`][crate::Error::MessageSizeLimitExceeded] when more than `limit` bytes
+/// are read. Reportable errors either fail decoding or are collected as warnings.
+pub(crate) struct CheckedReader<'r, R: ?Sized> {
+    inner: &'r mut R,
+    limit: u64,
+    position: u64,
+    strict: bool,
+    max_string_length: usize,
+    warnings: Vec<Error>,
+}
+
+impl<'r, R: ?Sized> CheckedReader<'r, R> {
+    pub fn new(inner: &'r mut R, limit: u64, strict: bool) -> Self {
+        Self {
+            inner,
+            limit,
+            position: 0,
+            strict,
+            max_string_length: usize::MAX,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Sets maximum length of ASCII strings; longer ones fail while being read.
+    pub fn with_max_string_length(mut self, max_string_length: usize) -> Self {
+        self.max_string_length = max_string_length;
+        self
+    }
+
+    /// Returns reportable errors collected so far.
+    pub fn into_warnings(self) -> Vec<Error> {
+        self.warnings
+    }
+
+    /// Returns number of bytes read so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn check_limit(&self, len: u64) -> Result<()> {
+        if len > self.limit - self.position {
+            return Err(Error::MessageSizeLimitExceeded(self.limit));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Reader + ?Sized> Reader for CheckedReader<'_, R> {
+    fn read_u8(&mut self) -> Result<u8> {
+        self.check_limit(1)?;
+        let b = self.inner.read_u8()?;
+        self.position += 1;
+        Ok(b)
+    }
+
+    fn report(&mut self, err: Error) -> Result<()> {
+        if self.strict {
+            return Err(err);
+        }
+        self.warnings.push(err);
+        Ok(())
+    }
+
+    fn read_ascii_string(&mut self) -> Result<String> {
+        read_ascii_string(self, self.max_string_length)
+    }
+
+    fn read_ascii_string_nullable(&mut self) -> Result<Option<String>> {
+        read_ascii_string_nullable(self, self.max_string_length)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.check_limit(buf.len() as u64)?;
+        self.inner.read_exact(buf)?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl<'a, R: ReadRaw<'a> + ?Sized> ReadRaw<'a> for CheckedReader<'_, R> {
+    fn read_raw(&mut self, len: usize) -> Result<Raw<'a>> {
+        self.check_limit(len as u64)?;
+        let raw = self.inner.read_raw(len)?;
+        self.position += len as u64;
+        Ok(raw)
+    }
 }
 
 /// Wrapper around `std::io::Read` that implements [`fastlib::Reader`][crate::decoder::reader::Reader].
-pub(crate) struct StreamReader<'a> {
+pub struct StreamReader<'a> {
     stream: &'a mut dyn Read,
 }
 
@@ -221,16 +605,100 @@ impl<'a> StreamReader<'a> {
 impl Reader for StreamReader<'_> {
     fn read_u8(&mut self) -> Result<u8> {
         let mut buf = [0; 1];
-        match self.stream.read_exact(&mut buf) {
-            Ok(()) => {}
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        match self.stream.read_exact(buf) {
+            Ok(()) => Ok(()),
             Err(err) => {
                 if err.kind() == ErrorKind::UnexpectedEof {
                     return Err(Error::UnexpectedEof);
                 }
-                return Err(Error::Dynamic(format!("Stream read error: {err}")));
+                Err(Error::Dynamic(format!("Stream read error: {err}")))
             }
         }
-        Ok(buf[0])
+    }
+}
+
+/// Wrapper around [`fastlib::Reader`][crate::decoder::reader::Reader] that counts bytes consumed from the underlying reader.
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Reader> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    /// Returns number of bytes read so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+impl<R: Reader> Reader for CountingReader<R> {
+    fn read_u8(&mut self) -> Result<u8> {
+        let b = self.inner.read_u8()?;
+        self.position += 1;
+        Ok(b)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.inner.read_exact(buf)?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+}
+
+/// Wrapper around [`fastlib::Reader`][crate::decoder::reader::Reader] that reads no more than `limit` bytes.
+/// Reading beyond the limit returns [`Error::UnexpectedEof`][crate::Error::UnexpectedEof].
+pub(crate) struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Reader> LimitedReader<R> {
+    pub fn new(inner: R, limit: u64) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+
+    /// Returns number of bytes left before the limit is reached.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Reads and drops all bytes left before the limit.
+    pub fn skip_remaining(&mut self) -> Result<()> {
+        while self.remaining > 0 {
+            self.read_u8()?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Reader> Reader for LimitedReader<R> {
+    fn read_u8(&mut self) -> Result<u8> {
+        if self.remaining == 0 {
+            return Err(Error::UnexpectedEof);
+        }
+        let b = self.inner.read_u8()?;
+        self.remaining -= 1;
+        Ok(b)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        if buf.len() as u64 > self.remaining {
+            return Err(Error::UnexpectedEof);
+        }
+        self.inner.read_exact(buf)?;
+        self.remaining -= buf.len() as u64;
+        Ok(())
     }
 }
 
@@ -354,6 +822,187 @@ mod tests {
         }
     }
 
+    #[test]
+    fn read_int_overflow() {
+        // 10 bytes encoding of i64::MIN and u64::MAX are valid
+        let mut buf = bytes::Bytes::from(vec![0x7f, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+        assert_eq!(buf.read_int().unwrap(), i64::MIN);
+        let mut buf = bytes::Bytes::from(vec![
+            0x01, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0xff,
+        ]);
+        assert_eq!(buf.read_uint().unwrap(), u64::MAX);
+
+        let mut buf = bytes::Bytes::from(vec![
+            0x02, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0x7f, 0xff,
+        ]);
+        assert!(matches!(buf.read_uint(), Err(Error::IntegerOverflow)));
+        let mut buf = bytes::Bytes::from(vec![0x3f, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+        assert!(matches!(buf.read_int(), Err(Error::IntegerOverflow)));
+    }
+
+    #[test]
+    fn nullable_integer_limits() {
+        use crate::encoder::writer::Writer;
+
+        // Non-null values are incremented by one, so u64::MAX and i64::MAX need 10 bytes.
+        let mut buf = bytes::BytesMut::new();
+        buf.write_uint_nullable(Some(u64::MAX)).unwrap();
+        assert_eq!(buf.to_vec(), vec![0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+        let mut buf = bytes::BytesMut::new();
+        buf.write_int_nullable(Some(i64::MAX)).unwrap();
+        assert_eq!(buf.to_vec(), vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+
+        for value in [None, Some(0), Some(u64::MAX - 1), Some(u64::MAX)] {
+            let mut buf = bytes::BytesMut::new();
+            buf.write_uint_nullable(value).unwrap();
+            assert_eq!(buf.freeze().read_uint_nullable().unwrap(), value);
+        }
+        for value in [None, Some(0), Some(-1), Some(i64::MIN), Some(i64::MAX)] {
+            let mut buf = bytes::BytesMut::new();
+            buf.write_int_nullable(value).unwrap();
+            assert_eq!(buf.freeze().read_int_nullable().unwrap(), value);
+        }
+
+        // One more than the largest value
+        let mut buf = bytes::Bytes::from(vec![0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0x81]);
+        assert!(matches!(
+            buf.read_uint_nullable(),
+            Err(Error::IntegerOverflow)
+        ));
+        let mut buf = bytes::Bytes::from(vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0x81]);
+        assert!(matches!(
+            buf.read_int_nullable(),
+            Err(Error::IntegerOverflow)
+        ));
+        // The extra bit is not allowed for mandatory values
+        let mut buf = bytes::Bytes::from(vec![0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+        assert!(matches!(buf.read_uint(), Err(Error::IntegerOverflow)));
+        let mut buf = bytes::Bytes::from(vec![0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+        assert!(matches!(buf.read_int(), Err(Error::IntegerOverflow)));
+    }
+
+    #[test]
+    fn checked_reader() {
+        let raw = [0x83, 0x41, 0x42, 0x43, 0x81];
+        let mut rdr = SliceReader::new(&raw);
+        let mut rdr = CheckedReader::new(&mut rdr, 4, true);
+        let len = rdr.read_uint().unwrap() as usize;
+        assert_eq!(rdr.read_raw(len).unwrap().as_ref(), b"ABC");
+        assert!(matches!(
+            rdr.read_u8(),
+            Err(Error::MessageSizeLimitExceeded(4))
+        ));
+    }
+
+    #[test]
+    fn checked_reader_string_length() {
+        let raw = [0x41, 0x42, 0x43, 0x44, 0x45, 0xc6];
+        let mut rdr = SliceReader::new(&raw);
+        let mut rdr = CheckedReader::new(&mut rdr, u64::MAX, true).with_max_string_length(2);
+        assert!(matches!(
+            rdr.read_ascii_string(),
+            Err(Error::LengthLimitExceeded(3, 2))
+        ));
+        // stops at the first character over the limit
+        assert_eq!(rdr.position(), 3);
+
+        let raw = [0x41, 0xc2, 0x00, 0x80, 0x43, 0x44, 0xc5];
+        let mut rdr = SliceReader::new(&raw);
+        let mut rdr = CheckedReader::new(&mut rdr, u64::MAX, true).with_max_string_length(2);
+        assert_eq!(rdr.read_ascii_string().unwrap(), "AB");
+        assert_eq!(
+            rdr.read_ascii_string_nullable().unwrap(),
+            Some(String::new())
+        );
+        assert!(matches!(
+            rdr.read_ascii_string_nullable(),
+            Err(Error::LengthLimitExceeded(3, 2))
+        ));
+    }
+
+    #[test]
+    fn read_exact() {
+        let raw: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let mut buf = [0; 3];
+        let mut rdr = bytes::Bytes::from(raw.clone());
+        rdr.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2]);
+        let mut cursor = std::io::Cursor::new(&raw);
+        let mut rdr = StreamReader::new(&mut cursor);
+        rdr.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0, 1, 2]);
+
+        // byte vectors longer than the preallocated buffer
+        let mut rdr = bytes::Bytes::from(raw.clone());
+        assert_eq!(read_vec(&mut rdr, 9_000).unwrap(), raw[..9_000]);
+        assert!(matches!(
+            read_vec(&mut rdr, 1_001),
+            Err(Error::UnexpectedEof)
+        ));
+        let mut rdr = bytes::Bytes::from(raw.clone());
+        let raw = CopyingReader(&mut rdr).read_raw(10_000).unwrap();
+        assert!(matches!(&raw, Raw::Owned(_)));
+        assert_eq!(raw.as_ref().len(), 10_000);
+    }
+
+    #[cfg(feature = "shared-bytes")]
+    #[test]
+    fn read_raw_shared() {
+        let raw = bytes::Bytes::from_static(b"ABCD");
+        let mut rdr = raw.clone();
+        let Raw::Shared(ab) = rdr.read_raw(2).unwrap() else {
+            panic!("expected shared bytes");
+        };
+        assert_eq!(ab, "AB");
+        // the sub-slice shares the buffer
+        assert_eq!(ab.as_ptr(), raw.as_ptr());
+        assert_eq!(rdr, "CD");
+        assert!(matches!(rdr.read_raw(3), Err(Error::UnexpectedEof)));
+    }
+
+    #[test]
+    fn report_overlong() {
+        fn code<T: std::fmt::Debug>(
+            raw: &[u8],
+            read: impl FnOnce(&mut CheckedReader<SliceReader>) -> Result<T>,
+        ) -> Option<ErrorCode> {
+            let mut rdr = SliceReader::new(raw);
+            let mut rdr = CheckedReader::new(&mut rdr, u64::MAX, true);
+            read(&mut rdr).err().and_then(|e| e.code())
+        }
+        // integers
+        assert_eq!(code(&[0x00, 0x81], |r| r.read_uint()), Some(ErrorCode::R6));
+        assert_eq!(code(&[0x00, 0xc0], |r| r.read_uint()), Some(ErrorCode::R6));
+        assert_eq!(code(&[0x00, 0x81], |r| r.read_int()), Some(ErrorCode::R6));
+        assert_eq!(code(&[0x7f, 0xc0], |r| r.read_int()), Some(ErrorCode::R6));
+        assert_eq!(code(&[0x00, 0xc0], |r| r.read_int()), None);
+        assert_eq!(code(&[0x7f, 0x81], |r| r.read_int()), None);
+        // strings
+        assert_eq!(code(&[0x00, 0x80], |r| r.read_ascii_string()), None);
+        assert_eq!(
+            code(&[0x00, 0xc1], |r| r.read_ascii_string()),
+            Some(ErrorCode::R9)
+        );
+        assert_eq!(
+            code(&[0x00, 0x00, 0x80], |r| r.read_ascii_string_nullable()),
+            None
+        );
+        assert_eq!(
+            code(&[0x00, 0xc1], |r| r.read_ascii_string_nullable()),
+            Some(ErrorCode::R9)
+        );
+        assert_eq!(
+            code(&[0x00, 0x00, 0xc1], |r| r.read_ascii_string_nullable()),
+            Some(ErrorCode::R9)
+        );
+        // presence map
+        assert_eq!(
+            code(&[0x01, 0x80], |r| r.read_presence_map()),
+            Some(ErrorCode::R7)
+        );
+        assert_eq!(code(&[0x00, 0x81], |r| r.read_presence_map()), None);
+    }
+
     #[test]
     fn read_int_nullable() {
         struct TestCase {
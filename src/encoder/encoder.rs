@@ -1,41 +1,66 @@
 use bytes::BytesMut;
 use std::io::{Cursor, Write};
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::base::instruction::Instruction;
 use crate::base::message::MessageVisitor;
 use crate::base::pmap::PresenceMap;
-use crate::base::types::{Dictionary, Template, TypeRef};
-use crate::base::value::{Value, ValueType};
-use crate::common::context::{Context, DictionaryType};
+use crate::base::types::Template;
+use crate::base::value::{Value, ValueRef};
+use crate::common::context::{Context, FieldSlots};
 use crate::common::definitions::Definitions;
+use crate::common::framing::Framing;
+use crate::common::program::{Field, Frame, MESSAGE_SCOPE, Op, locate_error};
 use crate::encoder::writer::{StreamWriter, Writer};
+use crate::typed::{self, FastMessage};
 use crate::utils::stacked::Stacked;
-use crate::{Error, Result};
+use crate::{Error, ErrorCode, Result, TemplateBuilder};
 
 /// Encoder for FAST protocol messages.
 pub struct Encoder {
-    pub(crate) definitions: Definitions,
+    pub(crate) definitions: Arc<Definitions>,
     pub(crate) context: Context,
 }
 
 impl Encoder {
     #[allow(unused)]
     pub(crate) fn new_from_templates(ts: Vec<Template>) -> Result<Self> {
-        Ok(Encoder {
-            definitions: Definitions::new_from_templates(ts)?,
-            context: Context::new(),
-        })
+        Ok(Self::with_definitions(Arc::new(
+            Definitions::new_from_templates(ts)?,
+        )))
     }
 
     /// Creates new encoder from given XML definition.
     /// # Errors
     /// Returns error if definition is ill-formed.
     pub fn new_from_xml(text: &str) -> Result<Self> {
-        Ok(Encoder {
-            definitions: Definitions::new_from_xml(text)?,
-            context: Context::new(),
-        })
+        Ok(Self::with_definitions(Arc::new(Definitions::new_from_xml(
+            text,
+        )?)))
+    }
+
+    /// Creates new encoder from templates built in code, see [`TemplateBuilder`].
+    /// # Errors
+    /// Returns error if any template is ill-formed.
+    pub fn new_from_builders(builders: impl IntoIterator<Item = TemplateBuilder>) -> Result<Self> {
+        Ok(Self::with_definitions(Arc::new(
+            Definitions::new_from_builders(builders)?,
+        )))
+    }
+
+    /// Creates new encoder that uses shared definitions.
+    #[must_use]
+    pub fn with_definitions(definitions: Arc<Definitions>) -> Self {
+        Encoder {
+            context: Context::new(definitions.slots),
+            definitions,
+        }
+    }
+
+    /// Returns definitions used by the encoder.
+    #[must_use]
+    pub fn definitions(&self) -> &Arc<Definitions> {
+        &self.definitions
     }
 
     pub fn reset(&mut self) {
@@ -98,357 +123,392 @@ impl Encoder {
     ) -> Result<()> {
         EncoderContext::new(self, wrt, msg).encode_template()
     }
+
+    /// Encodes typed message into a given writer, see [`FastMessage`].
+    /// # Errors
+    /// Returns error if encoding failed.
+    pub fn encode_typed<T: FastMessage>(&mut self, wrt: &mut impl Writer, msg: &T) -> Result<()> {
+        typed::encode_message(self, wrt, msg)
+    }
+
+    /// Encodes framed message into a Vec buffer.
+    /// Returns encoded buffer.
+    /// # Errors
+    /// Returns error if encoding failed.
+    pub fn encode_frame_vec<F: Framing>(
+        &mut self,
+        framing: &mut F,
+        header: &F::Header,
+        msg: &mut impl MessageVisitor,
+    ) -> Result<Vec<u8>> {
+        let mut buf = BytesMut::new();
+        self.encode_frame(framing, header, &mut buf, msg)?;
+        Ok(buf.to_vec())
+    }
+
+    /// Encodes framed message into a given stream.
+    /// # Errors
+    /// Returns error if encoding failed.
+    pub fn encode_frame_stream<F: Framing>(
+        &mut self,
+        framing: &mut F,
+        header: &F::Header,
+        wrt: &mut dyn Write,
+        msg: &mut impl MessageVisitor,
+    ) -> Result<()> {
+        let mut wrt = StreamWriter::new(wrt);
+        self.encode_frame(framing, header, &mut wrt, msg)
+    }
+
+    /// Encodes framed message into a given writer.
+    /// The message is encoded first, then the frame header and the message are written into the writer.
+    /// # Errors
+    /// Returns error if encoding failed.
+    pub fn encode_frame<F: Framing>(
+        &mut self,
+        framing: &mut F,
+        header: &F::Header,
+        wrt: &mut impl Writer,
+        msg: &mut impl MessageVisitor,
+    ) -> Result<()> {
+        let mut buf = BytesMut::new();
+        self.encode_writer(&mut buf, msg)?;
+        framing.write_header(wrt, header, buf.len() as u64)?;
+        wrt.write_buf(&buf)
+    }
 }
 
 /// Processing context of the encoder. It represents context state during one message encoding.
 /// Created when it starts encoding a new message and destroyed after encoding of a message.
 pub(crate) struct EncoderContext<'a, W, M> {
-    pub(crate) definitions: &'a mut Definitions,
+    pub(crate) definitions: &'a Definitions,
     pub(crate) context: &'a mut Context,
     pub(crate) wrt: &'a mut W,
     pub(crate) msg: &'a mut M,
 
-    // The current template id.
-    // It is updated when a template identifier is encountered in the stream. A static template reference can also change
-    // the current template as described in the Template Reference Instruction section.
-    pub(crate) template_id: Stacked<u32>,
-
-    // The dictionary set and initial value are described in the Operators section.
-    pub(crate) dictionary: Stacked<Dictionary>,
-
-    // The current application type is initially the special type `any`. The current application type changes when the processor
-    // encounters an element containing a `typeRef` element. The new type is applicable to the instructions contained within
-    // the element. The `typeRef` can appear in the <template>, <group> and <sequence> elements.
-    pub(crate) type_ref: Stacked<TypeRef>,
-
     // The presence map of the current segment.
     pub(crate) presence_map: Stacked<PresenceMap>,
 }
 
-impl<'a, W: Writer, M: MessageVisitor> EncoderContext<'a, W, M> {
+impl<'a, W: Writer, M> EncoderContext<'a, W, M> {
     pub(crate) fn new(d: &'a mut Encoder, w: &'a mut W, m: &'a mut M) -> Self {
         Self {
-            definitions: &mut d.definitions,
+            definitions: &d.definitions,
             context: &mut d.context,
             wrt: w,
             msg: m,
-            template_id: Stacked::new_empty(),
-            dictionary: Stacked::new(Dictionary::Global),
-            type_ref: Stacked::new(TypeRef::Any),
             presence_map: Stacked::new(PresenceMap::new_empty()),
         }
     }
 
-    // Encode a template to the stream.
-    fn encode_template(&mut self) -> Result<()> {
-        let template_name = self.msg.get_template_name()?;
-        let template = self
-            .definitions
+    // Returns the template of a message or of a dynamic template reference.
+    pub(crate) fn template_by_name(&self, name: &str) -> Result<&'a Template> {
+        self.definitions
             .templates_by_name
-            .get(&template_name)
-            .ok_or_else(|| Error::Dynamic(format!("Unknown template name: {template_name}")))?
-            .clone();
-
-        let mut buf = BytesMut::new();
-        self.encode_template_id(&mut buf, template.id)?;
-
-        // Update some context variables
-        let has_dictionary = self.switch_dictionary(&template.dictionary);
-        let has_type_ref = self.switch_type_ref(&template.type_ref);
-
-        self.encode_instructions(&mut buf, &template.instructions)?;
-
-        if has_dictionary {
-            self.restore_dictionary();
-        }
-        if has_type_ref {
-            self.restore_type_ref();
-        }
-
-        self.drop_template_id();
-
-        let mut buf2 = BytesMut::new();
-        self.write_presence_map(&mut buf2)?;
-        buf2.write_buf(buf.as_ref())?;
+            .get(name)
+            .map(AsRef::as_ref)
+            .ok_or_else(|| Error::Spec(ErrorCode::D8, format!("Unknown template name: {name}")))
+    }
 
-        self.wrt.write_buf(buf2.as_ref()) // presence map + template_id + instructions
+    // Returns the program of the template compiled for the scope.
+    pub(crate) fn program(&self, template: &Template, scope: usize) -> Result<&'a [Op]> {
+        self.definitions
+            .programs
+            .get(scope, template.id)
+            .ok_or_else(|| {
+                Error::Runtime(format!(
+                    "template {} has unresolved dictionary entries",
+                    template.name
+                ))
+            })
     }
 
     // Write presence map to the stream and remove if from the stack.
-    fn write_presence_map(&mut self, buf: &mut impl Writer) -> Result<()> {
+    pub(crate) fn write_presence_map(&mut self, buf: &mut impl Writer) -> Result<()> {
         let presence_map = self.presence_map.pop().unwrap();
-        buf.write_presence_map(presence_map.bitmap, presence_map.size)
-    }
-
-    // Encode template id to the buffer and change the current processing context accordingly.
-    fn encode_template_id(&mut self, buf: &mut impl Writer, template_id: u32) -> Result<()> {
-        self.template_id.push(template_id);
-        let instruction = self.definitions.template_id_instruction.clone();
-        instruction.inject(self, buf, Some(Value::UInt32(template_id)))
+        presence_map.write(buf)
     }
 
-    // Stop processing the current template id, restore the previous value in the processing context.
-    fn drop_template_id(&mut self) {
-        self.template_id.pop();
+    // Encode template id to the buffer.
+    pub(crate) fn encode_template_id(
+        &mut self,
+        buf: &mut impl Writer,
+        template_id: u32,
+    ) -> Result<()> {
+        let instruction = &self.definitions.template_id_instruction;
+        instruction.inject(
+            self,
+            buf,
+            Some(ValueRef::UInt32(template_id)),
+            FieldSlots::fixed(instruction),
+        )
     }
 
-    fn encode_instructions(
+    // Encode the value of the field, located by the field name on failure.
+    pub(crate) fn inject_field(
         &mut self,
         buf: &mut impl Writer,
-        instructions: &[Instruction],
+        field: &Field,
+        value: Option<ValueRef<'_>>,
     ) -> Result<()> {
-        for instruction in instructions {
-            match instruction.value_type {
-                ValueType::Sequence => {
-                    self.encode_sequence(buf, instruction)?;
-                }
-                ValueType::Group => {
-                    self.encode_group(buf, instruction)?;
-                }
-                ValueType::TemplateReference => {
-                    self.encode_template_reference(buf, instruction)?;
-                }
-                _ => {
-                    self.encode_field(buf, instruction)?;
-                }
-            }
-        }
-        Ok(())
+        let instruction = &*field.instruction;
+        instruction
+            .inject(self, buf, value, field.slots)
+            .map_err(|e| self.located(e, &instruction.name))
     }
 
-    fn encode_field(&mut self, buf: &mut impl Writer, instruction: &Instruction) -> Result<()> {
-        let value = self
-            .msg
-            .get_value(&instruction.name, &instruction.value_type)?;
-        instruction.inject(self, buf, value)
+    #[inline]
+    pub(crate) fn pmap_set_next_bit(&mut self, value: bool) {
+        self.presence_map.must_peek_mut().set_next_bit(value);
+    }
+
+    // Adds the element to the location of the error.
+    pub(crate) fn located(&self, err: Error, name: &str) -> Error {
+        err.within(name, self.presence_map.peek().map(PresenceMap::bit_index))
     }
 
-    fn encode_segment(
+    #[inline]
+    pub(crate) fn ctx_set(
         &mut self,
-        buf: &mut impl Writer,
-        instructions: &[Instruction],
+        i: &Instruction,
+        slot: Option<usize>,
+        v: Option<Value>,
     ) -> Result<()> {
-        self.presence_map.push(PresenceMap::new_empty());
-        let mut buf2 = BytesMut::new();
-        self.encode_instructions(&mut buf2, instructions)?;
-        self.write_presence_map(buf)?;
-        buf.write_buf(buf2.as_ref())
-    }
-
-    fn encode_group(&mut self, buf: &mut impl Writer, instruction: &Instruction) -> Result<()> {
-        if !self.msg.select_group(&instruction.name)? {
-            return if instruction.is_optional() {
-                self.pmap_set_next_bit(false);
-                Ok(())
-            } else {
-                Err(Error::Dynamic(format!(
-                    "Missing mandatory group: {}",
-                    instruction.name
-                )))
-            };
-        }
-        if instruction.is_optional() {
-            self.pmap_set_next_bit(true);
+        self.context.set(ctx_slot(i, slot)?, v);
+        Ok(())
+    }
+
+    // Returns the previous value of the instruction without copying it:
+    // `None` if it is undefined, `Some(None)` if it is empty.
+    #[inline]
+    pub(crate) fn ctx_get(
+        &self,
+        i: &Instruction,
+        slot: Option<usize>,
+    ) -> Result<Option<Option<&Value>>> {
+        let v = self.context.get(ctx_slot(i, slot)?).map(Option::as_ref);
+        if let Some(Some(v)) = v
+            && !i.matches_dictionary_value(v)
+        {
+            // It is a dynamic error [ERR D4] if the field of an operator accessing an entry does not have
+            // the same type as the value of the entry.
+            return Err(Error::Spec(
+                ErrorCode::D4,
+                format!("field {} has wrong value type in context", i.name),
+            ));
         }
+        Ok(v)
+    }
+}
 
-        let has_dictionary = self.switch_dictionary(&instruction.dictionary);
-        let has_type_ref = self.switch_type_ref(&instruction.type_ref);
+impl<W: Writer, M: MessageVisitor> EncoderContext<'_, W, M> {
+    // Encode a template to the stream.
+    fn encode_template(&mut self) -> Result<()> {
+        let template_name = self.msg.get_template_name()?;
+        let template = self.template_by_name(&template_name)?;
+        let mut buf = BytesMut::new();
+        self.encode_message(&mut buf, template)
+            .map_err(|e| e.in_template(template.id, &template.name))?;
 
-        if instruction.has_pmap.get() {
-            self.encode_segment(buf, &instruction.instructions)?;
-        } else {
-            self.encode_instructions(buf, &instruction.instructions)?;
-        }
+        let mut buf2 = BytesMut::new();
+        self.write_presence_map(&mut buf2)?;
+        buf2.write_buf(buf.as_ref())?;
 
-        if has_dictionary {
-            self.restore_dictionary();
-        }
-        if has_type_ref {
-            self.restore_type_ref();
-        }
+        self.wrt.write_buf(buf2.as_ref()) // presence map + template_id + instructions
+    }
 
-        self.msg.release_group()
+    // Encode template id and instructions of the message template to the buffer.
+    fn encode_message(&mut self, buf: &mut BytesMut, template: &Template) -> Result<()> {
+        let program = self.program(template, MESSAGE_SCOPE)?;
+        self.encode_template_id(buf, template.id)?;
+        self.encode_program(buf, program)
     }
 
-    fn encode_sequence(&mut self, buf: &mut impl Writer, instruction: &Instruction) -> Result<()> {
-        let length = self.msg.select_sequence(&instruction.name)?;
-        let length_instruction = instruction.instructions.first().unwrap();
+    // Encode instructions of the template compiled into the program.
+    fn encode_program(&mut self, buf: &mut BytesMut, program: &[Op]) -> Result<()> {
+        let mut frames = Vec::new();
+        let mut segments = Vec::new();
+        self.run_program(buf, program, &mut frames, &mut segments)
+            .map_err(|e| locate_error(e, program, &frames, |e, name| self.located(e, name)))
+    }
 
-        let has_dictionary = self.switch_dictionary(&instruction.dictionary);
-        let has_type_ref = self.switch_type_ref(&instruction.type_ref);
-        match length {
-            None => {
-                if instruction.is_optional() {
-                    length_instruction.inject(self, buf, None)?;
-                } else {
-                    return Err(Error::Dynamic(format!(
-                        "Missing mandatory sequence: {}",
-                        instruction.name
-                    )));
+    // Segments of groups and sequence items are encoded into their own buffers, because their presence maps
+    // precede them in the stream.
+    #[allow(clippy::too_many_lines)]
+    fn run_program(
+        &mut self,
+        buf: &mut BytesMut,
+        program: &[Op],
+        frames: &mut Vec<Frame>,
+        segments: &mut Vec<BytesMut>,
+    ) -> Result<()> {
+        let mut pc = 0;
+        while let Some(op) = program.get(pc) {
+            pc += 1;
+            match op {
+                Op::Field(field) => {
+                    self.encode_field(segments.last_mut().unwrap_or(buf), field)?;
                 }
-            }
-            Some(length) => {
-                length_instruction.inject(self, buf, Some(Value::UInt32(length as u32)))?;
-                for idx in 0..length {
-                    self.msg.select_sequence_item(idx)?;
-                    if instruction.has_pmap.get() {
-                        self.encode_segment(buf, &instruction.instructions[1..])?;
-                    } else {
-                        self.encode_instructions(buf, &instruction.instructions[1..])?;
+                Op::Group { group, end } => {
+                    frames.push(Frame::Group { start: pc - 1 });
+                    if !self.msg.select_group(&group.name)? {
+                        if !group.is_optional() {
+                            return Err(Error::Dynamic(format!(
+                                "Missing mandatory group: {}",
+                                group.name
+                            )));
+                        }
+                        self.pmap_set_next_bit(false);
+                        frames.pop();
+                        pc = end + 1;
+                        continue;
+                    }
+                    if group.is_optional() {
+                        self.pmap_set_next_bit(true);
+                    }
+                    if group.has_pmap {
+                        self.presence_map.push(PresenceMap::new_empty());
+                        segments.push(BytesMut::new());
                     }
+                }
+                Op::GroupEnd { has_pmap } => {
+                    if *has_pmap {
+                        self.write_segment(buf, segments)?;
+                    }
+                    self.msg.release_group()?;
+                    frames.pop();
+                }
+                Op::Sequence {
+                    sequence,
+                    length: length_field,
+                    end,
+                } => {
+                    let start = pc - 1;
+                    frames.push(Frame::Sequence {
+                        start,
+                        item: None,
+                        length: 0,
+                    });
+                    let out = segments.last_mut().unwrap_or(&mut *buf);
+                    let Some(length) = self.msg.select_sequence(&sequence.name)? else {
+                        if !sequence.is_optional() {
+                            return Err(Error::Dynamic(format!(
+                                "Missing mandatory sequence: {}",
+                                sequence.name
+                            )));
+                        }
+                        self.inject_field(out, length_field, None)?;
+                        frames.pop();
+                        pc = end + 1;
+                        continue;
+                    };
+                    self.inject_field(out, length_field, Some(ValueRef::UInt32(length as u32)))?;
+                    if length == 0 {
+                        self.msg.release_sequence()?;
+                        frames.pop();
+                        pc = end + 1;
+                        continue;
+                    }
+                    *frames.last_mut().unwrap() = Frame::Sequence {
+                        start,
+                        item: None,
+                        length,
+                    };
+                    self.start_sequence_item(frames, segments, sequence.has_pmap, 0)?;
+                }
+                Op::SequenceEnd { start, has_pmap } => {
+                    if *has_pmap {
+                        self.write_segment(buf, segments)?;
+                    }
+                    let Some(Frame::Sequence { item, length, .. }) = frames.last_mut() else {
+                        unreachable!();
+                    };
+                    let next = item.take().unwrap() + 1;
+                    let length = *length;
                     self.msg.release_sequence_item()?;
+                    if next < length {
+                        self.start_sequence_item(frames, segments, *has_pmap, next)?;
+                        pc = start + 1;
+                    } else {
+                        self.msg.release_sequence()?;
+                        frames.pop();
+                    }
+                }
+                Op::TemplateRef { instruction, .. } => {
+                    self.msg.select_template_ref(&instruction.name, false)?;
+                }
+                Op::TemplateRefEnd => {
+                    self.msg.release_template_ref()?;
+                }
+                Op::DynamicTemplateRef { scope } => {
+                    self.encode_dynamic_template_ref(segments.last_mut().unwrap_or(buf), *scope)?;
                 }
-                self.msg.release_sequence()?;
             }
         }
-        if has_dictionary {
-            self.restore_dictionary();
-        }
-        if has_type_ref {
-            self.restore_type_ref();
-        }
-
         Ok(())
     }
 
-    fn encode_template_reference(
+    fn start_sequence_item(
         &mut self,
-        buf: &mut impl Writer,
-        instruction: &Instruction,
+        frames: &mut [Frame],
+        segments: &mut Vec<BytesMut>,
+        has_pmap: bool,
+        idx: usize,
     ) -> Result<()> {
-        let is_dynamic = instruction.name.is_empty();
-
-        if is_dynamic {
-            let template_name = self
-                .msg
-                .select_template_ref(&instruction.name, true)?
-                .ok_or_else(|| {
-                    Error::Dynamic(format!(
-                        "Missing mandatory template reference: {}",
-                        instruction.name
-                    ))
-                })?;
-            let template = self
-                .definitions
-                .templates_by_name
-                .get(&template_name)
-                .ok_or_else(|| Error::Dynamic(format!("Unknown template name: {template_name}")))? // [ErrD09]
-                .clone();
-
-            let mut buf2 = BytesMut::new();
-            self.presence_map.push(PresenceMap::new_empty());
-            self.encode_template_id(&mut buf2, template.id)?;
-
-            let has_dictionary = self.switch_dictionary(&template.dictionary);
-            let has_type_ref = self.switch_type_ref(&template.type_ref);
-
-            self.encode_instructions(&mut buf2, &template.instructions)?;
-
-            if has_dictionary {
-                self.restore_dictionary();
-            }
-            if has_type_ref {
-                self.restore_type_ref();
-            }
-
-            self.drop_template_id();
-
-            self.write_presence_map(buf)?;
-            buf.write_buf(buf2.as_ref())?;
-        } else {
-            self.msg.select_template_ref(&instruction.name, false)?;
-            let template = self
-                .definitions
-                .templates_by_name
-                .get(&instruction.name)
-                .ok_or_else(|| Error::Dynamic(format!("Unknown template: {}", instruction.name)))? // [ErrD09]
-                .clone();
-
-            let has_dictionary = self.switch_dictionary(&template.dictionary);
-            let has_type_ref = self.switch_type_ref(&template.type_ref);
-
-            self.encode_instructions(buf, &template.instructions)?;
-
-            if has_dictionary {
-                self.restore_dictionary();
-            }
-            if has_type_ref {
-                self.restore_type_ref();
-            }
+        self.msg.select_sequence_item(idx)?;
+        if let Some(Frame::Sequence { item, .. }) = frames.last_mut() {
+            *item = Some(idx);
         }
-        self.msg.release_template_ref()
-    }
-
-    #[inline]
-    fn switch_dictionary(&mut self, dictionary: &Dictionary) -> bool {
-        if *dictionary == Dictionary::Inherit {
-            false
-        } else {
-            self.dictionary.push(dictionary.clone());
-            true
+        if has_pmap {
+            self.presence_map.push(PresenceMap::new_empty());
+            segments.push(BytesMut::new());
         }
+        Ok(())
     }
 
-    #[inline]
-    fn restore_dictionary(&mut self) {
-        _ = self.dictionary.pop();
-    }
-
-    #[inline]
-    fn switch_type_ref(&mut self, type_ref: &TypeRef) -> bool {
-        if *type_ref == TypeRef::Any {
-            false
-        } else {
-            self.type_ref.push(type_ref.clone());
-            true
-        }
+    // Writes the presence map and the finished segment to the enclosing segment.
+    fn write_segment(&mut self, buf: &mut BytesMut, segments: &mut Vec<BytesMut>) -> Result<()> {
+        let segment = segments.pop().unwrap();
+        let out = segments.last_mut().unwrap_or(buf);
+        self.write_presence_map(out)?;
+        out.write_buf(segment.as_ref())
     }
 
-    #[inline]
-    fn restore_type_ref(&mut self) {
-        _ = self.type_ref.pop();
+    fn encode_field(&mut self, buf: &mut impl Writer, field: &Field) -> Result<()> {
+        let instruction = &*field.instruction;
+        let value = self
+            .msg
+            .get_value(&instruction.name, &instruction.value_type)
+            .and_then(|v| instruction.wire_value(v))
+            .map_err(|e| self.located(e, &instruction.name))?;
+        self.inject_field(buf, field, value.map(ValueRef::from))
     }
 
-    #[inline]
-    pub(crate) fn pmap_set_next_bit(&mut self, value: bool) {
-        self.presence_map.must_peek_mut().set_next_bit(value);
-    }
+    // A dynamically referenced template is encoded as a segment with its own presence map and template identifier.
+    // Instructions of the referenced template are located within that template.
+    fn encode_dynamic_template_ref(&mut self, buf: &mut impl Writer, scope: usize) -> Result<()> {
+        let template_name = self
+            .msg
+            .select_template_ref("", true)?
+            .ok_or_else(|| Error::Dynamic("Missing mandatory template reference: ".to_string()))?;
+        let template = self.template_by_name(&template_name)?;
+        let program = self.program(template, scope)?;
 
-    #[inline]
-    pub(crate) fn ctx_set(&mut self, i: &Instruction, v: Option<Value>) {
-        self.context.set(self.make_dict_type(), i.key.clone(), v);
-    }
+        let mut buf2 = BytesMut::new();
+        self.presence_map.push(PresenceMap::new_empty());
+        self.encode_template_id(&mut buf2, template.id)?;
+        self.encode_program(&mut buf2, program)
+            .map_err(|e| self.located(e, &template.name))?;
 
-    #[inline]
-    pub(crate) fn ctx_get(&mut self, i: &Instruction) -> Result<Option<Option<Value>>> {
-        let v = self.context.get(self.make_dict_type(), &i.key);
-        if let Some(Some(ref v)) = v
-            && !i.value_type.matches_type(v)
-        {
-            // It is a dynamic error [ERR D4] if the field of an operator accessing an entry does not have
-            // the same type as the value of the entry.
-            return Err(Error::Runtime(format!(
-                "field {} has wrong value type in context",
-                i.name
-            ))); // [ERR D4]
-        }
-        Ok(v)
+        self.write_presence_map(buf)?;
+        buf.write_buf(buf2.as_ref())?;
+        self.msg.release_template_ref()
     }
+}
 
-    fn make_dict_type(&self) -> DictionaryType {
-        let dictionary = self.dictionary.must_peek();
-        match dictionary {
-            Dictionary::Inherit => unreachable!(),
-            Dictionary::Global => DictionaryType::Global,
-            Dictionary::Template => DictionaryType::Template(*self.template_id.must_peek()),
-            Dictionary::Type => {
-                let name = match self.type_ref.must_peek() {
-                    TypeRef::Any => Rc::from("__any__"),
-                    TypeRef::ApplicationType(name) => name.clone(),
-                };
-                DictionaryType::Type(name)
-            }
-            Dictionary::UserDefined(name) => DictionaryType::UserDefined(name.clone()),
-        }
-    }
+// Returns the slot of the instruction's previous value in the context storage,
+// resolved for the processing context when the program was compiled.
+#[inline]
+fn ctx_slot(i: &Instruction, slot: Option<usize>) -> Result<usize> {
+    slot.ok_or_else(|| Error::Runtime(format!("field {} has no dictionary entry", i.name)))
 }
@@ -24,7 +24,7 @@ pub trait Writer {
         Ok(())
     }
 
-    /// Writes presence map.
+    /// Writes presence map of `size` bits, up to 63. The encoder itself writes presence maps of any length.
     /// # Errors
     /// Returns error if bitmap is ill-formed or writer can't handle more bytes.
     fn write_presence_map(&mut self, bitmap: u64, size: u8) -> Result<()> {
@@ -82,7 +82,11 @@ pub trait Writer {
     fn write_uint_nullable(&mut self, value: Option<u64>) -> Result<()> {
         match value {
             None => self.write_uint(0),
-            Some(v) => self.write_uint(v + 1),
+            Some(v) => match v.checked_add(1) {
+                Some(v) => self.write_uint(v),
+                // `u64::MAX` is transferred as 2^64.
+                None => self.write_buf(&encode_number(u128::from(v) + 1, 10)),
+            },
         }
     }
     /// Writes non-nullable 64bit signed integer.
@@ -128,7 +132,11 @@ pub trait Writer {
     fn write_int_nullable(&mut self, value: Option<i64>) -> Result<()> {
         match value {
             None => self.write_int(0),
-            Some(v) if v >= 0 => self.write_int(v + 1),
+            Some(v) if v >= 0 => match v.checked_add(1) {
+                Some(v) => self.write_int(v),
+                // `i64::MAX` is transferred as 2^63.
+                None => self.write_buf(&encode_number(i128::from(v) + 1, 10)),
+            },
             Some(v) => self.write_int(v),
         }
     }
@@ -231,6 +239,18 @@ impl ToByte for u64 {
     }
 }
 
+impl ToByte for i128 {
+    fn to_byte(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl ToByte for u128 {
+    fn to_byte(&self) -> u8 {
+        *self as u8
+    }
+}
+
 fn encode_number<T>(number: T, len: usize) -> [u8; 10]
 where
     T: ToByte + Shr<usize, Output = T> + BitAnd<Output = T> + From<u8> + Copy,
@@ -253,6 +273,16 @@ where
     buf
 }
 
+impl<W: Writer + ?Sized> Writer for &mut W {
+    fn write_u8(&mut self, value: u8) -> Result<()> {
+        (**self).write_u8(value)
+    }
+
+    fn write_buf(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_buf(buf)
+    }
+}
+
 impl Writer for bytes::BytesMut {
     fn write_u8(&mut self, value: u8) -> Result<()> {
         self.put_u8(value);
@@ -96,6 +96,26 @@
 //! let msg: Message = fastlib::from_slice(&mut decoder, &raw_data)?;
 //! ```
 //!
+//! Messages are deserialized directly from the stream, without building an intermediate data model.
+//! Fields that the message types don't declare are decoded and skipped, so the dictionaries stay in sync.
+//! If a message fails to deserialize, it is still decoded to the end and the next message can be deserialized.
+//!
+//! To deserialize all messages from a stream use [`StreamDeserializer`]:
+//!
+//! ```rust,ignore
+//! use fastlib::{Decoder, StreamDeserializer};
+//!
+//! let mut decoder = Decoder::new_from_xml(include_str!("templates.xml"))?;
+//!
+//! // Raw data that contains several messages.
+//! let raw_data = bytes::Bytes::from(vec![ ... ]);
+//!
+//! for msg in StreamDeserializer::<_, Message>::new(&mut decoder, raw_data) {
+//!     let msg = msg?;
+//!     ...
+//! }
+//! ```
+//!
 //! To serialize a message call [`to_vec`], [`to_bytes`], [`to_writer`], [`to_stream`] or [`to_buffer`]:
 //!
 //! ```rust,ignore
@@ -115,6 +135,144 @@
 //! let raw: Vec<u8> = fastlib::to_vec(&mut encoder, &msg)?;
 //! ```
 //!
+//! Messages are serialized directly to the stream in the order of the template instructions, without building an intermediate data model.
+//! Struct fields that come in a different order are buffered until their instructions are reached; the output is the same.
+//!
+//! ## Generate message types
+//!
+//! Instead of writing the message types by hand, they can be generated from the templates in a build script.
+//! `CodeGenerator` emits the message enum, structs of templates, groups and sequence items, enums of `enum` and `set` fields,
+//! `Option`s of optional fields, `serde_bytes` byte vectors and dynamic template references following the rules above.
+//! Static template references are inlined:
+//!
+//! ```rust,ignore
+//! // build.rs
+//! use fastlib::{CodeGenerator, DecimalType, Definitions};
+//!
+//! fn main() {
+//!     let definitions = Definitions::new_from_xml(include_str!("templates.xml")).unwrap();
+//!     let code = CodeGenerator::new(&definitions)
+//!         .message_name("Message")
+//!         .decimal_type(DecimalType::Float)
+//!         .generate();
+//!     let out_dir = std::env::var("OUT_DIR").unwrap();
+//!     std::fs::write(std::path::Path::new(&out_dir).join("messages.rs"), code).unwrap();
+//! }
+//! ```
+//!
+//! ```rust,ignore
+//! // src/messages.rs
+//! include!(concat!(env!("OUT_DIR"), "/messages.rs"));
+//! ```
+//!
+//! The generated code requires `serde` with `derive` feature and `serde_bytes` dependencies.
+//!
+//! ## Typed messages
+//!
+//! The optional `fastlib-derive` crate generates decoding and encoding code specialized for a template at compile time.
+//! `#[derive(FastMessage)]` reads the XML templates and matches struct fields to the template instructions by names,
+//! ignoring case and underscores, or by `#[fast(rename = "...")]`. Fields of statically referenced templates are inlined,
+//! groups and sequence items are structs with `group` path attribute. If the struct doesn't match the template,
+//! e.g. a field is missing or has a different type, it is a compile error:
+//!
+//! ```rust,ignore
+//! use fastlib::{Decimal, Decoder, Encoder};
+//! use fastlib_derive::FastMessage;
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "templates.xml", template = "MDIncRefresh")]
+//! struct IncRefresh {
+//!     msg_seq_num: u32,
+//!     sending_time: u64,
+//!     md_entries: Vec<MDEntry>,
+//! }
+//!
+//! #[derive(FastMessage)]
+//! #[fast(xml = "templates.xml", template = "MDIncRefresh", group = "MDEntries")]
+//! struct MDEntry {
+//!     #[fast(rename = "MDUpdateAction")]
+//!     action: u32,
+//!     md_entry_px: Option<Decimal>,
+//! }
+//!
+//! let mut decoder = Decoder::new_from_xml(include_str!("templates.xml"))?;
+//! let msg: IncRefresh = decoder.decode_typed(&mut rdr)?;
+//!
+//! let mut encoder = Encoder::new_from_xml(include_str!("templates.xml"))?;
+//! encoder.encode_typed(&mut wrt, &msg)?;
+//! ```
+//!
+//! Decoder and encoder must be created from the same templates. Enum, set and time fields and dynamic template references
+//! are not supported by typed messages; use serde for such templates.
+//!
+//! ## Enum and set fields
+//!
+//! FAST 1.2 `enum` and `set` fields are decoded to `Value::Enum(value, name)` and `Value::Set(mask, names)`.
+//! With serde, an enum field maps onto a Rust enum with unit variants named after the elements
+//! and a set field maps onto a collection of such variants, e.g. `BTreeSet<Flag>`:
+//!
+//! ```xml
+//! <enum name="Side" id="54">
+//!     <element name="Buy" value="1"/>
+//!     <element name="Sell"/>
+//! </enum>
+//! <set name="Flags" id="100">
+//!     <element name="A"/>
+//!     <element name="B"/>
+//! </set>
+//! ```
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize)]
+//! enum Side { Buy, Sell }
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+//! enum Flag { A, B }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! #[serde(rename_all = "PascalCase")]
+//! struct Order {
+//!     side: Side,
+//!     flags: BTreeSet<Flag>,
+//! }
+//! ```
+//!
+//! Integer fields (`u32` for enums, `u64` for sets) can be used as well.
+//!
+//! ## Time fields
+//!
+//! FAST 1.2 `timestamp`, `date` and `timeOfDay` fields are decoded to `Value::Timestamp`, `Value::Date` and `Value::TimeOfDay`
+//! that carry the `unit` (`second`, `millisecond` (default), `microsecond` or `nanosecond`) and the `epoch` (`YYYY-MM-DD`, default `1970-01-01`)
+//! of the field. They are formatted as ISO 8601 strings by text and JSON factories. When encoding, a time in any unit and epoch,
+//! an ISO 8601 string or an integer number of units of the field can be used:
+//!
+//! ```xml
+//! <timestamp name="SendingTime" id="52" unit="second"/>
+//! <date name="TradeDate" id="75" epoch="2024-01-01"/>
+//! <timeOfDay name="Time" id="273"/>
+//! ```
+//!
+//! With serde, time fields map onto strings or integers. With `chrono` feature they map onto
+//! `DateTime<Utc>`, `NaiveDate` and `NaiveTime`, with `time` feature onto `OffsetDateTime`
+//! (using `time::serde::rfc3339`), `time::Date` and `time::Time`:
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize)]
+//! #[serde(rename_all = "PascalCase")]
+//! struct Trade {
+//!     sending_time: chrono::DateTime<chrono::Utc>,
+//!     trade_date: chrono::NaiveDate,
+//!     time: chrono::NaiveTime,
+//! }
+//! ```
+//!
+//! ## Template namespaces
+//!
+//! Template names are qualified with the namespace given by `templateNs` attribute of `<templates>`, `<template>`
+//! or `<templateRef>`, so template files that reuse names like `MsgHeader` in different namespaces can be loaded together.
+//! Static template references are resolved in their namespace. A template name that is used in several namespaces
+//! is reported to message factories and looked up by encoders as `{namespace}name`, unique names are used as is.
+//!
 //! ## Decode to JSON
 //!
 //! ```rust,ignore
@@ -136,6 +294,151 @@
 //! println!("{}", msg.json);
 //! ```
 //!
+//! To decode all messages from a stream use [`Decoder::iter_stream`] or [`Decoder::iter_reader`].
+//! The iterator yields messages with their byte offsets and stops at the end of the stream:
+//!
+//! ```rust,ignore
+//! for res in decoder.iter_stream(&mut file, JsonMessageFactory::new) {
+//!     let (offset, msg) = res?;
+//!     println!("{offset}: {}", msg.json);
+//! }
+//! ```
+//!
+//! ## Sharing definitions between threads
+//!
+//! Parse templates once into [`Definitions`] and share them between decoders and encoders running on different threads:
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use fastlib::{Decoder, Definitions};
+//!
+//! let definitions = Arc::new(Definitions::new_from_xml(include_str!("templates.xml"))?);
+//!
+//! // Each decoder keeps its own dictionaries.
+//! let mut decoder = Decoder::with_definitions(definitions.clone());
+//! ```
+//!
+//! ## Build templates in code
+//!
+//! Templates can also be built in code with `TemplateBuilder`. Fields are added with `field()`, `sequence()`,
+//! `group()` and `template_ref()`, other methods modify the last added field. Built templates are checked
+//! the same way as templates loaded from XML:
+//!
+//! ```rust,ignore
+//! use fastlib::{Decoder, TemplateBuilder, ValueType};
+//!
+//! let template = TemplateBuilder::new(1, "MDIncRefresh")
+//!     .field(ValueType::UInt32, 34, "MsgSeqNum").increment()
+//!     .sequence("MDEntries", |s| {
+//!         s.field(ValueType::UInt32, 279, "MDUpdateAction").copy().value("0")
+//!             .field(ValueType::Decimal, 270, "MDEntryPx").optional()
+//!             .exponent().copy()
+//!             .mantissa().delta()
+//!     })
+//!     .length(268, "NoMDEntries");
+//! let mut decoder = Decoder::new_from_builders([template])?;
+//! ```
+//!
+//! ## Inspect templates
+//!
+//! Loaded templates can be inspected with `Definitions::templates()`, `template_by_id()` and `template_by_name()`.
+//! Each `Template` and `Instruction` gives read-only access to names, ids, types, presence, operators, initial values,
+//! dictionaries, keys, application types, child instructions and presence map usage:
+//!
+//! ```rust,ignore
+//! let definitions = Definitions::new_from_xml(include_str!("templates.xml"))?;
+//! for template in definitions.templates() {
+//!     for field in template.instructions() {
+//!         println!("{} {} {:?} {:?}", template.name(), field.name(), field.value_type(), field.operator());
+//!     }
+//! }
+//! ```
+//!
+//! ## Validate templates
+//!
+//! `Definitions::validate_xml()` checks a template document without stopping at the first problem and returns
+//! `Diagnostic`s with the line and column of the offending element. Static errors of the FAST specification carry
+//! their `ErrorCode` (`S1`..`S5`); unknown, cyclic and forward template references, duplicates and dictionary
+//! entries shared by fields of different types are reported as well. Warnings don't prevent loading:
+//!
+//! ```rust,ignore
+//! use fastlib::{Definitions, Severity};
+//!
+//! for d in Definitions::validate_xml(include_str!("templates.xml")) {
+//!     println!("{d}"); // e.g. "12:9: error: [ERR S5] default operator of MDEntryPx has no initial value"
+//! }
+//! let (definitions, warnings) = Definitions::new_from_xml_validated(include_str!("templates.xml"))?;
+//! ```
+//!
+//! ## Write templates to XML
+//!
+//! `Definitions::to_xml()` writes the definitions back as a canonical template document, e.g. to normalize
+//! vendor template files. Type definitions are inlined and attributes with default values are omitted:
+//!
+//! ```rust,ignore
+//! let definitions = Definitions::new_from_xml(include_str!("templates.xml"))?;
+//! std::fs::write("normalized.xml", definitions.to_xml())?;
+//! ```
+//!
+//! ## Decoder limits
+//!
+//! The decoder rejects messages with too long strings, byte vectors or sequences, too deep nesting or too many bytes,
+//! so corrupt or hostile input can't exhaust memory or the stack. See [`DecoderLimits`] for the defaults:
+//!
+//! ```rust,ignore
+//! use fastlib::{Decoder, DecoderLimits};
+//!
+//! let mut decoder = Decoder::new_from_xml(include_str!("templates.xml"))?;
+//! decoder.set_limits(DecoderLimits {
+//!     max_message_bytes: 1500,
+//!     ..DecoderLimits::default()
+//! });
+//! ```
+//!
+//! ## Error codes
+//!
+//! Errors defined by the FAST specification are reported as [`Error::Spec`] with an [`ErrorCode`].
+//! Reportable errors (e.g. overlong encodings) are collected as [`Decoder::warnings`] of the last decoded message,
+//! unless [`ReportPolicy::Fail`] is set:
+//!
+//! ```rust,ignore
+//! use fastlib::{Decoder, ReportPolicy};
+//!
+//! decoder.set_report_policy(ReportPolicy::Fail);
+//! if let Err(err) = decoder.decode_vec(raw_data, &mut msg) {
+//!     println!("{:?}: {err}", err.code());
+//! }
+//! ```
+//!
+//! Errors that occur inside a message are wrapped into [`Error::Context`] that tells where the error occurred:
+//! the template, the path of the field (e.g. `MDIncRefresh/MDEntries[3]/MDEntryPx/mantissa`), the byte offset in the message
+//! and the presence map bit. The location is part of the error message. Use [`Error::kind`] to match the underlying error:
+//!
+//! ```rust,ignore
+//! use fastlib::Error;
+//!
+//! match decoder.decode_vec(raw_data, &mut msg) {
+//!     Err(err) if matches!(err.kind(), Error::UnexpectedEof) => { /* truncated message */ }
+//!     Err(err) => println!("{err}; field: {:?}", err.context().map(|c| &c.path)),
+//!     Ok(()) => {}
+//! }
+//! ```
+//!
+//! ## Decode framed messages
+//!
+//! Messages wrapped into transport framing are decoded with [`Decoder::decode_frame`] (and friends) using
+//! one of built-in [`Framing`] implementations: [`BlockSizeFraming`], [`SequenceNumberFraming`], [`LengthPrefixFraming`].
+//! The frame header is returned alongside the decoded message:
+//!
+//! ```rust,ignore
+//! use fastlib::{Decoder, JsonMessageFactory, SequenceNumberFraming};
+//!
+//! let mut msg = JsonMessageFactory::new();
+//! let (seq_num, _) = decoder.decode_frame_buffer(&mut SequenceNumberFraming, &packet, &mut msg)?;
+//! ```
+//!
+//! Framed messages are encoded with [`Encoder::encode_frame`], [`Encoder::encode_frame_vec`] or [`Encoder::encode_frame_stream`].
+//!
 //! ## Decode using own message factory
 //!
 //! **NOTE:** Decoding using own message factory is only required if very specific process of decoding
@@ -175,12 +478,36 @@
 //!
 //! For message factory implementation examples see [`TextMessageFactory`] and [`JsonMessageFactory`].
 //!
+//! ### Zero-copy decoding
+//!
+//! A message factory that implements [`MessageFactoryRef`] trait receives [`ValueRef`] values which borrow
+//! unicode strings and byte vectors from the decoded buffer instead of copying them:
+//!
+//! ```rust,ignore
+//! use fastlib::{MessageFactoryRef, ValueRef};
+//!
+//! impl<'a> MessageFactoryRef<'a> for MyMessageFactory<'a> {
+//!     fn set_value(&mut self, id: u32, name: &str, value: Option<ValueRef<'a>>) {
+//!         // ... keep the borrowed value ...
+//!     }
+//!     // ... other callbacks ...
+//! }
+//!
+//! let consumed = decoder.decode_buffer_ref(&raw_data, &mut msg)?;
+//! ```
+//!
+//! With `shared-bytes` feature, `Decoder::decode_bytes_ref` decodes from [`bytes::Bytes`] and passes unicode strings
+//! and byte vectors as reference-counted sub-slices of it, which outlive the decoding call.
+//!
 //! ## Features
 //!
 //! Name           | Default?
 //! ---------------|---
 //! `serde`        | ✔
 //! `rust_decimal` |
+//! `chrono`       |
+//! `time`         |
+//! `shared-bytes` |
 //!
 //! ### `serde`
 //!
@@ -192,15 +519,50 @@
 //! Provides `From<Decimal>` implementation for [`rust_decimal::Decimal`] and `TryFrom<rust_decimal::Decimal>` for [`Decimal`].
 //!
 //! [`rust_decimal`]: https://docs.rs/rust_decimal/latest/rust_decimal/
+//!
+//! ### `chrono`
+//!
+//! Enables integration with [`chrono`] library.
+//! Provides conversions between [`Timestamp`], [`Date`], [`TimeOfDay`] and `DateTime<Utc>`, `NaiveDate`, `NaiveTime`.
+//!
+//! [`chrono`]: https://docs.rs/chrono/latest/chrono/
+//!
+//! ### `time`
+//!
+//! Enables integration with [`time`] library.
+//! Provides conversions between [`Timestamp`], [`Date`], [`TimeOfDay`] and `OffsetDateTime`, `time::Date`, `time::Time`.
+//!
+//! [`time`]: https://docs.rs/time/latest/time/
+//!
+//! ### `shared-bytes`
+//!
+//! Enables `Decoder::decode_bytes_ref` that decodes unicode strings and byte vectors as reference-counted
+//! sub-slices of the [`bytes::Bytes`] buffer.
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_possible_wrap)]
 #![allow(clippy::cast_sign_loss)]
 #![allow(clippy::option_option)]
-pub use base::message::{MessageFactory, MessageVisitor};
-pub use base::{decimal::Decimal, value::Value, value::ValueType};
-pub use decoder::{decoder::Decoder, reader::Reader};
+pub use base::instruction::Instruction;
+pub use base::message::{MessageFactory, MessageFactoryRef, MessageVisitor};
+pub use base::time::{Date, TimeOfDay, TimeUnit, Timestamp};
+pub use base::types::{Dictionary, Element, Operator, Presence, Template, TypeRef};
+pub use base::{decimal::Decimal, value::Value, value::ValueRef, value::ValueType};
+pub use codegen::{CodeGenerator, DecimalType, TimeType};
+pub use common::builder::TemplateBuilder;
+pub use common::definitions::Definitions;
+pub use common::framing::{BlockSizeFraming, Framing, LengthPrefixFraming, SequenceNumberFraming};
+pub use common::validator::{Diagnostic, Severity};
+pub use decoder::{
+    decoder::{Decoder, ReportPolicy},
+    iter::MessageIter,
+    limits::DecoderLimits,
+    reader::Reader,
+};
 pub use encoder::{encoder::Encoder, writer::Writer};
 pub use text::{JsonMessageFactory, TextMessageFactory, TextMessageVisitor};
+pub use typed::{
+    FastFields, FastMessage, FieldDecoder, FieldEncoder, FieldValue, GroupValue, SequenceValue,
+};
 
 #[cfg(feature = "serde")]
 pub use de::*;
@@ -208,10 +570,12 @@ pub use de::*;
 pub use ser::*;
 
 mod base;
+mod codegen;
 mod common;
 mod decoder;
 mod encoder;
 mod text;
+mod typed;
 mod utils;
 
 #[cfg(feature = "serde")]
@@ -248,6 +612,42 @@ pub enum Error {
     #[error("Unexpected end of file/stream reached")]
     UnexpectedEof,
 
+    /// Errors defined by the FAST specification. See [`ErrorCode`].
+    #[error("[{0}] {1}")]
+    Spec(ErrorCode, String),
+
+    /// Integer encoded in the stream doesn't fit into 64 bits.
+    #[error("Integer overflow: encoded value doesn't fit into 64 bits")]
+    IntegerOverflow,
+
+    /// String or byte vector is longer than [`DecoderLimits`] allow.
+    #[error("Length {0} exceeds the limit of {1} bytes")]
+    LengthLimitExceeded(u64, usize),
+
+    /// Sequence has more elements than [`DecoderLimits`] allow.
+    #[error("Sequence length {0} exceeds the limit of {1}")]
+    SequenceLimitExceeded(u32, u32),
+
+    /// Groups, sequences and template references are nested deeper than [`DecoderLimits`] allow.
+    #[error("Nesting depth exceeds the limit of {0}")]
+    NestingLimitExceeded(usize),
+
+    /// Message is larger than [`DecoderLimits`] allow.
+    #[error("Message size exceeds the limit of {0} bytes")]
+    MessageSizeLimitExceeded(u64),
+
+    /// Template definitions have errors. Contains all diagnostics, see [`Definitions::validate_xml`].
+    #[error("Invalid template definitions: {}", .0.iter().find(|d| d.severity == Severity::Error).map(ToString::to_string).unwrap_or_default())]
+    Validation(Vec<Diagnostic>),
+
+    /// Error occurred while decoding or encoding a message, with its location in the message.
+    /// Use [`Error::kind`] to match the underlying error.
+    #[error("{source} ({context})")]
+    Context {
+        context: Box<ErrorContext>,
+        source: Box<Error>,
+    },
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
@@ -263,3 +663,227 @@ pub enum Error {
     #[error(transparent)]
     XMLTreeError(#[from] roxmltree::Error),
 }
+
+impl Error {
+    /// Returns the FAST specification error code of the error, if any.
+    #[must_use]
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self.kind() {
+            Error::Spec(code, _) => Some(*code),
+            Error::IntegerOverflow => Some(ErrorCode::D2),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error without its location, e.g. to match it by kind:
+    ///
+    /// ```rust,ignore
+    /// match decoder.decode_vec(raw_data, &mut msg) {
+    ///     Err(e) if matches!(e.kind(), Error::UnexpectedEof) => { ... }
+    ///     ...
+    /// }
+    /// ```
+    #[must_use]
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.kind(),
+            e => e,
+        }
+    }
+
+    /// Returns the location in the message where the error occurred, if known.
+    #[must_use]
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    // Adds the name of the enclosing element to the front of the location path.
+    // The presence map position is recorded by the innermost element only.
+    pub(crate) fn within(self, name: &str, pmap_bit: Option<usize>) -> Self {
+        let innermost = !matches!(self, Error::Context { .. });
+        self.with_context(|c| {
+            if innermost {
+                c.pmap_bit = pmap_bit;
+            }
+            if c.path.is_empty() {
+                c.path = name.to_string();
+            } else if c.path.starts_with('[') {
+                c.path.insert_str(0, name);
+            } else {
+                c.path.insert_str(0, &format!("{name}/"));
+            }
+        })
+    }
+
+    // Records the message template the error occurred in.
+    pub(crate) fn in_template(self, id: u32, name: &str) -> Self {
+        self.within(name, None).with_context(|c| {
+            c.template_id = Some(id);
+            c.template_name = Some(name.to_string());
+        })
+    }
+
+    // Records the byte offset in the message the error was detected at.
+    pub(crate) fn at_offset(self, offset: u64) -> Self {
+        self.with_context(|c| c.offset = Some(offset))
+    }
+
+    // `Error::Eof` marks the end of a stream of messages, so it is never wrapped.
+    fn with_context(self, f: impl FnOnce(&mut ErrorContext)) -> Self {
+        match self {
+            Error::Eof => Error::Eof,
+            Error::Context {
+                mut context,
+                source,
+            } => {
+                f(&mut context);
+                Error::Context { context, source }
+            }
+            e => {
+                let mut context = Box::default();
+                f(&mut context);
+                Error::Context {
+                    context,
+                    source: Box::new(e),
+                }
+            }
+        }
+    }
+}
+
+/// Location in a message where decoding or encoding failed. Attached to errors as [`Error::Context`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Identifier of the message template, if known.
+    pub template_id: Option<u32>,
+
+    /// Name of the message template, if known.
+    pub template_name: Option<String>,
+
+    /// Path of the failed instruction in the message, e.g. `MDIncRefresh/MDEntries[3]/MDEntryPx/mantissa`.
+    /// Fields of statically referenced templates are part of the referencing template.
+    pub path: String,
+
+    /// Number of bytes of the message read before the error was detected. Not known while encoding.
+    pub offset: Option<u64>,
+
+    /// Index of the next bit of the current presence map when the error occurred.
+    pub pmap_bit: Option<usize>,
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.template_id {
+            Some(id) => write!(f, "template {id}")?,
+            None => write!(f, "unknown template")?,
+        }
+        if !self.path.is_empty() {
+            write!(f, ", at {}", self.path)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, ", offset {offset}")?;
+        }
+        if let Some(bit) = self.pmap_bit {
+            write!(f, ", pmap bit {bit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Dynamic and reportable error codes defined by the FAST specification.
+///
+/// Dynamic errors (`D*`) always fail decoding. Reportable errors (`R*`) fail decoding or are collected
+/// as warnings depending on the [`ReportPolicy`] of the decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// The template definitions are not well-formed XML or don't conform to the schema.
+    S1,
+    /// An operator is specified for a field of a type to which the operator is not applicable.
+    S2,
+    /// An initial value can't be converted to the type of the field.
+    S3,
+    /// A field with the constant operator has no initial value.
+    S4,
+    /// A mandatory field with the default operator has no initial value.
+    S5,
+    /// The type of a field can't be converted to or from the type of the corresponding application field.
+    D1,
+    /// An integer in the stream does not fall within the bounds of the integer type of the field.
+    D2,
+    /// A decimal value can't be encoded due to the limits of the individual operators on its exponent and mantissa.
+    D3,
+    /// The type of the previous value is not the same as the type of the field of the current operator.
+    D4,
+    /// A mandatory field is not present in the stream, has an undefined previous value and there is no initial value.
+    D5,
+    /// A mandatory field is not present in the stream and has an empty previous value.
+    D6,
+    /// The subtraction length is larger than the length of the base value or does not fall in the range of an int32.
+    D7,
+    /// The name of a static template reference does not point to a known template.
+    D8,
+    /// There is no template associated with a template identifier appearing in the stream.
+    D9,
+    /// A value has a type the field can't be converted from, e.g. a sequence length that is not a `uInt32`.
+    D10,
+    /// A string doesn't match the syntax of the type it is converted to.
+    D11,
+    /// The block size preamble is zero.
+    D12,
+    /// A decimal exponent is out of the \[-63, 63\] range.
+    R1,
+    /// The result of applying a delta or tail to a unicode string is not a valid UTF-8 sequence.
+    R2,
+    /// A unicode string converted to an ASCII string contains non-ASCII characters.
+    R3,
+    /// An integer converted to another integer type does not fit into the range of that type.
+    R4,
+    /// A decimal converted to an integer has a fractional part.
+    R5,
+    /// An integer appears in an overlong encoding.
+    R6,
+    /// A presence map is overlong.
+    R7,
+    /// A presence map contains more bits than required.
+    R8,
+    /// A string appears in an overlong encoding.
+    R9,
+}
+
+impl ErrorCode {
+    /// Returns `true` for static errors, i.e. errors in template definitions.
+    #[must_use]
+    pub fn is_static(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::S1 | ErrorCode::S2 | ErrorCode::S3 | ErrorCode::S4 | ErrorCode::S5
+        )
+    }
+
+    /// Returns `true` for reportable errors, i.e. errors a decoder may choose to ignore.
+    #[must_use]
+    pub fn is_reportable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::R1
+                | ErrorCode::R2
+                | ErrorCode::R3
+                | ErrorCode::R4
+                | ErrorCode::R5
+                | ErrorCode::R6
+                | ErrorCode::R7
+                | ErrorCode::R8
+                | ErrorCode::R9
+        )
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ERR {self:?}")
+    }
+}
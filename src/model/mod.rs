@@ -1,19 +1,31 @@
+use std::sync::Arc;
+
+#[cfg(test)]
 use rustc_hash::FxHashMap as HashMap;
 
+#[cfg(test)]
+use crate::MessageFactory;
 use crate::Result;
+#[cfg(test)]
 use crate::base::message::MessageVisitor;
+#[cfg(test)]
 use crate::utils::stacked::Stacked;
-use crate::{Error, MessageFactory, Value, ValueType};
+use crate::{Error, Value, ValueType};
 
+#[cfg(test)]
 use self::template::TemplateData;
 use self::value::ValueData;
 
 mod decimal;
 pub(crate) mod template;
 pub(crate) mod value;
+pub(crate) mod wire;
 
 /// # Model Factory
 /// Creates a template model that later can be deserialized using Serde.
+/// Messages are deserialized directly from the stream by the wire deserializer, that follows the same data model;
+/// the factory is kept to test it.
+#[cfg(test)]
 #[derive(Debug, PartialEq)]
 pub struct ModelFactory {
     pub data: Option<TemplateData>,
@@ -27,6 +39,7 @@ pub struct ModelFactory {
     ref_num: Stacked<u32>,
 }
 
+#[cfg(test)]
 impl ModelFactory {
     pub fn new() -> Self {
         Self {
@@ -37,6 +50,7 @@ impl ModelFactory {
     }
 }
 
+#[cfg(test)]
 impl MessageFactory for ModelFactory {
     fn start_template(&mut self, _id: u32, name: &str) {
         self.context
@@ -158,8 +172,42 @@ impl MessageFactory for ModelFactory {
     }
 }
 
+/// Converts the data model value of field `name` into a value of the field type.
+pub(crate) fn field_value(
+    name: &str,
+    type_: &ValueType,
+    value: ValueData,
+) -> Result<Option<Value>> {
+    match value {
+        // Rust integer types don't have to match the width of the field type.
+        ValueData::Value(v) => v.map(|v| v.convert_to(type_)).transpose(),
+        // Set can be serialized as a collection of its element names.
+        ValueData::Sequence(items) if *type_ == ValueType::Set => {
+            let names = items
+                .into_iter()
+                .map(|item| match item {
+                    ValueData::Value(Some(Value::Enum(_, name))) => Ok(name),
+                    ValueData::Value(Some(
+                        Value::ASCIIString(name) | Value::UnicodeString(name),
+                    )) => Ok(Arc::from(name)),
+                    _ => Err(Error::Runtime(format!(
+                        "Set {name} element expected to be a name, got {item:?}"
+                    ))),
+                })
+                .collect::<Result<_>>()?;
+            Ok(Some(Value::Set(0, names)))
+        }
+        v => Err(Error::Runtime(format!(
+            "Field {name} expected to be ValueData::Value, got {v:?}"
+        ))),
+    }
+}
+
 /// # Model Visitor
 /// Template model for serialization and message encoding.
+/// Messages are serialized directly to the stream by the wire serializer, that follows the same data model;
+/// the visitor is kept to test it.
+#[cfg(test)]
 pub struct ModelVisitor {
     data: TemplateData,
 
@@ -175,8 +223,8 @@ pub struct ModelVisitor {
     ref_num: Stacked<u32>,
 }
 
+#[cfg(test)]
 impl ModelVisitor {
-    #[allow(unused)]
     pub fn new(data: TemplateData) -> Self {
         Self {
             data,
@@ -187,6 +235,7 @@ impl ModelVisitor {
     }
 }
 
+#[cfg(test)]
 impl MessageVisitor for ModelVisitor {
     fn get_template_name(&mut self) -> Result<String> {
         match self.data.value {
@@ -201,18 +250,13 @@ impl MessageVisitor for ModelVisitor {
         }
     }
 
-    fn get_value(&mut self, name: &str, _type_: &ValueType) -> Result<Option<Value>> {
+    fn get_value(&mut self, name: &str, type_: &ValueType) -> Result<Option<Value>> {
         // SAFETY: the reference to context is always valid because we never modify `self.data`
         let ctx = unsafe { self.context.must_peek().as_ref().unwrap() };
         match ctx {
             ValueData::Group(context) => {
                 if let Some(v) = context.get(name) {
-                    match v {
-                        ValueData::Value(v) => Ok(v.clone()),
-                        _ => Err(Error::Runtime(format!(
-                            "Field {name} expected to be ValueData::Value, got {v:?}"
-                        ))),
-                    }
+                    field_value(name, type_, v.clone())
                 } else {
                     Ok(None)
                 }
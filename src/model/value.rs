@@ -1,13 +1,17 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
 use rustc_hash::{FxBuildHasher, FxHashMap as HashMap};
-use serde::Serialize;
+use serde::de::value::SeqDeserializer;
 use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::{
     SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
     SerializeTupleStruct, SerializeTupleVariant,
 };
+use serde::{Serialize, forward_to_deserialize_any};
 
 use crate::model::template::TemplateData;
-use crate::{Decimal, Error, Value};
+use crate::{Date, Decimal, Error, TimeOfDay, Timestamp, Value, ValueRef};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum ValueData {
@@ -31,6 +35,11 @@ impl<'de> serde::Deserializer<'de> for ValueData {
             ValueData::Value(v) => match v {
                 None => visitor.visit_none(),
                 Some(v) => match v {
+                    Value::Bool(b) => visitor.visit_bool(b),
+                    Value::UInt8(n) => visitor.visit_u8(n),
+                    Value::Int8(n) => visitor.visit_i8(n),
+                    Value::UInt16(n) => visitor.visit_u16(n),
+                    Value::Int16(n) => visitor.visit_i16(n),
                     Value::UInt32(n) => visitor.visit_u32(n),
                     Value::Int32(n) => visitor.visit_i32(n),
                     Value::UInt64(n) => visitor.visit_u64(n),
@@ -38,6 +47,12 @@ impl<'de> serde::Deserializer<'de> for ValueData {
                     Value::Decimal(f) => visitor.visit_f64(f.to_float()),
                     Value::ASCIIString(s) | Value::UnicodeString(s) => visitor.visit_string(s),
                     Value::Bytes(b) => visitor.visit_byte_buf(b),
+                    Value::Enum(_, name) => visitor.visit_str(&name),
+                    Value::Set(_, names) => visitor.visit_seq(set_deserializer(names)),
+                    // Times are deserialized from their ISO 8601 representation.
+                    v @ (Value::Timestamp(_) | Value::Date(_) | Value::TimeOfDay(_)) => {
+                        visitor.visit_string(v.to_string())
+                    }
                 },
             },
             ValueData::Group(_) => self.deserialize_map(visitor),
@@ -48,25 +63,28 @@ impl<'de> serde::Deserializer<'de> for ValueData {
         }
     }
 
-    fn deserialize_bool<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::Static("boolean is not supported".to_string()))
+        // The visitor checks that the value fits into the target type.
+        self.deserialize_any(visitor)
     }
 
-    fn deserialize_i8<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::Static("i8 is not supported".to_string()))
+        // The visitor checks that the value fits into the target type.
+        self.deserialize_any(visitor)
     }
 
-    fn deserialize_i16<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::Static("i16 is not supported".to_string()))
+        // The visitor checks that the value fits into the target type.
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -77,7 +95,7 @@ impl<'de> serde::Deserializer<'de> for ValueData {
             ValueData::Value(v) => match v {
                 None => visitor.visit_none(),
                 Some(v) => match v {
-                    Value::Int32(n) => visitor.visit_i32(n),
+                    Value::Int32(n) | Value::Date(Date { days: n, .. }) => visitor.visit_i32(n),
                     _ => Err(Error::Runtime(format!(
                         "deserialize_i32: data model must be Value::Int32, got: {v:?}"
                     ))),
@@ -97,7 +115,9 @@ impl<'de> serde::Deserializer<'de> for ValueData {
             ValueData::Value(v) => match v {
                 None => visitor.visit_none(),
                 Some(v) => match v {
-                    Value::Int64(n) => visitor.visit_i64(n),
+                    Value::Int64(n) | Value::Timestamp(Timestamp { value: n, .. }) => {
+                        visitor.visit_i64(n)
+                    }
                     _ => Err(Error::Runtime(format!(
                         "deserialize_i64: data model must be Value::Int64, got: {v:?}"
                     ))),
@@ -109,18 +129,20 @@ impl<'de> serde::Deserializer<'de> for ValueData {
         }
     }
 
-    fn deserialize_u8<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::Static("u8 is not supported".to_string()))
+        // The visitor checks that the value fits into the target type.
+        self.deserialize_any(visitor)
     }
 
-    fn deserialize_u16<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::Static("u16 is not supported".to_string()))
+        // The visitor checks that the value fits into the target type.
+        self.deserialize_any(visitor)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -131,7 +153,7 @@ impl<'de> serde::Deserializer<'de> for ValueData {
             ValueData::Value(v) => match v {
                 None => visitor.visit_none(),
                 Some(v) => match v {
-                    Value::UInt32(n) => visitor.visit_u32(n),
+                    Value::UInt32(n) | Value::Enum(n, _) => visitor.visit_u32(n),
                     _ => Err(Error::Runtime(format!(
                         "deserialize_u64: data model must be Value::UInt32, got: {v:?}"
                     ))),
@@ -151,7 +173,9 @@ impl<'de> serde::Deserializer<'de> for ValueData {
             ValueData::Value(v) => match v {
                 None => visitor.visit_none(),
                 Some(v) => match v {
-                    Value::UInt64(n) => visitor.visit_u64(n),
+                    Value::UInt64(n)
+                    | Value::Set(n, _)
+                    | Value::TimeOfDay(TimeOfDay { value: n, .. }) => visitor.visit_u64(n),
                     _ => Err(Error::Runtime(format!(
                         "deserialize_u64: data model must be Value::UInt64, got: {v:?}"
                     ))),
@@ -233,6 +257,10 @@ impl<'de> serde::Deserializer<'de> for ValueData {
                 None => visitor.visit_none(),
                 Some(v) => match v {
                     Value::ASCIIString(s) | Value::UnicodeString(s) => visitor.visit_string(s),
+                    Value::Enum(_, name) => visitor.visit_str(&name),
+                    v @ (Value::Timestamp(_) | Value::Date(_) | Value::TimeOfDay(_)) => {
+                        visitor.visit_string(v.to_string())
+                    }
                     _ => Err(Error::Runtime(format!(
                         "deserialize_string: data model must be Value::ASCIIString or Value::UnicodeString, got: {v:?}"
                     ))),
@@ -323,6 +351,10 @@ impl<'de> serde::Deserializer<'de> for ValueData {
     {
         match self {
             ValueData::Sequence(q) => visitor.visit_seq(SequenceDeserializer::new(q)),
+            // Set is a collection of its element names.
+            ValueData::Value(Some(Value::Set(_, names))) => {
+                visitor.visit_seq(set_deserializer(names))
+            }
             _ => Err(Error::Runtime(format!(
                 "deserialize_seq: data model must be ValueData::Sequence, got {self:?}"
             ))),
@@ -394,6 +426,10 @@ impl<'de> serde::Deserializer<'de> for ValueData {
     {
         match self {
             ValueData::DynamicTemplateRef(t) => t.deserialize_enum(name, variants, visitor),
+            // Enum element is a unit variant.
+            ValueData::Value(Some(Value::Enum(_, name))) => {
+                visitor.visit_enum(name.to_string().into_deserializer())
+            }
             _ => Err(Error::Runtime(format!(
                 "deserialize_enum: data model must be ValueData::DynamicTemplateRef, got: {self:?}"
             ))),
@@ -416,6 +452,10 @@ impl<'de> serde::Deserializer<'de> for ValueData {
     }
 }
 
+fn set_deserializer(names: Vec<Arc<str>>) -> SeqDeserializer<impl Iterator<Item = String>, Error> {
+    SeqDeserializer::new(names.into_iter().map(|n| n.to_string()))
+}
+
 struct GroupDeserializer {
     items: <HashMap<String, ValueData> as IntoIterator>::IntoIter,
     value: Option<ValueData>,
@@ -492,6 +532,355 @@ impl<'de> SeqAccess<'de> for SequenceDeserializer {
     }
 }
 
+/// Deserializes a decoded field value without copying it into the data model.
+/// Follows the same mapping as [`ValueData::Value`]; borrowed strings and byte vectors are passed to the visitor
+/// as borrowed, so they are copied only if the target type owns them.
+pub(crate) struct ValueRefDeserializer<'v>(pub(crate) Option<ValueRef<'v>>);
+
+fn visit_str<'de, V: Visitor<'de>>(visitor: V, s: Cow<'_, str>) -> Result<V::Value, Error> {
+    match s {
+        Cow::Borrowed(s) => visitor.visit_str(s),
+        Cow::Owned(s) => visitor.visit_string(s),
+    }
+}
+
+fn visit_bytes<'de, V: Visitor<'de>>(visitor: V, b: Cow<'_, [u8]>) -> Result<V::Value, Error> {
+    match b {
+        Cow::Borrowed(b) => visitor.visit_bytes(b),
+        Cow::Owned(b) => visitor.visit_byte_buf(b),
+    }
+}
+
+impl<'v> ValueRefDeserializer<'v> {
+    // Unicode strings shared with the input buffer are valid UTF-8.
+    fn string(value: ValueRef<'v>) -> Option<Cow<'v, str>> {
+        match value {
+            ValueRef::ASCIIString(s) | ValueRef::UnicodeString(s) => Some(s),
+            ValueRef::SharedUnicodeString(s) => {
+                Some(Cow::Owned(String::from_utf8_lossy(&s).into_owned()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for ValueRefDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let Some(v) = self.0 else {
+            return visitor.visit_none();
+        };
+        match v {
+            ValueRef::Bool(b) => visitor.visit_bool(b),
+            ValueRef::UInt8(n) => visitor.visit_u8(n),
+            ValueRef::Int8(n) => visitor.visit_i8(n),
+            ValueRef::UInt16(n) => visitor.visit_u16(n),
+            ValueRef::Int16(n) => visitor.visit_i16(n),
+            ValueRef::UInt32(n) => visitor.visit_u32(n),
+            ValueRef::Int32(n) => visitor.visit_i32(n),
+            ValueRef::UInt64(n) => visitor.visit_u64(n),
+            ValueRef::Int64(n) => visitor.visit_i64(n),
+            ValueRef::Decimal(f) => visitor.visit_f64(f.to_float()),
+            ValueRef::Bytes(b) => visit_bytes(visitor, b),
+            ValueRef::SharedBytes(b) => visitor.visit_bytes(&b),
+            ValueRef::Enum(_, name) => visitor.visit_str(&name),
+            ValueRef::Set(_, names) => visitor.visit_seq(set_deserializer(names)),
+            // Times are deserialized from their ISO 8601 representation.
+            ValueRef::Timestamp(t) => visitor.visit_string(t.to_string()),
+            ValueRef::Date(d) => visitor.visit_string(d.to_string()),
+            ValueRef::TimeOfDay(t) => visitor.visit_string(t.to_string()),
+            ValueRef::ASCIIString(s) | ValueRef::UnicodeString(s) => visit_str(visitor, s),
+            ValueRef::SharedUnicodeString(s) => visitor.visit_str(&String::from_utf8_lossy(&s)),
+        }
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(ValueRef::Int32(n) | ValueRef::Date(Date { days: n, .. })) => visitor.visit_i32(n),
+            Some(v) => Err(Error::Runtime(format!(
+                "deserialize_i32: data model must be Value::Int32, got: {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(ValueRef::Int64(n) | ValueRef::Timestamp(Timestamp { value: n, .. })) => {
+                visitor.visit_i64(n)
+            }
+            Some(v) => Err(Error::Runtime(format!(
+                "deserialize_i64: data model must be Value::Int64, got: {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(ValueRef::UInt32(n) | ValueRef::Enum(n, _)) => visitor.visit_u32(n),
+            Some(v) => Err(Error::Runtime(format!(
+                "deserialize_u32: data model must be Value::UInt32, got: {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(
+                ValueRef::UInt64(n)
+                | ValueRef::Set(n, _)
+                | ValueRef::TimeOfDay(TimeOfDay { value: n, .. }),
+            ) => visitor.visit_u64(n),
+            Some(v) => Err(Error::Runtime(format!(
+                "deserialize_u64: data model must be Value::UInt64, got: {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Static("f32 is not supported".to_string()))
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(ValueRef::Decimal(n)) => visitor.visit_f64(n.to_float()),
+            Some(v) => Err(Error::Runtime(format!(
+                "deserialize_f64: data model must be Value::Decimal, got: {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let Some(v) = self.0 else {
+            return visitor.visit_none();
+        };
+        let err = format!(
+            "deserialize_char: data model must be Value::ASCIIString or Value::UnicodeString, got: {v:?}"
+        );
+        let s = Self::string(v).ok_or(Error::Runtime(err))?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if s.len() == 1 => visitor.visit_char(c),
+            _ => Err(Error::Runtime("deserialize_char: data model must be Value::ASCIIString or Value::UnicodeString of length 1".to_string())),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(ValueRef::Enum(_, name)) => visitor.visit_str(&name),
+            Some(ValueRef::Timestamp(t)) => visitor.visit_string(t.to_string()),
+            Some(ValueRef::Date(d)) => visitor.visit_string(d.to_string()),
+            Some(ValueRef::TimeOfDay(t)) => visitor.visit_string(t.to_string()),
+            Some(v) => {
+                let err = format!(
+                    "deserialize_string: data model must be Value::ASCIIString or Value::UnicodeString, got: {v:?}"
+                );
+                visit_str(visitor, Self::string(v).ok_or(Error::Runtime(err))?)
+            }
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(ValueRef::Bytes(b)) => visit_bytes(visitor, b),
+            Some(ValueRef::SharedBytes(b)) => visitor.visit_bytes(&b),
+            Some(v) => Err(Error::Runtime(format!(
+                "deserialize_byte_buf: data model must be Value::Bytes, got: {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            None => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Static("unit is not supported".to_string()))
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Static("unit_struct is not supported".to_string()))
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Static("newtype_struct is not supported".to_string()))
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            // Set is a collection of its element names.
+            Some(ValueRef::Set(_, names)) => visitor.visit_seq(set_deserializer(names)),
+            v => Err(Error::Runtime(format!(
+                "deserialize_seq: data model must be ValueData::Sequence, got {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Static("tuple is not supported".to_string()))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(ValueRef::Decimal(d)) if name == "Decimal" && len == 2 => visitor.visit_seq(d),
+            v if name == "Decimal" && len == 2 => Err(Error::Runtime(format!(
+                "deserialize_tuple_struct: expected Value::Decimal, got {v:?}"
+            ))),
+            v => Err(Error::Runtime(format!(
+                "deserialize_seq: unsupported data model {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Runtime(format!(
+            "deserialize_map: data model must be ValueData::Group, got: {:?}",
+            self.0
+        )))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            // Enum element is a unit variant.
+            Some(ValueRef::Enum(_, name)) => {
+                visitor.visit_enum(name.to_string().into_deserializer())
+            }
+            v => Err(Error::Runtime(format!(
+                "deserialize_enum: data model must be ValueData::DynamicTemplateRef, got: {v:?}"
+            ))),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Static("identifier is not supported".to_string()))
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 u8 u16
+    }
+}
+
 pub(crate) struct ValueDataSerializer;
 
 impl serde::Serializer for ValueDataSerializer {
@@ -505,19 +894,16 @@ impl serde::Serializer for ValueDataSerializer {
     type SerializeStruct = ValueDataGroupSerializer;
     type SerializeStructVariant = Self;
 
-    fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
-        Err(Error::Runtime(format!(
-            "Serialization to {} is not supported",
-            "bool"
-        )))
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(ValueData::Value(Some(Value::Bool(v))))
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        Ok(ValueData::Value(Some(Value::Int32(i32::from(v)))))
+        Ok(ValueData::Value(Some(Value::Int8(v))))
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        Ok(ValueData::Value(Some(Value::Int32(i32::from(v)))))
+        Ok(ValueData::Value(Some(Value::Int16(v))))
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
@@ -529,11 +915,11 @@ impl serde::Serializer for ValueDataSerializer {
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        Ok(ValueData::Value(Some(Value::UInt32(u32::from(v)))))
+        Ok(ValueData::Value(Some(Value::UInt8(v))))
     }
 
     fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        Ok(ValueData::Value(Some(Value::UInt32(u32::from(v)))))
+        Ok(ValueData::Value(Some(Value::UInt16(v))))
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
@@ -598,16 +984,17 @@ impl serde::Serializer for ValueDataSerializer {
         )))
     }
 
+    // Unit variants are elements of enum and set fields; they are resolved by name.
     fn serialize_unit_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
+        variant_index: u32,
+        variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        Err(Error::Runtime(format!(
-            "Serialization to {} is not supported",
-            "unit variant"
-        )))
+        Ok(ValueData::Value(Some(Value::Enum(
+            variant_index,
+            Arc::from(variant),
+        ))))
     }
 
     fn serialize_newtype_struct<T>(
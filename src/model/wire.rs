@@ -0,0 +1,1466 @@
+//! # Wire deserializer and serializer
+//!
+//! Deserializes messages directly from the stream. Serde visitors drive the decoder: fields are decoded
+//! when the visitor asks for their values, so user types are filled without building the intermediate
+//! data model of `ModelFactory`. Keys follow the same data model: fields and groups are keyed by their names,
+//! static template references are inlined, dynamic template references are keyed as `templateRef:N`,
+//! absent optional groups and sequences have no keys.
+//!
+//! Both directions run the template programs compiled with the definitions, see [`crate::common::program`].
+//!
+//! Instructions the visitor doesn't ask for are still decoded, so the dictionaries are updated the same way
+//! as by `Decoder::decode_*` methods. If the visitor fails, the rest of the message is decoded as well
+//! and the stream stays at a message boundary.
+//!
+//! Serialization walks the program operations in lockstep with the serialized value and follows the same
+//! data model, so the output is the same as `Encoder::encode_*` methods produce from `ModelVisitor`.
+//! Only struct fields that come before their instructions are reached are buffered in the data model;
+//! keys without instructions are ignored, instructions without keys are encoded as absent.
+//!
+use std::borrow::Cow;
+
+use bytes::BytesMut;
+use rustc_hash::FxHashMap as HashMap;
+use serde::de::value::{StrDeserializer, StringDeserializer};
+use serde::de::{
+    DeserializeSeed, EnumAccess, IgnoredAny, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::ser::{Impossible, SerializeMap, SerializeSeq, SerializeStruct};
+use serde::{Deserialize, Serialize, forward_to_deserialize_any};
+
+use crate::base::instruction::Instruction;
+use crate::base::pmap::PresenceMap;
+use crate::base::types::Template;
+use crate::base::value::{Value, ValueRef};
+use crate::common::program::{Field, MESSAGE_SCOPE, Op};
+use crate::decoder::decoder::DecoderContext;
+use crate::decoder::reader::{CopyingReader, ReadRaw};
+use crate::encoder::encoder::EncoderContext;
+use crate::model::field_value;
+use crate::model::template::TemplateData;
+use crate::model::value::{ValueData, ValueDataSerializer, ValueRefDeserializer};
+use crate::{Decoder, Encoder, Error, MessageFactory, Reader, Result, Writer};
+
+/// Decodes one message from `rdr` and deserializes it into `T`.
+/// The outer error means the message decoding failed and the stream is not at a message boundary anymore.
+/// The inner error means the message is decoded but `T` failed to deserialize from it.
+pub(crate) fn deserialize_message<'de, T>(
+    decoder: &mut Decoder,
+    rdr: &mut impl Reader,
+) -> Result<Result<T>>
+where
+    T: Deserialize<'de>,
+{
+    deserialize_message_raw(decoder, &mut CopyingReader(rdr))
+}
+
+/// Same as [`deserialize_message`], but strings and byte vectors borrowed from `rdr` are passed to
+/// the visitors without copying them.
+pub(crate) fn deserialize_message_raw<'b, 'de, T>(
+    decoder: &mut Decoder,
+    rdr: &mut impl ReadRaw<'b>,
+) -> Result<Result<T>>
+where
+    T: Deserialize<'de>,
+{
+    let mut state = DecodeState { failed: false };
+    decoder.decode_raw_with(rdr, &mut state, |ctx| {
+        match T::deserialize(MessageDeserializer { ctx: &mut *ctx }) {
+            Err(e) if ctx.msg.failed => Err(e),
+            res => Ok(res),
+        }
+    })
+}
+
+// Decoded values are passed to serde visitors instead of a message factory.
+// The factory only records if decoding failed, to tell decoding errors from deserialization errors.
+struct DecodeState {
+    failed: bool,
+}
+
+impl MessageFactory for DecodeState {
+    fn start_template(&mut self, _id: u32, _name: &str) {}
+    fn stop_template(&mut self) {}
+    fn set_value(&mut self, _id: u32, _name: &str, _value: Option<Value>) {}
+    fn start_sequence(&mut self, _id: u32, _name: &str, _length: u32) {}
+    fn start_sequence_item(&mut self, _index: u32) {}
+    fn stop_sequence_item(&mut self) {}
+    fn stop_sequence(&mut self) {}
+    fn start_group(&mut self, _name: &str) {}
+    fn stop_group(&mut self) {}
+    fn start_template_ref(&mut self, _name: &str, _dynamic: bool) {}
+    fn stop_template_ref(&mut self) {}
+}
+
+type Context<'a, R> = DecoderContext<'a, R, DecodeState>;
+
+// Runs decoding step `f` and records its failure.
+fn decode<'a, 'b, R, T>(
+    ctx: &mut Context<'a, R>,
+    f: impl FnOnce(&mut Context<'a, R>) -> Result<T>,
+) -> Result<T>
+where
+    R: ReadRaw<'b>,
+{
+    let res = f(ctx);
+    if res.is_err() {
+        ctx.msg.failed = true;
+    }
+    res
+}
+
+// Decodes the rest of the message part with `f` after the visitor failed with `err`,
+// unless decoding has failed already.
+fn recover(failed: bool, err: Error, f: impl FnOnce() -> Result<()>) -> Error {
+    if failed {
+        return err;
+    }
+    match f() {
+        Ok(()) => err,
+        Err(e) => e,
+    }
+}
+
+// Decodes the template identifier of a message or of a dynamic template reference and passes the template to the visitor.
+// The template is decoded by its program for `scope`.
+fn visit_template<'de, 'b, R, V>(
+    ctx: &mut Context<'_, R>,
+    visitor: V,
+    scope: usize,
+    dynamic: bool,
+) -> Result<V::Value>
+where
+    R: ReadRaw<'b>,
+    V: Visitor<'de>,
+{
+    decode(ctx, Context::decode_presence_map)?;
+    decode(ctx, Context::decode_template_id)?;
+    let template = decode(ctx, |c| c.current_template())?;
+    let program = decode(ctx, |c| c.program(template, scope))?;
+    let mut value = visitor.visit_enum(TemplateAccess {
+        ctx: &mut *ctx,
+        template,
+        program,
+        dynamic,
+    });
+    if !ctx.msg.failed {
+        ctx.drop_template_id();
+        if let Err(e) = decode(ctx, Context::drop_presence_map) {
+            value = Err(e);
+        }
+    }
+    if dynamic {
+        value
+    } else {
+        value.map_err(|e| e.in_template(template.id, &template.name))
+    }
+}
+
+// Deserializes a message as an enum: the template name is the variant, the template fields are its content.
+struct MessageDeserializer<'c, 'a, R> {
+    ctx: &'c mut Context<'a, R>,
+}
+
+impl<'de, 'b, R: ReadRaw<'b>> serde::Deserializer<'de> for MessageDeserializer<'_, '_, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::Static("message must be enum".to_string()))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_template(self.ctx, visitor, MESSAGE_SCOPE, false)
+    }
+}
+
+// Template of a message or of a dynamic template reference.
+struct TemplateAccess<'c, 'a, R> {
+    ctx: &'c mut Context<'a, R>,
+    template: &'a Template,
+    program: &'a [Op],
+    dynamic: bool,
+}
+
+impl<'b, R: ReadRaw<'b>> TemplateAccess<'_, '_, R> {
+    fn content<T>(self, f: impl FnOnce(SegmentDeserializer<'_, '_, R>) -> Result<T>) -> Result<T> {
+        let ctx = self.ctx;
+        let value = f(SegmentDeserializer {
+            ctx: &mut *ctx,
+            program: self.program,
+            start: 0,
+            end: self.program.len(),
+            has_pmap: false,
+        });
+        // Instructions of a dynamically referenced template are located within that template.
+        if self.dynamic {
+            value.map_err(|e| ctx.located(e, &self.template.name))
+        } else {
+            value
+        }
+    }
+
+    fn skip(self) -> Result<()> {
+        self.content(|s| serde::Deserializer::deserialize_ignored_any(s, IgnoredAny))
+            .map(|_| ())
+    }
+}
+
+impl<'de, 'b, R: ReadRaw<'b>> EnumAccess<'de> for TemplateAccess<'_, '_, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let name: &str = &self.template.name;
+        match seed.deserialize(StrDeserializer::<Error>::new(name)) {
+            Ok(variant) => Ok((variant, self)),
+            Err(e) => Err(recover(self.ctx.msg.failed, e, || self.skip())),
+        }
+    }
+}
+
+impl<'de, 'b, R: ReadRaw<'b>> VariantAccess<'de> for TemplateAccess<'_, '_, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.skip()
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.content(|s| seed.deserialize(s))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let err = Error::Static("template can't be deserialized as tuple variant".to_string());
+        Err(recover(self.ctx.msg.failed, err, || self.skip()))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.content(|s| serde::Deserializer::deserialize_any(s, visitor))
+    }
+}
+
+// Deserializes instructions of a template, a group or a sequence item as a map.
+// The instructions are the operations of the program from `start` up to `end`.
+// If any instruction needs a bit in a presence map, the instructions are represented as a segment.
+struct SegmentDeserializer<'c, 'a, R> {
+    ctx: &'c mut Context<'a, R>,
+    program: &'a [Op],
+    start: usize,
+    end: usize,
+    has_pmap: bool,
+}
+
+impl<'de, 'b, R: ReadRaw<'b>> serde::Deserializer<'de> for SegmentDeserializer<'_, '_, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.has_pmap {
+            decode(self.ctx, Context::decode_presence_map)?;
+        }
+        let mut fields = FieldsAccess::new(&mut *self.ctx, self.program, self.start, self.end);
+        let mut value = match visitor.visit_map(&mut fields) {
+            Ok(value) => fields.finish().map(|()| value),
+            Err(e) => Err(recover(fields.ctx.msg.failed, e, || fields.finish())),
+        };
+        if self.has_pmap
+            && !self.ctx.msg.failed
+            && let Err(e) = decode(self.ctx, Context::drop_presence_map)
+        {
+            value = Err(e);
+        }
+        value
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct
+        seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+// The instruction of the last key whose value is not deserialized yet.
+enum Pending<'a> {
+    Field(&'a Field),
+    // A present group. The nesting depth is entered.
+    Group {
+        group: &'a Instruction,
+        start: usize,
+        end: usize,
+    },
+    // A present sequence. The nesting depth is entered.
+    Sequence {
+        sequence: &'a Instruction,
+        start: usize,
+        end: usize,
+        length: u32,
+    },
+    // A dynamic template reference. The nesting depth is entered.
+    TemplateRef {
+        scope: usize,
+    },
+}
+
+impl<'a> Pending<'a> {
+    // The instruction keyed by its name. Dynamic template references are keyed by their numbers.
+    fn instruction(&self) -> Option<&'a Instruction> {
+        match self {
+            Pending::Field(field) => Some(&field.instruction),
+            Pending::Group { group: i, .. } | Pending::Sequence { sequence: i, .. } => Some(i),
+            Pending::TemplateRef { .. } => None,
+        }
+    }
+}
+
+// Decodes instructions one by one as map entries.
+struct FieldsAccess<'c, 'a, R> {
+    ctx: &'c mut Context<'a, R>,
+    program: &'a [Op],
+    pc: usize,
+    end: usize,
+    // Counter of dynamic template references, used to name them.
+    ref_num: u32,
+    // Counters of the enclosing templates while static template references are inlined.
+    outer: Vec<u32>,
+    pending: Option<Pending<'a>>,
+}
+
+impl<'c, 'a, 'b, R: ReadRaw<'b>> FieldsAccess<'c, 'a, R> {
+    fn new(ctx: &'c mut Context<'a, R>, program: &'a [Op], start: usize, end: usize) -> Self {
+        Self {
+            ctx,
+            program,
+            pc: start,
+            end,
+            ref_num: 0,
+            outer: Vec::new(),
+            pending: None,
+        }
+    }
+
+    // Decodes the instructions the visitor didn't ask for, so the stream and the dictionaries stay in sync.
+    fn finish(&mut self) -> Result<()> {
+        if self.pending.is_some() {
+            self.next_value::<IgnoredAny>()?;
+        }
+        while self.next_key::<IgnoredAny>()?.is_some() {
+            self.next_value::<IgnoredAny>()?;
+        }
+        Ok(())
+    }
+
+    // Moves to the next instruction that has a key and makes it pending. Returns `false` at the end of the instructions.
+    // Groups and sequences are skipped over; their instructions are decoded by the deserializer of the value.
+    fn next_instruction(&mut self) -> Result<bool> {
+        while self.pc < self.end {
+            let op = &self.program[self.pc];
+            self.pc += 1;
+            let ctx = &mut *self.ctx;
+            match op {
+                Op::Field(field) => self.pending = Some(Pending::Field(field)),
+                Op::Group { group, end } => {
+                    decode(ctx, Context::enter_nested).map_err(|e| ctx.located(e, &group.name))?;
+                    let start = self.pc;
+                    self.pc = end + 1;
+                    if group.is_optional() && !ctx.pmap_next_bit_set() {
+                        ctx.leave_nested();
+                        continue;
+                    }
+                    self.pending = Some(Pending::Group {
+                        group,
+                        start,
+                        end: *end,
+                    });
+                }
+                Op::Sequence {
+                    sequence,
+                    length,
+                    end,
+                } => {
+                    let start = self.pc;
+                    self.pc = end + 1;
+                    match decode(ctx, |c| Self::begin_sequence(c, length))
+                        .map_err(|e| ctx.located(e, &sequence.name))?
+                    {
+                        Some(length) => {
+                            self.pending = Some(Pending::Sequence {
+                                sequence,
+                                start,
+                                end: *end,
+                                length,
+                            });
+                        }
+                        None => continue,
+                    }
+                }
+                Op::TemplateRef { .. } => {
+                    decode(ctx, Context::enter_nested)?;
+                    self.outer.push(self.ref_num);
+                    self.ref_num = 0;
+                    continue;
+                }
+                Op::TemplateRefEnd => {
+                    ctx.leave_nested();
+                    self.ref_num = self.outer.pop().unwrap_or_default();
+                    continue;
+                }
+                Op::DynamicTemplateRef { scope } => {
+                    decode(ctx, Context::enter_nested)?;
+                    self.pending = Some(Pending::TemplateRef { scope: *scope });
+                }
+                Op::GroupEnd { .. } | Op::SequenceEnd { .. } => unreachable!(),
+            }
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // Enters a sequence and decodes its length. `None` if an optional sequence is absent.
+    fn begin_sequence(ctx: &mut Context<'a, R>, length: &Field) -> Result<Option<u32>> {
+        ctx.enter_nested()?;
+        let length = ctx.decode_sequence_length(length)?;
+        if length.is_none() {
+            ctx.leave_nested();
+        }
+        Ok(length)
+    }
+}
+
+impl<'de, 'b, R: ReadRaw<'b>> MapAccess<'de> for FieldsAccess<'_, '_, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.pending.is_some() {
+            self.next_value::<IgnoredAny>()?;
+        }
+        if !self.next_instruction()? {
+            return Ok(None);
+        }
+        if let Some(instruction) = self.pending.as_ref().and_then(Pending::instruction) {
+            let name: &str = &instruction.name;
+            seed.deserialize(StrDeserializer::<Error>::new(name))
+                .map(Some)
+        } else {
+            let key = format!("templateRef:{}", self.ref_num);
+            self.ref_num += 1;
+            seed.deserialize(StringDeserializer::<Error>::new(key))
+                .map(Some)
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let Some(pending) = self.pending.take() else {
+            return Err(Error::Runtime(
+                "next_value called before next_key".to_string(),
+            ));
+        };
+        let instruction = pending.instruction();
+        let value = seed.deserialize(FieldDeserializer {
+            ctx: &mut *self.ctx,
+            program: self.program,
+            pending,
+        });
+        match instruction {
+            Some(instruction) => value.map_err(|e| self.ctx.located(e, &instruction.name)),
+            None => value,
+        }
+    }
+}
+
+// Decodes the items of a sequence.
+struct ItemsAccess<'c, 'a, R> {
+    ctx: &'c mut Context<'a, R>,
+    program: &'a [Op],
+    sequence: &'a Instruction,
+    // The operations of the sequence item.
+    start: usize,
+    end: usize,
+    length: u32,
+    index: u32,
+}
+
+impl<'b, R: ReadRaw<'b>> ItemsAccess<'_, '_, R> {
+    // Decodes the items the visitor didn't ask for.
+    fn finish(&mut self) -> Result<()> {
+        while self.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(())
+    }
+}
+
+impl<'de, 'b, R: ReadRaw<'b>> SeqAccess<'de> for ItemsAccess<'_, '_, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.index == self.length {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        // If any instruction of the sequence needs to allocate a bit in a presence map, each element is represented
+        // as a segment in the transfer encoding.
+        seed.deserialize(SegmentDeserializer {
+            ctx: &mut *self.ctx,
+            program: self.program,
+            start: self.start,
+            end: self.end,
+            has_pmap: self.sequence.has_pmap,
+        })
+        .map(Some)
+        .map_err(|e| self.ctx.located(e, &format!("[{index}]")))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.length - self.index) as usize)
+    }
+}
+
+// Deserializes the value of one instruction.
+struct FieldDeserializer<'c, 'a, R> {
+    ctx: &'c mut Context<'a, R>,
+    program: &'a [Op],
+    pending: Pending<'a>,
+}
+
+impl<'b, R: ReadRaw<'b>> FieldDeserializer<'_, '_, R> {
+    fn value(ctx: &mut Context<'_, R>, field: &Field) -> Result<ValueRefDeserializer<'b>> {
+        let instruction = &*field.instruction;
+        let value = decode(ctx, |c| {
+            instruction.typed_value(instruction.extract(c, field.slots)?)
+        })?;
+        Ok(ValueRefDeserializer(value))
+    }
+}
+
+// Field values are deserialized as the data model values without copying them, other instructions are deserialized as structures.
+macro_rules! forward_to_value {
+    ($($method:ident($($arg:ident: $type:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $type,)* visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                match self.pending {
+                    Pending::Field(i) => Self::value(self.ctx, i)?.$method($($arg,)* visitor),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'b, R: ReadRaw<'b>> serde::Deserializer<'de> for FieldDeserializer<'_, '_, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let ctx = self.ctx;
+        match self.pending {
+            Pending::Field(i) => Self::value(ctx, i)?.deserialize_any(visitor),
+            Pending::Group { group, start, end } => {
+                // If any instruction of the group needs to allocate a bit in a presence map, the group is represented
+                // as a segment in the transfer encoding.
+                let value = SegmentDeserializer {
+                    ctx: &mut *ctx,
+                    program: self.program,
+                    start,
+                    end,
+                    has_pmap: group.has_pmap,
+                }
+                .deserialize_any(visitor);
+                ctx.leave_nested();
+                value
+            }
+            Pending::Sequence {
+                sequence,
+                start,
+                end,
+                length,
+            } => {
+                let mut items = ItemsAccess {
+                    ctx: &mut *ctx,
+                    program: self.program,
+                    sequence,
+                    start,
+                    end,
+                    length,
+                    index: 0,
+                };
+                let value = match visitor.visit_seq(&mut items) {
+                    Ok(value) => items.finish().map(|()| value),
+                    Err(e) => Err(recover(items.ctx.msg.failed, e, || items.finish())),
+                };
+                ctx.leave_nested();
+                value
+            }
+            Pending::TemplateRef { scope } => {
+                let value = visit_template(&mut *ctx, visitor, scope, true);
+                ctx.leave_nested();
+                value
+            }
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.pending {
+            Pending::Field(i) => Self::value(self.ctx, i)?.deserialize_option(visitor),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.pending {
+            Pending::Field(i) => {
+                Self::value(self.ctx, i)?.deserialize_enum(name, variants, visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_value! {
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_bytes()
+        deserialize_byte_buf()
+        deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_seq()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_identifier()
+        deserialize_ignored_any()
+    }
+}
+
+/// Serializes `value` and encodes it into `wrt` as one message.
+/// The value is serialized in the order of the template instructions. Struct fields that come in a different order
+/// are buffered until their instructions are reached.
+pub(crate) fn serialize_message<T, W>(encoder: &mut Encoder, wrt: &mut W, value: &T) -> Result<()>
+where
+    T: ?Sized + Serialize,
+    W: Writer,
+{
+    value.serialize(MessageSerializer {
+        ctx: &mut EncoderContext::new(encoder, wrt, &mut ()),
+    })
+}
+
+// Values are taken from serde serializers instead of a message visitor.
+type EncodeContext<'a, W> = EncoderContext<'a, W, ()>;
+
+// Encodes the template identifier and the instructions of a message or of a dynamic template reference.
+// The instructions are the program of the template and are serialized by `f`.
+fn serialize_template<'a, W: Writer>(
+    ctx: &mut EncodeContext<'a, W>,
+    out: &mut BytesMut,
+    template: &'a Template,
+    program: &'a [Op],
+    f: impl FnOnce(ValueSerializer<'_, 'a, W>) -> Result<()>,
+) -> Result<()> {
+    ctx.encode_template_id(out, template.id)?;
+    f(ValueSerializer {
+        ctx: &mut *ctx,
+        out,
+        target: Target::Template(template, Body::of(program)),
+    })
+}
+
+// Encodes the value of a field instruction.
+fn serialize_field<W: Writer>(
+    ctx: &mut EncodeContext<'_, W>,
+    out: &mut BytesMut,
+    field: &Field,
+    value: ValueData,
+) -> Result<()> {
+    let instruction = &*field.instruction;
+    let value = field_value(&instruction.name, &instruction.value_type, value)?;
+    let value = instruction.wire_value(value)?;
+    instruction.inject(ctx, out, value.map(ValueRef::from), field.slots)
+}
+
+macro_rules! not_supported {
+    ($($method:ident$(<$t:ident>)?($($arg:ident: $type:ty),*) -> $ret:ty => $what:literal)*) => {
+        $(
+            fn $method$(<$t: ?Sized + Serialize>)?(self, $($arg: $type),*) -> Result<$ret> {
+                Err(Error::Runtime(format!(
+                    "Serialization to {} is not supported",
+                    $what
+                )))
+            }
+        )*
+    };
+}
+
+// Serializes a message as an enum: the template name is the variant, the template fields are its content.
+struct MessageSerializer<'c, 'a, W> {
+    ctx: &'c mut EncodeContext<'a, W>,
+}
+
+impl<W: Writer> serde::Serializer for MessageSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    not_supported! {
+        serialize_bool(_v: bool) -> () => "bool"
+        serialize_i8(_v: i8) -> () => "i8"
+        serialize_i16(_v: i16) -> () => "i16"
+        serialize_i32(_v: i32) -> () => "i32"
+        serialize_i64(_v: i64) -> () => "i64"
+        serialize_u8(_v: u8) -> () => "u8"
+        serialize_u16(_v: u16) -> () => "u16"
+        serialize_u32(_v: u32) -> () => "u32"
+        serialize_u64(_v: u64) -> () => "u64"
+        serialize_f32(_v: f32) -> () => "f32"
+        serialize_f64(_v: f64) -> () => "f64"
+        serialize_char(_v: char) -> () => "char"
+        serialize_str(_v: &str) -> () => "str"
+        serialize_bytes(_v: &[u8]) -> () => "bytes"
+        serialize_unit_struct(_v: &'static str) -> () => "unit struct"
+        serialize_none() -> () => "none"
+        serialize_some<T>(_value: &T) -> () => "some"
+        serialize_unit() -> () => "unit"
+        serialize_unit_variant(_name: &'static str, _variant_index: u32, _variant: &'static str) -> () => "unit variant"
+        serialize_newtype_struct<T>(_name: &'static str, _value: &T) -> () => "newtype struct"
+        serialize_seq(_len: Option<usize>) -> Self::SerializeSeq => "seq"
+        serialize_tuple(_len: usize) -> Self::SerializeTuple => "tuple"
+        serialize_tuple_struct(_name: &'static str, _len: usize) -> Self::SerializeTupleStruct => "tuple struct"
+        serialize_tuple_variant(_name: &'static str, _variant_index: u32, _variant: &'static str, _len: usize) -> Self::SerializeTupleVariant => "tuple variant"
+        serialize_map(_len: Option<usize>) -> Self::SerializeMap => "map"
+        serialize_struct(_name: &'static str, _len: usize) -> Self::SerializeStruct => "struct"
+        serialize_struct_variant(_name: &'static str, _variant_index: u32, _variant: &'static str, _len: usize) -> Self::SerializeStructVariant => "struct variant"
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let ctx = self.ctx;
+        let template = ctx.template_by_name(variant)?;
+        let mut buf = BytesMut::new();
+        ctx.program(template, MESSAGE_SCOPE)
+            .and_then(|program| {
+                serialize_template(ctx, &mut buf, template, program, |s| value.serialize(s))
+            })
+            .map_err(|e| e.in_template(template.id, &template.name))?;
+
+        let mut buf2 = BytesMut::new();
+        ctx.write_presence_map(&mut buf2)?;
+        buf2.write_buf(buf.as_ref())?;
+
+        ctx.wrt.write_buf(buf2.as_ref()) // presence map + template_id + instructions
+    }
+}
+
+// Operations of a template, a group or a sequence item: from `start` up to `end`.
+#[derive(Clone, Copy)]
+struct Body<'a> {
+    program: &'a [Op],
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Body<'a> {
+    fn of(program: &'a [Op]) -> Self {
+        Self {
+            program,
+            start: 0,
+            end: program.len(),
+        }
+    }
+}
+
+// The instructions a serialized value is encoded with.
+#[derive(Clone, Copy)]
+enum Target<'a> {
+    // Instructions of a message or of a dynamic template reference.
+    Template(&'a Template, Body<'a>),
+    Group(&'a Instruction, Body<'a>),
+    Sequence(&'a Instruction, &'a Field, Body<'a>),
+    // Instructions of an item of the sequence.
+    Item(&'a Instruction, Body<'a>),
+    // A dynamic template reference. The referenced template is encoded by its program for the scope.
+    TemplateRef(usize),
+}
+
+impl<'a> Target<'a> {
+    // The target of the operation at `pc` keyed in a group. `None` for fields.
+    fn of(program: &'a [Op], pc: usize) -> Option<Self> {
+        match &program[pc] {
+            Op::Group { group, end } => Some(Target::Group(
+                group,
+                Body {
+                    program,
+                    start: pc + 1,
+                    end: *end,
+                },
+            )),
+            Op::Sequence {
+                sequence,
+                length,
+                end,
+            } => Some(Target::Sequence(
+                sequence,
+                length,
+                Body {
+                    program,
+                    start: pc + 1,
+                    end: *end,
+                },
+            )),
+            Op::DynamicTemplateRef { scope } => Some(Target::TemplateRef(*scope)),
+            _ => None,
+        }
+    }
+}
+
+// Serializes a value of a template, a group, a sequence or a dynamic template reference.
+struct ValueSerializer<'c, 'a, W> {
+    ctx: &'c mut EncodeContext<'a, W>,
+    out: &'c mut BytesMut,
+    target: Target<'a>,
+}
+
+impl<'c, 'a, W: Writer> ValueSerializer<'c, 'a, W> {
+    fn unexpected(&self, got: &str) -> Error {
+        let expected = match self.target {
+            Target::Template(t, _) => format!("Template {} data expected to be a group", t.name),
+            Target::Group(i, _) => format!("Field {} expected to be a group", i.name),
+            Target::Sequence(i, _, _) => format!("Field {} expected to be a sequence", i.name),
+            Target::Item(..) => "Sequence item expected to be a group".to_string(),
+            Target::TemplateRef(_) => "Template reference expected to be an enum".to_string(),
+        };
+        Error::Runtime(format!("{expected}, got {got}"))
+    }
+
+    // Encodes the target as absent.
+    fn absent(self) -> Result<()> {
+        match self.target {
+            Target::Group(i, _) => {
+                if i.is_optional() {
+                    self.ctx.pmap_set_next_bit(false);
+                    Ok(())
+                } else {
+                    Err(Error::Dynamic(format!(
+                        "Missing mandatory group: {}",
+                        i.name
+                    )))
+                }
+            }
+            Target::Sequence(i, length, _) => {
+                if !i.is_optional() {
+                    return Err(Error::Dynamic(format!(
+                        "Missing mandatory sequence: {}",
+                        i.name
+                    )));
+                }
+                self.ctx.inject_field(self.out, length, None)
+            }
+            Target::TemplateRef(_) => Err(Error::Dynamic(
+                "Missing mandatory template reference: ".to_string(),
+            )),
+            Target::Template(..) | Target::Item(..) => Err(self.unexpected("none")),
+        }
+    }
+
+    // Starts encoding the instructions of a template, a group or a sequence item.
+    // `pending` are the values of the instructions serialized already.
+    fn fields(self, pending: HashMap<String, ValueData>) -> Result<FieldsSerializer<'c, 'a, W>> {
+        let ctx = self.ctx;
+        let (body, segment) = match self.target {
+            Target::Template(_, body) => (body, false),
+            Target::Group(i, body) => {
+                if i.is_optional() {
+                    ctx.pmap_set_next_bit(true);
+                }
+                (body, i.has_pmap)
+            }
+            Target::Item(i, body) => (body, i.has_pmap),
+            Target::Sequence(..) | Target::TemplateRef(_) => {
+                let s = Self { ctx, ..self };
+                return Err(s.unexpected("struct"));
+            }
+        };
+        // If any instruction needs to allocate a bit in a presence map, the instructions are represented as a segment.
+        if segment {
+            ctx.presence_map.push(PresenceMap::new_empty());
+        }
+        Ok(FieldsSerializer {
+            ctx,
+            out: self.out,
+            buf: BytesMut::new(),
+            segment,
+            program: body.program,
+            pc: body.start,
+            end: body.end,
+            ref_num: 0,
+            outer: Vec::new(),
+            pending,
+            key: None,
+        })
+    }
+
+    // Starts encoding the items of a sequence. If the length is unknown, the items are buffered.
+    fn items(self, length: Option<usize>) -> Result<ItemsSerializer<'c, 'a, W>> {
+        let Target::Sequence(sequence, length_field, body) = self.target else {
+            return Err(self.unexpected("seq"));
+        };
+        let ctx = self.ctx;
+        let Some(length) = length else {
+            return Ok(ItemsSerializer {
+                ctx,
+                out: self.out,
+                sequence,
+                length_field,
+                body,
+                length: 0,
+                index: 0,
+                buffered: Some(Vec::new()),
+            });
+        };
+        ctx.inject_field(
+            self.out,
+            length_field,
+            Some(ValueRef::UInt32(length as u32)),
+        )?;
+        Ok(ItemsSerializer {
+            ctx,
+            out: self.out,
+            sequence,
+            length_field,
+            body,
+            length,
+            index: 0,
+            buffered: None,
+        })
+    }
+
+    // Encodes a dynamic template reference. The template instructions are serialized by `f`.
+    fn template_ref(
+        self,
+        name: &str,
+        f: impl FnOnce(ValueSerializer<'_, 'a, W>) -> Result<()>,
+    ) -> Result<()> {
+        let Target::TemplateRef(scope) = self.target else {
+            return Err(self.unexpected(&format!("template reference {name}")));
+        };
+        let ctx = self.ctx;
+        let template = ctx.template_by_name(name)?;
+        let program = ctx.program(template, scope)?;
+        let mut buf = BytesMut::new();
+        ctx.presence_map.push(PresenceMap::new_empty());
+        serialize_template(ctx, &mut buf, template, program, f)
+            .map_err(|e| ctx.located(e, &template.name))?;
+        ctx.write_presence_map(self.out)?;
+        self.out.write_buf(buf.as_ref())
+    }
+
+    // Encodes a value buffered in the data model.
+    fn serialize_data(self, value: Option<ValueData>) -> Result<()> {
+        match (self.target, value) {
+            (_, None | Some(ValueData::None | ValueData::Value(None))) => self.absent(),
+            (
+                Target::Template(..) | Target::Group(..) | Target::Item(..),
+                Some(ValueData::Group(values)),
+            ) => self.fields(values)?.finish(),
+            (Target::Sequence(..), Some(ValueData::Sequence(items))) => {
+                let mut items_ser = self.items(Some(items.len()))?;
+                for item in items {
+                    items_ser.item(|s| s.serialize_data(Some(item)))?;
+                }
+                items_ser.finish()
+            }
+            (Target::TemplateRef(_), Some(ValueData::DynamicTemplateRef(t))) => {
+                let TemplateData { name, value } = *t;
+                self.template_ref(&name, |s| s.serialize_data(Some(value)))
+            }
+            (_, Some(v)) => Err(self.unexpected(&format!("{v:?}"))),
+        }
+    }
+}
+
+// Values that can't be encoded with the instructions of the target are reported in terms of the data model.
+macro_rules! forward_to_data {
+    ($($method:ident($($arg:ident: $type:ty),*))*) => {
+        $(
+            fn $method(self, $($arg: $type),*) -> Result<()> {
+                let value = ValueDataSerializer.$method($($arg),*)?;
+                self.serialize_data(Some(value))
+            }
+        )*
+    };
+}
+
+impl<'c, 'a, W: Writer> serde::Serializer for ValueSerializer<'c, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = ItemsSerializer<'c, 'a, W>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = FieldsSerializer<'c, 'a, W>;
+    type SerializeStruct = FieldsSerializer<'c, 'a, W>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    forward_to_data! {
+        serialize_bool(v: bool)
+        serialize_i8(v: i8)
+        serialize_i16(v: i16)
+        serialize_i32(v: i32)
+        serialize_i64(v: i64)
+        serialize_u8(v: u8)
+        serialize_u16(v: u16)
+        serialize_u32(v: u32)
+        serialize_u64(v: u64)
+        serialize_f32(v: f32)
+        serialize_f64(v: f64)
+        serialize_char(v: char)
+        serialize_str(v: &str)
+        serialize_bytes(v: &[u8])
+        serialize_unit()
+        serialize_unit_struct(name: &'static str)
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.absent()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let value = ValueDataSerializer.serialize_newtype_struct(name, value)?;
+        self.serialize_data(Some(value))
+    }
+
+    // Dynamic template references are serialized as enums: the template name is the variant.
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.template_ref(variant, |s| value.serialize(s))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.items(len)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(self.unexpected("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(self.unexpected(&format!("tuple struct {name}")))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(self.unexpected(&format!("tuple variant {variant}")))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.fields(HashMap::default())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.fields(HashMap::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(self.unexpected(&format!("struct variant {variant}")))
+    }
+}
+
+// Encodes instructions one by one as struct fields or map entries arrive.
+// A value whose instruction isn't reached yet is buffered in the data model until it is.
+struct FieldsSerializer<'c, 'a, W> {
+    ctx: &'c mut EncodeContext<'a, W>,
+    out: &'c mut BytesMut,
+    buf: BytesMut,
+    segment: bool,
+    program: &'a [Op],
+    pc: usize,
+    end: usize,
+    // Counter of dynamic template references, used to name them.
+    ref_num: u32,
+    // Counters of the enclosing templates while static template references are inlined.
+    outer: Vec<u32>,
+    pending: HashMap<String, ValueData>,
+    // The key of a map entry whose value is not serialized yet.
+    key: Option<String>,
+}
+
+impl<'a, W: Writer> FieldsSerializer<'_, 'a, W> {
+    // Returns the next operation that has a key. Static template references are entered and left on the way.
+    fn current(&mut self) -> Option<&'a Op> {
+        while self.pc < self.end {
+            let op = &self.program[self.pc];
+            match op {
+                Op::TemplateRef { .. } => {
+                    self.outer.push(self.ref_num);
+                    self.ref_num = 0;
+                }
+                Op::TemplateRefEnd => self.ref_num = self.outer.pop().unwrap_or_default(),
+                _ => return Some(op),
+            }
+            self.pc += 1;
+        }
+        None
+    }
+
+    // Moves past the current operation, including the instructions of a group or a sequence.
+    fn advance(&mut self, op: &Op) {
+        self.pc = match op {
+            Op::Group { end, .. } | Op::Sequence { end, .. } => end + 1,
+            _ => self.pc + 1,
+        };
+        if let Op::DynamicTemplateRef { .. } = op {
+            self.ref_num += 1;
+        }
+    }
+
+    // The key of the current operation. Dynamic template references are keyed by their numbers.
+    fn key_of(&self, op: &'a Op) -> Cow<'a, str> {
+        if let Op::DynamicTemplateRef { .. } = op {
+            Cow::Owned(format!("templateRef:{}", self.ref_num))
+        } else {
+            Cow::Borrowed(op.name())
+        }
+    }
+
+    fn matches(&self, op: &Op, key: &str) -> bool {
+        if let Op::DynamicTemplateRef { .. } = op {
+            key.strip_prefix("templateRef:")
+                .and_then(|n| n.parse::<u32>().ok())
+                == Some(self.ref_num)
+        } else {
+            op.name() == key
+        }
+    }
+
+    // Instructions other than template references are located by their names.
+    fn locate(&mut self, op: &Op, value: Result<()>) -> Result<()> {
+        if let Op::DynamicTemplateRef { .. } = op {
+            value
+        } else {
+            value.map_err(|e| self.ctx.located(e, op.name()))
+        }
+    }
+
+    fn entry<T>(&mut self, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self.current() {
+            Some(op) if self.matches(op, key) => {
+                let target = Target::of(self.program, self.pc);
+                self.advance(op);
+                let res = match (target, op) {
+                    (Some(target), _) => value.serialize(ValueSerializer {
+                        ctx: &mut *self.ctx,
+                        out: &mut self.buf,
+                        target,
+                    }),
+                    (None, Op::Field(field)) => value
+                        .serialize(ValueDataSerializer)
+                        .and_then(|value| serialize_field(self.ctx, &mut self.buf, field, value)),
+                    (None, _) => unreachable!(),
+                };
+                self.locate(op, res)?;
+                self.flush()
+            }
+            _ => {
+                let value = value.serialize(ValueDataSerializer)?;
+                self.pending.insert(key.to_string(), value);
+                Ok(())
+            }
+        }
+    }
+
+    // Encodes the buffered values whose instructions are reached.
+    fn flush(&mut self) -> Result<()> {
+        while !self.pending.is_empty()
+            && let Some(op) = self.current()
+        {
+            let Some(value) = self.pending.remove(self.key_of(op).as_ref()) else {
+                break;
+            };
+            self.encode_data(op, Some(value))?;
+        }
+        Ok(())
+    }
+
+    // Encodes the current operation from the data model.
+    fn encode_data(&mut self, op: &'a Op, value: Option<ValueData>) -> Result<()> {
+        let target = Target::of(self.program, self.pc);
+        self.advance(op);
+        let res = match (target, op) {
+            (Some(target), _) => ValueSerializer {
+                ctx: &mut *self.ctx,
+                out: &mut self.buf,
+                target,
+            }
+            .serialize_data(value),
+            (None, Op::Field(field)) => serialize_field(
+                self.ctx,
+                &mut self.buf,
+                field,
+                value.unwrap_or(ValueData::Value(None)),
+            ),
+            (None, _) => unreachable!(),
+        };
+        self.locate(op, res)
+    }
+
+    // Encodes the rest of the instructions from the buffered values, the instructions without values are absent.
+    // Values of unknown keys are ignored.
+    fn finish(mut self) -> Result<()> {
+        while let Some(op) = self.current() {
+            let value = self.pending.remove(self.key_of(op).as_ref());
+            self.encode_data(op, value)?;
+        }
+        if self.segment {
+            self.ctx.write_presence_map(self.out)?;
+        }
+        self.out.write_buf(self.buf.as_ref())
+    }
+}
+
+impl<W: Writer> SerializeStruct for FieldsSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Writer> SerializeMap for FieldsSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let ValueData::Value(Some(Value::ASCIIString(s) | Value::UnicodeString(s))) =
+            key.serialize(ValueDataSerializer)?
+        else {
+            return Err(Error::Runtime(
+                "serialize_key: key must be a string".to_string(),
+            ));
+        };
+        self.key = Some(s);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let Some(key) = self.key.take() else {
+            return Err(Error::Runtime(
+                "serialize_value called before serialize_key".to_string(),
+            ));
+        };
+        self.entry(&key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+// Encodes the items of a sequence.
+struct ItemsSerializer<'c, 'a, W> {
+    ctx: &'c mut EncodeContext<'a, W>,
+    out: &'c mut BytesMut,
+    sequence: &'a Instruction,
+    length_field: &'a Field,
+    // The operations of the sequence item.
+    body: Body<'a>,
+    length: usize,
+    index: usize,
+    // Items of a sequence of unknown length. They are encoded when the length is known.
+    buffered: Option<Vec<ValueData>>,
+}
+
+impl<'a, W: Writer> ItemsSerializer<'_, 'a, W> {
+    // Encodes the next item, its instructions are serialized by `f`.
+    fn item(&mut self, f: impl FnOnce(ValueSerializer<'_, 'a, W>) -> Result<()>) -> Result<()> {
+        let index = self.index;
+        self.index += 1;
+        if index >= self.length {
+            return Err(Error::Runtime(format!(
+                "Sequence {} has more items than its length {}",
+                self.sequence.name, self.length
+            )));
+        }
+        f(ValueSerializer {
+            ctx: &mut *self.ctx,
+            out: &mut *self.out,
+            target: Target::Item(self.sequence, self.body),
+        })
+        .map_err(|e| self.ctx.located(e, &format!("[{index}]")))
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some(items) = self.buffered {
+            return ValueSerializer {
+                ctx: self.ctx,
+                out: self.out,
+                target: Target::Sequence(self.sequence, self.length_field, self.body),
+            }
+            .serialize_data(Some(ValueData::Sequence(items)));
+        }
+        if self.index != self.length {
+            return Err(Error::Runtime(format!(
+                "Sequence {} has fewer items than its length {}",
+                self.sequence.name, self.length
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<W: Writer> SerializeSeq for ItemsSerializer<'_, '_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(items) = &mut self.buffered {
+            items.push(value.serialize(ValueDataSerializer)?);
+            return Ok(());
+        }
+        self.item(|s| value.serialize(s))
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
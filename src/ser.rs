@@ -1,9 +1,10 @@
-use std::io::Write;
+use std::io::{Cursor, Write};
 
+use bytes::BytesMut;
 use serde::ser::Serialize;
 
-use crate::model::ModelVisitor;
-use crate::model::template::TemplateData;
+use crate::encoder::writer::StreamWriter;
+use crate::model::wire::serialize_message;
 use crate::{Encoder, Error, Result, Writer};
 
 /// Encode single message into `Vec<u8>`.
@@ -14,13 +15,9 @@ pub fn to_vec<T>(encoder: &mut Encoder, value: &T) -> Result<Vec<u8>>
 where
     T: ?Sized + Serialize,
 {
-    // Serialise user data into internal data model
-    let mut data = TemplateData::new_empty();
-    value.serialize(&mut data)?;
-
-    // Encode FAST message from internal data model
-    let mut msg = ModelVisitor::new(data);
-    encoder.encode_vec(&mut msg)
+    let mut buf = BytesMut::new();
+    serialize_message(encoder, &mut buf, value)?;
+    Ok(buf.to_vec())
 }
 
 /// Encode single message into `bytes::BufMut`.
@@ -31,13 +28,9 @@ pub fn to_bytes<T>(encoder: &mut Encoder, value: &T) -> Result<bytes::BytesMut>
 where
     T: ?Sized + Serialize,
 {
-    // Serialise user data into internal data model
-    let mut data = TemplateData::new_empty();
-    value.serialize(&mut data)?;
-
-    // Encode FAST message from internal data model
-    let mut msg = ModelVisitor::new(data);
-    encoder.encode_bytes(&mut msg)
+    let mut buf = BytesMut::new();
+    serialize_message(encoder, &mut buf, value)?;
+    Ok(buf)
 }
 
 /// Encode single message into object that implements `fastlib::Writer` trait.
@@ -48,13 +41,7 @@ pub fn to_writer<T>(encoder: &mut Encoder, wrt: &mut impl Writer, value: &T) ->
 where
     T: ?Sized + Serialize,
 {
-    // Serialise user data into internal data model
-    let mut data = TemplateData::new_empty();
-    value.serialize(&mut data)?;
-
-    // Encode FAST message from internal data model
-    let mut msg = ModelVisitor::new(data);
-    encoder.encode_writer(wrt, &mut msg)
+    serialize_message(encoder, wrt, value)
 }
 
 /// Encode single message into object that implements `std::io::Write` trait.
@@ -65,13 +52,7 @@ pub fn to_stream<T>(encoder: &mut Encoder, wrt: &mut dyn Write, value: &T) -> Re
 where
     T: ?Sized + Serialize,
 {
-    // Serialise user data into internal data model
-    let mut data = TemplateData::new_empty();
-    value.serialize(&mut data)?;
-
-    // Encode FAST message from internal data model
-    let mut msg = ModelVisitor::new(data);
-    encoder.encode_stream(wrt, &mut msg)
+    serialize_message(encoder, &mut StreamWriter::new(wrt), value)
 }
 
 /// Encode single message into pre-allocated buffer.
@@ -83,13 +64,9 @@ pub fn to_buffer<T>(encoder: &mut Encoder, buffer: &mut [u8], value: &T) -> Resu
 where
     T: ?Sized + Serialize,
 {
-    // Serialise user data into internal data model
-    let mut data = TemplateData::new_empty();
-    value.serialize(&mut data)?;
-
-    // Encode FAST message from internal data model
-    let mut msg = ModelVisitor::new(data);
-    encoder.encode_buffer(buffer, &mut msg)
+    let mut buffer = Cursor::new(buffer);
+    serialize_message(encoder, &mut StreamWriter::new(&mut buffer), value)?;
+    Ok(buffer.position() as usize)
 }
 
 impl serde::ser::Error for Error {
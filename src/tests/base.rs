@@ -2,15 +2,25 @@
 //!
 //! See: https://github.com/mcsakoff/goFAST/tree/main
 //!
+use std::borrow::Cow;
 use std::io::Cursor;
+use std::sync::Arc;
 
 use rustc_hash::FxHashMap as HashMap;
 
+use crate::base::types::TypeRef;
+use crate::common::context::Context;
+use crate::common::definitions::Definitions;
 use crate::decoder::decoder::Decoder;
 use crate::encoder::encoder::Encoder;
 use crate::model::value::ValueData;
 use crate::model::{ModelFactory, ModelVisitor};
-use crate::{Decimal, Error};
+use crate::{
+    BlockSizeFraming, Date, Decimal, DecoderLimits, Error, ErrorCode, ErrorContext,
+    JsonMessageFactory, LengthPrefixFraming, MessageFactoryRef, ReportPolicy, Result,
+    SequenceNumberFraming, Severity, Template, TemplateBuilder, TextMessageFactory,
+    TextMessageVisitor, TimeOfDay, TimeUnit, Timestamp, ValueRef,
+};
 
 use super::*;
 
@@ -19,7 +29,7 @@ fn parse_xml_template() {
     let d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
     test_templates(
         &d,
-        &vec![
+        &[
             TestTemplate {
                 id: 1,
                 name: "Integer",
@@ -853,6 +863,136 @@ fn decode_dynamic_reference() {
     );
 }
 
+#[test]
+fn static_reference_dictionaries() {
+    // Static reference keeps the processing context, so each message has its own template dictionary entry.
+    let xml = r#"<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Common" dictionary="template">
+        <uInt32 name="X" id="1"><copy/></uInt32>
+    </template>
+    <template name="A" id="1"><templateRef name="Common"/></template>
+    <template name="B" id="2"><templateRef name="Common"/></template>
+</templates>"#;
+    let mut e = Encoder::new_from_xml(xml).unwrap();
+    let mut d = Decoder::new_from_xml(xml).unwrap();
+    for (text, raw) in [
+        ("A=<X=5>", vec![0xe0, 0x81, 0x85]),
+        ("B=<X=5>", vec![0xe0, 0x82, 0x85]),
+        ("A=<X=5>", vec![0xc0, 0x81]),
+        ("B=<X=6>", vec![0xe0, 0x82, 0x86]),
+    ] {
+        let encoded = e
+            .encode_vec(&mut TextMessageVisitor::from_text(text).unwrap())
+            .unwrap();
+        assert_eq!(encoded, raw, "{text}");
+        let mut msg = TextMessageFactory::new();
+        d.decode_vec(raw, &mut msg).unwrap();
+        assert_eq!(msg.text, text);
+    }
+}
+
+#[test]
+fn nested_sequences_and_groups() {
+    let xml = r#"<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Outer" id="1">
+        <uInt32 name="A" id="1"><copy/></uInt32>
+        <sequence name="Items">
+            <length name="NoItems" id="6"/>
+            <uInt32 name="B" id="2"><increment/></uInt32>
+            <group name="G" presence="optional">
+                <uInt32 name="C" id="3"><copy/></uInt32>
+                <sequence name="Inner">
+                    <length name="NoInner" id="7"/>
+                    <uInt32 name="D" id="4"><copy/></uInt32>
+                </sequence>
+            </group>
+        </sequence>
+        <group name="Tail">
+            <uInt32 name="E" id="5"/>
+        </group>
+    </template>
+</templates>"#;
+    let mut e = Encoder::new_from_xml(xml).unwrap();
+    let mut d = Decoder::new_from_xml(xml).unwrap();
+    for text in [
+        "Outer=<A=1|Items=<B=1|G=<C=2|Inner=<D=3><D=3><D=4>>><B=2><B=3|G=<C=2|Inner=<D=4>>>|Tail=<E=5>>",
+        "Outer=<A=1|Items=<B=4|G=<C=7|Inner=<D=4><D=5>>>|Tail=<E=6>>",
+        "Outer=<A=2|Items=<B=5><B=6>|Tail=<E=7>>",
+    ] {
+        let raw = e
+            .encode_vec(&mut TextMessageVisitor::from_text(text).unwrap())
+            .unwrap();
+        let mut msg = TextMessageFactory::new();
+        d.decode_vec(raw, &mut msg).unwrap();
+        assert_eq!(msg.text, text);
+    }
+}
+
+#[test]
+fn dynamic_reference_dictionaries() {
+    // A dynamically referenced template uses the application type of the element the reference is in,
+    // so `Y` has separate entries in the type dictionary without application type and in the one of `Quote`.
+    let xml = r#"<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="T" id="3" dictionary="type">
+        <uInt32 name="Y" id="1"><copy/></uInt32>
+    </template>
+    <template name="Ref" id="2" dictionary="template">
+        <uInt32 name="X" id="2"><copy/></uInt32>
+    </template>
+    <template name="A" id="1">
+        <group name="G" dictionary="type" typeRef="Quote">
+            <templateRef/>
+        </group>
+        <sequence name="S">
+            <length name="N" id="3"/>
+            <templateRef/>
+        </sequence>
+    </template>
+</templates>"#;
+    let template = |name: &str, field: &str, value: u32| TemplateData {
+        name: name.to_string(),
+        value: ValueData::Group(HashMap::from_iter([(
+            field.to_string(),
+            ValueData::Value(Some(Value::UInt32(value))),
+        )])),
+    };
+    let template_ref = |t: TemplateData| {
+        ValueData::Group(HashMap::from_iter([(
+            "templateRef:0".to_string(),
+            ValueData::DynamicTemplateRef(Box::new(t)),
+        )]))
+    };
+    let a = TemplateData {
+        name: "A".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            ("G".to_string(), template_ref(template("T", "Y", 6))),
+            (
+                "S".to_string(),
+                ValueData::Sequence(vec![
+                    template_ref(template("Ref", "X", 7)),
+                    template_ref(template("Ref", "X", 7)),
+                ]),
+            ),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(xml).unwrap();
+    let mut d = Decoder::new_from_xml(xml).unwrap();
+    for (data, raw) in [
+        (template("T", "Y", 5), Some(vec![0xe0, 0x83, 0x85])),
+        (a, None),
+        // `Y` is copied from the first message, the template identifier is the last referenced one.
+        (template("T", "Y", 5), Some(vec![0xc0, 0x83])),
+    ] {
+        let encoded = e.encode_vec(&mut ModelVisitor::new(data.clone())).unwrap();
+        if let Some(raw) = raw {
+            assert_eq!(encoded, raw, "{}", data.name);
+        }
+        let mut msg = ModelFactory::new();
+        d.decode_vec(encoded, &mut msg).unwrap();
+        assert_eq!(msg.data.unwrap(), data);
+    }
+}
+
 #[test]
 fn decode_eof() {
     let mut r: Cursor<Vec<u8>> = Cursor::new(vec![]);
@@ -861,7 +1001,7 @@ fn decode_eof() {
     let res = d.decode_stream(&mut r, &mut msg);
     match res {
         Err(Error::Eof) => {}
-        _ => assert!(false, "Expected Eof"),
+        _ => panic!("Expected Eof"),
     }
 }
 
@@ -872,9 +1012,1703 @@ fn decode_unexpected_eof() {
     let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
     let res = d.decode_stream(&mut r, &mut msg);
     match res {
-        Err(Error::UnexpectedEof) => {}
-        Err(e) => assert!(false, "Unexpected error: {:?}", e),
-        Ok(_) => assert!(false, "Expected Err(UnexpectedEof)"),
+        Err(e) if matches!(e.kind(), Error::UnexpectedEof) => {}
+        Err(e) => panic!("Unexpected error: {:?}", e),
+        Ok(_) => panic!("Expected Err(UnexpectedEof)"),
+    }
+}
+
+#[test]
+fn decode_messages_iter() {
+    let raw = vec![
+        0xc0, 0x86, 0x81, 0x80, 0x82, // Group
+        0xe0, 0x88, 0x86, 0x87, // StaticReference
+    ];
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut r = Cursor::new(raw);
+    let mut it = d.iter_stream(&mut r, ModelFactory::new);
+    let (offset, msg) = it.next().unwrap().unwrap();
+    assert_eq!(offset, 0);
+    assert_eq!(msg.data.unwrap().name, "Group");
+    let (offset, msg) = it.next().unwrap().unwrap();
+    assert_eq!(offset, 5);
+    assert_eq!(msg.data.unwrap().name, "StaticReference");
+    assert_eq!(it.offset(), 9);
+    assert!(it.next().is_none());
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn decode_messages_iter_truncated() {
+    let mut raw = bytes::Bytes::from_static(&[
+        0xc0, 0x86, 0x81, 0x80, 0x82, // Group
+        0xe0, 0x88, 0x86, // truncated StaticReference
+    ]);
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut it = d.iter_reader(&mut raw, ModelFactory::new);
+    let (offset, _) = it.next().unwrap().unwrap();
+    assert_eq!(offset, 0);
+    match it.next() {
+        Some(Err(e)) if matches!(e.kind(), Error::UnexpectedEof) => {}
+        r => panic!(
+            "Expected Err(UnexpectedEof), got {:?}",
+            r.map(|r| r.map(|(o, _)| o))
+        ),
+    }
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn decode_encode_frames() {
+    let raw = vec![
+        0x85, 0xc0, 0x86, 0x81, 0x80, 0x82, // block size + Group
+        0x84, 0xe0, 0x88, 0x86, 0x87, // block size + StaticReference
+    ];
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut e = Encoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut framing = BlockSizeFraming;
+    let mut offset = 0;
+    let mut encoded = Vec::new();
+    for name in ["Group", "StaticReference"] {
+        let mut msg = ModelFactory::new();
+        let ((), n) = d
+            .decode_frame_buffer(&mut framing, &raw[offset..], &mut msg)
+            .unwrap();
+        offset += n as usize;
+        let data = msg.data.unwrap();
+        assert_eq!(data.name, name);
+        let mut msg = ModelVisitor::new(data);
+        encoded.extend(e.encode_frame_vec(&mut framing, &(), &mut msg).unwrap());
+    }
+    assert_eq!(offset, raw.len());
+    assert_eq!(encoded, raw);
+    let mut msg = ModelFactory::new();
+    match d.decode_frame_buffer(&mut framing, &raw[offset..], &mut msg) {
+        Err(Error::Eof) => {}
+        r => panic!("Expected Err(Eof), got {:?}", r.map(|(_, n)| n)),
+    }
+}
+
+#[test]
+fn decode_frames_errors() {
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut framing = LengthPrefixFraming::U16BigEndian;
+    // the frame is longer than the message; the reader is left at the next frame
+    let mut raw = bytes::Bytes::from_static(&[
+        0x00, 0x06, 0xc0, 0x86, 0x81, 0x80, 0x82, 0xff, // Group + extra byte
+        0x00, 0x05, 0xc0, 0x86, 0x81, 0x80, 0x82, // Group
+    ]);
+    let mut msg = ModelFactory::new();
+    match d.decode_frame(&mut framing, &mut raw, &mut msg) {
+        Err(Error::Runtime(_)) => {}
+        r => panic!("Expected Err(Runtime), got {r:?}"),
+    }
+    let mut msg = ModelFactory::new();
+    d.decode_frame(&mut framing, &mut raw, &mut msg).unwrap();
+    assert!(raw.is_empty());
+    // the message fails in the middle of the frame; the reader is left at the next frame
+    let mut raw = bytes::Bytes::from_static(&[
+        0x00, 0x05, 0xc0, 0xff, 0x81, 0x80, 0x82, // unknown template
+        0x00, 0x05, 0xc0, 0x86, 0x81, 0x80, 0x82, // Group
+    ]);
+    let mut msg = ModelFactory::new();
+    assert!(d.decode_frame(&mut framing, &mut raw, &mut msg).is_err());
+    let mut msg = ModelFactory::new();
+    d.decode_frame(&mut framing, &mut raw, &mut msg).unwrap();
+    assert_eq!(msg.data.unwrap().name, "Group");
+    assert!(raw.is_empty());
+    // the frame is shorter than the message
+    let mut raw = bytes::Bytes::from_static(&[0x00, 0x03, 0xc0, 0x86, 0x81, 0x80, 0x82]);
+    let mut msg = ModelFactory::new();
+    match d.decode_frame(&mut framing, &mut raw, &mut msg) {
+        Err(e) if matches!(e.kind(), Error::UnexpectedEof) => {}
+        r => panic!("Expected Err(UnexpectedEof), got {r:?}"),
+    }
+    // the message fails and the stream ends before the end of the frame
+    let mut raw = bytes::Bytes::from_static(&[0x00, 0x10, 0xc0, 0xff, 0x81]);
+    let mut msg = ModelFactory::new();
+    match d.decode_frame(&mut framing, &mut raw, &mut msg) {
+        Err(e) if e.code() == Some(ErrorCode::D9) => {}
+        r => panic!("Expected Err(D9), got {r:?}"),
+    }
+    // the frame is longer than the message size limit; the payload is not read
+    d.set_limits(DecoderLimits {
+        max_message_bytes: 8,
+        ..DecoderLimits::default()
+    });
+    let mut raw = bytes::Bytes::from_static(&[0x00, 0x09, 0xc0, 0x86, 0x81, 0x80, 0x82]);
+    let mut msg = ModelFactory::new();
+    match d.decode_frame(&mut framing, &mut raw, &mut msg) {
+        Err(Error::MessageSizeLimitExceeded(8)) => {}
+        r => panic!("Expected Err(MessageSizeLimitExceeded), got {r:?}"),
+    }
+    assert_eq!(raw.len(), 5);
+    d.set_limits(DecoderLimits::default());
+    // the header without a message
+    let mut raw = bytes::Bytes::from_static(&[0x01, 0x00, 0x00, 0x00]);
+    let mut msg = ModelFactory::new();
+    match d.decode_frame(&mut SequenceNumberFraming, &mut raw, &mut msg) {
+        Err(e) if matches!(e.kind(), Error::UnexpectedEof) => {}
+        r => panic!("Expected Err(UnexpectedEof), got {r:?}"),
+    }
+}
+
+#[test]
+fn decode_sequence_number_frame() {
+    let raw = [0x2a, 0x00, 0x00, 0x00, 0xe0, 0x88, 0x86, 0x87];
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut msg = ModelFactory::new();
+    let (seq_num, n) = d
+        .decode_frame_buffer(&mut SequenceNumberFraming, &raw, &mut msg)
+        .unwrap();
+    assert_eq!(seq_num, 42);
+    assert_eq!(n, 8);
+    assert_eq!(msg.data.unwrap().name, "StaticReference");
+}
+
+#[test]
+fn decode_transactional() {
+    // the message is truncated after the copy decimal updated the dictionary
+    let raw = [0xf8, 0x84, 0xfe, 0x04, 0x83];
+
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut msg = ModelFactory::new();
+    assert!(d.decode_buffer(&raw, &mut msg).is_err());
+    assert_ne!(d.context, Context::new(d.definitions.slots));
+
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    d.set_transactional(true);
+    let mut msg = ModelFactory::new();
+    match d.decode_buffer(&raw, &mut msg) {
+        Err(e) if matches!(e.kind(), Error::UnexpectedEof) => {}
+        r => panic!("Expected Err(UnexpectedEof), got {r:?}"),
+    }
+    assert_eq!(d.context, Context::new(d.definitions.slots));
+}
+
+#[test]
+fn try_decode_partial_buffer() {
+    let raw = [
+        0xf8, 0x84, 0xfe, 0x04, 0x83, 0xff, 0x0c, 0x8a, 0xfc, 0xa0, 0x80, // Decimal
+        0xd8, 0x84, 0xff, 0x0c, 0x8a, 0xfc, 0x80,
+        0x80, // Decimal with copied value and zero delta
+    ];
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut offset = 0;
+    let mut decoded = Vec::new();
+    // feed the data one byte at a time
+    for end in 0..=raw.len() {
+        let mut msg = ModelFactory::new();
+        if let Some(n) = d.try_decode_buffer(&raw[offset..end], &mut msg).unwrap() {
+            offset += n as usize;
+            decoded.push(msg.data.unwrap());
+        }
+    }
+    assert_eq!(offset, raw.len());
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0], decoded[1]);
+}
+
+// Collects field values borrowed from the decoded buffer.
+#[derive(Default)]
+struct BorrowingFactory<'a> {
+    values: Vec<(String, Option<ValueRef<'a>>)>,
+}
+
+impl<'a> MessageFactoryRef<'a> for BorrowingFactory<'a> {
+    fn start_template(&mut self, _id: u32, _name: &str) {}
+    fn stop_template(&mut self) {}
+    fn set_value(&mut self, _id: u32, name: &str, value: Option<ValueRef<'a>>) {
+        self.values.push((name.to_string(), value));
+    }
+    fn start_sequence(&mut self, _id: u32, _name: &str, _length: u32) {}
+    fn start_sequence_item(&mut self, _index: u32) {}
+    fn stop_sequence_item(&mut self) {}
+    fn stop_sequence(&mut self) {}
+    fn start_group(&mut self, _name: &str) {}
+    fn stop_group(&mut self) {}
+    fn start_template_ref(&mut self, _name: &str, _dynamic: bool) {}
+    fn stop_template_ref(&mut self) {}
+}
+
+#[test]
+fn decode_buffer_ref() {
+    let raw = [
+        0xc0, 0x82, 0x61, 0x62, 0xe3, 0x64, 0x65, 0xe6, 0x83, 0x67, 0x68, 0x69, 0x84, 0x6b, 0x6c,
+        0x6d, // String
+        0xc0, 0x83, 0x81, 0xc1, 0x82, 0xb3, // ByteVector
+    ];
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut msg = BorrowingFactory::default();
+    let n = d.decode_buffer_ref(&raw, &mut msg).unwrap();
+    assert_eq!(n, 16);
+    let n = d.decode_buffer_ref(&raw[16..], &mut msg).unwrap();
+    assert_eq!(n, 6);
+
+    let values: HashMap<_, _> = msg.values.into_iter().collect();
+    assert!(matches!(
+        &values["MandatoryUnicode"],
+        Some(ValueRef::UnicodeString(Cow::Borrowed("ghi")))
+    ));
+    assert!(matches!(
+        &values["OptionalUnicode"],
+        Some(ValueRef::UnicodeString(Cow::Borrowed("klm")))
+    ));
+    assert!(matches!(
+        &values["MandatoryVector"],
+        Some(ValueRef::Bytes(Cow::Borrowed([0xc1])))
+    ));
+    assert!(matches!(
+        &values["OptionalVector"],
+        Some(ValueRef::Bytes(Cow::Borrowed([0xb3])))
+    ));
+    assert_eq!(
+        values["MandatoryAscii"].clone().map(ValueRef::into_owned),
+        Some(Value::ASCIIString("abc".to_string()))
+    );
+
+    // message factories that need owned values work the same way
+    let mut msg = ModelFactory::new();
+    assert_eq!(d.decode_buffer_ref(&raw[16..], &mut msg).unwrap(), 6);
+    assert_eq!(msg.data.unwrap().name, "ByteVector");
+
+    // truncated message
+    let mut msg = BorrowingFactory::default();
+    assert!(matches!(
+        d.decode_buffer_ref(&raw[..10], &mut msg)
+            .as_ref()
+            .map_err(Error::kind),
+        Err(Error::UnexpectedEof)
+    ));
+}
+
+#[cfg(feature = "shared-bytes")]
+#[test]
+fn decode_bytes_ref() {
+    let raw = bytes::Bytes::from_static(&[
+        0xc0, 0x82, 0x61, 0x62, 0xe3, 0x64, 0x65, 0xe6, 0x83, 0x67, 0x68, 0x69, 0x84, 0x6b, 0x6c,
+        0x6d, // String
+        0xc0, 0x83, 0x81, 0xc1, 0x82, 0xb3, // ByteVector
+    ]);
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let mut rdr = raw.clone();
+    let mut msg = BorrowingFactory::default();
+    d.decode_bytes_ref(&mut rdr, &mut msg).unwrap();
+    d.decode_bytes_ref(&mut rdr, &mut msg).unwrap();
+    assert!(rdr.is_empty());
+
+    let values: HashMap<_, _> = msg.values.into_iter().collect();
+    // sub-slices of the buffer
+    let shared = |name: &str| match &values[name] {
+        Some(ValueRef::SharedUnicodeString(b) | ValueRef::SharedBytes(b)) => {
+            (b.as_ptr() as usize - raw.as_ptr() as usize, b.clone())
+        }
+        v => panic!("expected shared value of {name}, got {v:?}"),
+    };
+    assert_eq!(shared("MandatoryUnicode"), (9, bytes::Bytes::from("ghi")));
+    assert_eq!(shared("OptionalUnicode"), (13, bytes::Bytes::from("klm")));
+    assert_eq!(
+        shared("MandatoryVector"),
+        (19, bytes::Bytes::from(&[0xc1][..]))
+    );
+    assert_eq!(
+        shared("OptionalVector"),
+        (21, bytes::Bytes::from(&[0xb3][..]))
+    );
+    assert_eq!(
+        values["OptionalUnicode"].clone().map(ValueRef::into_owned),
+        Some(Value::UnicodeString("klm".to_string()))
+    );
+}
+
+#[test]
+fn decode_limits() {
+    fn decode(raw: &[u8], limits: DecoderLimits) -> Result<u64> {
+        let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+        d.set_limits(limits);
+        d.decode_buffer(raw, &mut ModelFactory::new())
+            .map_err(|e| match e {
+                Error::Context { source, .. } => *source,
+                e => e,
+            })
+    }
+    let string = [
+        0xc0, 0x82, 0x61, 0x62, 0xe3, 0x64, 0x65, 0xe6, 0x83, 0x67, 0x68, 0x69, 0x84, 0x6b, 0x6c,
+        0x6d,
+    ];
+    let bytes = [0xc0, 0x83, 0x81, 0xc1, 0x82, 0xb3];
+    let sequence = [0xc0, 0x85, 0x81, 0x81, 0x82, 0x80, 0x81, 0xc0, 0x82];
+    let group = [0xc0, 0x86, 0x81, 0xc0, 0x82, 0x83];
+
+    assert!(decode(&string, DecoderLimits::default()).is_ok());
+    assert!(matches!(
+        decode(
+            &string,
+            DecoderLimits {
+                max_string_length: 2,
+                ..DecoderLimits::default()
+            }
+        ),
+        Err(Error::LengthLimitExceeded(3, 2))
+    ));
+    assert!(matches!(
+        decode(
+            &bytes,
+            DecoderLimits {
+                max_byte_vector_length: 0,
+                ..DecoderLimits::default()
+            }
+        ),
+        Err(Error::LengthLimitExceeded(1, 0))
+    ));
+    assert!(matches!(
+        decode(
+            &sequence,
+            DecoderLimits {
+                max_sequence_length: 0,
+                ..DecoderLimits::default()
+            }
+        ),
+        Err(Error::SequenceLimitExceeded(1, 0))
+    ));
+    assert!(
+        decode(
+            &group,
+            DecoderLimits {
+                max_nesting_depth: 2,
+                ..DecoderLimits::default()
+            }
+        )
+        .is_ok()
+    );
+    assert!(matches!(
+        decode(
+            &group,
+            DecoderLimits {
+                max_nesting_depth: 1,
+                ..DecoderLimits::default()
+            }
+        ),
+        Err(Error::NestingLimitExceeded(1))
+    ));
+    assert!(matches!(
+        decode(
+            &string,
+            DecoderLimits {
+                max_message_bytes: 8,
+                ..DecoderLimits::default()
+            }
+        ),
+        Err(Error::MessageSizeLimitExceeded(8))
+    ));
+
+    // byte vector that claims to be 2^35 bytes long
+    let hostile = [0xc0, 0x83, 0x01, 0x00, 0x00, 0x00, 0x00, 0x80];
+    assert!(matches!(
+        decode(&hostile, DecoderLimits::default()),
+        Err(Error::LengthLimitExceeded(0x8_0000_0000, _))
+    ));
+    assert!(matches!(
+        decode(&hostile, DecoderLimits::unlimited()),
+        Err(Error::UnexpectedEof)
+    ));
+}
+
+#[test]
+fn decode_error_codes() {
+    fn decode(raw: &[u8], policy: ReportPolicy) -> (Result<u64>, Vec<Option<ErrorCode>>) {
+        let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+        d.set_report_policy(policy);
+        let res = d.decode_buffer(raw, &mut ModelFactory::new());
+        (res, d.warnings().iter().map(Error::code).collect())
+    }
+    let group = [0xc0, 0x86, 0x81, 0x80, 0x82];
+    assert!(matches!(decode(&group, ReportPolicy::Fail), (Ok(5), w) if w.is_empty()));
+
+    // dynamic errors
+    let (res, _) = decode(&[0xc0, 0xff], ReportPolicy::Warn);
+    assert_eq!(res.unwrap_err().code(), Some(ErrorCode::D9));
+    // uInt32 out of range
+    let (res, _) = decode(
+        &[0xc0, 0x81, 0x10, 0x00, 0x00, 0x00, 0x80],
+        ReportPolicy::Warn,
+    );
+    assert_eq!(res.unwrap_err().code(), Some(ErrorCode::D2));
+
+    // reportable errors: overlong integer, overlong presence map, unused presence map bits
+    for (raw, code) in [
+        (vec![0xc0, 0x00, 0x86, 0x81, 0x80, 0x82], ErrorCode::R6),
+        (vec![0x40, 0x80, 0x86, 0x81, 0x80, 0x82], ErrorCode::R7),
+        (vec![0xc1, 0x86, 0x81, 0x80, 0x82], ErrorCode::R8),
+    ] {
+        let (res, warnings) = decode(&raw, ReportPolicy::Warn);
+        assert_eq!(res.unwrap(), raw.len() as u64);
+        assert_eq!(warnings, vec![Some(code)]);
+        let (res, warnings) = decode(&raw, ReportPolicy::Fail);
+        assert_eq!(res.unwrap_err().code(), Some(code));
+        assert!(warnings.is_empty());
+    }
+}
+
+#[test]
+fn error_context() {
+    let mut d = Decoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    // the second element of the sequence has uInt32 out of range
+    let raw = [
+        0xc0, 0x85, 0x81, 0x82, 0x81, 0x80, 0x10, 0x00, 0x00, 0x00, 0x80,
+    ];
+    let err = d.decode_buffer(&raw, &mut ModelFactory::new()).unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::D2));
+    assert!(matches!(err.kind(), Error::Spec(ErrorCode::D2, _)));
+    assert_eq!(
+        err.context(),
+        Some(&ErrorContext {
+            template_id: Some(5),
+            template_name: Some("Sequence".to_string()),
+            path: "Sequence/OuterSequence[1]/OuterTestData".to_string(),
+            offset: Some(11),
+            pmap_bit: Some(1),
+        })
+    );
+
+    // decimal mantissa doesn't fit into 64 bits
+    let raw = [
+        0xc0, 0x84, 0x81, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80,
+    ];
+    let err = d.decode_buffer(&raw, &mut ModelFactory::new()).unwrap_err();
+    assert!(matches!(err.kind(), Error::IntegerOverflow));
+    let ctx = err.context().unwrap();
+    assert_eq!(ctx.path, "Decimal/MandatoryDecimal/mantissa");
+    assert_eq!(ctx.pmap_bit, Some(2));
+    assert_eq!(
+        err.to_string(),
+        "Integer overflow: encoded value doesn't fit into 64 bits \
+         (template 4, at Decimal/MandatoryDecimal/mantissa, offset 13, pmap bit 2)"
+    );
+
+    // the end of the stream is never wrapped
+    assert!(matches!(
+        d.decode_buffer(&[], &mut ModelFactory::new()),
+        Err(Error::Eof)
+    ));
+
+    // missing field of a sequence element
+    let data = TemplateData {
+        name: "Sequence".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "TestData".to_string(),
+                ValueData::Value(Some(Value::UInt32(1))),
+            ),
+            (
+                "OuterSequence".to_string(),
+                ValueData::Sequence(vec![ValueData::Group(HashMap::default())]),
+            ),
+            ("NextOuterSequence".to_string(), ValueData::Sequence(vec![])),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+    let err = e.encode_vec(&mut ModelVisitor::new(data)).unwrap_err();
+    let ctx = err.context().unwrap();
+    assert_eq!(ctx.template_id, Some(5));
+    assert_eq!(ctx.path, "Sequence/OuterSequence[0]/OuterTestData");
+    assert_eq!(ctx.offset, None);
+    assert!(
+        err.to_string()
+            .ends_with("(template 5, at Sequence/OuterSequence[0]/OuterTestData, pmap bit 1)")
+    );
+}
+
+#[test]
+fn long_presence_map() {
+    // 80 optional fields plus the template id need 81 presence map bits
+    let fields: String = (0..80)
+        .map(|i| {
+            format!(
+                r#"<uInt32 name="F{i}" id="{}" presence="optional"><default/></uInt32>"#,
+                i + 1
+            )
+        })
+        .collect();
+    let xml = format!(
+        r#"<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1"><template name="Long" id="1">{fields}</template></templates>"#
+    );
+    let data = TemplateData {
+        name: "Long".to_string(),
+        value: ValueData::Group(HashMap::from_iter(
+            [0, 62, 63, 70].map(|i| (format!("F{i}"), ValueData::Value(Some(Value::UInt32(i))))),
+        )),
+    };
+    let mut e = Encoder::new_from_xml(&xml).unwrap();
+    let raw = e.encode_vec(&mut ModelVisitor::new(data.clone())).unwrap();
+    // trailing presence map byte with no bits set is not written
+    assert_eq!(raw.len(), 11 + 1 + 4);
+    assert_eq!(raw[10], 0xa0);
+
+    let mut d = Decoder::new_from_xml(&xml).unwrap();
+    d.set_report_policy(ReportPolicy::Fail);
+    let mut msg = ModelFactory::new();
+    d.decode_vec(raw, &mut msg).unwrap();
+    let ValueData::Group(mut decoded) = msg.data.unwrap().value else {
+        panic!("expected group");
+    };
+    decoded.retain(|_, v| *v != ValueData::Value(None));
+    assert_eq!(ValueData::Group(decoded), data.value);
+}
+
+const FAST12_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Types" id="1">
+        <boolean name="Bool" id="1"/>
+        <boolean name="OptBool" id="2" presence="optional"/>
+        <uInt8 name="UInt8" id="3"/>
+        <int8 name="Int8" id="4"/>
+        <uInt16 name="UInt16" id="5"><increment/></uInt16>
+        <int16 name="Int16" id="6"><delta/></int16>
+    </template>
+</templates>
+"#;
+
+#[test]
+fn decode_encode_fast12_types() {
+    let raw = vec![
+        0xe0, 0x81, // pmap, template id
+        0x81, 0x81, // Bool, OptBool
+        0x01, 0xc8, // UInt8
+        0x7f, 0x9c, // Int8
+        0x07, 0xe8, // UInt16
+        0x7d, 0xd4, // Int16
+    ];
+    let data = TemplateData {
+        name: "Types".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "Bool".to_string(),
+                ValueData::Value(Some(Value::Bool(true))),
+            ),
+            (
+                "OptBool".to_string(),
+                ValueData::Value(Some(Value::Bool(false))),
+            ),
+            (
+                "UInt8".to_string(),
+                ValueData::Value(Some(Value::UInt8(200))),
+            ),
+            (
+                "Int8".to_string(),
+                ValueData::Value(Some(Value::Int8(-100))),
+            ),
+            (
+                "UInt16".to_string(),
+                ValueData::Value(Some(Value::UInt16(1000))),
+            ),
+            (
+                "Int16".to_string(),
+                ValueData::Value(Some(Value::Int16(-300))),
+            ),
+        ])),
+    };
+    let mut d = Decoder::new_from_xml(FAST12_TYPES).unwrap();
+    let mut msg = ModelFactory::new();
+    d.decode_vec(raw.clone(), &mut msg).unwrap();
+    assert_eq!(msg.data.unwrap(), data);
+
+    let mut e = Encoder::new_from_xml(FAST12_TYPES).unwrap();
+    assert_eq!(e.encode_vec(&mut ModelVisitor::new(data)).unwrap(), raw);
+
+    let mut d = Decoder::new_from_xml(FAST12_TYPES).unwrap();
+    let mut msg = TextMessageFactory::new();
+    d.decode_vec(raw, &mut msg).unwrap();
+    assert_eq!(
+        msg.text,
+        "Types=<Bool=true|OptBool=false|UInt8=200|Int8=-100|UInt16=1000|Int16=-300>"
+    );
+
+    // values out of the field type range
+    for raw in [
+        vec![0xc0, 0x81, 0x82],                         // Bool = 2
+        vec![0xc0, 0x81, 0x81, 0x80, 0x02, 0x80],       // UInt8 = 256
+        vec![0xc0, 0x81, 0x81, 0x80, 0x81, 0x7e, 0xff], // Int8 = -129
+    ] {
+        let mut d = Decoder::new_from_xml(FAST12_TYPES).unwrap();
+        let err = d.decode_vec(raw, &mut ModelFactory::new()).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::D2), "{err}");
+    }
+
+    // increment operator is not applicable to boolean
+    let res = Definitions::new_from_xml(
+        r#"<templates><template name="T" id="1"><boolean name="B" id="1"><increment/></boolean></template></templates>"#,
+    );
+    assert!(matches!(res, Err(Error::Static(_))));
+}
+
+#[test]
+fn decode_encode_enum_set_previous_values() {
+    let xml = r#"<templates><template name="Order" id="1">
+        <enum name="Side" id="54"><element name="Buy"/><element name="Sell"/><copy/></enum>
+        <set name="Flags" id="100"><element name="A"/><element name="B"/><copy/></set>
+        <timestamp name="Time" id="60"><delta/></timestamp>
+        <date name="Date" id="75"><increment/></date>
+    </template></templates>"#;
+    let data = |time: i64, date: i32| TemplateData {
+        name: "Order".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "Side".to_string(),
+                ValueData::Value(Some(Value::Enum(1, Arc::from("Sell")))),
+            ),
+            (
+                "Flags".to_string(),
+                ValueData::Value(Some(Value::Set(3, vec![Arc::from("A"), Arc::from("B")]))),
+            ),
+            (
+                "Time".to_string(),
+                ValueData::Value(Some(Value::Timestamp(Timestamp::new(
+                    time,
+                    TimeUnit::Millisecond,
+                    0,
+                )))),
+            ),
+            (
+                "Date".to_string(),
+                ValueData::Value(Some(Value::Date(Date::new(date, 0)))),
+            ),
+        ])),
+    };
+    // Previous values of enum, set and time fields are integers in the dictionary.
+    let mut e = Encoder::new_from_xml(xml).unwrap();
+    let mut d = Decoder::new_from_xml(xml).unwrap();
+    for (time, date) in [(1000, 19000), (1500, 19001)] {
+        let raw = e
+            .encode_vec(&mut ModelVisitor::new(data(time, date)))
+            .unwrap();
+        let mut msg = ModelFactory::new();
+        d.decode_vec(raw, &mut msg).unwrap();
+        assert_eq!(msg.data.unwrap(), data(time, date));
+    }
+}
+
+const ENUM_SET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Order" id="1">
+        <enum name="Side" id="54">
+            <element name="Buy" value="1"/>
+            <element name="Sell"/>
+            <element name="Cross" value="8"/>
+        </enum>
+        <enum name="Type" id="40" presence="optional">
+            <element name="Market"/>
+            <element name="Limit"/>
+            <copy value="Limit"/>
+        </enum>
+        <set name="Flags" id="100">
+            <element name="A"/>
+            <element name="B"/>
+            <element name="C"/>
+        </set>
+    </template>
+</templates>
+"#;
+
+#[test]
+fn decode_encode_enum_set() {
+    let raw = vec![0xc0, 0x81, 0x82, 0x85];
+    let data = TemplateData {
+        name: "Order".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "Side".to_string(),
+                ValueData::Value(Some(Value::Enum(2, Arc::from("Sell")))),
+            ),
+            (
+                "Type".to_string(),
+                ValueData::Value(Some(Value::Enum(1, Arc::from("Limit")))),
+            ),
+            (
+                "Flags".to_string(),
+                ValueData::Value(Some(Value::Set(5, vec![Arc::from("A"), Arc::from("C")]))),
+            ),
+        ])),
+    };
+    let mut d = Decoder::new_from_xml(ENUM_SET).unwrap();
+    let mut msg = ModelFactory::new();
+    d.decode_vec(raw.clone(), &mut msg).unwrap();
+    assert_eq!(msg.data.unwrap(), data);
+
+    let mut e = Encoder::new_from_xml(ENUM_SET).unwrap();
+    assert_eq!(e.encode_vec(&mut ModelVisitor::new(data)).unwrap(), raw);
+
+    // encode from names or integers
+    let data = TemplateData {
+        name: "Order".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "Side".to_string(),
+                ValueData::Value(Some(Value::Enum(0, Arc::from("Sell")))),
+            ),
+            (
+                "Type".to_string(),
+                ValueData::Value(Some(Value::ASCIIString("Limit".to_string()))),
+            ),
+            (
+                "Flags".to_string(),
+                ValueData::Value(Some(Value::Set(1, vec![Arc::from("C")]))),
+            ),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(ENUM_SET).unwrap();
+    assert_eq!(e.encode_vec(&mut ModelVisitor::new(data)).unwrap(), raw);
+    let data = TemplateData {
+        name: "Order".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            ("Side".to_string(), ValueData::Value(Some(Value::UInt32(2)))),
+            ("Type".to_string(), ValueData::Value(Some(Value::UInt32(1)))),
+            (
+                "Flags".to_string(),
+                ValueData::Value(Some(Value::UInt64(5))),
+            ),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(ENUM_SET).unwrap();
+    assert_eq!(e.encode_vec(&mut ModelVisitor::new(data)).unwrap(), raw);
+
+    let mut d = Decoder::new_from_xml(ENUM_SET).unwrap();
+    let mut msg = TextMessageFactory::new();
+    d.decode_vec(raw.clone(), &mut msg).unwrap();
+    assert_eq!(msg.text, "Order=<Side=Sell|Type=Limit|Flags=A,C>");
+    let mut e = Encoder::new_from_xml(ENUM_SET).unwrap();
+    let mut msg = TextMessageVisitor::from_text(&msg.text).unwrap();
+    assert_eq!(e.encode_vec(&mut msg).unwrap(), raw);
+
+    let mut d = Decoder::new_from_xml(ENUM_SET).unwrap();
+    let mut msg = JsonMessageFactory::new();
+    d.decode_vec(raw, &mut msg).unwrap();
+    assert_eq!(
+        msg.json,
+        r#"{"Order":{"Side":"Sell","Type":"Limit","Flags":["A","C"]}}"#
+    );
+
+    // values that are not elements
+    for raw in [vec![0xc0, 0x81, 0x85, 0x80], vec![0xc0, 0x81, 0x82, 0x88]] {
+        let mut d = Decoder::new_from_xml(ENUM_SET).unwrap();
+        let err = d.decode_vec(raw, &mut ModelFactory::new()).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::D2), "{err}");
+    }
+    let data = TemplateData {
+        name: "Order".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "Side".to_string(),
+                ValueData::Value(Some(Value::Enum(0, Arc::from("Short")))),
+            ),
+            (
+                "Flags".to_string(),
+                ValueData::Value(Some(Value::UInt64(0))),
+            ),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(ENUM_SET).unwrap();
+    assert!(e.encode_vec(&mut ModelVisitor::new(data)).is_err());
+
+    // duplicate elements
+    let res = Definitions::new_from_xml(
+        r#"<templates><template name="T" id="1"><enum name="E" id="1"><element name="A" value="1"/><element name="B" value="1"/></enum></template></templates>"#,
+    );
+    assert!(matches!(res, Err(Error::Static(_))));
+}
+
+const TIMES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Trade" id="1">
+        <timestamp name="SendingTime" id="52" unit="second"/>
+        <date name="TradeDate" id="75" epoch="2024-01-01"/>
+        <timeOfDay name="Time" id="273">
+            <delta/>
+        </timeOfDay>
+    </template>
+</templates>
+"#;
+
+#[test]
+fn decode_encode_times() {
+    let raw = vec![
+        0xc0, 0x81, 0x06, 0x33, 0x06, 0x57, 0xc8, 0x01, 0x9d, 0x15, 0x3a, 0x4b, 0xbb,
+    ];
+    let data = TemplateData {
+        name: "Trade".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "SendingTime".to_string(),
+                ValueData::Value(Some(Value::Timestamp(Timestamp::new(
+                    1_717_677_000,
+                    TimeUnit::Second,
+                    0,
+                )))),
+            ),
+            (
+                "TradeDate".to_string(),
+                ValueData::Value(Some(Value::Date(Date::new(157, 19_723)))),
+            ),
+            (
+                "Time".to_string(),
+                ValueData::Value(Some(Value::TimeOfDay(TimeOfDay::new(
+                    45_000_123,
+                    TimeUnit::Millisecond,
+                )))),
+            ),
+        ])),
+    };
+    let mut d = Decoder::new_from_xml(TIMES).unwrap();
+    let mut msg = ModelFactory::new();
+    d.decode_vec(raw.clone(), &mut msg).unwrap();
+    assert_eq!(msg.data.unwrap(), data);
+
+    let mut e = Encoder::new_from_xml(TIMES).unwrap();
+    assert_eq!(e.encode_vec(&mut ModelVisitor::new(data)).unwrap(), raw);
+
+    // encode from other units and epochs, strings or integers
+    let data = TemplateData {
+        name: "Trade".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "SendingTime".to_string(),
+                ValueData::Value(Some(Value::Timestamp(Timestamp::new(
+                    1_717_677_000_000,
+                    TimeUnit::Millisecond,
+                    0,
+                )))),
+            ),
+            (
+                "TradeDate".to_string(),
+                ValueData::Value(Some(Value::ASCIIString("2024-06-06".to_string()))),
+            ),
+            (
+                "Time".to_string(),
+                ValueData::Value(Some(Value::UInt64(45_000_123))),
+            ),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(TIMES).unwrap();
+    assert_eq!(e.encode_vec(&mut ModelVisitor::new(data)).unwrap(), raw);
+
+    let mut d = Decoder::new_from_xml(TIMES).unwrap();
+    let mut msg = TextMessageFactory::new();
+    d.decode_vec(raw.clone(), &mut msg).unwrap();
+    assert_eq!(
+        msg.text,
+        "Trade=<SendingTime=2024-06-06T12:30:00Z|TradeDate=2024-06-06|Time=12:30:00.123>"
+    );
+    let mut e = Encoder::new_from_xml(TIMES).unwrap();
+    let mut msg = TextMessageVisitor::from_text(&msg.text).unwrap();
+    assert_eq!(e.encode_vec(&mut msg).unwrap(), raw);
+
+    let mut d = Decoder::new_from_xml(TIMES).unwrap();
+    let mut msg = JsonMessageFactory::new();
+    d.decode_vec(raw, &mut msg).unwrap();
+    assert_eq!(
+        msg.json,
+        r#"{"Trade":{"SendingTime":"2024-06-06T12:30:00Z","TradeDate":"2024-06-06","Time":"12:30:00.123"}}"#
+    );
+
+    // time of day past midnight
+    let mut d = Decoder::new_from_xml(TIMES).unwrap();
+    let err = d
+        .decode_vec(
+            vec![0xc0, 0x81, 0x80, 0x80, 0x29, 0x19, 0x38, 0x80],
+            &mut ModelFactory::new(),
+        )
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::D2), "{err}");
+
+    // the value doesn't fit into the unit of the field
+    let data = TemplateData {
+        name: "Trade".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "SendingTime".to_string(),
+                ValueData::Value(Some(Value::ASCIIString(
+                    "2024-06-06T12:30:00.5Z".to_string(),
+                ))),
+            ),
+            (
+                "TradeDate".to_string(),
+                ValueData::Value(Some(Value::Int32(0))),
+            ),
+            ("Time".to_string(), ValueData::Value(Some(Value::UInt64(0)))),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(TIMES).unwrap();
+    assert!(e.encode_vec(&mut ModelVisitor::new(data)).is_err());
+
+    // unit and epoch are validated
+    for xml in [
+        r#"<templates><template name="T" id="1"><timestamp name="T" id="1" unit="minute"/></template></templates>"#,
+        r#"<templates><template name="T" id="1"><date name="D" id="1" unit="second"/></template></templates>"#,
+        r#"<templates><template name="T" id="1"><timeOfDay name="T" id="1" epoch="2000-01-01"/></template></templates>"#,
+        r#"<templates><template name="T" id="1"><timestamp name="T" id="1" epoch="2000"/></template></templates>"#,
+    ] {
+        let res = Definitions::new_from_xml(xml);
+        assert!(matches!(res, Err(Error::Static(_))), "{xml}");
+    }
+}
+
+const TYPE_DEFINITIONS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.2">
+    <define name="Price">
+        <decimal><delta/></decimal>
+    </define>
+    <define name="Qty">
+        <type name="Size"/>
+    </define>
+    <define name="Size">
+        <uInt32><copy/></uInt32>
+    </define>
+    <template name="Quote" id="1">
+        <define name="Legs">
+            <sequence>
+                <field name="LegPrice" id="566"><type name="Price"/></field>
+            </sequence>
+        </define>
+        <field name="Bid" id="132"><type name="Price"/></field>
+        <field name="Ask" id="133" presence="optional"><type name="Price"/></field>
+        <field name="Size" id="134"><type name="Qty"/></field>
+        <field name="Symbol" id="55"><string/></field>
+        <field name="Legs"><type name="Legs"/></field>
+    </template>
+</templates>
+"#;
+
+const TYPE_DEFINITIONS_INLINE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Quote" id="1">
+        <decimal name="Bid" id="132"><delta/></decimal>
+        <decimal name="Ask" id="133" presence="optional"><delta/></decimal>
+        <uInt32 name="Size" id="134"><copy/></uInt32>
+        <string name="Symbol" id="55"/>
+        <sequence name="Legs">
+            <decimal name="LegPrice" id="566"><delta/></decimal>
+        </sequence>
+    </template>
+</templates>
+"#;
+
+#[test]
+fn decode_encode_type_definitions() {
+    let text = "Quote=<Bid=1.5|Ask=1.6|Size=100|Symbol=ABC|Legs=<LegPrice=1.5><LegPrice=1.6>>";
+    let mut e = Encoder::new_from_xml(TYPE_DEFINITIONS_INLINE).unwrap();
+    let raw = e
+        .encode_vec(&mut TextMessageVisitor::from_text(text).unwrap())
+        .unwrap();
+    let mut e = Encoder::new_from_xml(TYPE_DEFINITIONS).unwrap();
+    assert_eq!(
+        e.encode_vec(&mut TextMessageVisitor::from_text(text).unwrap())
+            .unwrap(),
+        raw
+    );
+    let mut d = Decoder::new_from_xml(TYPE_DEFINITIONS).unwrap();
+    let mut msg = TextMessageFactory::new();
+    d.decode_vec(raw, &mut msg).unwrap();
+    assert_eq!(msg.text, text);
+
+    for (xml, err) in [
+        (
+            r#"<templates><template name="T" id="1"><field name="F" id="1"><type name="Price"/></field></template></templates>"#,
+            "unknown type: Price",
+        ),
+        (
+            r#"<templates><define name="A"><type name="B"/></define><define name="B"><type name="A"/></define><template name="T" id="1"><field name="F" id="1"><type name="A"/></field></template></templates>"#,
+            "cyclic type definition: A -> B -> A",
+        ),
+        (
+            r#"<templates><define name="A"><uInt32/></define><define name="A"><int32/></define></templates>"#,
+            "duplicate type definition: A",
+        ),
+        (
+            r#"<templates><template name="T" id="1"><field name="F" id="1"/></template></templates>"#,
+            "<field/> F must have exactly one type element",
+        ),
+    ] {
+        match Definitions::new_from_xml(xml) {
+            Err(Error::Static(e)) => assert_eq!(e, err),
+            _ => panic!("static error expected: {err}"),
+        }
+    }
+}
+
+const NAMESPACES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1" templateNs="http://a" ns="http://app">
+    <template name="MsgHeader">
+        <uInt32 name="SeqNum" id="34"/>
+    </template>
+    <template name="Quote" id="1" typeRef="Quote">
+        <templateRef name="MsgHeader"/>
+        <string name="Symbol" id="55"/>
+    </template>
+    <template name="MsgHeader" templateNs="http://b">
+        <uInt32 name="SeqNum" id="34"><copy/></uInt32>
+    </template>
+    <template name="Trade" id="2" templateNs="http://b">
+        <templateRef name="MsgHeader"/>
+        <string name="Symbol" id="55"/>
+    </template>
+    <template name="Heartbeat" id="3">
+        <templateRef name="MsgHeader" templateNs="http://b"/>
+    </template>
+</templates>
+"#;
+
+#[test]
+fn decode_encode_namespaces() {
+    for (raw, text) in [
+        (vec![0xc0, 0x81, 0x85, 0xc1], "Quote=<SeqNum=5|Symbol=A>"),
+        (vec![0xe0, 0x82, 0x85, 0xc1], "Trade=<SeqNum=5|Symbol=A>"),
+        (vec![0xe0, 0x83, 0x85], "Heartbeat=<SeqNum=5>"),
+    ] {
+        let mut d = Decoder::new_from_xml(NAMESPACES).unwrap();
+        let mut msg = TextMessageFactory::new();
+        d.decode_vec(raw.clone(), &mut msg).unwrap();
+        assert_eq!(msg.text, text);
+        let mut e = Encoder::new_from_xml(NAMESPACES).unwrap();
+        let mut msg = TextMessageVisitor::from_text(text).unwrap();
+        assert_eq!(e.encode_vec(&mut msg).unwrap(), raw);
+    }
+
+    // ambiguous names are qualified, unique names are available as is
+    let defs = Definitions::new_from_xml(NAMESPACES).unwrap();
+    assert!(!defs.templates_by_name.contains_key("MsgHeader"));
+    let t = &defs.templates_by_name["{http://b}MsgHeader"];
+    assert_eq!(t.name, "{http://b}MsgHeader");
+    let t = &defs.templates_by_name["Quote"];
+    assert_eq!(t.namespace.as_deref(), Some("http://a"));
+    assert_eq!(
+        t.type_ref,
+        TypeRef::ApplicationType(Arc::from("{http://app}Quote"))
+    );
+    assert!(Arc::ptr_eq(t, &defs.templates_by_name["{http://a}Quote"]));
+
+    for (xml, err) in [
+        (
+            r#"<templates templateNs="a"><template name="T" id="1"/><template name="T" id="2"/></templates>"#,
+            "duplicate template name: {a}T",
+        ),
+        (
+            r#"<templates><template name="T" id="1"/><template name="U" id="1"/></templates>"#,
+            "duplicate template id: 1",
+        ),
+    ] {
+        match Definitions::new_from_xml(xml) {
+            Err(Error::Static(e)) => assert_eq!(e, err),
+            _ => panic!("static error expected: {err}"),
+        }
+    }
+}
+
+#[test]
+fn decode_encode_length_element() {
+    let with_length = r#"<templates>
+        <template name="News" id="1">
+            <byteVector name="RawData" id="96"><length name="RawDataLength" id="95"/><copy/></byteVector>
+            <string name="Text" id="58" charset="unicode" presence="optional"><delta/><length name="EncodedTextLen" id="354"/></string>
+        </template>
+    </templates>"#;
+    let without_length = r#"<templates>
+        <template name="News" id="1">
+            <byteVector name="RawData" id="96"><copy/></byteVector>
+            <string name="Text" id="58" charset="unicode" presence="optional"><delta/></string>
+        </template>
+    </templates>"#;
+    let data = TemplateData {
+        name: "News".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "RawData".to_string(),
+                ValueData::Value(Some(Value::Bytes(vec![0x01, 0x02, 0x03]))),
+            ),
+            (
+                "Text".to_string(),
+                ValueData::Value(Some(Value::UnicodeString("Grüße".to_string()))),
+            ),
+        ])),
+    };
+    let mut e = Encoder::new_from_xml(without_length).unwrap();
+    let raw = e.encode_vec(&mut ModelVisitor::new(data.clone())).unwrap();
+    let mut e = Encoder::new_from_xml(with_length).unwrap();
+    assert_eq!(
+        e.encode_vec(&mut ModelVisitor::new(data.clone())).unwrap(),
+        raw
+    );
+    let mut d = Decoder::new_from_xml(with_length).unwrap();
+    let mut msg = ModelFactory::new();
+    d.decode_vec(raw, &mut msg).unwrap();
+    assert_eq!(msg.data.unwrap(), data);
+
+    let defs = Definitions::new_from_xml(with_length).unwrap();
+    let length = &defs.templates_by_name["News"].instructions[1].instructions[0];
+    assert_eq!(length.name, "EncodedTextLen");
+    assert_eq!(length.id, 354);
+    assert!(length.is_optional());
+
+    for xml in [
+        r#"<templates><template name="T" id="1"><string name="S" id="1"><length name="L"/></string></template></templates>"#,
+        r#"<templates><template name="T" id="1"><byteVector name="B" id="1"><length name="L"/><length name="M"/></byteVector></template></templates>"#,
+        r#"<templates><template name="T" id="1"><byteVector name="B" id="1"><copy/><delta/></byteVector></template></templates>"#,
+    ] {
+        let res = Definitions::new_from_xml(xml);
+        assert!(matches!(res, Err(Error::Static(_))), "{xml}");
+    }
+}
+
+#[test]
+fn definitions_to_xml() {
+    fn describe(defs: &Definitions) -> Vec<String> {
+        defs.templates
+            .iter()
+            .map(|t| {
+                format!(
+                    "{} {} {:?} {:?} {:?} {:?}",
+                    t.id, t.name, t.namespace, t.dictionary, t.type_ref, t.instructions
+                )
+            })
+            .collect()
+    }
+
+    for xml in [
+        include_str!("templates/base.xml"),
+        include_str!("templates/spec.xml"),
+        include_str!("templates/spec2.xml"),
+        include_str!("../../tests/templates.xml"),
+        FAST12_TYPES,
+        ENUM_SET,
+        TIMES,
+        TYPE_DEFINITIONS,
+        NAMESPACES,
+        r#"<templates ns="app">
+            <template name="T" id="1" dictionary="template" typeRef="Msg">
+                <decimal name="A" id="1"><copy value="-0.05"/></decimal>
+                <decimal name="B" id="2" presence="optional" key="b"><exponent><default value="-2"/></exponent><mantissa dictionary="m"><delta value="10"/></mantissa></decimal>
+                <decimal name="C" id="3"><increment value="1500"/></decimal>
+                <sequence name="S" ns="" typeRef="Leg"><length name="N" id="2"><copy/></length><string name="X" id="4"><constant value="a &amp; &quot;b&quot;&#10;"/></string></sequence>
+                <sequence name="U" presence="optional"><uInt32 name="Y" id="5"/></sequence>
+                <group name="G" presence="optional" dictionary="g"><byteVector name="Z" id="6"><default value="0aff"/><length id="7"/></byteVector></group>
+                <enum name="E" id="8"><element name="A" value="3"/><element name="B"/><default value="B"/></enum>
+                <set name="F" id="9"><element name="X"/><element name="Y"/><copy value="X Y"/></set>
+                <timestamp name="TS" id="10" unit="nanosecond" epoch="2000-01-01"><copy value="2024-06-06T12:30:00Z"/></timestamp>
+                <boolean name="Bool" id="11"><default value="true"/></boolean>
+                <templateRef/>
+            </template>
+        </templates>"#,
+    ] {
+        let defs = Definitions::new_from_xml(xml).unwrap();
+        let written = defs.to_xml();
+        let parsed = Definitions::new_from_xml(&written).unwrap();
+        assert_eq!(describe(&parsed), describe(&defs), "{written}");
+        assert_eq!(parsed.to_xml(), written);
+    }
+
+    let defs = Definitions::new_from_xml(TYPE_DEFINITIONS).unwrap();
+    assert_eq!(
+        defs.to_xml(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Quote" id="1">
+        <decimal name="Bid" id="132">
+            <exponent>
+                <delta/>
+            </exponent>
+            <mantissa>
+                <delta/>
+            </mantissa>
+        </decimal>
+        <decimal name="Ask" id="133" presence="optional">
+            <exponent>
+                <delta/>
+            </exponent>
+            <mantissa>
+                <delta/>
+            </mantissa>
+        </decimal>
+        <uInt32 name="Size" id="134">
+            <copy/>
+        </uInt32>
+        <string name="Symbol" id="55"/>
+        <sequence name="Legs">
+            <decimal name="LegPrice" id="566">
+                <exponent>
+                    <delta/>
+                </exponent>
+                <mantissa>
+                    <delta/>
+                </mantissa>
+            </decimal>
+        </sequence>
+    </template>
+</templates>
+"#
+    );
+}
+
+#[test]
+fn build_templates() {
+    let xml = r#"<templates>
+        <template name="Header" id="0" templateNs="hdr">
+            <uInt32 name="MsgSeqNum" id="34"><increment/></uInt32>
+        </template>
+        <template name="T" id="1" dictionary="template" ns="app" typeRef="Msg">
+            <templateRef name="Header" templateNs="hdr"/>
+            <decimal name="A" id="1"><copy value="-0.05"/></decimal>
+            <decimal name="B" id="2" presence="optional" key="b"><exponent><default value="-2"/></exponent><mantissa dictionary="m"><delta value="10"/></mantissa></decimal>
+            <decimal name="C" id="3"><increment value="1500"/></decimal>
+            <sequence name="S" ns="" typeRef="Leg"><length name="N" id="2"><copy/></length><string name="X" id="4"><constant value="a"/></string></sequence>
+            <sequence name="U" presence="optional"><uInt32 name="Y" id="5"/></sequence>
+            <group name="G" presence="optional" dictionary="g"><byteVector name="Z" id="6"><default value="0aff"/><length id="7"/></byteVector></group>
+            <enum name="E" id="8"><element name="A" value="3"/><element name="B"/><default value="B"/></enum>
+            <set name="F" id="9"><element name="X"/><element name="Y"/><copy value="X Y"/></set>
+            <timestamp name="TS" id="10" unit="nanosecond" epoch="2000-01-01"><copy value="2024-06-06T12:30:00Z"/></timestamp>
+            <boolean name="Bool" id="11"><default value="true"/></boolean>
+            <templateRef/>
+        </template>
+    </templates>"#;
+    let builders = || {
+        [
+            TemplateBuilder::new(0, "Header")
+                .template_ns("hdr")
+                .field(ValueType::UInt32, 34, "MsgSeqNum")
+                .increment(),
+            TemplateBuilder::new(1, "T")
+                .template_dictionary("template")
+                .template_type_ref("{app}Msg")
+                .template_ref("{hdr}Header")
+                .field(ValueType::Decimal, 1, "A")
+                .copy()
+                .value("-0.05")
+                .field(ValueType::Decimal, 2, "B")
+                .optional()
+                .key("b")
+                .exponent()
+                .default()
+                .value("-2")
+                .mantissa()
+                .dictionary("m")
+                .delta()
+                .value("10")
+                .field(ValueType::Decimal, 3, "C")
+                .increment()
+                .value("1500")
+                .sequence("S", |s| {
+                    s.field(ValueType::ASCIIString, 4, "X")
+                        .constant()
+                        .value("a")
+                })
+                .type_ref("Leg")
+                .length(2, "N")
+                .copy()
+                .sequence("U", |s| s.field(ValueType::UInt32, 5, "Y"))
+                .optional()
+                .group("G", |g| {
+                    g.field(ValueType::Bytes, 6, "Z")
+                        .default()
+                        .value("0aff")
+                        .length(7, "Z:length")
+                })
+                .optional()
+                .dictionary("g")
+                .field(ValueType::Enum, 8, "E")
+                .element_with_value("A", 3)
+                .element("B")
+                .default()
+                .value("B")
+                .field(ValueType::Set, 9, "F")
+                .element("X")
+                .element("Y")
+                .copy()
+                .value("X Y")
+                .field(ValueType::Timestamp, 10, "TS")
+                .unit(TimeUnit::Nanosecond)
+                .epoch(10957)
+                .copy()
+                .value("2024-06-06T12:30:00Z")
+                .field(ValueType::Boolean, 11, "Bool")
+                .default()
+                .value("true")
+                .dynamic_template_ref(),
+        ]
+    };
+    let built = Definitions::new_from_builders(builders()).unwrap();
+    let parsed = Definitions::new_from_xml(xml).unwrap();
+    assert_eq!(built.to_xml(), parsed.to_xml());
+    for (b, p) in built.templates.iter().zip(&parsed.templates) {
+        assert_eq!(b.require_pmap, p.require_pmap);
+        assert_eq!(
+            format!("{:?}", b.instructions),
+            format!("{:?}", p.instructions)
+        );
+    }
+
+    // Built templates encode and decode messages.
+    let templates = || {
+        [TemplateBuilder::new(1, "Quote")
+            .field(ValueType::UInt32, 34, "MsgSeqNum")
+            .increment()
+            .sequence("Entries", |s| {
+                s.field(ValueType::Decimal, 270, "Px")
+                    .exponent()
+                    .copy()
+                    .mantissa()
+                    .delta()
+            })
+            .length(268, "NoEntries")]
+    };
+    let data = TemplateData {
+        name: "Quote".to_string(),
+        value: ValueData::Group(HashMap::from_iter([
+            (
+                "MsgSeqNum".to_string(),
+                ValueData::Value(Some(Value::UInt32(7))),
+            ),
+            (
+                "Entries".to_string(),
+                ValueData::Sequence(vec![
+                    ValueData::Group(HashMap::from_iter([(
+                        "Px".to_string(),
+                        ValueData::Value(Some(Value::Decimal(Decimal::new(-2, 1050)))),
+                    )])),
+                    ValueData::Group(HashMap::from_iter([(
+                        "Px".to_string(),
+                        ValueData::Value(Some(Value::Decimal(Decimal::new(-2, 1075)))),
+                    )])),
+                ]),
+            ),
+        ])),
+    };
+    let mut e = Encoder::new_from_builders(templates()).unwrap();
+    let raw = e.encode_vec(&mut ModelVisitor::new(data.clone())).unwrap();
+    let mut d = Decoder::new_from_builders(templates()).unwrap();
+    let mut msg = ModelFactory::new();
+    d.decode_vec(raw, &mut msg).unwrap();
+    assert_eq!(msg.data.unwrap(), data);
+
+    // Built templates are checked the same way as parsed ones.
+    let invalid = [
+        TemplateBuilder::new(1, "T").copy(),
+        TemplateBuilder::new(1, "T").field(ValueType::UInt32, 0, "A"),
+        TemplateBuilder::new(1, "T").field(ValueType::Sequence, 1, "A"),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::UInt32, 1, "A")
+            .constant(),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::UInt32, 1, "A")
+            .tail(),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::UInt32, 1, "A")
+            .value("1"),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::UInt32, 1, "A")
+            .copy()
+            .value("x"),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::UInt32, 1, "A")
+            .exponent(),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::UInt32, 1, "A")
+            .length(2, "L"),
+        TemplateBuilder::new(1, "T").field(ValueType::Enum, 1, "E"),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::Date, 1, "D")
+            .unit(TimeUnit::Second),
+        TemplateBuilder::new(1, "T")
+            .field(ValueType::Decimal, 1, "A")
+            .copy()
+            .mantissa()
+            .delta(),
+        TemplateBuilder::new(1, "T").group("G", |g| g.copy()),
+        TemplateBuilder::new(1, "T").template_ref("Unknown"),
+    ];
+    for (i, b) in invalid.into_iter().enumerate() {
+        let res = Definitions::new_from_builders([b]);
+        assert!(res.is_err(), "builder #{i}");
+    }
+    let res = Definitions::new_from_builders([
+        TemplateBuilder::new(1, "T"),
+        TemplateBuilder::new(1, "U"),
+    ]);
+    assert!(matches!(res, Err(Error::Static(_))));
+}
+
+#[test]
+fn definitions_introspection() {
+    let defs = Definitions::new_from_xml(
+        r#"<templates ns="app">
+        <template name="Header" id="0" templateNs="hdr">
+            <uInt32 name="MsgSeqNum" id="34"><increment/></uInt32>
+        </template>
+        <template name="Quote" id="1" dictionary="template" typeRef="Msg">
+            <templateRef name="Header" templateNs="hdr"/>
+            <uInt32 name="A" id="1"><delta/></uInt32>
+            <string name="B" id="2" presence="optional"><constant value="x"/></string>
+            <decimal name="Px" id="3" key="px"><exponent><copy value="-2"/></exponent><mantissa><delta/></mantissa></decimal>
+            <sequence name="Legs" typeRef="Leg"><length name="NoLegs" id="4"><copy/></length><uInt32 name="C" id="5"/></sequence>
+            <group name="G" presence="optional"><uInt32 name="D" id="6"/></group>
+            <enum name="E" id="7" dictionary="e"><element name="X" value="3"/><element name="Y"/><default value="Y"/></enum>
+        </template>
+    </templates>"#,
+    )
+    .unwrap();
+    let names: Vec<_> = defs.templates().map(Template::name).collect();
+    assert_eq!(names, ["Header", "Quote"]);
+    assert!(defs.template_by_id(0).is_none());
+    assert_eq!(defs.template_by_name("{hdr}Header").unwrap().id(), 0);
+    let header = defs.template_by_name("Header").unwrap();
+    assert_eq!(header.namespace(), Some("hdr"));
+    assert!(header.requires_presence_map());
+
+    let t = defs.template_by_id(1).unwrap();
+    assert_eq!(t.name(), "Quote");
+    assert_eq!(t.namespace(), None);
+    assert_eq!(t.dictionary(), &Dictionary::Template);
+    assert_eq!(
+        t.type_ref(),
+        &TypeRef::ApplicationType(Arc::from("{app}Msg"))
+    );
+    assert!(t.requires_presence_map());
+
+    let fields = t.instructions();
+    let describe: Vec<_> = fields
+        .iter()
+        .map(|i| {
+            (
+                i.name(),
+                i.id(),
+                i.value_type().clone(),
+                i.presence(),
+                i.operator(),
+                i.has_presence_map_bit(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        describe,
+        [
+            (
+                "Header",
+                0,
+                ValueType::TemplateReference,
+                Presence::Mandatory,
+                Operator::None,
+                true
+            ),
+            (
+                "A",
+                1,
+                ValueType::UInt32,
+                Presence::Mandatory,
+                Operator::Delta,
+                false
+            ),
+            (
+                "B",
+                2,
+                ValueType::ASCIIString,
+                Presence::Optional,
+                Operator::Constant,
+                true
+            ),
+            (
+                "Px",
+                3,
+                ValueType::Decimal,
+                Presence::Mandatory,
+                Operator::None,
+                true
+            ),
+            (
+                "Legs",
+                0,
+                ValueType::Sequence,
+                Presence::Mandatory,
+                Operator::None,
+                true
+            ),
+            (
+                "G",
+                0,
+                ValueType::Group,
+                Presence::Optional,
+                Operator::None,
+                true
+            ),
+            (
+                "E",
+                7,
+                ValueType::Enum,
+                Presence::Mandatory,
+                Operator::Default,
+                true
+            ),
+        ]
+    );
+    assert_eq!(fields[0].template_ns(), Some("hdr"));
+    assert_eq!(
+        fields[2].initial_value(),
+        Some(Value::ASCIIString("x".to_string()))
+    );
+
+    let px = &fields[3];
+    assert!(px.has_presence_map());
+    assert_eq!(px.key(), "px");
+    let exponent = &px.instructions()[0];
+    assert_eq!(exponent.value_type(), &ValueType::Exponent);
+    assert_eq!(exponent.key(), "px:exponent");
+    assert_eq!(exponent.operator(), Operator::Copy);
+    assert_eq!(exponent.initial_value(), Some(Value::Int32(-2)));
+    assert!(exponent.has_presence_map_bit());
+    assert!(!px.instructions()[1].has_presence_map_bit());
+
+    let legs = &fields[4];
+    assert!(!legs.has_presence_map());
+    assert_eq!(
+        legs.type_ref(),
+        &TypeRef::ApplicationType(Arc::from("{app}Leg"))
+    );
+    let length = &legs.instructions()[0];
+    assert_eq!(
+        (length.name(), length.id(), length.operator()),
+        ("NoLegs", 4, Operator::Copy)
+    );
+    assert_eq!(legs.instructions()[1].name(), "C");
+
+    let e = &fields[6];
+    assert_eq!(e.dictionary(), &Dictionary::UserDefined(Arc::from("e")));
+    assert_eq!(e.dictionary().name(), Some("e"));
+    let elements: Vec<_> = e.elements().iter().map(|e| (e.name(), e.value())).collect();
+    assert_eq!(elements, [("X", 3), ("Y", 4)]);
+    assert_eq!(e.initial_value(), Some(Value::Enum(4, Arc::from("Y"))));
+}
+
+#[test]
+fn validate_definitions() {
+    let xml = r#"<templates>
+    <template name="A" id="1">
+        <uInt32 name="X" id="1"><increment/></uInt32>
+        <boolean name="B" id="2"><increment/></boolean>
+        <uInt32 name="C" id="3"><constant/></uInt32>
+        <uInt32 name="D" id="4"><default/></uInt32>
+        <uInt32 name="E" id="5"><copy value="x"/></uInt32>
+        <sequence name="S"><string name="F"/></sequence>
+        <templateRef name="Missing"/>
+        <templateRef name="C1"/>
+    </template>
+    <template name="A" id="1">
+        <int32 name="X" id="1"><copy/></int32>
+    </template>
+    <template name="C1"><templateRef name="C2"/></template>
+    <template name="C2"><templateRef name="C1"/></template>
+    <template name="Unused"><uInt32 name="U" id="1"/></template>
+</templates>"#;
+    let diagnostics: Vec<_> = Definitions::validate_xml(xml)
+        .into_iter()
+        .map(|d| (d.line, d.column, d.severity, d.code))
+        .collect();
+    assert_eq!(
+        diagnostics,
+        [
+            (4, 9, Severity::Error, Some(ErrorCode::S2)),
+            (5, 9, Severity::Error, Some(ErrorCode::S4)),
+            (6, 9, Severity::Error, Some(ErrorCode::S5)),
+            (7, 9, Severity::Error, Some(ErrorCode::S3)),
+            (8, 28, Severity::Error, None),
+            (9, 9, Severity::Error, Some(ErrorCode::D8)),
+            (10, 9, Severity::Error, None),  // C1 is defined after A
+            (12, 5, Severity::Error, None),  // duplicate name
+            (12, 5, Severity::Error, None),  // duplicate id
+            (15, 25, Severity::Error, None), // cycle
+            (16, 25, Severity::Error, None), // cycle
+            (17, 5, Severity::Warning, None),
+        ]
+    );
+    match Definitions::new_from_xml_validated(xml) {
+        Err(Error::Validation(d)) => assert_eq!(d.len(), 12),
+        r => panic!("expected validation error, got {:?}", r.err()),
+    }
+    let d = Definitions::validate_xml("<templates>\n<template name=\"A\" id=\"1\">\n</templates>");
+    assert_eq!(
+        (d[0].line, d[0].severity, d[0].code),
+        (3, Severity::Error, Some(ErrorCode::S1))
+    );
+
+    // Fields of different types sharing a dictionary entry load, but fail at runtime.
+    let xml = r#"<templates>
+    <template name="A" id="1">
+        <uInt32 name="X" id="1"><copy/></uInt32>
+        <decimal name="Px" id="2"><exponent><copy/></exponent><mantissa><delta/></mantissa></decimal>
+    </template>
+    <template name="B" id="2">
+        <int32 name="X" id="1"><copy/></int32>
+        <int32 name="Y" id="3" dictionary="template"><copy/></int32>
+        <int64 name="Z" id="4"><copy key="Px:mantissa"/></int64>
+    </template>
+    <template name="C" id="3">
+        <uInt32 name="Y" id="3" dictionary="template"><copy/></uInt32>
+        <string name="Px:exponent" id="5"><copy/></string>
+    </template>
+</templates>"#;
+    let (_, warnings) = Definitions::new_from_xml_validated(xml).unwrap();
+    let warnings: Vec<_> = warnings
+        .iter()
+        .map(|d| (d.line, d.column, d.severity, d.code))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (7, 9, Severity::Warning, Some(ErrorCode::D4)),
+            (13, 9, Severity::Warning, Some(ErrorCode::D4)),
+        ]
+    );
+
+    for xml in [
+        include_str!("templates/base.xml"),
+        include_str!("templates/spec.xml"),
+        include_str!("templates/spec2.xml"),
+        include_str!("../../tests/templates.xml"),
+        FAST12_TYPES,
+        ENUM_SET,
+        TIMES,
+        TYPE_DEFINITIONS,
+        NAMESPACES,
+    ] {
+        let diagnostics = Definitions::validate_xml(xml);
+        assert!(
+            diagnostics.iter().all(|d| d.severity == Severity::Warning),
+            "{diagnostics:?}"
+        );
+        assert!(Definitions::new_from_xml_validated(xml).is_ok());
+    }
+}
+
+#[test]
+fn shared_definitions() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Definitions>();
+    assert_send_sync::<Decoder>();
+    assert_send_sync::<Encoder>();
+
+    let definitions =
+        Arc::new(Definitions::new_from_xml(include_str!("templates/base.xml")).unwrap());
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let definitions = definitions.clone();
+            std::thread::spawn(move || {
+                let raw = vec![0xc0, 0x86, 0x81, 0x80, 0x82];
+                let mut d = Decoder::with_definitions(definitions.clone());
+                let mut e = Encoder::with_definitions(definitions);
+                let mut msg = ModelFactory::new();
+                d.decode_vec(raw.clone(), &mut msg).unwrap();
+                let mut msg = ModelVisitor::new(msg.data.unwrap());
+                assert_eq!(e.encode_vec(&mut msg).unwrap(), raw);
+            })
+        })
+        .collect();
+    for h in handles {
+        h.join().unwrap();
     }
 }
 
@@ -939,7 +2773,48 @@ fn encode_to_buffer_eof() {
     let mut e = Encoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
     match e.encode_buffer(&mut buffer, &mut msg) {
         Err(Error::IoError(_)) => {}
-        Err(e) => assert!(false, "Unexpected error: {:?}", e),
-        Ok(_) => assert!(false, "Expected Err(UnexpectedEof)"),
+        Err(e) => panic!("Unexpected error: {:?}", e),
+        Ok(_) => panic!("Expected Err(UnexpectedEof)"),
+    }
+}
+
+#[test]
+fn encode_error_codes() {
+    fn encode(template: &str, field: &str, value: Value) -> Option<ErrorCode> {
+        let data = TemplateData {
+            name: template.to_string(),
+            value: ValueData::Group(HashMap::from_iter([(
+                field.to_string(),
+                ValueData::Value(Some(value)),
+            )])),
+        };
+        let mut e = Encoder::new_from_xml(include_str!("templates/base.xml")).unwrap();
+        let mut buffer = [0u8; 64];
+        e.encode_buffer(&mut buffer, &mut ModelVisitor::new(data))
+            .unwrap_err()
+            .code()
     }
+    // value of a type that can't be converted to the field type
+    assert_eq!(
+        encode(
+            "Integer",
+            "MandatoryUint32",
+            Value::ASCIIString("1".to_string())
+        ),
+        Some(ErrorCode::D1)
+    );
+    // unicode string with non-ASCII characters
+    assert_eq!(
+        encode(
+            "String",
+            "MandatoryAscii",
+            Value::UnicodeString("\u{e9}".to_string())
+        ),
+        Some(ErrorCode::R3)
+    );
+    // integer out of range of the field type
+    assert_eq!(
+        encode("Integer", "MandatoryUint32", Value::UInt64(1 << 40)),
+        Some(ErrorCode::R4)
+    );
 }
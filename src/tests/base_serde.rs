@@ -8,8 +8,10 @@ use serde::ser::Serialize;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::Debug;
 
+use crate::model::template::TemplateData;
+use crate::model::{ModelFactory, ModelVisitor};
 use crate::ser::to_vec;
-use crate::{Decimal, Decoder, Encoder, from_buffer};
+use crate::{Decimal, Decoder, Encoder, Error, StreamDeserializer, from_buffer};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 enum Message {
@@ -137,7 +139,7 @@ const DEFINITION: &str = include_str!("templates/base.xml");
 
 fn do_test<'de, M>(raw: Vec<u8>, data: M)
 where
-    M: ?Sized + Serialize + Deserialize<'de> + PartialEq + Debug,
+    M: Serialize + Deserialize<'de> + PartialEq + Debug,
 {
     {
         let mut d = Decoder::new_from_xml(DEFINITION).unwrap();
@@ -339,3 +341,649 @@ fn decode_strings_boxed() {
         })),
     )
 }
+
+#[test]
+fn decode_stream_deserializer() {
+    let raw = bytes::Bytes::from_static(&[
+        0xc0, 0x86, 0x81, 0x80, 0x82, // Group
+        0xe0, 0x88, 0x86, 0x87, // StaticReference
+        0xc0, 0x86, 0x81, // truncated Group
+    ]);
+    let mut d = Decoder::new_from_xml(DEFINITION).unwrap();
+    let mut it = StreamDeserializer::<_, Message>::new(&mut d, raw);
+    assert_eq!(
+        it.next().unwrap().unwrap(),
+        Message::Group(GroupMsg {
+            test_data: 1,
+            outer_group: OuterGroup {
+                outer_test_data: 2,
+                inner_group: None,
+            },
+        })
+    );
+    assert_eq!(it.byte_offset(), 5);
+    assert_eq!(
+        it.next().unwrap().unwrap(),
+        Message::StaticReference(StaticReferenceMsg {
+            pre_ref_data: 6,
+            ref_data: RefDataMsg { test_data: 7 },
+        })
+    );
+    assert_eq!(it.byte_offset(), 9);
+    match it.next() {
+        Some(Err(e)) if matches!(e.kind(), Error::UnexpectedEof) => {}
+        r => panic!("Expected Err(UnexpectedEof), got {r:?}"),
+    }
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn decode_stream_deserializer_not_enum() {
+    let raw = bytes::Bytes::from_static(&[
+        0xc0, 0x86, 0x81, 0x80, 0x82, // Group
+    ]);
+    let mut d = Decoder::new_from_xml(DEFINITION).unwrap();
+    let mut it = StreamDeserializer::<_, GroupMsg>::new(&mut d, raw);
+    assert!(it.next().unwrap().is_err());
+    assert_eq!(it.byte_offset(), 0);
+    assert!(it.next().is_none());
+}
+
+const FAST12_DEFINITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Types" id="1">
+        <boolean name="Bool" id="1"/>
+        <boolean name="OptBool" id="2" presence="optional"/>
+        <uInt8 name="UInt8" id="3"/>
+        <int8 name="Int8" id="4"/>
+        <uInt16 name="UInt16" id="5"><increment/></uInt16>
+        <int16 name="Int16" id="6"><delta/></int16>
+    </template>
+</templates>
+"#;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Fast12Message {
+    Types(TypesMsg),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TypesMsg {
+    bool: bool,
+    opt_bool: Option<bool>,
+    #[serde(rename = "UInt8")]
+    uint8: u8,
+    int8: i8,
+    #[serde(rename = "UInt16")]
+    uint16: u16,
+    int16: i16,
+}
+
+#[test]
+fn decode_fast12_types() {
+    let raw = vec![
+        0xe0, 0x81, 0x81, 0x81, 0x01, 0xc8, 0x7f, 0x9c, 0x07, 0xe8, 0x7d, 0xd4,
+    ];
+    let data = Fast12Message::Types(TypesMsg {
+        bool: true,
+        opt_bool: Some(false),
+        uint8: 200,
+        int8: -100,
+        uint16: 1000,
+        int16: -300,
+    });
+    let mut d = Decoder::new_from_xml(FAST12_DEFINITION).unwrap();
+    let (msg, _) = from_buffer::<Fast12Message>(&mut d, &raw).unwrap();
+    assert_eq!(msg, data, "decode mismatch");
+    let mut e = Encoder::new_from_xml(FAST12_DEFINITION).unwrap();
+    assert_eq!(to_vec(&mut e, &data).unwrap(), raw, "encode mismatch");
+}
+
+// Narrow integer types can be used for wider fields.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum NarrowMessage {
+    Group(NarrowGroupMsg),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NarrowGroupMsg {
+    test_data: u8,
+    outer_group: OuterGroup,
+}
+
+#[test]
+fn decode_narrow_integers() {
+    do_test(
+        vec![0xc0, 0x86, 0x81, 0x80, 0x82],
+        NarrowMessage::Group(NarrowGroupMsg {
+            test_data: 1,
+            outer_group: OuterGroup {
+                outer_test_data: 2,
+                inner_group: None,
+            },
+        }),
+    );
+}
+
+const ENUM_SET_DEFINITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Order" id="1">
+        <enum name="Side" id="54">
+            <element name="Buy" value="1"/>
+            <element name="Sell"/>
+            <element name="Cross" value="8"/>
+        </enum>
+        <enum name="Type" id="40" presence="optional">
+            <element name="Market"/>
+            <element name="Limit"/>
+            <copy value="Limit"/>
+        </enum>
+        <set name="Flags" id="100">
+            <element name="A"/>
+            <element name="B"/>
+            <element name="C"/>
+        </set>
+    </template>
+</templates>
+"#;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum EnumSetMessage {
+    Order(OrderMsg),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Side {
+    Buy,
+    Sell,
+    Cross,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum OrderType {
+    Market,
+    Limit,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum Flag {
+    A,
+    B,
+    C,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct OrderMsg {
+    side: Side,
+    #[serde(rename = "Type")]
+    order_type: Option<OrderType>,
+    flags: std::collections::BTreeSet<Flag>,
+}
+
+#[test]
+fn decode_enum_set() {
+    let raw = vec![0xc0, 0x81, 0x82, 0x85];
+    let data = EnumSetMessage::Order(OrderMsg {
+        side: Side::Sell,
+        order_type: Some(OrderType::Limit),
+        flags: [Flag::A, Flag::C].into(),
+    });
+    let mut d = Decoder::new_from_xml(ENUM_SET_DEFINITION).unwrap();
+    let (msg, _) = from_buffer::<EnumSetMessage>(&mut d, &raw).unwrap();
+    assert_eq!(msg, data, "decode mismatch");
+    let mut e = Encoder::new_from_xml(ENUM_SET_DEFINITION).unwrap();
+    assert_eq!(to_vec(&mut e, &data).unwrap(), raw, "encode mismatch");
+}
+
+const TIMES_DEFINITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Trade" id="1">
+        <timestamp name="SendingTime" id="52" unit="second"/>
+        <date name="TradeDate" id="75" epoch="2024-01-01"/>
+        <timeOfDay name="Time" id="273">
+            <delta/>
+        </timeOfDay>
+    </template>
+</templates>
+"#;
+
+const TIMES_RAW: [u8; 13] = [
+    0xc0, 0x81, 0x06, 0x33, 0x06, 0x57, 0xc8, 0x01, 0x9d, 0x15, 0x3a, 0x4b, 0xbb,
+];
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum TimesMessage {
+    Trade(TradeMsg),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TradeMsg {
+    sending_time: String,
+    trade_date: String,
+    time: u64,
+}
+
+#[test]
+fn decode_times() {
+    let data = TimesMessage::Trade(TradeMsg {
+        sending_time: "2024-06-06T12:30:00Z".to_string(),
+        trade_date: "2024-06-06".to_string(),
+        time: 45_000_123,
+    });
+    let mut d = Decoder::new_from_xml(TIMES_DEFINITION).unwrap();
+    let (msg, _) = from_buffer::<TimesMessage>(&mut d, &TIMES_RAW).unwrap();
+    assert_eq!(msg, data, "decode mismatch");
+    let mut e = Encoder::new_from_xml(TIMES_DEFINITION).unwrap();
+    assert_eq!(to_vec(&mut e, &data).unwrap(), TIMES_RAW, "encode mismatch");
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn decode_times_chrono() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Message {
+        Trade(Trade),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Trade {
+        sending_time: chrono::DateTime<chrono::Utc>,
+        trade_date: chrono::NaiveDate,
+        time: chrono::NaiveTime,
+    }
+
+    let data = Message::Trade(Trade {
+        sending_time: "2024-06-06T12:30:00Z".parse().unwrap(),
+        trade_date: chrono::NaiveDate::from_ymd_opt(2024, 6, 6).unwrap(),
+        time: chrono::NaiveTime::from_hms_milli_opt(12, 30, 0, 123).unwrap(),
+    });
+    let mut d = Decoder::new_from_xml(TIMES_DEFINITION).unwrap();
+    let (msg, _) = from_buffer::<Message>(&mut d, &TIMES_RAW).unwrap();
+    assert_eq!(msg, data, "decode mismatch");
+    let mut e = Encoder::new_from_xml(TIMES_DEFINITION).unwrap();
+    assert_eq!(to_vec(&mut e, &data).unwrap(), TIMES_RAW, "encode mismatch");
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn decode_times_time() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Message {
+        Trade(Trade),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Trade {
+        #[serde(with = "time::serde::rfc3339")]
+        sending_time: time::OffsetDateTime,
+        trade_date: time::Date,
+        time: time::Time,
+    }
+
+    let data = Message::Trade(Trade {
+        sending_time: time::OffsetDateTime::from_unix_timestamp(1_717_677_000).unwrap(),
+        trade_date: time::Date::from_calendar_date(2024, time::Month::June, 6).unwrap(),
+        time: time::Time::from_hms_milli(12, 30, 0, 123).unwrap(),
+    });
+    let mut d = Decoder::new_from_xml(TIMES_DEFINITION).unwrap();
+    let (msg, _) = from_buffer::<Message>(&mut d, &TIMES_RAW).unwrap();
+    assert_eq!(msg, data, "decode mismatch");
+    let mut e = Encoder::new_from_xml(TIMES_DEFINITION).unwrap();
+    assert_eq!(to_vec(&mut e, &data).unwrap(), TIMES_RAW, "encode mismatch");
+}
+
+const WIRE_DEFINITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<templates xmlns="http://www.fixprotocol.org/ns/fast/td/1.1">
+    <template name="Quote" id="1">
+        <string name="Symbol" id="1"><copy/></string>
+        <uInt32 name="Price" id="2"><delta/></uInt32>
+        <group name="Venue" presence="optional">
+            <string name="Name" id="3"><copy/></string>
+        </group>
+        <sequence name="Legs">
+            <length name="NoLegs" id="4"/>
+            <uInt32 name="Ratio" id="5"><copy/></uInt32>
+        </sequence>
+        <templateRef/>
+    </template>
+    <template name="Note" id="2">
+        <string name="Text" id="6"><copy/></string>
+    </template>
+</templates>
+"#;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum QuoteMessage {
+    Quote(Quote),
+    Note(Note),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Quote {
+    symbol: String,
+    price: u32,
+    venue: Option<Venue>,
+    legs: Vec<Leg>,
+    #[serde(rename = "templateRef:0")]
+    note: Box<QuoteMessage>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Venue {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Leg {
+    ratio: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Note {
+    text: String,
+}
+
+fn quotes() -> Vec<QuoteMessage> {
+    let quote = |price, venue: Option<&str>, legs: &[u32]| {
+        QuoteMessage::Quote(Quote {
+            symbol: "ABC".to_string(),
+            price,
+            venue: venue.map(|name| Venue {
+                name: name.to_string(),
+            }),
+            legs: legs.iter().map(|&ratio| Leg { ratio }).collect(),
+            note: Box::new(QuoteMessage::Note(Note {
+                text: "hi".to_string(),
+            })),
+        })
+    };
+    vec![
+        quote(100, Some("X"), &[1, 2]),
+        quote(300, None, &[2]),
+        quote(250, Some("X"), &[2, 2]),
+    ]
+}
+
+fn encode_quotes() -> Vec<u8> {
+    let mut e = Encoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    quotes()
+        .iter()
+        .flat_map(|m| to_vec(&mut e, m).unwrap())
+        .collect()
+}
+
+#[test]
+fn deserialize_from_wire() {
+    let raw = encode_quotes();
+
+    // Direct deserialization matches the data model built by `ModelFactory`
+    let mut d1 = Decoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    let mut d2 = Decoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    let mut buf = raw.as_slice();
+    for expected in quotes() {
+        let mut msg = ModelFactory::new();
+        let n = d1.decode_buffer(buf, &mut msg).unwrap();
+        let model = QuoteMessage::deserialize(msg.data.unwrap()).unwrap();
+        let (direct, m) = from_buffer::<QuoteMessage>(&mut d2, buf).unwrap();
+        assert_eq!(n, m);
+        assert_eq!(model, expected);
+        assert_eq!(direct, expected);
+        buf = &buf[usize::try_from(n).unwrap()..];
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn deserialize_from_wire_skipped_fields() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Message {
+        Quote(Quote),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Quote {
+        price: u32,
+    }
+
+    // Skipped fields are still decoded, so the dictionaries stay in sync
+    let mut d = Decoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    let prices: Vec<_> =
+        StreamDeserializer::<_, Message>::new(&mut d, bytes::Bytes::from(encode_quotes()))
+            .map(|m| match m.unwrap() {
+                Message::Quote(q) => q.price,
+            })
+            .collect();
+    assert_eq!(prices, vec![100, 300, 250]);
+}
+
+#[test]
+fn deserialize_from_wire_borrowed() {
+    // Accepts only values the deserializer hands over without copying them.
+    #[derive(Debug, PartialEq)]
+    struct Borrowed(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for Borrowed {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BorrowedVisitor;
+
+            impl serde::de::Visitor<'_> for BorrowedVisitor {
+                type Value = Borrowed;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("borrowed string or bytes")
+                }
+
+                fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Borrowed, E> {
+                    Ok(Borrowed(v.as_bytes().to_vec()))
+                }
+
+                fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Borrowed, E> {
+                    Ok(Borrowed(v.to_vec()))
+                }
+
+                fn visit_string<E: serde::de::Error>(self, _: String) -> Result<Borrowed, E> {
+                    Err(E::custom("string is copied"))
+                }
+
+                fn visit_byte_buf<E: serde::de::Error>(self, _: Vec<u8>) -> Result<Borrowed, E> {
+                    Err(E::custom("bytes are copied"))
+                }
+            }
+
+            deserializer.deserialize_any(BorrowedVisitor)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Message {
+        String(StringMsg),
+        ByteVector(BytesMsg),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct StringMsg {
+        mandatory_ascii: String,
+        optional_ascii: Option<String>,
+        mandatory_unicode: Borrowed,
+        optional_unicode: Option<Borrowed>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct BytesMsg {
+        mandatory_vector: Borrowed,
+        optional_vector: Option<Borrowed>,
+    }
+
+    let mut d = Decoder::new_from_xml(DEFINITION).unwrap();
+    let msg: Message = crate::from_slice(
+        &mut d,
+        &[
+            0xc0, 0x82, 0x61, 0x62, 0xe3, 0x64, 0x65, 0xe6, 0x83, 0x67, 0x68, 0x69, 0x84, 0x6b,
+            0x6c, 0x6d,
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        msg,
+        Message::String(StringMsg {
+            mandatory_ascii: "abc".to_string(),
+            optional_ascii: Some("def".to_string()),
+            mandatory_unicode: Borrowed(b"ghi".to_vec()),
+            optional_unicode: Some(Borrowed(b"klm".to_vec())),
+        })
+    );
+    let (msg, _) = from_buffer::<Message>(&mut d, &[0xc0, 0x83, 0x81, 0xc1, 0x82, 0xb3]).unwrap();
+    assert_eq!(
+        msg,
+        Message::ByteVector(BytesMsg {
+            mandatory_vector: Borrowed(vec![0xc1]),
+            optional_vector: Some(Borrowed(vec![0xb3])),
+        })
+    );
+}
+
+#[test]
+fn deserialize_from_wire_error() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Message {
+        Quote(Quote),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Quote {
+        price: u8,
+        legs: Vec<Leg>,
+        venue: Option<Venue>,
+    }
+
+    // The message is decoded to the end when deserialization fails, so the next message is decoded correctly
+    let mut d = Decoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    let mut it = StreamDeserializer::<_, Message>::new(&mut d, bytes::Bytes::from(encode_quotes()));
+    assert_eq!(
+        it.next().unwrap().unwrap(),
+        Message::Quote(Quote {
+            price: 100,
+            legs: vec![Leg { ratio: 1 }, Leg { ratio: 2 }],
+            venue: Some(Venue {
+                name: "X".to_string()
+            }),
+        })
+    );
+    let err = it.next().unwrap().unwrap_err();
+    assert_eq!(err.context().unwrap().path, "Quote/Price");
+    assert_eq!(
+        it.next().unwrap().unwrap(),
+        Message::Quote(Quote {
+            price: 250,
+            legs: vec![Leg { ratio: 2 }, Leg { ratio: 2 }],
+            venue: Some(Venue {
+                name: "X".to_string()
+            }),
+        })
+    );
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn serialize_to_wire() {
+    // Direct serialization matches the encoding of the data model visited by `ModelVisitor`
+    let mut e1 = Encoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    let mut e2 = Encoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    for msg in quotes() {
+        let mut data = TemplateData::new_empty();
+        msg.serialize(&mut data).unwrap();
+        let model = e1.encode_vec(&mut ModelVisitor::new(data)).unwrap();
+        assert_eq!(to_vec(&mut e2, &msg).unwrap(), model);
+    }
+}
+
+#[test]
+fn serialize_to_wire_out_of_order() {
+    #[derive(Serialize)]
+    enum Message {
+        Quote(Quote),
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Quote {
+        #[serde(rename = "templateRef:0")]
+        note: Box<QuoteMessage>,
+        #[serde(serialize_with = "unknown_length")]
+        legs: Vec<Leg>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        venue: Option<Venue>,
+        comment: &'static str,
+        price: u32,
+        symbol: String,
+    }
+
+    fn unknown_length<S: serde::Serializer>(legs: &[Leg], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(legs.iter().filter(|_| true))
+    }
+
+    // Fields that come before their instructions are buffered, unknown fields are ignored,
+    // absent optional fields are encoded as absent
+    let mut e = Encoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    let raw: Vec<u8> = quotes()
+        .into_iter()
+        .flat_map(|m| {
+            let QuoteMessage::Quote(q) = m else {
+                unreachable!()
+            };
+            let msg = Message::Quote(Quote {
+                note: q.note,
+                legs: q.legs,
+                venue: q.venue,
+                comment: "ignored",
+                price: q.price,
+                symbol: q.symbol,
+            });
+            to_vec(&mut e, &msg).unwrap()
+        })
+        .collect();
+    assert_eq!(raw, encode_quotes());
+}
+
+#[test]
+fn serialize_to_wire_error() {
+    #[derive(Serialize)]
+    enum Message {
+        Quote(Quote),
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Quote {
+        symbol: String,
+        price: u32,
+        #[serde(rename = "templateRef:0")]
+        note: Box<QuoteMessage>,
+    }
+
+    let mut e = Encoder::new_from_xml(WIRE_DEFINITION).unwrap();
+    let msg = Message::Quote(Quote {
+        symbol: "ABC".to_string(),
+        price: 100,
+        note: Box::new(QuoteMessage::Note(Note {
+            text: "hi".to_string(),
+        })),
+    });
+    let err = to_vec(&mut e, &msg).unwrap_err();
+    assert!(matches!(err.kind(), Error::Dynamic(_)), "{err:?}");
+    assert_eq!(err.context().unwrap().path, "Quote/Legs");
+}
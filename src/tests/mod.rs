@@ -34,7 +34,7 @@ pub struct TestTemplate {
     instructions: Vec<TestField>,
 }
 
-pub fn test_templates(d: &Decoder, tts: &Vec<TestTemplate>) {
+pub fn test_templates(d: &Decoder, tts: &[TestTemplate]) {
     assert_eq!(
         d.definitions.templates.len(),
         tts.len(),
@@ -42,31 +42,26 @@ pub fn test_templates(d: &Decoder, tts: &Vec<TestTemplate>) {
     );
     for (t, tt) in d.definitions.templates.iter().zip(tts) {
         assert_eq!(t.id, tt.id, "{} id mismatch", t.name);
-        assert_eq!(t.name, t.name, "{} name mismatch", t.name);
+        assert_eq!(t.name, tt.name, "{} name mismatch", t.name);
         assert_eq!(
             t.dictionary, tt.dictionary,
             "{} dictionary mismatch",
             t.name
         );
-        test_instructions(&t.instructions, &tt.instructions, &tt.name);
+        test_instructions(&t.instructions, &tt.instructions, tt.name);
     }
 }
 
-pub fn test_instructions(iss: &Vec<Instruction>, tis: &Vec<TestField>, name: &str) {
+pub fn test_instructions(iss: &[Instruction], tis: &[TestField], name: &str) {
     assert_eq!(iss.len(), tis.len(), "{name} fields count mismatch");
     for (t, tt) in iss.iter().zip(tis) {
         assert_eq!(t.id, tt.id, "{} id mismatch", tt.name);
-        assert_eq!(t.name, t.name, "{} name mismatch", tt.name);
+        assert_eq!(t.name, tt.name, "{} name mismatch", tt.name);
         assert_eq!(t.presence, tt.presence, "{} presence mismatch", tt.name);
         assert_eq!(t.operator, tt.operator, "{} operator mismatch", tt.name);
         assert_eq!(t.value_type, tt.value, "{} value mismatch", tt.name);
-        assert_eq!(
-            t.has_pmap.get(),
-            tt.has_pmap,
-            "{} has_pmap mismatch",
-            tt.name
-        );
-        test_instructions(&t.instructions, &tt.instructions, &tt.name);
+        assert_eq!(t.has_pmap, tt.has_pmap, "{} has_pmap mismatch", tt.name);
+        test_instructions(&t.instructions, &tt.instructions, tt.name);
     }
 }
 
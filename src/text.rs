@@ -63,6 +63,11 @@ impl MessageFactory for TextMessageFactory {
         if let Some(value) = value {
             self.delimiter();
             let value = match value {
+                Value::Bool(v) => format!("{v}"),
+                Value::UInt8(v) => format!("{v}"),
+                Value::Int8(v) => format!("{v}"),
+                Value::UInt16(v) => format!("{v}"),
+                Value::Int16(v) => format!("{v}"),
                 Value::UInt32(v) => format!("{v}"),
                 Value::Int32(v) => format!("{v}"),
                 Value::UInt64(v) => format!("{v}"),
@@ -70,6 +75,11 @@ impl MessageFactory for TextMessageFactory {
                 Value::Decimal(v) => v.to_string(),
                 Value::ASCIIString(v) | Value::UnicodeString(v) => v.clone(),
                 Value::Bytes(b) => bytes_to_string(&b),
+                v @ (Value::Enum(..)
+                | Value::Set(..)
+                | Value::Timestamp(_)
+                | Value::Date(_)
+                | Value::TimeOfDay(_)) => v.to_string(),
             };
             let _ = write!(&mut self.text, "{name}={value}");
         }
@@ -178,6 +188,11 @@ impl MessageFactory for JsonMessageFactory {
         if let Some(value) = value {
             self.delimiter();
             let value = match value {
+                Value::Bool(v) => format!("{v}"),
+                Value::UInt8(v) => format!("{v}"),
+                Value::Int8(v) => format!("{v}"),
+                Value::UInt16(v) => format!("{v}"),
+                Value::Int16(v) => format!("{v}"),
                 Value::UInt32(v) => format!("{v}"),
                 Value::Int32(v) => format!("{v}"),
                 Value::UInt64(v) => format!("{v}"),
@@ -185,6 +200,20 @@ impl MessageFactory for JsonMessageFactory {
                 Value::Decimal(v) => format!("{v}"),
                 Value::ASCIIString(v) | Value::UnicodeString(v) => format!("\"{v}\""),
                 Value::Bytes(b) => bytes_to_string(&b),
+                Value::Enum(v, name) => {
+                    if name.is_empty() {
+                        format!("{v}")
+                    } else {
+                        format!("\"{name}\"")
+                    }
+                }
+                Value::Set(_, names) => {
+                    let names: Vec<_> = names.iter().map(|n| format!("\"{n}\"")).collect();
+                    format!("[{}]", names.join(","))
+                }
+                v @ (Value::Timestamp(_) | Value::Date(_) | Value::TimeOfDay(_)) => {
+                    format!("\"{v}\"")
+                }
             };
             let _ = write!(&mut self.json, "\"{name}\":{value}");
         }
@@ -0,0 +1,688 @@
+//! # Typed messages
+//!
+//! Message types that implement [`FastMessage`] are decoded and encoded by code specialized for their template,
+//! usually generated with `#[derive(FastMessage)]` of `fastlib-derive` crate. The generated code addresses
+//! the instructions of the template by their positions and steps through the program the template is compiled into,
+//! so values are read and written without looking up fields by names or resolving dictionaries.
+//! The definitions used by the decoder and the encoder must have the same templates
+//! as the definitions the code was generated from.
+//!
+use std::borrow::Cow;
+use std::mem;
+
+use bytes::BytesMut;
+
+use crate::base::instruction::Instruction;
+use crate::base::pmap::PresenceMap;
+use crate::base::types::Template;
+use crate::base::value::{Value, ValueRef, ValueType};
+use crate::common::program::{Field, MESSAGE_SCOPE, Op};
+use crate::decoder::decoder::DecoderContext;
+use crate::decoder::reader::{CopyingReader, ReadRaw};
+use crate::encoder::encoder::EncoderContext;
+use crate::{Decimal, Decoder, Encoder, Error, ErrorCode, MessageFactory, Reader, Result, Writer};
+
+/// Type whose fields are the instructions of a template, a group or a sequence item.
+pub trait FastFields: Sized {
+    /// Decodes the instructions into the fields.
+    /// # Errors
+    /// Returns error if decoding failed.
+    fn decode_fields<D: FieldDecoder>(decoder: &mut D) -> Result<Self>;
+
+    /// Encodes the fields with the instructions.
+    /// # Errors
+    /// Returns error if encoding failed.
+    fn encode_fields<E: FieldEncoder>(&self, encoder: &mut E) -> Result<()>;
+}
+
+/// Message type of a template, see [`Decoder::decode_typed`] and [`Encoder::encode_typed`].
+pub trait FastMessage: FastFields {
+    /// The name of the message template.
+    const TEMPLATE_NAME: &'static str;
+}
+
+/// Decodes the instructions of the current template, group or sequence item by their positions.
+/// Instructions must be decoded in order.
+pub trait FieldDecoder {
+    /// Decodes the field at `index`.
+    /// # Errors
+    /// Returns error if decoding failed or the field can't be converted to `T`.
+    fn field<T: FieldValue>(&mut self, index: usize) -> Result<T>;
+
+    /// Decodes the group at `index`.
+    /// # Errors
+    /// Returns error if decoding failed.
+    fn group<G: GroupValue>(&mut self, index: usize) -> Result<G>;
+
+    /// Decodes the sequence at `index`.
+    /// # Errors
+    /// Returns error if decoding failed.
+    fn sequence<S: SequenceValue>(&mut self, index: usize) -> Result<S>;
+
+    /// Decodes the instructions of the template statically referenced at `index` with `f`.
+    /// # Errors
+    /// Returns error if decoding failed.
+    fn template_ref<T, F>(&mut self, index: usize, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>;
+}
+
+/// Encodes the instructions of the current template, group or sequence item by their positions.
+/// Instructions must be encoded in order.
+pub trait FieldEncoder {
+    /// Encodes the field at `index`.
+    /// # Errors
+    /// Returns error if encoding failed.
+    fn field<T: FieldValue>(&mut self, index: usize, value: &T) -> Result<()>;
+
+    /// Encodes the group at `index`.
+    /// # Errors
+    /// Returns error if encoding failed.
+    fn group<G: GroupValue>(&mut self, index: usize, value: &G) -> Result<()>;
+
+    /// Encodes the sequence at `index`.
+    /// # Errors
+    /// Returns error if encoding failed.
+    fn sequence<S: SequenceValue>(&mut self, index: usize, value: &S) -> Result<()>;
+
+    /// Encodes the instructions of the template statically referenced at `index` with `f`.
+    /// # Errors
+    /// Returns error if encoding failed.
+    fn template_ref<F>(&mut self, index: usize, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>;
+}
+
+/// Rust type of a field value. `Option<T>` is the type of optional fields.
+pub trait FieldValue: Sized {
+    /// Converts decoded value of the field.
+    /// # Errors
+    /// Returns error if the value has a different type or a mandatory value is absent.
+    fn from_value(value: Option<ValueRef<'_>>) -> Result<Self>;
+
+    /// Returns the value to encode. Strings and byte vectors are borrowed from `self`.
+    fn to_value(&self) -> Option<ValueRef<'_>>;
+}
+
+macro_rules! field_value {
+    ($($type:ty => $variant:ident,)*) => {
+        $(
+            impl FieldValue for $type {
+                fn from_value(value: Option<ValueRef<'_>>) -> Result<Self> {
+                    match value {
+                        Some(ValueRef::$variant(v)) => Ok(v),
+                        v => Err(unexpected_value(v, stringify!($type))),
+                    }
+                }
+
+                fn to_value(&self) -> Option<ValueRef<'_>> {
+                    Some(ValueRef::$variant(self.clone()))
+                }
+            }
+        )*
+    };
+}
+
+field_value! {
+    bool => Bool,
+    u8 => UInt8,
+    i8 => Int8,
+    u16 => UInt16,
+    i16 => Int16,
+    u32 => UInt32,
+    i32 => Int32,
+    u64 => UInt64,
+    i64 => Int64,
+    Decimal => Decimal,
+}
+
+impl FieldValue for String {
+    fn from_value(value: Option<ValueRef<'_>>) -> Result<Self> {
+        match value {
+            Some(ValueRef::ASCIIString(v) | ValueRef::UnicodeString(v)) => Ok(v.into_owned()),
+            Some(ValueRef::SharedUnicodeString(v)) => Ok(String::from_utf8_lossy(&v).into_owned()),
+            v => Err(unexpected_value(v, "String")),
+        }
+    }
+
+    fn to_value(&self) -> Option<ValueRef<'_>> {
+        if self.is_ascii() {
+            Some(ValueRef::ASCIIString(Cow::Borrowed(self)))
+        } else {
+            Some(ValueRef::UnicodeString(Cow::Borrowed(self)))
+        }
+    }
+}
+
+impl FieldValue for Vec<u8> {
+    fn from_value(value: Option<ValueRef<'_>>) -> Result<Self> {
+        match value {
+            Some(ValueRef::Bytes(v)) => Ok(v.into_owned()),
+            Some(ValueRef::SharedBytes(v)) => Ok(v.into()),
+            v => Err(unexpected_value(v, "Vec<u8>")),
+        }
+    }
+
+    fn to_value(&self) -> Option<ValueRef<'_>> {
+        Some(ValueRef::Bytes(Cow::Borrowed(self)))
+    }
+}
+
+impl<T: FieldValue> FieldValue for Option<T> {
+    fn from_value(value: Option<ValueRef<'_>>) -> Result<Self> {
+        value.map(|v| T::from_value(Some(v))).transpose()
+    }
+
+    fn to_value(&self) -> Option<ValueRef<'_>> {
+        self.as_ref().and_then(FieldValue::to_value)
+    }
+}
+
+fn unexpected_value(value: Option<ValueRef<'_>>, type_: &str) -> Error {
+    match value {
+        Some(v) => Error::Spec(
+            ErrorCode::D1,
+            format!("Field value {v:?} can't be converted to {type_}"),
+        ),
+        None => Error::Dynamic("Missing mandatory field".to_string()),
+    }
+}
+
+// Converts the value to the type of the field as it is transferred.
+// Strings and byte vectors are transferred as they are, so they stay borrowed.
+fn wire_value<'v>(
+    instruction: &Instruction,
+    value: Option<ValueRef<'v>>,
+) -> Result<Option<ValueRef<'v>>> {
+    match (&instruction.value_type, value) {
+        (ValueType::ASCIIString | ValueType::UnicodeString | ValueType::Bytes, value) => Ok(value),
+        (type_, value) => {
+            let value = value
+                .map(|v| v.into_owned().convert_to(type_))
+                .transpose()?;
+            Ok(instruction.wire_value(value)?.map(ValueRef::from))
+        }
+    }
+}
+
+/// Rust type of a group. `Option<T>` is the type of optional groups.
+pub trait GroupValue: Sized {
+    type Fields: FastFields;
+
+    /// Converts decoded group.
+    /// # Errors
+    /// Returns error if a mandatory group is absent.
+    fn from_group(group: Option<Self::Fields>) -> Result<Self>;
+
+    /// Returns the group to encode.
+    fn as_group(&self) -> Option<&Self::Fields>;
+}
+
+impl<T: FastFields> GroupValue for T {
+    type Fields = T;
+
+    fn from_group(group: Option<T>) -> Result<Self> {
+        group.ok_or_else(|| Error::Dynamic("Missing mandatory group".to_string()))
+    }
+
+    fn as_group(&self) -> Option<&T> {
+        Some(self)
+    }
+}
+
+impl<T: FastFields> GroupValue for Option<T> {
+    type Fields = T;
+
+    fn from_group(group: Option<T>) -> Result<Self> {
+        Ok(group)
+    }
+
+    fn as_group(&self) -> Option<&T> {
+        self.as_ref()
+    }
+}
+
+/// Rust type of a sequence. `Option<Vec<T>>` is the type of optional sequences.
+pub trait SequenceValue: Sized {
+    type Item: FastFields;
+
+    /// Converts decoded items.
+    /// # Errors
+    /// Returns error if a mandatory sequence is absent.
+    fn from_items(items: Option<Vec<Self::Item>>) -> Result<Self>;
+
+    /// Returns the items to encode.
+    fn as_items(&self) -> Option<&[Self::Item]>;
+}
+
+impl<T: FastFields> SequenceValue for Vec<T> {
+    type Item = T;
+
+    fn from_items(items: Option<Vec<T>>) -> Result<Self> {
+        items.ok_or_else(|| Error::Dynamic("Missing mandatory sequence".to_string()))
+    }
+
+    fn as_items(&self) -> Option<&[T]> {
+        Some(self)
+    }
+}
+
+impl<T: FastFields> SequenceValue for Option<Vec<T>> {
+    type Item = T;
+
+    fn from_items(items: Option<Vec<T>>) -> Result<Self> {
+        Ok(items)
+    }
+
+    fn as_items(&self) -> Option<&[T]> {
+        self.as_deref()
+    }
+}
+
+// The error of a call that doesn't match the next operation of the program.
+fn mismatch(index: usize) -> Error {
+    Error::Runtime(format!(
+        "Template doesn't match the message type at instruction #{index}"
+    ))
+}
+
+// Position in the program of the message template. The instructions of the current template,
+// group or sequence item are numbered from 0 and must be processed in order.
+struct Cursor<'a> {
+    program: &'a [Op],
+    pc: usize,
+    // The index of the next instruction of the current template, group or sequence item.
+    next: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(program: &'a [Op]) -> Self {
+        Self {
+            program,
+            pc: 0,
+            next: 0,
+        }
+    }
+
+    // Returns the operation of the instruction at `index`.
+    fn op(&mut self, index: usize) -> Result<&'a Op> {
+        let op = self
+            .program
+            .get(self.pc)
+            .filter(|op| index == self.next && !is_end(op))
+            .ok_or_else(|| mismatch(index))?;
+        self.pc += 1;
+        self.next += 1;
+        Ok(op)
+    }
+
+    // Starts the instructions of a group, a sequence item or a referenced template at `pc`.
+    // Returns the index of the next instruction of the enclosing element.
+    fn enter(&mut self, pc: usize) -> usize {
+        self.pc = pc;
+        mem::replace(&mut self.next, 0)
+    }
+
+    // Finishes the instructions started with `enter()`; all of them must be processed.
+    fn leave(&mut self, end: usize, next: usize) -> Result<()> {
+        if self.pc != end {
+            return Err(mismatch(self.next));
+        }
+        self.pc = end + 1;
+        self.next = next;
+        Ok(())
+    }
+
+    // Checks that all instructions of the message template are processed.
+    fn finish(&self) -> Result<()> {
+        if self.pc == self.program.len() {
+            Ok(())
+        } else {
+            Err(mismatch(self.next))
+        }
+    }
+}
+
+fn is_end(op: &Op) -> bool {
+    matches!(
+        op,
+        Op::GroupEnd { .. } | Op::SequenceEnd { .. } | Op::TemplateRefEnd
+    )
+}
+
+/// Decodes one message from `rdr` as `T`.
+/// A message of another template is decoded to the end and reported as an error.
+pub(crate) fn decode_message<T: FastMessage>(
+    decoder: &mut Decoder,
+    rdr: &mut impl Reader,
+) -> Result<T> {
+    decoder.decode_raw_with(&mut CopyingReader(rdr), &mut Skip, |ctx| {
+        ctx.decode_presence_map()?;
+        ctx.decode_template_id()?;
+        let template = ctx.current_template()?;
+        if template.name != T::TEMPLATE_NAME {
+            ctx.decode_message(template)
+                .map_err(|e| e.in_template(template.id, &template.name))?;
+            return Err(Error::Runtime(format!(
+                "Message {} is not {}",
+                template.name,
+                T::TEMPLATE_NAME
+            )));
+        }
+        let program = ctx.program(template, MESSAGE_SCOPE)?;
+        let mut decoder = TypedDecoder {
+            ctx: &mut *ctx,
+            cursor: Cursor::new(program),
+        };
+        let msg = T::decode_fields(&mut decoder)
+            .and_then(|msg| decoder.cursor.finish().map(|()| msg))
+            .map_err(|e| e.in_template(template.id, &template.name))?;
+        ctx.drop_template_id();
+        ctx.drop_presence_map()?;
+        Ok(msg)
+    })
+}
+
+// Decoded values are returned by the typed decoder instead of a message factory.
+struct Skip;
+
+impl MessageFactory for Skip {
+    fn start_template(&mut self, _id: u32, _name: &str) {}
+    fn stop_template(&mut self) {}
+    fn set_value(&mut self, _id: u32, _name: &str, _value: Option<Value>) {}
+    fn start_sequence(&mut self, _id: u32, _name: &str, _length: u32) {}
+    fn start_sequence_item(&mut self, _index: u32) {}
+    fn stop_sequence_item(&mut self) {}
+    fn stop_sequence(&mut self) {}
+    fn start_group(&mut self, _name: &str) {}
+    fn stop_group(&mut self) {}
+    fn start_template_ref(&mut self, _name: &str, _dynamic: bool) {}
+    fn stop_template_ref(&mut self) {}
+}
+
+struct TypedDecoder<'c, 'a, R> {
+    ctx: &'c mut DecoderContext<'a, R, Skip>,
+    cursor: Cursor<'a>,
+}
+
+impl<'b, R: ReadRaw<'b>> TypedDecoder<'_, '_, R> {
+    // Decodes the instructions of a group or a sequence item, starting at `pc`, with `T`.
+    fn segment<T: FastFields>(&mut self, pc: usize, end: usize, has_pmap: bool) -> Result<T> {
+        let next = self.cursor.enter(pc);
+        if has_pmap {
+            self.ctx.decode_presence_map()?;
+        }
+        let value = T::decode_fields(self)?;
+        self.cursor.leave(end, next)?;
+        if has_pmap {
+            self.ctx.drop_presence_map()?;
+        }
+        Ok(value)
+    }
+
+    fn decode_group<T: FastFields>(
+        &mut self,
+        group: &Instruction,
+        end: usize,
+    ) -> Result<Option<T>> {
+        self.ctx.enter_nested()?;
+        let value = if group.is_optional() && !self.ctx.pmap_next_bit_set() {
+            self.cursor.pc = end + 1;
+            None
+        } else {
+            // If any instruction of the group needs to allocate a bit in a presence map, the group is represented
+            // as a segment in the transfer encoding.
+            Some(self.segment(self.cursor.pc, end, group.has_pmap)?)
+        };
+        self.ctx.leave_nested();
+        Ok(value)
+    }
+
+    fn decode_sequence<T: FastFields>(
+        &mut self,
+        sequence: &Instruction,
+        length: &Field,
+        end: usize,
+    ) -> Result<Option<Vec<T>>> {
+        self.ctx.enter_nested()?;
+        let start = self.cursor.pc;
+        let items = match self.ctx.decode_sequence_length(length)? {
+            Some(length) => {
+                let mut items = Vec::with_capacity(length as usize);
+                for idx in 0..length {
+                    // If any instruction of the sequence needs to allocate a bit in a presence map, each element
+                    // is represented as a segment in the transfer encoding.
+                    let item = self
+                        .segment(start, end, sequence.has_pmap)
+                        .map_err(|e| self.ctx.located(e, &format!("[{idx}]")))?;
+                    items.push(item);
+                }
+                Some(items)
+            }
+            None => None,
+        };
+        self.cursor.pc = end + 1;
+        self.ctx.leave_nested();
+        Ok(items)
+    }
+}
+
+impl<'b, R: ReadRaw<'b>> FieldDecoder for TypedDecoder<'_, '_, R> {
+    fn field<T: FieldValue>(&mut self, index: usize) -> Result<T> {
+        let Op::Field(field) = self.cursor.op(index)? else {
+            return Err(mismatch(index));
+        };
+        let value = self.ctx.decode_field(field)?;
+        T::from_value(value).map_err(|e| self.ctx.located(e, &field.instruction.name))
+    }
+
+    fn group<G: GroupValue>(&mut self, index: usize) -> Result<G> {
+        let Op::Group { group, end } = self.cursor.op(index)? else {
+            return Err(mismatch(index));
+        };
+        self.decode_group(group, *end)
+            .and_then(G::from_group)
+            .map_err(|e| self.ctx.located(e, &group.name))
+    }
+
+    fn sequence<S: SequenceValue>(&mut self, index: usize) -> Result<S> {
+        let Op::Sequence {
+            sequence,
+            length,
+            end,
+        } = self.cursor.op(index)?
+        else {
+            return Err(mismatch(index));
+        };
+        self.decode_sequence(sequence, length, *end)
+            .and_then(S::from_items)
+            .map_err(|e| self.ctx.located(e, &sequence.name))
+    }
+
+    fn template_ref<T, F>(&mut self, index: usize, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        let Op::TemplateRef { end, .. } = self.cursor.op(index)? else {
+            return Err(mismatch(index));
+        };
+        self.ctx.enter_nested()?;
+        let next = self.cursor.enter(self.cursor.pc);
+        let value = f(self)?;
+        self.cursor.leave(*end, next)?;
+        self.ctx.leave_nested();
+        Ok(value)
+    }
+}
+
+/// Encodes `msg` into `wrt` as one message.
+pub(crate) fn encode_message<T: FastMessage>(
+    encoder: &mut Encoder,
+    wrt: &mut impl Writer,
+    msg: &T,
+) -> Result<()> {
+    let mut unit = ();
+    let mut ctx = EncoderContext::new(encoder, wrt, &mut unit);
+    let template = ctx.template_by_name(T::TEMPLATE_NAME)?;
+    let buf = encode_template(&mut ctx, template, msg)
+        .map_err(|e| e.in_template(template.id, &template.name))?;
+
+    let mut buf2 = BytesMut::new();
+    ctx.write_presence_map(&mut buf2)?;
+    buf2.write_buf(buf.as_ref())?;
+
+    ctx.wrt.write_buf(buf2.as_ref()) // presence map + template_id + instructions
+}
+
+// Encodes template id and instructions of the message template.
+fn encode_template<W: Writer, T: FastFields>(
+    ctx: &mut EncoderContext<'_, W, ()>,
+    template: &Template,
+    msg: &T,
+) -> Result<BytesMut> {
+    let program = ctx.program(template, MESSAGE_SCOPE)?;
+    let mut buf = BytesMut::new();
+    ctx.encode_template_id(&mut buf, template.id)?;
+    let mut encoder = TypedEncoder {
+        ctx,
+        buf,
+        cursor: Cursor::new(program),
+    };
+    msg.encode_fields(&mut encoder)?;
+    encoder.cursor.finish()?;
+    Ok(encoder.buf)
+}
+
+struct TypedEncoder<'c, 'a, W> {
+    ctx: &'c mut EncoderContext<'a, W, ()>,
+    buf: BytesMut,
+    cursor: Cursor<'a>,
+}
+
+impl<W: Writer> TypedEncoder<'_, '_, W> {
+    // Encodes the instructions of a group or a sequence item, starting at `pc`, with `value`.
+    fn segment<T: FastFields>(
+        &mut self,
+        pc: usize,
+        end: usize,
+        has_pmap: bool,
+        value: &T,
+    ) -> Result<()> {
+        let next = self.cursor.enter(pc);
+        if !has_pmap {
+            value.encode_fields(self)?;
+            return self.cursor.leave(end, next);
+        }
+        self.ctx.presence_map.push(PresenceMap::new_empty());
+        let buf = mem::take(&mut self.buf);
+        value.encode_fields(self)?;
+        self.cursor.leave(end, next)?;
+        let buf2 = mem::replace(&mut self.buf, buf);
+        self.ctx.write_presence_map(&mut self.buf)?;
+        self.buf.write_buf(buf2.as_ref())
+    }
+
+    fn encode_group<T: FastFields>(
+        &mut self,
+        group: &Instruction,
+        end: usize,
+        value: Option<&T>,
+    ) -> Result<()> {
+        let Some(value) = value else {
+            return if group.is_optional() {
+                self.ctx.pmap_set_next_bit(false);
+                self.cursor.pc = end + 1;
+                Ok(())
+            } else {
+                Err(Error::Dynamic(format!(
+                    "Missing mandatory group: {}",
+                    group.name
+                )))
+            };
+        };
+        if group.is_optional() {
+            self.ctx.pmap_set_next_bit(true);
+        }
+        self.segment(self.cursor.pc, end, group.has_pmap, value)
+    }
+
+    fn encode_sequence<T: FastFields>(
+        &mut self,
+        sequence: &Instruction,
+        length: &Field,
+        end: usize,
+        items: Option<&[T]>,
+    ) -> Result<()> {
+        let start = self.cursor.pc;
+        match items {
+            None => {
+                if sequence.is_optional() {
+                    self.ctx.inject_field(&mut self.buf, length, None)?;
+                } else {
+                    return Err(Error::Dynamic(format!(
+                        "Missing mandatory sequence: {}",
+                        sequence.name
+                    )));
+                }
+            }
+            Some(items) => {
+                self.ctx.inject_field(
+                    &mut self.buf,
+                    length,
+                    Some(ValueRef::UInt32(items.len() as u32)),
+                )?;
+                for (idx, item) in items.iter().enumerate() {
+                    self.segment(start, end, sequence.has_pmap, item)
+                        .map_err(|e| self.ctx.located(e, &format!("[{idx}]")))?;
+                }
+            }
+        }
+        self.cursor.pc = end + 1;
+        Ok(())
+    }
+}
+
+impl<W: Writer> FieldEncoder for TypedEncoder<'_, '_, W> {
+    fn field<T: FieldValue>(&mut self, index: usize, value: &T) -> Result<()> {
+        let Op::Field(field) = self.cursor.op(index)? else {
+            return Err(mismatch(index));
+        };
+        let instruction = &*field.instruction;
+        let value = wire_value(instruction, value.to_value())
+            .map_err(|e| self.ctx.located(e, &instruction.name))?;
+        self.ctx.inject_field(&mut self.buf, field, value)
+    }
+
+    fn group<G: GroupValue>(&mut self, index: usize, value: &G) -> Result<()> {
+        let Op::Group { group, end } = self.cursor.op(index)? else {
+            return Err(mismatch(index));
+        };
+        self.encode_group(group, *end, value.as_group())
+            .map_err(|e| self.ctx.located(e, &group.name))
+    }
+
+    fn sequence<S: SequenceValue>(&mut self, index: usize, value: &S) -> Result<()> {
+        let Op::Sequence {
+            sequence,
+            length,
+            end,
+        } = self.cursor.op(index)?
+        else {
+            return Err(mismatch(index));
+        };
+        self.encode_sequence(sequence, length, *end, value.as_items())
+            .map_err(|e| self.ctx.located(e, &sequence.name))
+    }
+
+    fn template_ref<F>(&mut self, index: usize, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        let Op::TemplateRef { end, .. } = self.cursor.op(index)? else {
+            return Err(mismatch(index));
+        };
+        let next = self.cursor.enter(self.cursor.pc);
+        f(self)?;
+        self.cursor.leave(*end, next)
+    }
+}
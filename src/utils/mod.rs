@@ -1,2 +1,3 @@
 pub(crate) mod bytes;
 pub(crate) mod stacked;
+pub(crate) mod xml;
@@ -0,0 +1,67 @@
+use std::fmt::{Display, Write};
+
+/// A minimal XML element tree used to write template definitions.
+pub(crate) struct XmlElement {
+    tag: &'static str,
+    attributes: Vec<(&'static str, String)>,
+    children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    pub(crate) fn new(tag: &'static str) -> Self {
+        Self {
+            tag,
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub(crate) fn attribute(&mut self, name: &'static str, value: impl Display) {
+        self.attributes.push((name, format!("{value}")));
+    }
+
+    pub(crate) fn remove_attribute(&mut self, name: &str) {
+        self.attributes.retain(|(n, _)| *n != name);
+    }
+
+    pub(crate) fn child(&mut self, child: XmlElement) {
+        self.children.push(child);
+    }
+
+    /// Writes the element and its children indented by 4 spaces per level.
+    pub(crate) fn write(&self, out: &mut String, depth: usize) {
+        let indent = "    ".repeat(depth);
+        let _ = write!(out, "{indent}<{}", self.tag);
+        for (name, value) in &self.attributes {
+            let _ = write!(out, " {name}=\"{}\"", escape(value));
+        }
+        if self.children.is_empty() {
+            out.push_str("/>\n");
+            return;
+        }
+        out.push_str(">\n");
+        for child in &self.children {
+            child.write(out, depth + 1);
+        }
+        let _ = writeln!(out, "{indent}</{}>", self.tag);
+    }
+}
+
+// Escapes attribute value. Whitespace characters other than space are escaped as well,
+// otherwise XML parsers normalize them to spaces.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' => out.push_str("&#9;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,141 @@
+//! # Tests of the generated message types
+//!
+//! The generated files are checked in, so the tests fail if the generator output changes.
+//! Regenerate them with `CodeGenerator` after a deliberate change.
+//!
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeSet;
+
+use fastlib::{CodeGenerator, Decimal, Decoder, Definitions, Encoder};
+
+mod cqg {
+    include!("generated/cqg.rs");
+}
+
+mod types {
+    include!("generated/types.rs");
+}
+
+const CQG_DEFINITION: &str = include_str!("templates.xml");
+const TYPES_DEFINITION: &str = include_str!("codegen.xml");
+
+#[test]
+fn generated_code_is_up_to_date() {
+    for (xml, code) in [
+        (CQG_DEFINITION, include_str!("generated/cqg.rs")),
+        (TYPES_DEFINITION, include_str!("generated/types.rs")),
+    ] {
+        let definitions = Definitions::new_from_xml(xml).unwrap();
+        assert_eq!(CodeGenerator::new(&definitions).generate(), code);
+    }
+}
+
+#[test]
+fn cqg_messages() {
+    use cqg::{MdHeartbeat, MdLogout, Message};
+
+    let raw = [
+        vec![
+            0xc0, 0x84, 0x81, 0x23, 0x7a, 0x17, 0x15, 0x15, 0x2c, 0x58, 0x80,
+        ],
+        vec![
+            0xc0, 0x86, 0x83, 0x23, 0x7a, 0x1a, 0x19, 0x36, 0x3b, 0x5f, 0xc8, 0x52, 0x65, 0x71,
+            0x75, 0x65, 0x73, 0x74, 0x20, 0x74, 0x69, 0x6d, 0x65, 0x6f, 0x75, 0xf4,
+        ],
+    ];
+    let data = [
+        Message::MdHeartbeat(MdHeartbeat {
+            message_type: "0".to_string(),
+            appl_ver_id: "8".to_string(),
+            sender_comp_id: "CQG".to_string(),
+            msg_seq_num: 1,
+            sending_time: 20240606000000000,
+        }),
+        Message::MdLogout(MdLogout {
+            message_type: "5".to_string(),
+            appl_ver_id: "8".to_string(),
+            sender_comp_id: "CQG".to_string(),
+            msg_seq_num: 3,
+            sending_time: 20240710222409672,
+            text: Some("Request timeout".to_string()),
+        }),
+    ];
+
+    let mut d = Decoder::new_from_xml(CQG_DEFINITION).unwrap();
+    let mut e = Encoder::new_from_xml(CQG_DEFINITION).unwrap();
+    for (raw, data) in raw.iter().zip(&data) {
+        let msg: Message = fastlib::from_slice(&mut d, raw).unwrap();
+        assert_eq!(&msg, data);
+        assert_eq!(&fastlib::to_vec(&mut e, data).unwrap(), raw);
+    }
+}
+
+#[test]
+fn generated_types_round_trip() {
+    use types::*;
+
+    let messages = [
+        Message::Trade(Trade {
+            msg_seq_num: 1,
+            sending_time: 1_717_677_000_123,
+            side: Side::Sell,
+            flags: Some(BTreeSet::from([Flags::A, Flags::B])),
+            px: Some(Decimal::new(-2, 12345)),
+            raw_data: vec![1, 2, 3],
+            signature: None,
+            trade_date: 19_880,
+            r#type: true,
+            instrument: Some(Instrument {
+                symbol: "ESZ4".to_string(),
+                legs: vec![LegsItem {
+                    leg_symbol: "ESH5".to_string(),
+                    side: Side::Buy,
+                }],
+            }),
+            legs: Some(vec![TradeLegsItem {
+                leg_symbol: "ESM5".to_string(),
+                side: LegsItemSide::_2,
+            }]),
+        }),
+        Message::Trade(Trade {
+            msg_seq_num: 2,
+            sending_time: 1_717_677_000_456,
+            side: Side::Buy,
+            flags: None,
+            px: None,
+            raw_data: vec![],
+            signature: Some(vec![0xff]),
+            trade_date: 19_881,
+            r#type: false,
+            instrument: None,
+            legs: None,
+        }),
+        Message::String(StringMsg {
+            text: "hello".to_string(),
+        }),
+        Message::Batch(Batch {
+            msg_seq_num: 3,
+            sending_time: 1_717_677_001_000,
+            template_ref_0: Box::new(Message::String(StringMsg {
+                text: "first".to_string(),
+            })),
+            extra: Extra {
+                template_ref_0: Box::new(Message::String(StringMsg {
+                    text: "second".to_string(),
+                })),
+                template_ref_1: Box::new(Message::String(StringMsg {
+                    text: "third".to_string(),
+                })),
+            },
+        }),
+    ];
+
+    let mut d = Decoder::new_from_xml(TYPES_DEFINITION).unwrap();
+    let mut e = Encoder::new_from_xml(TYPES_DEFINITION).unwrap();
+    for msg in messages {
+        let raw = fastlib::to_vec(&mut e, &msg).unwrap();
+        let decoded: Message = fastlib::from_slice(&mut d, &raw).unwrap();
+        assert_eq!(decoded, msg);
+    }
+}
@@ -7,6 +7,7 @@ use serde_derive::{Deserialize, Serialize};
 use fastlib::{Decimal, Decoder, Encoder};
 
 /// Message templates must be implements as `enum`.
+#[allow(clippy::large_enum_variant, clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 enum Message {
     MDSecurityDefinition(SecurityDefinition),
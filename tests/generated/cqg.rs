@@ -0,0 +1,186 @@
+// Generated by fastlib::CodeGenerator from template definitions. Do not edit.
+
+#[allow(clippy::large_enum_variant, clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Message {
+    #[serde(rename = "MDSecurityDefinition")]
+    MdSecurityDefinition(MdSecurityDefinition),
+    #[serde(rename = "MDHeartbeat")]
+    MdHeartbeat(MdHeartbeat),
+    #[serde(rename = "MDLogon")]
+    MdLogon(MdLogon),
+    #[serde(rename = "MDLogout")]
+    MdLogout(MdLogout),
+    #[serde(rename = "MDSecurityDefinitionRequest")]
+    MdSecurityDefinitionRequest(MdSecurityDefinitionRequest),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MdSecurityDefinition {
+    pub message_type: String,
+    #[serde(rename = "ApplVerID")]
+    pub appl_ver_id: String,
+    #[serde(rename = "SenderCompID")]
+    pub sender_comp_id: String,
+    pub msg_seq_num: u32,
+    pub sending_time: u64,
+    pub tot_num_reports: u32,
+    pub events: Option<Vec<EventsItem>>,
+    pub security_group: Option<String>,
+    pub symbol: Option<String>,
+    pub security_name: String,
+    pub security_desc: String,
+    #[serde(rename = "SecurityID")]
+    pub security_id: u32,
+    #[serde(rename = "SecurityIDSource")]
+    pub security_id_source: u32,
+    #[serde(rename = "CFICode")]
+    pub cfi_code: String,
+    pub security_exchange: Option<String>,
+    #[serde(rename = "CQGSecurityName")]
+    pub cqg_security_name: Option<String>,
+    pub strike_price: Option<fastlib::Decimal>,
+    pub strike_currency: Option<String>,
+    pub currency: Option<String>,
+    pub settl_currency: Option<String>,
+    #[serde(rename = "MDFeedTypes")]
+    pub md_feed_types: Option<Vec<MdFeedTypesItem>>,
+    pub instr_attrib: Option<Vec<InstrAttribItem>>,
+    pub maturity_month_year: Option<u64>,
+    pub min_price_increment: Option<fastlib::Decimal>,
+    pub min_price_increment_amount: Option<fastlib::Decimal>,
+    pub display_factor: Option<fastlib::Decimal>,
+    #[serde(rename = "ApplID")]
+    pub appl_id: String,
+    pub most_active_flag: Option<String>,
+    pub connections: Vec<ConnectionsItem>,
+    pub trading_sessions: Vec<TradingSessionsItem>,
+    pub underlyings: Option<Vec<UnderlyingsItem>>,
+    pub security_sub_type: Option<String>,
+    pub legs: Option<Vec<LegsItem>>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventsItem {
+    pub event_type: u32,
+    pub event_date: u64,
+    pub event_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MdFeedTypesItem {
+    #[serde(rename = "MDFeedType")]
+    pub md_feed_type: String,
+    pub market_depth: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct InstrAttribItem {
+    pub instr_attrib_type: u64,
+    pub instr_attrib_value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConnectionsItem {
+    pub connection_type: u32,
+    #[serde(rename = "ConnectionIPAddress")]
+    pub connection_ip_address: String,
+    pub connection_port_number: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TradingSessionsItem {
+    pub trade_date: u64,
+    pub trad_ses_start_time: u64,
+    pub trad_ses_open_time: u64,
+    pub trad_ses_close_time: u64,
+    pub trad_ses_end_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UnderlyingsItem {
+    #[serde(rename = "UnderlyingSecurityID")]
+    pub underlying_security_id: u32,
+    #[serde(rename = "UnderlyingSecurityIDSource")]
+    pub underlying_security_id_source: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LegsItem {
+    pub leg_symbol: String,
+    pub leg_security_desc: String,
+    pub leg_ratio_qty: fastlib::Decimal,
+    #[serde(rename = "LegSecurityID")]
+    pub leg_security_id: u32,
+    #[serde(rename = "LegSecurityIDSource")]
+    pub leg_security_id_source: u32,
+    pub leg_side: u32,
+    pub leg_security_group: String,
+    #[serde(rename = "LegCFICode")]
+    pub leg_cfi_code: String,
+    pub leg_currency: String,
+    pub leg_maturity_month_year: u64,
+    pub leg_strike_price: fastlib::Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MdHeartbeat {
+    pub message_type: String,
+    #[serde(rename = "ApplVerID")]
+    pub appl_ver_id: String,
+    #[serde(rename = "SenderCompID")]
+    pub sender_comp_id: String,
+    pub msg_seq_num: u32,
+    pub sending_time: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MdLogon {
+    pub message_type: String,
+    #[serde(rename = "ApplVerID")]
+    pub appl_ver_id: String,
+    #[serde(rename = "SenderCompID")]
+    pub sender_comp_id: String,
+    pub msg_seq_num: u32,
+    pub sending_time: u64,
+    pub encrypt_method: u32,
+    pub heartbeat_int: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MdLogout {
+    pub message_type: String,
+    #[serde(rename = "ApplVerID")]
+    pub appl_ver_id: String,
+    #[serde(rename = "SenderCompID")]
+    pub sender_comp_id: String,
+    pub msg_seq_num: u32,
+    pub sending_time: u64,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MdSecurityDefinitionRequest {
+    pub message_type: String,
+    #[serde(rename = "ApplVerID")]
+    pub appl_ver_id: String,
+    #[serde(rename = "SenderCompID")]
+    pub sender_comp_id: String,
+    pub msg_seq_num: u32,
+    pub sending_time: u64,
+    #[serde(rename = "ApplID")]
+    pub appl_id: String,
+    pub text: Option<String>,
+}
@@ -0,0 +1,97 @@
+// Generated by fastlib::CodeGenerator from template definitions. Do not edit.
+
+#[allow(clippy::large_enum_variant, clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Message {
+    Trade(Trade),
+    String(StringMsg),
+    Batch(Batch),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Trade {
+    pub msg_seq_num: u32,
+    pub sending_time: i64,
+    pub side: Side,
+    pub flags: Option<std::collections::BTreeSet<Flags>>,
+    pub px: Option<fastlib::Decimal>,
+    #[serde(with = "serde_bytes")]
+    pub raw_data: Vec<u8>,
+    #[serde(default, with = "serde_bytes")]
+    pub signature: Option<Vec<u8>>,
+    pub trade_date: i32,
+    #[serde(rename = "type")]
+    pub r#type: bool,
+    pub instrument: Option<Instrument>,
+    pub legs: Option<Vec<TradeLegsItem>>,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Flags {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Instrument {
+    pub symbol: String,
+    pub legs: Vec<LegsItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LegsItem {
+    pub leg_symbol: String,
+    pub side: Side,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TradeLegsItem {
+    pub leg_symbol: String,
+    pub side: LegsItemSide,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+pub enum LegsItemSide {
+    #[serde(rename = "1")]
+    _1,
+    #[serde(rename = "2")]
+    _2,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StringMsg {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Batch {
+    pub msg_seq_num: u32,
+    pub sending_time: i64,
+    #[serde(rename = "templateRef:0")]
+    pub template_ref_0: Box<Message>,
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Extra {
+    #[serde(rename = "templateRef:0")]
+    pub template_ref_0: Box<Message>,
+    #[serde(rename = "templateRef:1")]
+    pub template_ref_1: Box<Message>,
+}